@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Throughput};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::es_option::{DEFAULT_SEND_BUFFER_POOL, ESServeOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum BenchMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for BenchMsg {}
+
+const PAYLOAD_SIZE: usize = 1024;
+
+// a client sending to a server which receives and counts the messages
+struct Bench {
+    runtime: Runtime,
+    local: LocalSet,
+    client: Client<BenchMsg>,
+    received: Arc<AtomicU64>,
+    notifier: Notifier,
+}
+
+impl Bench {
+    fn new(port: u16, send_buffer_pool: usize) -> Self {
+        let notifier = Notifier::new();
+        let address = format!("127.0.0.1:{}", port);
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = received.clone();
+        let task_notifier = notifier.clone();
+        let server = NodeBuilder::new()
+            .set_node_id(1)
+            .set_notifier(notifier.clone())
+            .set_listen_address(address.clone())
+            .build::<BenchMsg, _>(FnHandler::<BenchMsg>::new()
+                .set_on_accepted(move |ep| {
+                    let counter = counter.clone();
+                    spawn_local_task(task_notifier.clone(), "receive", async move {
+                        while ep.recv().await.is_ok() {
+                            let _ = counter.fetch_add(1, Ordering::SeqCst);
+                        }
+                    })?;
+                    Ok(())
+                }))
+            .unwrap();
+        let client = ClientBuilder::new()
+            .set_node_id(2)
+            .set_server_addr(address)
+            .set_notifier(notifier.clone())
+            .set_send_buffer_pool(send_buffer_pool)
+            .build::<BenchMsg>()
+            .unwrap();
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        let local = LocalSet::new();
+        server.run_local(&local);
+        client.run(&local);
+        local.block_on(&runtime, async {
+            server.serve(ESServeOpt::default()).await.unwrap();
+            client.connect(OptClientConnect::new()).await.unwrap();
+        });
+        Self { runtime, local, client, received, notifier }
+    }
+
+    // send the messages, and wait until all of them were received
+    fn run(&self, messages: u64) -> Duration {
+        self.local.block_on(&self.runtime, async {
+            let target = self.received.load(Ordering::SeqCst) + messages;
+            let start = Instant::now();
+            for _ in 0..messages {
+                let m = Message::new(BenchMsg::Data(vec![0u8; PAYLOAD_SIZE]), 2, 1);
+                self.client.send(m).await.unwrap();
+            }
+            while self.received.load(Ordering::SeqCst) < target {
+                sleep(Duration::from_micros(100)).await;
+            }
+            start.elapsed()
+        })
+    }
+}
+
+impl Drop for Bench {
+    fn drop(&mut self) {
+        self.notifier.notify_all();
+    }
+}
+
+// the sends of an endpoint reusing the frame buffers of its pool, against allocating one for
+// every message
+fn bench_send_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("send_buffer");
+    group.throughput(Throughput::Bytes(PAYLOAD_SIZE as u64));
+    for (port, pool) in [(8586, 0), (8587, DEFAULT_SEND_BUFFER_POOL)] {
+        let bench = Bench::new(port, pool);
+        group.bench_with_input(BenchmarkId::new("pool", pool), &pool, |b, _| {
+            b.iter_custom(|iters| { bench.run(iters) });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_send_buffer);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Throughput};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::es_option::{DEFAULT_WRITE_BATCH_BYTES, DEFAULT_WRITE_BATCH_MAX, ESServeOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum BenchMsg {
+    Data(u64),
+}
+
+impl MsgTrait for BenchMsg {}
+
+// the concurrent senders, the writer task takes the frames they queued as a batch
+const SENDERS: u64 = 100;
+
+// a client sending to a server which receives and counts the messages
+struct Bench {
+    runtime: Runtime,
+    local: LocalSet,
+    client: Client<BenchMsg>,
+    received: Arc<AtomicU64>,
+    notifier: Notifier,
+}
+
+impl Bench {
+    fn new(port: u16, write_batch_max: usize) -> Self {
+        let notifier = Notifier::new();
+        let address = format!("127.0.0.1:{}", port);
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = received.clone();
+        let task_notifier = notifier.clone();
+        let server = NodeBuilder::new()
+            .set_node_id(1)
+            .set_notifier(notifier.clone())
+            .set_listen_address(address.clone())
+            .build::<BenchMsg, _>(FnHandler::<BenchMsg>::new()
+                .set_on_accepted(move |ep| {
+                    let counter = counter.clone();
+                    spawn_local_task(task_notifier.clone(), "receive", async move {
+                        while ep.recv().await.is_ok() {
+                            let _ = counter.fetch_add(1, Ordering::SeqCst);
+                        }
+                    })?;
+                    Ok(())
+                }))
+            .unwrap();
+        let client = ClientBuilder::new()
+            .set_node_id(2)
+            .set_server_addr(address)
+            .set_notifier(notifier.clone())
+            .set_write_batch(write_batch_max, DEFAULT_WRITE_BATCH_BYTES)
+            .build::<BenchMsg>()
+            .unwrap();
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        let local = LocalSet::new();
+        server.run_local(&local);
+        client.run(&local);
+        local.block_on(&runtime, async {
+            server.serve(ESServeOpt::default()).await.unwrap();
+            client.connect(OptClientConnect::new()).await.unwrap();
+        });
+        Self { runtime, local, client, received, notifier }
+    }
+
+    // send the messages by the concurrent senders, and wait until all of them were received
+    fn run(&self, messages: u64) -> Duration {
+        self.local.block_on(&self.runtime, async {
+            let per_sender = messages.div_ceil(SENDERS);
+            let target = self.received.load(Ordering::SeqCst) + per_sender * SENDERS;
+            let start = Instant::now();
+            for s in 0..SENDERS {
+                let client = self.client.clone();
+                spawn_local_task(self.notifier.clone(), "send", async move {
+                    for i in 0..per_sender {
+                        let m = Message::new(BenchMsg::Data(s * per_sender + i), 2, 1);
+                        client.send(m).await.unwrap();
+                    }
+                }).unwrap();
+            }
+            while self.received.load(Ordering::SeqCst) < target {
+                sleep(Duration::from_micros(100)).await;
+            }
+            start.elapsed()
+        })
+    }
+}
+
+impl Drop for Bench {
+    fn drop(&mut self) {
+        self.notifier.notify_all();
+    }
+}
+
+// the writes of the frames of the concurrent sends coalesced into batches, against one write
+// for every frame
+fn bench_write_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_batch");
+    group.throughput(Throughput::Elements(1));
+    for (port, max) in [(8637, 1), (8638, DEFAULT_WRITE_BATCH_MAX)] {
+        let bench = Bench::new(port, max);
+        group.bench_with_input(BenchmarkId::new("max", max), &max, |b, _| {
+            b.iter_custom(|iters| { bench.run(iters) });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_write_batch);
+criterion_main!(benches);
@@ -0,0 +1,101 @@
+use bytes::BytesMut;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// a larger buffer is dropped when given back, a large message does not pin its memory
+pub const MAX_POOLED_BUFFER_SIZE: usize = 64 * 1024;
+
+// The frame buffers of the sends of an endpoint, taken by the sending tasks to copy the
+// encoded message in, and given back by the encoder of the writer task after the frame was
+// copied into the write buffer of the stream, a buffer is owned by one send at a time.
+// The read path needs no pool, the frames are split off the read buffer of the stream, which
+// reuses their memory once they were received.
+pub struct BufferPool {
+    buffers: SyncMutex<Vec<BytesMut>>,
+    // keep up to this number of buffers, 0 allocates a buffer for every send
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: SyncMutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+        }
+    }
+
+    // an empty buffer of at least the capacity
+    pub fn take(&self, capacity: usize) -> BytesMut {
+        let opt_buf = if self.max_buffers > 0 {
+            self.buffers.lock().unwrap().pop()
+        } else {
+            None
+        };
+        match opt_buf {
+            Some(mut buf) => {
+                buf.reserve(capacity);
+                buf
+            }
+            None => { BytesMut::with_capacity(capacity) }
+        }
+    }
+
+    // truncate the buffer and keep it for the next take, or drop it when the pool is full
+    pub fn give(&self, buf: BytesMut) {
+        if self.max_buffers == 0 || buf.capacity() > MAX_POOLED_BUFFER_SIZE {
+            return;
+        }
+        let mut buf = buf;
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    pub fn pooled(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BufMut;
+
+    use crate::buffer_pool::{BufferPool, MAX_POOLED_BUFFER_SIZE};
+
+    #[test]
+    fn test_buffer_pool_reuse() {
+        let pool = BufferPool::new(2);
+        let mut buf = pool.take(100);
+        buf.put_slice(&[1u8; 100]);
+        let ptr = buf.as_ptr();
+        pool.give(buf);
+        assert_eq!(pool.pooled(), 1);
+
+        // the same memory, truncated
+        let buf = pool.take(50);
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= 100);
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(pool.pooled(), 0);
+    }
+
+    #[test]
+    fn test_buffer_pool_limits() {
+        let pool = BufferPool::new(2);
+        let bufs: Vec<_> = (0..3).map(|_| { pool.take(10) }).collect();
+        for buf in bufs {
+            pool.give(buf);
+        }
+        assert_eq!(pool.pooled(), 2);
+
+        let _ = pool.take(10);
+        pool.give(pool.take(MAX_POOLED_BUFFER_SIZE + 1));
+        assert_eq!(pool.pooled(), 1);
+
+        let disabled = BufferPool::new(0);
+        disabled.give(disabled.take(10));
+        assert_eq!(disabled.pooled(), 0);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+use tracing::trace;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::net_error;
+use crate::notifier::Notifier;
+use crate::opt_call::{InflightPolicy, OptCall};
+use crate::task::spawn_local_task;
+use crate::task_trace;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// the correlation ID of a message, a response carries the ID of its request
+pub type CorrelationFn<M> = Arc<dyn Fn(&Message<M>) -> u64 + Send + Sync>;
+
+// the time a request may take, from the call to the response, None for no deadline, see
+// `RpcEnvelope::deadline_fn`
+pub type DeadlineFn<M> = Arc<dyn Fn(&Message<M>) -> Option<Duration> + Send + Sync>;
+
+type ResponseSender<M> = oneshot::Sender<Res<Message<M>>>;
+
+// Request and response over an endpoint.
+// `call` sends a request and waits for the incoming message of the same correlation ID, the
+// calls are pipelined, a task reads the endpoint and completes the outstanding calls in any
+// order. The incoming messages matching no outstanding call are dropped, the endpoint should
+// not be read by others.
+pub struct Caller<M: MsgTrait + 'static> {
+    endpoint: Arc<dyn EndpointAsync<M>>,
+    correlation: CorrelationFn<M>,
+    pending: Arc<SyncMutex<Pending<M>>>,
+    // the permits of the outstanding calls, None for unlimited
+    opt_inflight: Option<Arc<Semaphore>>,
+    opt_call: OptCall,
+    opt_deadline: Option<DeadlineFn<M>>,
+}
+
+struct Pending<M: MsgTrait + 'static> {
+    waiters: HashMap<u64, ResponseSender<M>>,
+    // why the reader stopped, the following calls fail with it
+    opt_closed: Option<ET>,
+}
+
+// remove the waiter when the call returns, by a response, a failure, a timeout or cancellation
+struct PendingGuard<M: MsgTrait + 'static> {
+    pending: Arc<SyncMutex<Pending<M>>>,
+    id: u64,
+}
+
+impl<M: MsgTrait + 'static> Caller<M> {
+    // must be called in a LocalSet, the reader task is cancelled by the notifier
+    pub fn new(
+        endpoint: Arc<dyn EndpointAsync<M>>,
+        correlation: CorrelationFn<M>,
+        opt_call: OptCall,
+        notifier: Notifier,
+    ) -> Res<Self> {
+        let pending = Arc::new(SyncMutex::new(Pending {
+            waiters: HashMap::new(),
+            opt_closed: None,
+        }));
+        let opt_inflight = if opt_call.max_inflight() > 0 {
+            Some(Arc::new(Semaphore::new(opt_call.max_inflight())))
+        } else {
+            None
+        };
+        let ep = endpoint.clone();
+        let p = pending.clone();
+        let c = correlation.clone();
+        let task_name = format!("caller reader {}", endpoint.remote_address());
+        spawn_local_task(notifier, task_name.as_str(), async move {
+            Self::read_loop(ep, c, p).await;
+        })?;
+        Ok(Self {
+            endpoint,
+            correlation,
+            pending,
+            opt_inflight,
+            opt_call,
+            opt_deadline: None,
+        })
+    }
+
+    // A call fails with `net_error::timeout` when the deadline of the request passed before
+    // the response, the deadline is timed by a local monotonic timer started by the call, it
+    // covers the wait for a permit and the send, along with `OptCall::set_timeout`.
+    pub fn set_deadline(self, deadline: DeadlineFn<M>) -> Self {
+        let mut s = self;
+        s.opt_deadline = Some(deadline);
+        s
+    }
+
+    pub fn endpoint(&self) -> Arc<dyn EndpointAsync<M>> {
+        self.endpoint.clone()
+    }
+
+    // the number of the outstanding calls
+    pub fn inflight(&self) -> usize {
+        let pending = self.pending.lock().unwrap();
+        pending.waiters.len()
+    }
+
+    // send the request and wait for its response, the request is not sent before a permit of
+    // `max_inflight` was taken, the outstanding call is removed when it failed
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn call(&self, request: Message<M>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let opt_deadline = self.opt_deadline.as_ref().and_then(|f| { f(&request) });
+        match opt_deadline {
+            Some(duration) => {
+                match timeout(duration, self.call_inner(request)).await {
+                    Ok(r) => { r }
+                    Err(_) => { Err(net_error::timeout("the deadline of the call")) }
+                }
+            }
+            None => { self.call_inner(request).await }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn call_inner(&self, request: Message<M>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let _permit = self.acquire_inflight().await?;
+        let id = (self.correlation)(&request);
+        let (s, r) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some(e) = &pending.opt_closed {
+                return Err(e.clone());
+            }
+            if pending.waiters.contains_key(&id) {
+                return Err(ET::ExistingSuchElement);
+            }
+            let _ = pending.waiters.insert(id, s);
+        }
+        let _guard = PendingGuard {
+            pending: self.pending.clone(),
+            id,
+        };
+        self.endpoint.send(request).await?;
+        let r = match self.opt_call.timeout() {
+            Some(duration) => {
+                match timeout(duration, r).await {
+                    Ok(r) => { r }
+                    Err(_) => { return Err(net_error::timeout("waiting for the response")); }
+                }
+            }
+            None => { r.await }
+        };
+        match r {
+            Ok(r) => { r }
+            // the reader task was cancelled
+            Err(_) => { Err(ET::EOF) }
+        }
+    }
+
+    async fn acquire_inflight(&self) -> Res<Option<OwnedSemaphorePermit>> {
+        let semaphore = match &self.opt_inflight {
+            Some(s) => { s.clone() }
+            None => { return Ok(None); }
+        };
+        let r = match self.opt_call.inflight_policy() {
+            InflightPolicy::Wait => { semaphore.acquire_owned().await.ok() }
+            InflightPolicy::Error => { semaphore.try_acquire_owned().ok() }
+        };
+        match r {
+            Some(permit) => { Ok(Some(permit)) }
+            None => { Err(net_error::too_many_inflight(self.opt_call.max_inflight())) }
+        }
+    }
+
+    async fn read_loop(
+        endpoint: Arc<dyn EndpointAsync<M>>,
+        correlation: CorrelationFn<M>,
+        pending: Arc<SyncMutex<Pending<M>>>,
+    ) {
+        let e = loop {
+            let m = match endpoint.recv().await {
+                Ok(m) => { m }
+                Err(e) => { break e; }
+            };
+            let id = correlation(&m);
+            let opt_waiter = {
+                let mut pending = pending.lock().unwrap();
+                pending.waiters.remove(&id)
+            };
+            match opt_waiter {
+                Some(waiter) => { let _ = waiter.send(Ok(m)); }
+                None => { trace!("drop the response {}, no outstanding call", id); }
+            }
+        };
+        let waiters: Vec<ResponseSender<M>> = {
+            let mut pending = pending.lock().unwrap();
+            pending.opt_closed = Some(e.clone());
+            pending.waiters.drain().map(|(_, w)| { w }).collect()
+        };
+        for waiter in waiters {
+            let _ = waiter.send(Err(e.clone()));
+        }
+    }
+}
+
+impl<M: MsgTrait + 'static> Drop for PendingGuard<M> {
+    fn drop(&mut self) {
+        let mut pending = self.pending.lock().unwrap();
+        let _ = pending.waiters.remove(&self.id);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{Instrument, trace_span};
+
+use crate::endpoint_inner::_Endpoint;
+use crate::net_error;
+use crate::task_trace;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// A logical channel of an endpoint, opened by `EndpointAsync::open_channel`, its messages
+// share the connection with the default channel of `send` and `recv`, and the other channels.
+//
+// The flow is controlled by credits: the receiving side grants the peer the capacity of its
+// queue when it opens the channel, and the frames taken by `recv` back in batches. A `send`
+// waits for a credit, so it waits until the peer opened the channel too, a peer ignoring the
+// credits fails the connection. A slow consumer of a channel holds back the senders of this
+// channel only, the reader task never waits for its queue.
+pub struct ChannelHandle<M: MsgTrait + 'static> {
+    id: u16,
+    endpoint: Arc<_Endpoint>,
+    receiver: Mutex<mpsc::Receiver<BytesMut>>,
+    // the frames the peer allows to send
+    credits: Arc<Semaphore>,
+    // the frames received since the last credit granted
+    consumed: AtomicU32,
+    // the consumed frames are granted back by this number
+    grant_batch: u32,
+    _pd: PhantomData<M>,
+}
+
+// the channels of an endpoint other than the default one, routed by the reader task
+pub(crate) struct Channels {
+    inner: SyncMutex<ChannelsInner>,
+}
+
+struct ChannelsInner {
+    channels: HashMap<u16, ChannelState>,
+    // the reader task stopped
+    closed: bool,
+}
+
+// a channel is known after it was opened here, or the peer granted credits on it
+struct ChannelState {
+    // the received frames, Some after the channel was opened here
+    opt_queue: Option<mpsc::Sender<BytesMut>>,
+    credits: Arc<Semaphore>,
+}
+
+// where the reader task put a frame of a channel
+pub(crate) enum Route {
+    Delivered,
+    // the handle of the channel was dropped
+    Discarded,
+    // the channel was not opened here, the frame is given back
+    Unknown(BytesMut),
+    // the queue is full, the peer sent more frames than it was granted
+    Overrun,
+}
+
+impl<M: MsgTrait + 'static> ChannelHandle<M> {
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub(crate) async fn open(endpoint: Arc<_Endpoint>, id: u16) -> Res<Self> {
+        let _t = task_trace!();
+        let (receiver, credits, capacity) = endpoint.open_channel(id).await?;
+        Ok(Self {
+            id,
+            endpoint,
+            receiver: Mutex::new(receiver),
+            credits,
+            consumed: AtomicU32::new(0),
+            grant_batch: (capacity / 2).max(1),
+            _pd: Default::default(),
+        })
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    // wait for a credit of the peer, and send as `EndpointAsync::send`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let permit = self.credits.acquire()
+            .instrument(trace_span!("credit"))
+            .await
+            // the reader task stopped
+            .map_err(|_| { net_error::send_closed() })?;
+        // the credit is used up by the frame
+        permit.forget();
+        self.endpoint.send_channel(self.id, m).await
+    }
+
+    // receive the next message of the channel, the concurrent recvs are served in FIFO order
+    // as the ones of `EndpointAsync::recv`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn recv(&self) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let b = {
+            let mut receiver = self.receiver.lock().instrument(trace_span!("lock")).await;
+            match receiver.recv().await {
+                Some(b) => { b }
+                None => { return Err(self.endpoint.reader_state().reason()); }
+            }
+        };
+        let consumed = self.consumed.fetch_add(1, Ordering::SeqCst) + 1;
+        if consumed >= self.grant_batch {
+            let n = self.consumed.swap(0, Ordering::SeqCst);
+            if n > 0 {
+                // the write direction may have been shut down
+                let _ = self.endpoint.grant_credit(self.id, n).await;
+            }
+        }
+        let (m, _) = self.endpoint.decode_received::<M>(b)?;
+        Ok(m)
+    }
+}
+
+impl Channels {
+    pub fn new() -> Self {
+        Self {
+            inner: SyncMutex::new(ChannelsInner {
+                channels: HashMap::new(),
+                closed: false,
+            }),
+        }
+    }
+
+    // register the queue of the channel and return its credits, a channel is opened once,
+    // ET::EOF after the reader task stopped
+    pub fn open(&self, id: u16, queue: mpsc::Sender<BytesMut>) -> Res<Arc<Semaphore>> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return Err(ET::EOF);
+        }
+        let state = inner.channels.entry(id).or_insert_with(ChannelState::new);
+        if state.opt_queue.is_some() {
+            return Err(ET::ExistingSuchElement);
+        }
+        state.opt_queue = Some(queue);
+        Ok(state.credits.clone())
+    }
+
+    // queue the frame without waiting
+    pub fn route(&self, id: u16, b: BytesMut) -> Route {
+        let inner = self.inner.lock().unwrap();
+        let opt_queue = inner.channels.get(&id).and_then(|c| { c.opt_queue.as_ref() });
+        match opt_queue {
+            Some(queue) => {
+                match queue.try_send(b) {
+                    Ok(()) => { Route::Delivered }
+                    Err(TrySendError::Full(_)) => { Route::Overrun }
+                    Err(TrySendError::Closed(_)) => { Route::Discarded }
+                }
+            }
+            None => { Route::Unknown(b) }
+        }
+    }
+
+    // the peer allows n frames more on the channel
+    pub fn grant(&self, id: u16, n: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return;
+        }
+        let state = inner.channels.entry(id).or_insert_with(ChannelState::new);
+        let room = Semaphore::MAX_PERMITS - state.credits.available_permits();
+        state.credits.add_permits((n as usize).min(room));
+    }
+
+    // the reader task stopped, the sends waiting for credits fail, the recvs take the frames
+    // already queued, and then fail with the reason
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        for (_, state) in inner.channels.drain() {
+            state.credits.close();
+        }
+    }
+}
+
+impl ChannelState {
+    fn new() -> Self {
+        Self {
+            opt_queue: None,
+            credits: Arc::new(Semaphore::new(0)),
+        }
+    }
+}
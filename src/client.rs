@@ -1,23 +1,59 @@
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use async_trait::async_trait;
+use futures::{FutureExt, Stream, StreamExt};
+use futures::future::LocalBoxFuture;
+use futures::stream::{BoxStream, FuturesUnordered};
+use rand::Rng;
 use scupt_util::error_type::ET;
 use scupt_util::message::{Message, MsgTrait};
 use scupt_util::node_id::NID;
 use scupt_util::res::Res;
-use tokio::sync::Mutex;
+use tokio::select;
+use tokio::sync::watch;
 use tokio::task::LocalSet;
-use tokio::time::sleep;
+use tokio::time::timeout;
+use tracing::warn;
 
+use crate::clock::{Clock, default_clock, timeout_of};
+use crate::dedup::SendSession;
 use crate::endpoint_async::EndpointAsync;
-use crate::es_option::ESConnectOption;
-use crate::handle_event::HandleEvent;
-use crate::node::Node;
+use crate::endpoint_parallel::{EndpointParallel, Reconnect};
+use crate::es_option::{
+    DEFAULT_RECV_QUEUE_CAPACITY,
+    DEFAULT_SEND_BUFFER_POOL,
+    DEFAULT_SEND_QUEUE_CAPACITY,
+    DEFAULT_WRITE_BATCH_BYTES,
+    DEFAULT_WRITE_BATCH_MAX,
+    ESConnectOption,
+};
+use crate::event_sink_async::EventSinkAsync;
+use crate::handle_event::{HandleEvent, HandleEventDummy};
+use crate::net_error;
+use crate::node::{Node, NodeHandle};
 use crate::notifier::Notifier;
+use crate::opt_close::StopMode;
+use crate::opt_send::OptSend;
+use crate::overflow_policy::OverflowPolicy;
+use crate::priority::Priority;
+use crate::proxy::ProxyConfig;
+use crate::resolver::{DnsResolver, HostResolver};
+use crate::task::spawn_local_task;
 use crate::task_trace;
+use crate::test_controller::TestController;
+use crate::transport::{StreamTransport, Transport};
+
+type SyncRwLock<T> = std::sync::RwLock<T>;
+
+// a connect of happy eyeballs, raced with the others of the attempt
+type PendingConnect<M> = LocalBoxFuture<'static, Res<Option<Arc<dyn EndpointAsync<M>>>>>;
+
+// the connection attempt delay recommended by RFC 8305
+pub const DEFAULT_HAPPY_EYEBALLS_DELAY_MS: u64 = 250;
 
 #[derive(Clone)]
 pub struct Client<M: MsgTrait + 'static> {
@@ -27,17 +63,63 @@ pub struct Client<M: MsgTrait + 'static> {
 pub struct ClientInner<M: MsgTrait + 'static> {
     nid: NID,
     addr: String,
-    node: Node<M, Handler>,
-    opt_endpoint: Mutex<Option<Arc<dyn EndpointAsync<M>>>>,
+    node: Node<M, Handler<M>>,
+    // the endpoint options of every connect attempt
+    opt_connect: ESConnectOption,
+    // the retries of `connect_default`
+    connect_opt: OptClientConnect,
+    // look up a server address which is a host name, on every attempt
+    host_resolver: Arc<dyn HostResolver>,
+    // a lookup not done within it fails the attempt, 0 waits for the resolver
+    resolve_timeout_ms: u64,
+    // an attempt tries every address of the lookup in turn, instead of one
+    try_all_addresses: bool,
+    // see `OptClient::parallel_connections`
+    parallel_connections: usize,
+    // the clock of the retries and of the timeouts of a connect, the one of the node too
+    clock: Arc<dyn Clock>,
+    // swapped by connect and disconnect, the lock is only held to clone the endpoint out
+    opt_endpoint: SyncRwLock<Option<Arc<dyn EndpointAsync<M>>>>,
+    // bumped under the write lock of `opt_endpoint` on every swap, wakes the pending recvs
+    generation: watch::Sender<u64>,
+    state: watch::Sender<ClientState>,
+    // set by the first `close`
+    closed: AtomicBool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientState {
+    Disconnected,
+    Connected,
 }
 
 
-struct Handler {}
+// forward the events of the node to the handler given by the user
+struct Handler<M: MsgTrait + 'static> {
+    inner: Arc<dyn HandleEvent<M>>,
+}
 
 impl<M: MsgTrait + 'static> Client<M> {
     pub fn new(node_id: NID, name: String, addr: String, opt_client: OptClient, notifier: Notifier) -> Res<Self> {
+        Self::new_with_handler(node_id, name, addr, opt_client, notifier, Arc::new(HandleEventDummy::default()))
+    }
+
+    // the handler observes the events of the client, such as the errors and the disconnection,
+    // a malformed server address fails with `net_error::addr_parse`, see
+    // `ClientBuilder::set_server_addr`
+    pub fn new_with_handler(
+        node_id: NID,
+        name: String,
+        addr: String,
+        opt_client: OptClient,
+        notifier: Notifier,
+        handler: Arc<dyn HandleEvent<M>>,
+    ) -> Res<Self> {
+        if !is_server_addr(addr.as_str()) {
+            return Err(net_error::addr_parse(addr.as_str()));
+        }
         Ok(Self {
-            inner: Arc::new(ClientInner::new(node_id, name, addr, opt_client, notifier)?)
+            inner: Arc::new(ClientInner::new(node_id, name, addr, opt_client, notifier, handler)?)
         })
     }
 
@@ -45,30 +127,167 @@ impl<M: MsgTrait + 'static> Client<M> {
         self.inner.run(local);
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn is_connected(&self) -> bool {
         let _t = task_trace!();
         self.inner.is_connected().await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn connect(&self, opt: OptClientConnect) -> Res<()> {
         let _t = task_trace!();
         self.inner.connect(opt).await
     }
 
-    #[async_backtrace::framed]
+    // connect with `OptClient::connect_opt`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_default(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.connect_default().await
+    }
+
+    // connect a new endpoint with the same options, and return it to the caller instead of
+    // keeping it for `send` and `recv`, the caller owns and closes it, the endpoint of the
+    // client is not replaced
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_endpoint(&self, opt: OptClientConnect) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        self.inner.connect_endpoint(opt).await
+    }
+
+    // The endpoint currently used by `send` and `recv`, None if not connected, to pass it to
+    // the code written against EndpointAsync. The handle is not updated by a later `connect` or
+    // `disconnect`, nor by a reconnect, once replaced it is closed and its calls fail.
+    pub fn endpoint(&self) -> Option<Arc<dyn EndpointAsync<M>>> {
+        self.inner.opt_endpoint()
+    }
+
+    // the sink of the node under the client, to connect or serve more endpoints on it
+    pub fn default_event_sink(&self) -> Arc<dyn EventSinkAsync<M>> {
+        self.inner.node.default_event_sink()
+    }
+
+    pub fn state(&self) -> ClientState {
+        self.inner.state()
+    }
+
+    // wait until the client was connected, by a `connect` running in another task, return
+    // immediately if it is already connected, or `net_error::timeout` after the duration
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn wait_connected(&self, duration: Duration) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.wait_connected(duration).await
+    }
+
+    // the endpoint is taken under the lock and the lock released before the write, the frames
+    // of concurrent sends are queued for the writer task of the endpoint, a slow write does not
+    // block the other sends, `is_connected` or `state`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn send(&self, message: Message<M>) -> Res<()> {
         let _t = task_trace!();
         self.inner.send(message).await
     }
 
-    #[async_backtrace::framed]
+    // a High priority message is written before the queued Normal and Low ones, see `Priority`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_priority(&self, message: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_priority(message, priority).await
+    }
+
+    // send the messages on the current endpoint, see `EndpointAsync::send_all`, the messages
+    // after a failure are not sent
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_all<'a, I: Iterator<Item=Message<M>> + Send + 'a>(&self, messages: I) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        self.inner.send_all(Box::new(messages)).await
+    }
+
+    // see `EndpointAsync::send_stream`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_stream<'a, S: Stream<Item=Message<M>> + Send + 'a>(&self, messages: S) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        self.inner.send_stream(messages.boxed()).await
+    }
+
+    // the socket is flushed right after the message, it is not coalesced with the messages sent
+    // after it, see `OptSend::enable_flush`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_flush(&self, message: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_flush(message).await
+    }
+
+    // resolve after the message was flushed to the socket, not after the server received it,
+    // see `EndpointAsync::send_confirmed`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_confirmed(&self, message: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_confirmed(message).await
+    }
+
+    // a recv pending on an endpoint replaced by `connect` or `disconnect` fails with
+    // `net_error::net_reset`, a retry picks up the current endpoint, `net_error::net_error_kind`
+    // tells a client not connected from a connection reset and from a peer closed cleanly
+    // The clones of a client may recv concurrently, such as a pool of workers, every message
+    // goes to one of them, in the order they called, see `EndpointAsync::recv`.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn recv(&self) -> Res<Message<M>> {
         let _t = task_trace!();
         self.inner.recv().await
     }
 
+    // see `EndpointAsync::recv_filter`, it fails as `recv` when the endpoint was replaced, the
+    // messages passed over stay with the old endpoint
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn recv_filter(&self, pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        self.inner.recv_filter(pred).await
+    }
+
+    // the round trip time of a ping on the connection, `net_error::timeout` if the pong did
+    // not arrive within the duration, the peer is then likely gone and worth a reconnect, the
+    // concurrent pings are told apart by their nonces, see `EndpointAsync::ping`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        self.inner.ping(duration).await
+    }
+
+    // swap out the endpoint, and close it after the messages already queued were written,
+    // the following sends fail with ET::NetNotConnected until the client connects again
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn disconnect(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.disconnect().await
+    }
+
+    // Close the connection and stop the node, call it before dropping the last clone of the
+    // client, the drop cannot wait for the cleanup and only aborts the tasks of the node.
+    // Close again is a no-op, the sends and connects of the closed client fail with
+    // ET::NetNotConnected, and so do the sends in flight if the endpoint was shut down first.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.close().await
+    }
+
+    // close as `close`, StopMode::Drain bounds the time to write the queued messages, of the
+    // endpoint of the client and of the others of its node, StopMode::Abort drops them
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close_with(&self, mode: StopMode) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.close_with(mode).await
+    }
+
+    // signal the server there are no more messages by shutting down the write direction,
+    // responses can still be received by `recv`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close_send(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.close_send().await
+    }
+
     pub fn node_id(&self) -> NID {
         self.inner.nid
     }
@@ -76,21 +295,359 @@ impl<M: MsgTrait + 'static> Client<M> {
     pub fn server_addr(&self) -> String {
         self.inner.addr.clone()
     }
+
+    // the node under the client, to inspect or share it
+    pub fn node_handle(&self) -> NodeHandle<M> {
+        self.inner.node.handle()
+    }
+
+    // the fault injection rules, None if `OptClient::enable_testing` is false
+    pub fn test_controller(&self) -> Option<Arc<TestController<M>>> {
+        self.inner.node.test_controller()
+    }
 }
 
-impl Handler {
-    fn new() -> Self {
-        Self {}
+impl<M: MsgTrait + 'static> Handler<M> {
+    fn new(inner: Arc<dyn HandleEvent<M>>) -> Self {
+        Self { inner }
     }
 }
 
+// The options of the client and its connection, the default is what a client used before the
+// tuning fields were added.
+#[derive(Clone)]
 pub struct OptClient {
     pub enable_testing: bool,
+    // set TCP_NODELAY on the socket
+    pub nodelay: bool,
+    // see `ESConnectOption::set_send_queue_capacity`, must be greater than 0
+    pub send_queue_capacity: usize,
+    // see `ESConnectOption::set_recv_queue_capacity`, must be greater than 0
+    pub recv_queue_capacity: usize,
+    // see `ESConnectOption::set_overflow_policy`
+    pub overflow_policy: OverflowPolicy,
+    // see `ESConnectOption::set_max_message_size`
+    pub max_message_size: Option<usize>,
+    // used by `Client::connect_default`
+    pub connect_opt: OptClientConnect,
+    // see `ESConnectOption::set_proxy`
+    pub proxy: Option<ProxyConfig>,
+    // The connections of the client to the server, must be greater than 0. The sends go to
+    // the connections in turn, the messages received by all of them are returned by `recv`,
+    // in order for the messages of one connection, in no particular order across them. A
+    // failed connection is reconnected alone, the others carry the sends meanwhile.
+    pub parallel_connections: usize,
+}
+
+impl OptClient {
+    pub fn new() -> Self {
+        Self {
+            enable_testing: false,
+            nodelay: false,
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
+            recv_queue_capacity: DEFAULT_RECV_QUEUE_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            max_message_size: None,
+            connect_opt: OptClientConnect::new(),
+            proxy: None,
+            parallel_connections: 1,
+        }
+    }
+
+    fn validate(&self) -> Res<()> {
+        if self.send_queue_capacity == 0 {
+            return Err(net_error::invalid_option_of("send_queue_capacity", "a queue of 0 frames"));
+        }
+        if self.recv_queue_capacity == 0 {
+            return Err(net_error::invalid_option_of("recv_queue_capacity", "a queue of 0 frames"));
+        }
+        if self.parallel_connections == 0 {
+            return Err(net_error::invalid_option_of("parallel_connections", "no connection of the client"));
+        }
+        Ok(())
+    }
+
+    fn opt_connect(&self) -> ESConnectOption {
+        ESConnectOption::new()
+            .enable_nodelay(self.nodelay)
+            .set_send_queue_capacity(self.send_queue_capacity)
+            .set_recv_queue_capacity(self.recv_queue_capacity)
+            .set_overflow_policy(self.overflow_policy)
+            .set_max_message_size(self.max_message_size)
+            .set_proxy(self.proxy.clone())
+    }
+}
+
+impl Default for OptClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+// Build a Client, the node id and the server address are required, the name defaults to
+// "client_<node id>" and the notifier to a new one. The options are validated when building,
+// the error names the offending field.
+pub struct ClientBuilder {
+    opt_node_id: Option<NID>,
+    opt_name: Option<String>,
+    addr: String,
+    opt_client: OptClient,
+    opt_notifier: Option<Notifier>,
+    transport: Transport,
+    opt_stream_transport: Option<Arc<dyn StreamTransport>>,
+    dedup: bool,
+    idle_timeout_ms: u64,
+    write_timeout_ms: u64,
+    write_batch_max: usize,
+    write_batch_bytes: usize,
+    send_buffer_pool: usize,
+    resend_unsent: bool,
+    advertise_name: bool,
+    opt_host_resolver: Option<Arc<dyn HostResolver>>,
+    resolve_timeout_ms: u64,
+    try_all_addresses: bool,
+    opt_clock: Option<Arc<dyn Clock>>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            opt_node_id: None,
+            opt_name: None,
+            addr: String::new(),
+            opt_client: OptClient::new(),
+            opt_notifier: None,
+            transport: Transport::default(),
+            opt_stream_transport: None,
+            dedup: false,
+            idle_timeout_ms: 0,
+            write_timeout_ms: 0,
+            write_batch_max: DEFAULT_WRITE_BATCH_MAX,
+            write_batch_bytes: DEFAULT_WRITE_BATCH_BYTES,
+            send_buffer_pool: DEFAULT_SEND_BUFFER_POOL,
+            resend_unsent: false,
+            advertise_name: false,
+            opt_host_resolver: None,
+            resolve_timeout_ms: 0,
+            try_all_addresses: false,
+            opt_clock: None,
+        }
+    }
+
+    pub fn set_node_id(self, node_id: NID) -> Self {
+        let mut s = self;
+        s.opt_node_id = Some(node_id);
+        s
+    }
+
+    pub fn set_name(self, name: String) -> Self {
+        let mut s = self;
+        s.opt_name = Some(name);
+        s
+    }
+
+    // an IPv4 or a bracketed IPv6 socket address, such as "[::1]:8080", or a host name and a
+    // port, such as "localhost:8080", resolved by every attempt of a connect, the attempts of
+    // a connect with retries go through the resolved addresses in turn
+    pub fn set_server_addr(self, addr: String) -> Self {
+        let mut s = self;
+        s.addr = addr;
+        s
+    }
+
+    pub fn enable_testing(self, enable: bool) -> Self {
+        let mut s = self;
+        s.opt_client.enable_testing = enable;
+        s
+    }
+
+    // replace the options of the client, including `enable_testing`, see `OptClient`
+    pub fn set_opt_client(self, opt_client: OptClient) -> Self {
+        let mut s = self;
+        s.opt_client = opt_client;
+        s
+    }
+
+    pub fn set_notifier(self, notifier: Notifier) -> Self {
+        let mut s = self;
+        s.opt_notifier = Some(notifier);
+        s
+    }
+
+    // Transport::Memory connects to a node served in the same process, without sockets and
+    // ports, for tests. It is independent of `enable_testing`, the endpoints of a testing
+    // client do not write the messages.
+    pub fn set_transport(self, transport: Transport) -> Self {
+        let mut s = self;
+        s.transport = transport;
+        s
+    }
+
+    // connect by the streams of the transport given by the user, see `Node::set_stream_transport`
+    pub fn set_stream_transport(self, stream_transport: Arc<dyn StreamTransport>) -> Self {
+        let mut s = self;
+        s.opt_stream_transport = Some(stream_transport);
+        s.transport = Transport::Custom;
+        s
+    }
+
+    // the endpoint options of the connection, see the same ones of `ESConnectOption`
+    pub fn enable_dedup(self, dedup: bool) -> Self {
+        let mut s = self;
+        s.dedup = dedup;
+        s
+    }
+
+    pub fn set_idle_timeout_ms(self, idle_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.idle_timeout_ms = idle_timeout_ms;
+        s
+    }
+
+    pub fn set_write_timeout_ms(self, write_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.write_timeout_ms = write_timeout_ms;
+        s
+    }
+
+    pub fn set_write_batch(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.write_batch_max = max;
+        s.write_batch_bytes = bytes;
+        s
+    }
+
+    pub fn set_send_buffer_pool(self, buffers: usize) -> Self {
+        let mut s = self;
+        s.send_buffer_pool = buffers;
+        s
+    }
+
+    // the frames left unsent by a failed connection are sent by the next `Client::connect`
+    pub fn enable_resend_unsent(self, resend_unsent: bool) -> Self {
+        let mut s = self;
+        s.resend_unsent = resend_unsent;
+        s
+    }
+
+    // send the name of the client to the server, see `OptNode::enable_advertise_name`
+    pub fn enable_advertise_name(self, advertise_name: bool) -> Self {
+        let mut s = self;
+        s.advertise_name = advertise_name;
+        s
+    }
+
+    // look up the host name of the server address by the resolver, the default is
+    // `DnsResolver`
+    pub fn set_host_resolver(self, resolver: Arc<dyn HostResolver>) -> Self {
+        let mut s = self;
+        s.opt_host_resolver = Some(resolver);
+        s
+    }
+
+    // a lookup of the host name not done within it fails the attempt, which is retried as a
+    // refused one, so that a stalled resolver does not stall the retries, 0 waits for it
+    pub fn set_resolve_timeout_ms(self, resolve_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.resolve_timeout_ms = resolve_timeout_ms;
+        s
+    }
+
+    // every attempt of a connect tries all the addresses of the lookup in turn before it
+    // fails, the default tries one address an attempt
+    pub fn enable_try_all_addresses(self, try_all_addresses: bool) -> Self {
+        let mut s = self;
+        s.try_all_addresses = try_all_addresses;
+        s
+    }
+
+    // the clock of the retries and the timeouts of a connect, and of the ones of the node of
+    // the client, see `Clock`, the default is the tokio timer
+    pub fn set_clock(self, clock: Arc<dyn Clock>) -> Self {
+        let mut s = self;
+        s.opt_clock = Some(clock);
+        s
+    }
+
+    pub fn build<M: MsgTrait + 'static>(self) -> Res<Client<M>> {
+        self.build_with_handler(Arc::new(HandleEventDummy::default()))
+    }
+
+    pub fn build_with_handler<M: MsgTrait + 'static>(self, handler: Arc<dyn HandleEvent<M>>) -> Res<Client<M>> {
+        let node_id = match self.opt_node_id {
+            Some(id) => { id }
+            None => { return Err(net_error::invalid_option_of("node_id", "the node id of the client is not set")); }
+        };
+        if self.addr.is_empty() {
+            return Err(net_error::invalid_option_of("server_addr", "the server address of the client is empty"));
+        }
+        if !is_server_addr(self.addr.as_str()) {
+            return Err(net_error::addr_parse_of("server_addr", self.addr.as_str()));
+        }
+        if self.write_batch_max == 0 {
+            return Err(net_error::invalid_option_of("write_batch_max", "a write batch of 0 frames"));
+        }
+        if self.transport == Transport::Custom && self.opt_stream_transport.is_none() {
+            return Err(net_error::invalid_option_of("stream_transport", "Transport::Custom without a StreamTransport"));
+        }
+        let name = self.opt_name.unwrap_or_else(|| { format!("client_{}", node_id) });
+        let notifier = self.opt_notifier.unwrap_or_default();
+        let mut inner = ClientInner::new(
+            node_id,
+            name,
+            self.addr,
+            self.opt_client,
+            notifier,
+            handler,
+        )?;
+        inner.opt_connect = inner.opt_connect.clone()
+            .enable_dedup(self.dedup)
+            .set_idle_timeout_ms(self.idle_timeout_ms)
+            .set_write_timeout_ms(self.write_timeout_ms)
+            .set_write_batch(self.write_batch_max, self.write_batch_bytes)
+            .set_send_buffer_pool(self.send_buffer_pool)
+            .enable_resend_unsent(self.resend_unsent);
+        if self.dedup {
+            // the connections of the client number their frames by one session, the peer drops
+            // the ones a replacing connection resent and it received already
+            inner.opt_connect = inner.opt_connect.clone().set_dedup_session(Some(Arc::new(SendSession::new())));
+        }
+        if let Some(r) = self.opt_host_resolver {
+            inner.host_resolver = r;
+        }
+        inner.resolve_timeout_ms = self.resolve_timeout_ms;
+        inner.try_all_addresses = self.try_all_addresses;
+        if let Some(clock) = self.opt_clock {
+            inner.node.set_clock(clock.clone());
+            inner.clock = clock;
+        }
+        inner.node.set_transport(self.transport);
+        inner.node.set_opt_node(inner.node.opt_node().enable_advertise_name(self.advertise_name));
+        if let Some(t) = self.opt_stream_transport {
+            inner.node.set_stream_transport(t);
+        }
+        Ok(Client { inner: Arc::new(inner) })
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
 pub struct OptClientConnect {
     pub retry_max: u64,
     pub retry_wait_ms: u64,
+    // an attempt not connected within it times out and is retried, 0 waits for the transport
+    pub connect_timeout_ms: u64,
+    // see `OptClientConnect::enable_happy_eyeballs`
+    pub happy_eyeballs: bool,
+    // the delay before the next address of an attempt of happy eyeballs is started
+    pub happy_eyeballs_delay_ms: u64,
+    // see `OptClientConnect::set_jitter_fraction`
+    pub jitter_fraction: f64,
 }
 
 impl OptClientConnect {
@@ -98,10 +655,59 @@ impl OptClientConnect {
         Self {
             retry_max: 0,
             retry_wait_ms: 50,
+            connect_timeout_ms: 0,
+            happy_eyeballs: false,
+            happy_eyeballs_delay_ms: DEFAULT_HAPPY_EYEBALLS_DELAY_MS,
+            jitter_fraction: 0.0,
+        }
+    }
+
+    // Every attempt connects to all the addresses of the server, alternating the IPv6 and the
+    // IPv4 ones from the family of the first, RFC 8305. The next address is started when the
+    // previous one failed or did not connect within `happy_eyeballs_delay_ms`, the first
+    // connected one is kept, the others are closed. It overrides
+    // `ClientBuilder::enable_try_all_addresses`.
+    pub fn enable_happy_eyeballs(self, happy_eyeballs: bool) -> Self {
+        let mut s = self;
+        s.happy_eyeballs = happy_eyeballs;
+        s
+    }
+
+    // Every wait before a retry, and before a reconnect of a parallel connection, is drawn
+    // uniformly from `retry_wait_ms` * (1 +- fraction), so the clients of a restarted server
+    // do not reconnect in lockstep. A fraction in [0, 1], 0 waits exactly `retry_wait_ms`.
+    pub fn set_jitter_fraction(self, fraction: f64) -> Self {
+        let mut s = self;
+        s.jitter_fraction = fraction;
+        s
+    }
+
+    // The wait before the next retry, see `OptClientConnect::set_jitter_fraction`. The jitter
+    // is drawn from `rand::thread_rng`, a CSPRNG of each thread seeded from the OS, so the
+    // clients of different processes and threads draw independent waits.
+    pub fn retry_wait(&self) -> Duration {
+        jittered(Duration::from_millis(self.retry_wait_ms), self.jitter_fraction)
+    }
+
+    fn validate(&self) -> Res<()> {
+        if !(0.0..=1.0).contains(&self.jitter_fraction) {
+            return Err(net_error::invalid_option_of(
+                "jitter_fraction",
+                format!("{} not in [0, 1]", self.jitter_fraction).as_str(),
+            ));
         }
+        Ok(())
     }
 }
 
+// the base wait scaled by a uniform random factor in [1 - fraction, 1 + fraction]
+pub(crate) fn jittered(base: Duration, fraction: f64) -> Duration {
+    if fraction <= 0.0 {
+        return base;
+    }
+    base.mul_f64(1.0 + rand::thread_rng().gen_range(-fraction..=fraction))
+}
+
 impl Default for OptClientConnect {
     fn default() -> Self {
         Self::new()
@@ -109,12 +715,30 @@ impl Default for OptClientConnect {
 }
 
 impl<M: MsgTrait + 'static> ClientInner<M> {
-    pub fn new(node_id: NID, name: String, addr: String, opt: OptClient, notifier: Notifier) -> Res<Self> {
+    pub fn new(
+        node_id: NID,
+        name: String,
+        addr: String,
+        opt: OptClient,
+        notifier: Notifier,
+        handler: Arc<dyn HandleEvent<M>>,
+    ) -> Res<Self> {
+        opt.validate()?;
         let r = Self {
             nid: node_id.clone(),
             addr,
-            node: Node::new(node_id, name, Handler::new(), opt.enable_testing, notifier)?,
+            node: Node::new(node_id, name, Handler::new(handler), opt.enable_testing, notifier)?,
+            opt_connect: opt.opt_connect(),
+            connect_opt: opt.connect_opt.clone(),
+            host_resolver: Arc::new(DnsResolver::default()),
+            resolve_timeout_ms: 0,
+            try_all_addresses: false,
+            parallel_connections: opt.parallel_connections,
+            clock: default_clock(),
             opt_endpoint: Default::default(),
+            generation: watch::channel(0).0,
+            state: watch::channel(ClientState::Disconnected).0,
+            closed: AtomicBool::new(false),
         };
         Ok(r)
     }
@@ -123,79 +747,650 @@ impl<M: MsgTrait + 'static> ClientInner<M> {
         self.node.run_local(local);
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn is_connected(&self) -> bool {
         let _t = task_trace!();
-        let g = self.opt_endpoint.lock().await;
+        let g = self.opt_endpoint.read().unwrap();
         g.is_some()
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn connect(&self, opt: OptClientConnect) -> Res<()> {
         let _t = task_trace!();
-        let mut opt_ep = None;
+        opt.validate()?;
+        let opt_ep = self.connect_retry(opt.clone()).await?;
+        let opt_ep = match opt_ep {
+            Some(e) if self.parallel_connections > 1 => { Some(self.connect_parallel(e, &opt).await?) }
+            opt_ep => { opt_ep }
+        };
+        if let Some(e) = opt_ep {
+            // closed while connecting, the new endpoint is not kept
+            if self.is_closed() {
+                let _ = e.close().await;
+                return Err(ET::NetNotConnected);
+            }
+            // the frames left unsent by the previous connection go before any new send
+            if self.opt_connect.resend_unsent() {
+                if let Some(old) = self.opt_endpoint() {
+                    if let Ok(unsent) = old.take_unsent().await {
+                        if !unsent.is_empty() {
+                            let _ = e.resend_unsent(unsent).await;
+                        }
+                    }
+                }
+            }
+            let opt_old = self.swap_endpoint(Some(e));
+            let _ = self.state.send_replace(ClientState::Connected);
+            // connected again without a disconnect, the old endpoint is closed
+            if let Some(old) = opt_old {
+                let _ = old.shutdown_write().await;
+                let _ = old.close().await;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_default(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.connect(self.connect_opt.clone()).await
+    }
+
+    // connect the other connections to the address of the first one, the endpoint of all of
+    // them replaces the one of the client, see `OptClient::parallel_connections`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_parallel(
+        &self,
+        first: Arc<dyn EndpointAsync<M>>,
+        opt: &OptClientConnect,
+    ) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        let address = first.remote_address();
+        let mut endpoints = vec![first];
+        while endpoints.len() < self.parallel_connections {
+            match self.connect_attempt_timeout(address, 1, None, opt.connect_timeout_ms).await {
+                Ok(Some(e)) => { endpoints.push(e); }
+                Ok(None) => { break; }
+                Err(e) => {
+                    for ep in endpoints {
+                        let _ = ep.close().await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let sink = self.node.default_event_sink();
+        let node_id = self.nid;
+        let opt_connect = self.opt_connect.clone()
+            .enable_no_wait(false)
+            .enable_return_endpoint(true);
+        let timeout_ms = opt.connect_timeout_ms;
+        let clock = self.clock.clone();
+        let reconnect: Reconnect<M> = Arc::new(move || {
+            let f = connect_by(sink.clone(), node_id, address, opt_connect.clone(), timeout_ms, clock.clone());
+            async move {
+                match f.await? {
+                    Some(e) => { Ok(e) }
+                    None => { Err(ET::NetNotConnected) }
+                }
+            }.boxed_local()
+        });
+        let retry_wait = Duration::from_millis(opt.retry_wait_ms);
+        let ep = EndpointParallel::start(
+            endpoints, reconnect, retry_wait, opt.jitter_fraction, self.clock.clone(), self.node.stop_notify(),
+        );
+        Ok(Arc::new(ep))
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_endpoint(&self, opt: OptClientConnect) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        opt.validate()?;
+        match self.connect_retry(opt).await? {
+            Some(e) => { Ok(e) }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
+
+    // Connect until an attempt succeeds or the retries run out. The retries run out with the
+    // error of the last attempt, or `net_error::connect_timeout` if every attempt timed out.
+    // Every attempt resolves the server address again.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_retry(&self, opt: OptClientConnect) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        if self.is_closed() {
+            return Err(ET::NetNotConnected);
+        }
         let mut n = opt.retry_max;
+        let mut attempt = 0;
+        let mut all_timed_out = true;
+        let mut opt_error = None;
         while opt.retry_max == 0 || n > 0 {
-            let sockaddr = SocketAddr::from_str(self.addr.as_str()).unwrap();
-            let r = self.node.default_event_sink().connect(
-                self.nid, sockaddr,
-                ESConnectOption::new()
-                    .enable_no_wait(false)
-                    .enable_return_endpoint(true)).await;
-            if let Ok(e) = r {
-                opt_ep = e;
-                break;
-            } else {
-                sleep(Duration::from_millis(opt.retry_wait_ms)).await;
+            attempt += 1;
+            let r = match self.resolve().await {
+                Ok(addresses) => {
+                    self.connect_addresses(&addresses, attempt, opt_error.clone(), &opt).await
+                }
+                Err(e) => { Err(e) }
+            };
+            match r {
+                Ok(e) => {
+                    return Ok(e);
+                }
+                // such as the address in use, retrying would fail the same way
+                Err(e) if !net_error::is_retryable(&e) => {
+                    return Err(e);
+                }
+                Err(e) => {
+                    all_timed_out = all_timed_out && net_error::is_timeout(&e);
+                    opt_error = Some(e);
+                    self.clock.sleep(opt.retry_wait()).await;
+                }
             }
             if n > 0 {
                 n -= 1;
             }
         };
+        match opt_error {
+            Some(_) if all_timed_out => { Err(net_error::connect_timeout(attempt)) }
+            Some(e) => { Err(e) }
+            None => { Ok(None) }
+        }
+    }
+
+    // the attempts go through the resolved addresses in turn, or each attempt tries all of
+    // them, see `ClientBuilder::enable_try_all_addresses`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_addresses(
+        &self,
+        addresses: &[SocketAddr],
+        attempt: u64,
+        opt_last_error: Option<ET>,
+        opt: &OptClientConnect,
+    ) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        let timeout_ms = opt.connect_timeout_ms;
+        if opt.happy_eyeballs && addresses.len() > 1 {
+            let delay = Duration::from_millis(opt.happy_eyeballs_delay_ms);
+            return self.connect_happy_eyeballs(addresses, attempt, opt_last_error, timeout_ms, delay).await;
+        }
+        if !self.try_all_addresses {
+            let address = addresses[((attempt - 1) as usize) % addresses.len()];
+            return self.connect_attempt_timeout(address, attempt, opt_last_error, timeout_ms).await;
+        }
+        let mut opt_error = opt_last_error;
+        for address in addresses {
+            match self.connect_attempt_timeout(*address, attempt, opt_error.clone(), timeout_ms).await {
+                Ok(e) => { return Ok(e); }
+                Err(e) if !net_error::is_retryable(&e) => { return Err(e); }
+                Err(e) => { opt_error = Some(e); }
+            }
+        }
+        match opt_error {
+            Some(e) => { Err(e) }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
+
+    // the connects to the addresses are started one after another, each after the previous
+    // one failed or the delay passed, and raced, see `OptClientConnect::enable_happy_eyeballs`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_happy_eyeballs(
+        &self,
+        addresses: &[SocketAddr],
+        attempt: u64,
+        opt_last_error: Option<ET>,
+        timeout_ms: u64,
+        delay: Duration,
+    ) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        let addresses = interleave_families(addresses);
+        let mut pending = FuturesUnordered::new();
+        let mut next = 0;
+        let mut start_next = true;
+        let mut opt_error = opt_last_error;
+        loop {
+            if start_next && next < addresses.len() {
+                pending.push(self.connect_attempt_owned(addresses[next], attempt, opt_error.clone(), timeout_ms));
+                next += 1;
+            }
+            start_next = false;
+            if pending.is_empty() {
+                break;
+            }
+            let more = next < addresses.len();
+            let opt_done = select! {
+                r = pending.next() => { r }
+                _ = self.clock.sleep(delay), if more => { None }
+            };
+            match opt_done {
+                Some(Ok(e)) => {
+                    self.close_pending(pending);
+                    return Ok(e);
+                }
+                Some(Err(e)) if !net_error::is_retryable(&e) => {
+                    self.close_pending(pending);
+                    return Err(e);
+                }
+                Some(Err(e)) => {
+                    opt_error = Some(e);
+                    start_next = true;
+                }
+                None => {
+                    start_next = true;
+                }
+            }
+        }
+        match opt_error {
+            Some(e) => { Err(e) }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
+
+    // the connects which lost the race are left to complete, their endpoints are closed
+    fn close_pending(&self, pending: FuturesUnordered<PendingConnect<M>>) {
+        if pending.is_empty() {
+            return;
+        }
+        let mut pending = pending;
+        let _ = spawn_local_task(self.node.stop_notify(), "close the connects not kept", async move {
+            while let Some(r) = pending.next().await {
+                if let Ok(Some(e)) = r {
+                    let _ = e.close().await;
+                }
+            }
+        });
+    }
+
+    // a connect attempt which does not borrow the client, as `connect_attempt_timeout`
+    fn connect_attempt_owned(
+        &self,
+        address: SocketAddr,
+        attempt: u64,
+        opt_last_error: Option<ET>,
+        timeout_ms: u64,
+    ) -> PendingConnect<M> {
+        let opt = self.opt_connect.clone()
+            .enable_no_wait(false)
+            .enable_return_endpoint(true)
+            .set_attempt(attempt, opt_last_error);
+        connect_by(self.node.default_event_sink(), self.nid, address, opt, timeout_ms, self.clock.clone())
+    }
+
+    // the socket address, or the addresses of the host name, a lookup not done within the
+    // resolve timeout fails with `net_error::timeout`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn resolve(&self) -> Res<Vec<SocketAddr>> {
+        let _t = task_trace!();
+        if let Ok(a) = SocketAddr::from_str(self.addr.as_str()) {
+            return Ok(vec![a]);
+        }
+        let lookup = self.host_resolver.lookup(self.addr.as_str());
+        let addresses = if self.resolve_timeout_ms == 0 {
+            lookup.await?
+        } else {
+            match timeout_of(self.clock.as_ref(), Duration::from_millis(self.resolve_timeout_ms), lookup).await {
+                Some(r) => { r? }
+                None => { return Err(net_error::timeout("resolving the server address")); }
+            }
+        };
+        if addresses.is_empty() {
+            return Err(net_error::addr_parse(self.addr.as_str()));
+        }
+        Ok(addresses)
+    }
+
+    // an attempt timed out fails with `net_error::timeout`, and is retried
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_attempt_timeout(
+        &self,
+        address: SocketAddr,
+        attempt: u64,
+        opt_last_error: Option<ET>,
+        timeout_ms: u64,
+    ) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        if timeout_ms == 0 {
+            return self.connect_attempt(address, attempt, opt_last_error).await;
+        }
+        let connect = self.connect_attempt(address, attempt, opt_last_error);
+        match timeout_of(self.clock.as_ref(), Duration::from_millis(timeout_ms), connect).await {
+            Some(r) => { r }
+            None => { Err(net_error::timeout("connecting an attempt")) }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn disconnect(&self) -> Res<()> {
+        let _t = task_trace!();
+        let opt_ep = self.swap_endpoint(None);
+        let _ = self.state.send_replace(ClientState::Disconnected);
+        match opt_ep {
+            Some(e) => {
+                // the messages already queued are written before the connection is closed
+                let _ = e.shutdown_write().await;
+                e.close().await
+            }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
 
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close(&self) -> Res<()> {
+        let _t = task_trace!();
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let opt_ep = self.swap_endpoint(None);
+        let _ = self.state.send_replace(ClientState::Disconnected);
         if let Some(e) = opt_ep {
-            let mut guard = self.opt_endpoint.lock().await;
-            *guard = Some(e);
+            let _ = e.shutdown_write().await;
+            let _ = e.close().await;
         }
+        // the node may have been stopped by its notifier already
+        let _ = self.node.handle().shutdown().await;
         Ok(())
     }
 
-    #[async_backtrace::framed]
-    pub async fn send(&self, message: Message<M>) -> Res<()> {
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close_with(&self, mode: StopMode) -> Res<()> {
         let _t = task_trace!();
-        let guard = self.opt_endpoint.lock().await;
-        if let Some(e) = &(*guard) {
-            e.send(message).await?;
+        if self.closed.swap(true, Ordering::SeqCst) {
             return Ok(());
-        } else {
-            Err(ET::NetNotConnected)
         }
+        // kept alive by the swapped out endpoint until the node drained it
+        let opt_ep = self.swap_endpoint(None);
+        let _ = self.state.send_replace(ClientState::Disconnected);
+        let _ = self.node.handle().shutdown_with(mode).await;
+        if let Some(e) = opt_ep {
+            let _ = e.close().await;
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub fn state(&self) -> ClientState {
+        *self.state.borrow()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn wait_connected(&self, duration: Duration) -> Res<()> {
+        let _t = task_trace!();
+        let mut receiver = self.state.subscribe();
+        let r = timeout(duration, async move {
+            receiver.wait_for(|s| { *s == ClientState::Connected }).await.is_ok()
+        }).await;
+        match r {
+            Ok(true) => { Ok(()) }
+            Ok(false) => { Err(ET::NetNotConnected) }
+            Err(_) => { Err(net_error::timeout("waiting for the client to connect")) }
+        }
+    }
+
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(
+        name = "connect_attempt", level = "debug", skip_all,
+        fields(nid = self.nid, addr = %address, attempt = attempt)
+    ))]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_attempt(
+        &self,
+        address: SocketAddr,
+        attempt: u64,
+        opt_last_error: Option<ET>,
+    ) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        // the handler of the node reports the attempt
+        self.node.default_event_sink().connect(
+            self.nid, address,
+            self.opt_connect.clone()
+                .enable_no_wait(false)
+                .enable_return_endpoint(true)
+                .set_attempt(attempt, opt_last_error)).await
+    }
+
+    // the lock is not held while sending or receiving, concurrent sends can preempt each other
+    // by priority, and a pending recv does not block the sends
+    fn endpoint(&self) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let guard = self.opt_endpoint.read().unwrap();
+        match &(*guard) {
+            Some(e) => { Ok(e.clone()) }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
+
+    pub fn opt_endpoint(&self) -> Option<Arc<dyn EndpointAsync<M>>> {
+        self.opt_endpoint.read().unwrap().clone()
+    }
+
+    // the endpoint and its generation, read under the same lock
+    fn endpoint_generation(&self) -> Res<(Arc<dyn EndpointAsync<M>>, u64)> {
+        let guard = self.opt_endpoint.read().unwrap();
+        match &(*guard) {
+            Some(e) => { Ok((e.clone(), *self.generation.borrow())) }
+            None => { Err(ET::NetNotConnected) }
+        }
+    }
+
+    fn swap_endpoint(&self, opt_endpoint: Option<Arc<dyn EndpointAsync<M>>>) -> Option<Arc<dyn EndpointAsync<M>>> {
+        let mut guard = self.opt_endpoint.write().unwrap();
+        let opt_old = std::mem::replace(&mut *guard, opt_endpoint);
+        self.generation.send_modify(|g| { *g += 1; });
+        opt_old
+    }
+
+    // a send racing with `disconnect` either completes on the old endpoint, or fails with
+    // ET::NetNotConnected if the endpoint was shut down before the message was written, the
+    // errors of the current endpoint are returned as is, see `net_error::net_error_kind`
+    fn send_result(&self, endpoint: &Arc<dyn EndpointAsync<M>>, r: Res<()>) -> Res<()> {
+        match r {
+            Err(e) if net_error::is_send_closed(&e) || net_error::is_writer_stopped(&e) => {
+                let guard = self.opt_endpoint.read().unwrap();
+                let is_current = match &(*guard) {
+                    Some(current) => {
+                        Arc::as_ptr(current) as *const u8 == Arc::as_ptr(endpoint) as *const u8
+                    }
+                    None => { false }
+                };
+                if is_current {
+                    Err(e)
+                } else {
+                    Err(ET::NetNotConnected)
+                }
+            }
+            r => { r }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send(&self, message: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        let r = e.send(message).await;
+        self.send_result(&e, r)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_priority(&self, message: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        let r = e.send_priority(message, priority).await;
+        self.send_result(&e, r)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_all(&self, messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        let e = match self.endpoint() {
+            Ok(e) => { e }
+            Err(e) => { return (0, Err(e)); }
+        };
+        let (n, r) = e.send_all(messages).await;
+        (n, self.send_result(&e, r))
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_stream(&self, messages: BoxStream<'_, Message<M>>) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        let e = match self.endpoint() {
+            Ok(e) => { e }
+            Err(e) => { return (0, Err(e)); }
+        };
+        let (n, r) = e.send_stream(messages).await;
+        (n, self.send_result(&e, r))
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_flush(&self, message: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        let r = e.send_opt(message, OptSend::new().enable_flush(true)).await;
+        self.send_result(&e, r)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_confirmed(&self, message: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        let r = e.send_confirmed(message).await;
+        self.send_result(&e, r)
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn recv(&self) -> Res<Message<M>> {
         let _t = task_trace!();
-        let guard = self.opt_endpoint.lock().await;
-        if let Some(e) = &(*guard) {
-            let m = e.recv().await?;
-            return Ok(m);
-        } else {
-            Err(ET::NetNotConnected)
+        let (e, generation) = self.endpoint_generation()?;
+        let mut replaced = self.generation.subscribe();
+        let r = select! {
+            r = e.recv() => { r }
+            _ = replaced.wait_for(|g| { *g != generation }) => { return Err(net_error::net_reset()); }
+        };
+        match r {
+            // the old endpoint was closed by the swap
+            Err(_) if *self.generation.borrow() != generation => { Err(net_error::net_reset()) }
+            r => { r }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn recv_filter(&self, pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let (e, generation) = self.endpoint_generation()?;
+        let mut replaced = self.generation.subscribe();
+        let r = select! {
+            r = e.recv_filter(pred) => { r }
+            _ = replaced.wait_for(|g| { *g != generation }) => { return Err(net_error::net_reset()); }
+        };
+        match r {
+            Err(_) if *self.generation.borrow() != generation => { Err(net_error::net_reset()) }
+            r => { r }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        e.ping(duration).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn close_send(&self) -> Res<()> {
+        let _t = task_trace!();
+        let e = self.endpoint()?;
+        e.shutdown_write().await
+    }
+}
+
+impl<M: MsgTrait + 'static> Drop for ClientInner<M> {
+    fn drop(&mut self) {
+        if !self.is_closed() {
+            warn!("client {} was dropped without close, abort its node", self.nid);
+            let _ = self.node.stop_notify().notify_all();
         }
     }
 }
 
 #[async_trait]
-impl<M: MsgTrait + 'static> HandleEvent<M> for Handler {
-    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<M>>) -> Res<()> {
-        Ok(())
+impl<M: MsgTrait + 'static> HandleEvent<M> for Handler<M> {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
+        self.inner.on_accepted(endpoint).await
     }
 
-    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> {
-        Ok(())
+    async fn on_connected(&self, address: SocketAddr, endpoint: Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> {
+        self.inner.on_connected(address, endpoint).await
+    }
+
+    async fn on_connect_attempt(&self, address: SocketAddr, attempt: u64, last_error: Option<ET>) {
+        self.inner.on_connect_attempt(address, attempt, last_error).await
+    }
+
+    async fn on_error(&self, error: ET) {
+        self.inner.on_error(error).await
+    }
+
+    async fn on_disconnected(&self, address: SocketAddr, reason: ET) {
+        self.inner.on_disconnected(address, reason).await
     }
 
-    async fn on_error(&self, _: ET) {}
+    async fn on_stop(&self) {
+        self.inner.on_stop().await
+    }
+}
+
+// connect by the sink of the node, as `ClientInner::connect_attempt_timeout`
+fn connect_by<M: MsgTrait + 'static>(
+    sink: Arc<dyn EventSinkAsync<M>>,
+    node_id: NID,
+    address: SocketAddr,
+    opt: ESConnectOption,
+    timeout_ms: u64,
+    clock: Arc<dyn Clock>,
+) -> PendingConnect<M> {
+    async move {
+        let connect = sink.connect(node_id, address, opt);
+        if timeout_ms == 0 {
+            return connect.await;
+        }
+        match timeout_of(clock.as_ref(), Duration::from_millis(timeout_ms), connect).await {
+            Some(r) => { r }
+            None => { Err(net_error::timeout("connecting an attempt")) }
+        }
+    }.boxed_local()
+}
+
+// the addresses of the two families alternate, from the family of the first one
+fn interleave_families(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_v6 = addresses[0].is_ipv6();
+    let (first, second): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses.iter()
+        .partition(|a| { a.is_ipv6() == first_v6 });
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    let mut interleaved = Vec::with_capacity(addresses.len());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => { break; }
+            (a, b) => {
+                interleaved.extend(a);
+                interleaved.extend(b);
+            }
+        }
+    }
+    interleaved
+}
 
-    async fn on_stop(&self) {}
-}
\ No newline at end of file
+// a socket address, or a host name and a port
+fn is_server_addr(addr: &str) -> bool {
+    if SocketAddr::from_str(addr).is_ok() {
+        return true;
+    }
+    match addr.rsplit_once(':') {
+        Some((host, port)) => {
+            let is_host = |c: char| { c.is_ascii_alphanumeric() || c == '-' || c == '.' };
+            !host.is_empty() && host.chars().all(is_host) && u16::from_str(port).is_ok()
+        }
+        None => { false }
+    }
+}
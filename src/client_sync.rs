@@ -0,0 +1,91 @@
+use std::future::Future;
+use std::time::Duration;
+
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use scupt_util::res_of::res_io;
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::LocalSet;
+use tokio::time::timeout;
+
+use crate::client::{Client, OptClient, OptClientConnect};
+use crate::net_error;
+use crate::notifier::Notifier;
+
+// the longest a drop waits for the tasks of the client to stop
+const DROP_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A blocking Client for the code without a runtime, it owns a current thread runtime and the
+// LocalSet the client runs on, every call blocks the thread until the async one completed.
+// It must not be used within a runtime, dropping it closes the client and shuts down the
+// runtime.
+pub struct ClientSync<M: MsgTrait + 'static> {
+    client: Client<M>,
+    notifier: Notifier,
+    // dropped before the runtime, the tasks release their sockets within it
+    local: LocalSet,
+    runtime: Runtime,
+}
+
+impl<M: MsgTrait + 'static> ClientSync<M> {
+    pub fn new(node_id: NID, name: String, addr: String, opt_client: OptClient) -> Res<Self> {
+        let runtime = res_io(Builder::new_current_thread().enable_all().build())?;
+        let notifier = Notifier::new();
+        let client = Client::new(node_id, name, addr, opt_client, notifier.clone())?;
+        let local = LocalSet::new();
+        client.run(&local);
+        Ok(Self {
+            client,
+            notifier,
+            local,
+            runtime,
+        })
+    }
+
+    pub fn connect(&self, opt: OptClientConnect) -> Res<()> {
+        self.block_on(self.client.connect(opt))
+    }
+
+    pub fn send(&self, message: Message<M>) -> Res<()> {
+        self.block_on(self.client.send(message))
+    }
+
+    pub fn recv(&self) -> Res<Message<M>> {
+        self.block_on(self.client.recv())
+    }
+
+    // `net_error::timeout` if no message arrived within the duration
+    pub fn recv_timeout(&self, duration: Duration) -> Res<Message<M>> {
+        self.block_on(async {
+            match timeout(duration, self.client.recv()).await {
+                Ok(r) => { r }
+                Err(_) => { Err(net_error::timeout("receiving a message")) }
+            }
+        })
+    }
+
+    pub fn disconnect(&self) -> Res<()> {
+        self.block_on(self.client.disconnect())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.block_on(self.client.is_connected())
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.local.block_on(&self.runtime, future)
+    }
+}
+
+impl<M: MsgTrait + 'static> Drop for ClientSync<M> {
+    fn drop(&mut self) {
+        let client = self.client.clone();
+        let notifier = self.notifier.clone();
+        self.local.block_on(&self.runtime, async move {
+            let _ = timeout(DROP_TIMEOUT, client.close()).await;
+            let _ = notifier.notify_all();
+            let _ = timeout(DROP_TIMEOUT, notifier.wait_all_terminated()).await;
+        });
+    }
+}
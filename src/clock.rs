@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::select;
+use tokio::sync::watch;
+
+// The time of the timers of a node, its endpoints and its client: the retries and the attempt
+// timeouts of `Client::connect`, the idle and the write timeouts, the ping timeout, and the
+// refill of a rate limit. The default one is the tokio timer, a test may set a `ManualClock`,
+// see `NodeBuilder::set_clock` and `ClientBuilder::set_clock`, to advance the time by hand.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    async fn sleep(&self, duration: Duration);
+}
+
+// the tokio timer, which follows `tokio::time::pause` too
+#[derive(Default)]
+pub struct TokioClock {}
+
+#[async_trait]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+// A clock which only moves by `advance`, the sleeps complete when the time advanced past
+// their deadlines, in no particular order among the ones of the same deadline.
+pub struct ManualClock {
+    start: Instant,
+    // the time advanced since the start, the sleeps watch it
+    elapsed: watch::Sender<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(Duration::ZERO);
+        Self {
+            start: Instant::now(),
+            elapsed: sender,
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|e| { *e += duration });
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let mut receiver = self.elapsed.subscribe();
+        let deadline = *receiver.borrow_and_update() + duration;
+        while *receiver.borrow_and_update() < deadline {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// the clock of the nodes and the clients not given one
+pub(crate) fn default_clock() -> Arc<dyn Clock> {
+    Arc::new(TokioClock::default())
+}
+
+// the output of the future, or None if the duration passed first on the clock
+pub(crate) async fn timeout_of<F: Future>(clock: &dyn Clock, duration: Duration, f: F) -> Option<F::Output> {
+    select! {
+        r = f => { Some(r) }
+        _ = clock.sleep(duration) => { None }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use crate::clock::{Clock, ManualClock};
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = Arc::new(ManualClock::new());
+        let start = clock.now();
+        let mut sleep = clock.sleep(Duration::from_secs(10));
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(9));
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!((&mut sleep).now_or_never().is_some());
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+    }
+}
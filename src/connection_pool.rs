@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::sync::Mutex;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::es_option::ESConnectOpt;
+use crate::event_sink_async::EventSinkAsync;
+use crate::net_error;
+use crate::task_trace;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// The outbound endpoints of a node, one for each peer.
+// `get_or_connect` returns the live endpoint of the peer, or dials it through the event sink,
+// the concurrent calls for the same peer wait for a single connect.
+// An endpoint is dropped from the pool when it was closed, or when `send` failed on it, the
+// next `get_or_connect` dials again.
+pub struct ConnectionPool<M: MsgTrait + 'static> {
+    sink: Arc<dyn EventSinkAsync<M>>,
+    // the max number of the pooled peers, 0 for unlimited
+    max_connections: usize,
+    slots: SyncMutex<HashMap<NID, Arc<Slot<M>>>>,
+}
+
+struct Slot<M: MsgTrait + 'static> {
+    // None before the first connect or after the endpoint was dropped, the lock is held while
+    // dialing
+    endpoint: Mutex<Option<Arc<dyn EndpointAsync<M>>>>,
+}
+
+impl<M: MsgTrait + 'static> ConnectionPool<M> {
+    pub fn new(sink: Arc<dyn EventSinkAsync<M>>, max_connections: usize) -> Self {
+        Self {
+            sink,
+            max_connections,
+            slots: SyncMutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    // the number of the pooled peers, including the ones being dialed
+    pub fn len(&self) -> usize {
+        let slots = self.slots.lock().unwrap();
+        slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn get_or_connect(&self, node_id: NID, address: SocketAddr) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        let slot = self.slot(node_id)?;
+        let mut guard = slot.endpoint.lock().await;
+        if let Some(ep) = &*guard {
+            if !ep.is_closed() {
+                return Ok(ep.clone());
+            }
+        }
+        *guard = None;
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let opt_ep = self.sink.connect(node_id, address, opt).await?;
+        let ep = match opt_ep {
+            Some(ep) => { ep }
+            None => { return Err(ET::NoneOption); }
+        };
+        *guard = Some(ep.clone());
+        Ok(ep)
+    }
+
+    // send by the pooled endpoint of the peer, the endpoint is dropped from the pool on error
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send(&self, node_id: NID, address: SocketAddr, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let ep = self.get_or_connect(node_id, address).await?;
+        let r = ep.send(m).await;
+        if r.is_err() {
+            self.invalidate(node_id, &ep).await;
+        }
+        r
+    }
+
+    // drop the endpoint of the peer from the pool, the endpoint is not closed
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn remove(&self, node_id: NID) -> Option<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        let opt_slot = {
+            let mut slots = self.slots.lock().unwrap();
+            slots.remove(&node_id)
+        };
+        match opt_slot {
+            Some(slot) => { slot.endpoint.lock().await.take() }
+            None => { None }
+        }
+    }
+
+    // drop the endpoint only if it is still the pooled one, it may have been re-dialed
+    async fn invalidate(&self, node_id: NID, ep: &Arc<dyn EndpointAsync<M>>) {
+        let opt_slot = {
+            let slots = self.slots.lock().unwrap();
+            slots.get(&node_id).cloned()
+        };
+        if let Some(slot) = opt_slot {
+            let mut guard = slot.endpoint.lock().await;
+            let same = match &*guard {
+                Some(e) => { Arc::as_ptr(e) as *const u8 == Arc::as_ptr(ep) as *const u8 }
+                None => { false }
+            };
+            if same {
+                *guard = None;
+            }
+        }
+    }
+
+    // the slot of the peer, a full pool evicts the peers without a live endpoint first
+    fn slot(&self, node_id: NID) -> Res<Arc<Slot<M>>> {
+        let mut slots = self.slots.lock().unwrap();
+        if let Some(slot) = slots.get(&node_id) {
+            return Ok(slot.clone());
+        }
+        if self.max_connections > 0 && slots.len() >= self.max_connections {
+            slots.retain(|_, slot| { !slot.is_evictable() });
+            if slots.len() >= self.max_connections {
+                return Err(net_error::pool_full(self.max_connections));
+            }
+        }
+        let slot = Arc::new(Slot {
+            endpoint: Mutex::new(None),
+        });
+        let _ = slots.insert(node_id, slot.clone());
+        Ok(slot)
+    }
+}
+
+impl<M: MsgTrait + 'static> Slot<M> {
+    // a slot being dialed is locked and kept
+    fn is_evictable(&self) -> bool {
+        match self.endpoint.try_lock() {
+            Ok(guard) => {
+                match &*guard {
+                    Some(ep) => { ep.is_closed() }
+                    None => { true }
+                }
+            }
+            Err(_) => { false }
+        }
+    }
+}
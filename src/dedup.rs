@@ -0,0 +1,409 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::frame::MAX_SEQ;
+
+// the max number of the sequence numbers received ahead of a gap, a peer which opens a wider
+// gap has the connection closed
+pub const MAX_PENDING: usize = 4096;
+
+// the max number of the sessions a node keeps, the receiving state of the ones without a
+// connection is dropped first, the longest detached first
+pub const MAX_SESSIONS: usize = 1024;
+
+// the sequence number following `seq`, 1 follows MAX_SEQ
+fn next_of(seq: u64) -> u64 {
+    if seq >= MAX_SEQ { 1 } else { seq + 1 }
+}
+
+// the distance from `a` forward to `b`, in the serial number space 1..=MAX_SEQ, `b` is ahead
+// of `a` if it is in 1..=MAX_SEQ / 2
+fn distance(a: u64, b: u64) -> u64 {
+    (b + MAX_SEQ - a) % MAX_SEQ
+}
+
+fn is_ahead(a: u64, b: u64) -> bool {
+    let d = distance(a, b);
+    d != 0 && d <= MAX_SEQ / 2
+}
+
+// the sequence number of the frame the `n`th of a session, from 1
+fn seq_of(n: u64) -> u64 {
+    (n - 1) % MAX_SEQ + 1
+}
+
+// Duplicate detection by frame sequence number, in the serial number space 1..=MAX_SEQ, the
+// numbers wrap around to 1 after MAX_SEQ.
+// `watermark` is the sequence number which all the ones up to it have been received, a number
+// not ahead of it is a duplicate, `pending` keeps the sequence numbers received ahead of the
+// watermark, which were received out of order, e.g. by a replay filling a gap.
+pub struct Dedup {
+    watermark: u64,
+    pending: HashSet<u64>,
+}
+
+impl Dedup {
+    // the first sequence number expected is 1
+    pub fn new() -> Self {
+        Self {
+            watermark: MAX_SEQ,
+            pending: Default::default(),
+        }
+    }
+
+    pub fn watermark(&self) -> u64 {
+        self.watermark
+    }
+
+    // return true if this is the first time the sequence number was seen and the frame should
+    // be delivered, false if it is a duplicate, an error if it opens a gap of more than
+    // MAX_PENDING numbers received
+    pub fn accept(&mut self, seq: u64) -> Result<bool, String> {
+        if !is_ahead(self.watermark, seq) || self.pending.contains(&seq) {
+            return Ok(false);
+        }
+        if seq != next_of(self.watermark) && self.pending.len() >= MAX_PENDING {
+            return Err(format!("{} sequence numbers received ahead of {}", self.pending.len(), next_of(self.watermark)));
+        }
+        let _ = self.pending.insert(seq);
+        self.advance();
+        Ok(true)
+    }
+
+    // give up the gaps before `low`, the sender would never send them
+    pub fn skip_to(&mut self, low: u64) {
+        let floor = if low <= 1 { MAX_SEQ } else { low - 1 };
+        if !is_ahead(self.watermark, floor) {
+            return;
+        }
+        self.watermark = floor;
+        let watermark = self.watermark;
+        self.pending.retain(|seq| { is_ahead(watermark, *seq) });
+        self.advance();
+    }
+
+    fn advance(&mut self) {
+        while self.pending.remove(&next_of(self.watermark)) {
+            self.watermark = next_of(self.watermark);
+        }
+    }
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The sequence numbers of the frames sent by a session, across the connections of it, see
+// `ESConnectOption::enable_dedup`. The writer of a connection stamps a frame when it takes it
+// to write, the number is outstanding until the frame was written, or given up, the one of a
+// frame kept unsent is resent with it.
+pub struct SendSession {
+    id: u64,
+    state: Mutex<SendState>,
+}
+
+struct SendState {
+    // the number of the next frame, from 1, it does not wrap around, the sequence number does
+    next: u64,
+    outstanding: BTreeSet<u64>,
+}
+
+// the number of a frame stamped by a session, given back when it is dropped
+pub struct Stamp {
+    n: u64,
+    session: Arc<SendSession>,
+}
+
+impl SendSession {
+    pub fn new() -> Self {
+        Self {
+            id: rand::random::<u64>(),
+            state: Mutex::new(SendState {
+                next: 1,
+                outstanding: Default::default(),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn stamp(self: &Arc<Self>) -> Stamp {
+        let mut state = self.state.lock().unwrap();
+        let n = state.next;
+        state.next += 1;
+        let _ = state.outstanding.insert(n);
+        Stamp {
+            n,
+            session: self.clone(),
+        }
+    }
+
+    // the sequence number of the earliest frame outstanding, or of the next one, the frames
+    // before it were written or given up
+    pub fn low(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        seq_of(state.outstanding.first().copied().unwrap_or(state.next))
+    }
+}
+
+impl Default for SendSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stamp {
+    pub fn seq(&self) -> u64 {
+        seq_of(self.n)
+    }
+}
+
+impl Drop for Stamp {
+    fn drop(&mut self) {
+        let _ = self.session.state.lock().unwrap().outstanding.remove(&self.n);
+    }
+}
+
+// The receiving state of the sessions of the peers of a node, by id, it outlives the
+// connections of a session, a replacing connection keeps the watermark of the previous one.
+pub struct DedupSessions {
+    state: Mutex<SessionsState>,
+}
+
+struct SessionsState {
+    sessions: HashMap<u64, Arc<Mutex<RecvSession>>>,
+}
+
+struct RecvSession {
+    dedup: Dedup,
+    next_epoch: u64,
+    // the epochs of the connections attached
+    live: BTreeSet<u64>,
+    // the low announced by each connection, applied once no connection attached before it is
+    // left, whose frames in flight may still fill the gaps before it
+    floors: Vec<(u64, u64)>,
+    detached_at: Instant,
+}
+
+// a connection attached to a session, detached when it is dropped
+pub struct Attached {
+    epoch: u64,
+    session: Arc<Mutex<RecvSession>>,
+}
+
+impl DedupSessions {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SessionsState {
+                sessions: Default::default(),
+            }),
+        }
+    }
+
+    // attach a connection to the session of the id, created if it is not kept, the frames of
+    // the session before `low` would never be sent
+    pub fn attach(&self, id: u64, low: u64) -> Attached {
+        let session = {
+            let mut state = self.state.lock().unwrap();
+            if !state.sessions.contains_key(&id) && state.sessions.len() >= MAX_SESSIONS {
+                state.evict();
+            }
+            state.sessions.entry(id).or_insert_with(|| {
+                Arc::new(Mutex::new(RecvSession {
+                    dedup: Dedup::new(),
+                    next_epoch: 0,
+                    live: Default::default(),
+                    floors: vec![],
+                    detached_at: Instant::now(),
+                }))
+            }).clone()
+        };
+        let epoch = {
+            let mut s = session.lock().unwrap();
+            let epoch = s.next_epoch;
+            s.next_epoch += 1;
+            let _ = s.live.insert(epoch);
+            s.floors.push((epoch, low));
+            s.apply_floors();
+            epoch
+        };
+        Attached {
+            epoch,
+            session,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().sessions.len()
+    }
+}
+
+impl Default for DedupSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionsState {
+    // drop the session detached the longest, none if all of them are attached
+    fn evict(&mut self) {
+        let opt_id = self.sessions.iter().filter_map(|(id, s)| {
+            let s = s.lock().unwrap();
+            if s.live.is_empty() { Some((s.detached_at, *id)) } else { None }
+        }).min().map(|(_, id)| { id });
+        if let Some(id) = opt_id {
+            let _ = self.sessions.remove(&id);
+        }
+    }
+}
+
+impl RecvSession {
+    fn apply_floors(&mut self) {
+        let opt_first = self.live.first().copied();
+        let mut floors = std::mem::take(&mut self.floors);
+        floors.retain(|(epoch, low)| {
+            match opt_first {
+                Some(first) if *epoch > first => { true }
+                _ => {
+                    self.dedup.skip_to(*low);
+                    false
+                }
+            }
+        });
+        self.floors = floors;
+    }
+}
+
+impl Attached {
+    // see `Dedup::accept`
+    pub fn accept(&self, seq: u64) -> Result<bool, String> {
+        self.session.lock().unwrap().dedup.accept(seq)
+    }
+}
+
+impl Drop for Attached {
+    fn drop(&mut self) {
+        let mut s = self.session.lock().unwrap();
+        let _ = s.live.remove(&self.epoch);
+        s.detached_at = Instant::now();
+        s.apply_floors();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::dedup::{Dedup, DedupSessions, MAX_PENDING, SendSession};
+    use crate::frame::MAX_SEQ;
+
+    #[test]
+    fn test_dedup_replay() {
+        let mut dedup = Dedup::new();
+        for seq in 1..=3 {
+            assert!(dedup.accept(seq).unwrap());
+        }
+        // a replay resend the frames already delivered
+        assert!(!dedup.accept(2).unwrap());
+        assert!(!dedup.accept(3).unwrap());
+        assert!(dedup.accept(4).unwrap());
+        assert!(!dedup.accept(1).unwrap());
+        assert_eq!(dedup.watermark(), 4);
+    }
+
+    #[test]
+    fn test_dedup_gap_filling() {
+        let mut dedup = Dedup::new();
+        assert!(dedup.accept(1).unwrap());
+        assert!(dedup.accept(3).unwrap());
+        assert!(dedup.accept(5).unwrap());
+        assert_eq!(dedup.watermark(), 1);
+        assert!(!dedup.accept(3).unwrap());
+
+        assert!(dedup.accept(2).unwrap());
+        assert_eq!(dedup.watermark(), 3);
+        assert!(dedup.accept(4).unwrap());
+        assert_eq!(dedup.watermark(), 5);
+        assert!(!dedup.accept(4).unwrap());
+        assert!(!dedup.accept(5).unwrap());
+        assert!(dedup.accept(6).unwrap());
+    }
+
+    // the numbers after MAX_SEQ start from 1 again, the ones before the wrap are duplicates
+    #[test]
+    fn test_dedup_wrap() {
+        let mut dedup = Dedup::new();
+        // a skip goes at most half of the number space forward
+        dedup.skip_to(MAX_SEQ / 2);
+        dedup.skip_to(MAX_SEQ - 1);
+        assert_eq!(dedup.watermark(), MAX_SEQ - 2);
+        assert!(dedup.accept(MAX_SEQ - 1).unwrap());
+        assert!(dedup.accept(1).unwrap());
+        assert!(dedup.accept(MAX_SEQ).unwrap());
+        assert_eq!(dedup.watermark(), 1);
+        assert!(!dedup.accept(MAX_SEQ).unwrap());
+        assert!(!dedup.accept(MAX_SEQ - 1).unwrap());
+        assert!(!dedup.accept(1).unwrap());
+        assert!(dedup.accept(2).unwrap());
+    }
+
+    #[test]
+    fn test_dedup_pending_cap() {
+        let mut dedup = Dedup::new();
+        for seq in 2..MAX_PENDING as u64 + 2 {
+            assert!(dedup.accept(seq).unwrap());
+        }
+        assert!(dedup.accept(MAX_PENDING as u64 + 2).is_err());
+        // the gap is filled
+        assert!(dedup.accept(1).unwrap());
+        assert_eq!(dedup.watermark(), MAX_PENDING as u64 + 1);
+    }
+
+    #[test]
+    fn test_send_session() {
+        let session = Arc::new(SendSession::new());
+        assert_eq!(session.low(), 1);
+        let stamps: Vec<_> = (0..3).map(|_| { session.stamp() }).collect();
+        assert_eq!(stamps.iter().map(|s| { s.seq() }).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let mut stamps = stamps.into_iter();
+        let first = stamps.next().unwrap();
+        drop(stamps);
+        assert_eq!(session.low(), 1);
+        drop(first);
+        assert_eq!(session.low(), 4);
+    }
+
+    // the watermark of a session is kept across its connections, the gaps before the low of a
+    // connection are given up once the connections before it were detached
+    #[test]
+    fn test_dedup_sessions() {
+        let sessions = DedupSessions::new();
+        let first = sessions.attach(7, 1);
+        assert!(first.accept(1).unwrap());
+        assert!(first.accept(3).unwrap());
+
+        // 2 may still arrive on the first connection
+        let second = sessions.attach(7, 4);
+        assert!(!second.accept(3).unwrap());
+        assert!(second.accept(5).unwrap());
+        assert!(first.accept(2).unwrap());
+        drop(first);
+        assert!(!second.accept(2).unwrap());
+
+        // 6 was given up by the sender
+        drop(second);
+        let third = sessions.attach(7, 7);
+        assert!(!third.accept(5).unwrap());
+        assert!(third.accept(7).unwrap());
+        assert_eq!(sessions.len(), 1);
+
+        let other = sessions.attach(8, 1);
+        assert!(other.accept(1).unwrap());
+        assert_eq!(sessions.len(), 2);
+    }
+}
@@ -1,16 +1,289 @@
+use std::any::Any;
+use std::io::IoSlice;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use bytes::BytesMut;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use scupt_util::error_type::ET;
 use scupt_util::message::{Message, MsgTrait};
 use scupt_util::res::Res;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use crate::channel::ChannelHandle;
+use crate::dedup::Stamp;
+use crate::endpoint_sink::{EndpointSink, EndpointStream};
+use crate::es_option::DEFAULT_WRITE_BATCH_MAX;
+use crate::negotiated::{NegotiatedLimits, NegotiatedParams};
+use crate::net_error;
+use crate::opt_close::CloseOption;
+use crate::opt_send::OptSend;
+use crate::priority::Priority;
+use crate::rate_limit::RateLimit;
+use crate::transport::RawStream;
 
 #[async_trait]
 pub trait EndpointAsync<M: MsgTrait + 'static>: Send + Sync {
     fn remote_address(&self) -> SocketAddr;
 
+    // true if the endpoint was accepted by a listener, false if it was created by a connect
+    fn is_inbound(&self) -> bool;
+
+    // true if the endpoint can no longer send or receive, the connection was closed by the
+    // peer, failed, or the write direction was shut down
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    // the name the peer node advertised, see `OptNode::enable_advertise_name`, None until its
+    // name frame was read, or if it does not advertise it, it is not authenticated
+    fn peer_name(&self) -> Option<String> {
+        None
+    }
+
+    // the params in effect on the connection, agreed with the peer if both sides enabled
+    // `ESConnectOption::enable_negotiation`, see `NegotiatedParams`, the stock stream
+    // endpoints support it, the others return `net_error::unsupported`
+    fn negotiated(&self) -> Res<NegotiatedParams> {
+        Err(net_error::unsupported("negotiated"))
+    }
+
+    // the max message sizes of the connection, the sends are limited by the one agreed with the
+    // peer, see `NegotiatedLimits`, the stock stream endpoints support it, the others return
+    // `net_error::unsupported`
+    fn negotiated_limits(&self) -> Res<NegotiatedLimits> {
+        Err(net_error::unsupported("negotiated_limits"))
+    }
+
+    // replace the inbound budget of the endpoint, None for unlimited, see `RateLimit`, the
+    // stock stream endpoints support it, the others return `net_error::unsupported`
+    fn set_rate_limit(&self, _opt_limit: Option<RateLimit>) -> Res<()> {
+        Err(net_error::unsupported("set_rate_limit"))
+    }
+
+    // Attach the state of the application, such as a session, to the endpoint, None to clear
+    // it, it is dropped when the endpoint is closed or the connection is gone. The stock
+    // stream endpoints support it, the others return `net_error::unsupported`.
+    fn set_user_data(&self, _opt_data: Option<Box<dyn Any + Send + Sync>>) -> Res<()> {
+        Err(net_error::unsupported("set_user_data"))
+    }
+
+    // the data set by `set_user_data`, see `user_data_of` for the typed one
+    fn user_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        None
+    }
+
     async fn send(&self, m: Message<M>) -> Res<()>;
 
+    // Send as `send`, return the bytes written on the wire for the message: the frame headers,
+    // the checksums and the fragment headers included, the frames are not compressed. With a
+    // user frame codec, they are the bytes of its encoding, its own framing included. The
+    // stock endpoints support it, the others return `net_error::unsupported`.
+    async fn send_counted(&self, _m: Message<M>) -> Res<usize> {
+        Err(net_error::unsupported("send_counted"))
+    }
+
+    // send in the lane of the priority, see `Priority`
+    async fn send_priority(&self, m: Message<M>, _priority: Priority) -> Res<()> {
+        self.send(m).await
+    }
+
+    // send by the option, see `OptSend::enable_flush` and `OptSend::set_priority`, the
+    // endpoints without a writer task send it as `send_priority`
+    async fn send_opt(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        self.send_priority(m, opt.priority()).await
+    }
+
+    // Send the messages in their order, return the number of them written, all of them when
+    // the result is Ok, and the failure which stopped it, such as the connection closed by the
+    // peer midway. The stock stream endpoints hand the writer task the chunks of the write
+    // batch, written by one write each, and queue a chunk without waiting for the write of the
+    // previous one, the others send the messages one by one.
+    async fn send_all(&self, messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>) -> (u64, Res<()>) {
+        let mut count = 0;
+        for m in messages {
+            if let Err(e) = self.send(m).await {
+                return (count, Err(e));
+            }
+            count += 1;
+        }
+        (count, Ok(()))
+    }
+
+    // `send_all` of the messages of a stream, the messages ready together are sent as a batch
+    async fn send_stream(&self, messages: BoxStream<'_, Message<M>>) -> (u64, Res<()>) {
+        let mut count = 0;
+        let mut chunks = messages.ready_chunks(DEFAULT_WRITE_BATCH_MAX);
+        while let Some(chunk) = chunks.next().await {
+            let (n, r) = self.send_all(Box::new(chunk.into_iter())).await;
+            count += n;
+            if r.is_err() {
+                return (count, r);
+            }
+        }
+        (count, Ok(()))
+    }
+
+    // Resolve after the writer flushed this message to the socket, the stock endpoints already
+    // do so for `send`, an endpoint which completes a send earlier should override it.
+    // It is a flush, not an acknowledgement: the message may still be lost in the buffers or
+    // the network, or not processed by the peer, wait for a response of the peer for that,
+    // see `Caller`.
+    async fn send_confirmed(&self, m: Message<M>) -> Res<()> {
+        self.send(m).await
+    }
+
+    // Send the header message followed by the raw payload slices, as one frame, to forward a
+    // large payload without encoding it into the message. The peer reads both by
+    // `recv_vectored`, a plain `recv` returns the header only.
+    // The stock stream endpoints copy the slices once into the frame buffer, the others return
+    // `net_error::unsupported`.
+    async fn send_vectored(&self, _header: &Message<M>, _payload: &[IoSlice<'_>]) -> Res<()> {
+        Err(net_error::unsupported("send_vectored"))
+    }
+
+    // The concurrent recvs of the stock stream endpoints are served in FIFO order, each message
+    // is returned to exactly one of them, the first one waiting, so a pool of workers can pull
+    // from one endpoint. A cancelled recv loses no message.
     async fn recv(&self) -> Res<Message<M>>;
 
+    // Receive the first message matching the predicate, such as the next message of a kind
+    // on an endpoint multiplexing several protocols. The messages passed over stay queued in
+    // their order for the following `recv`s, which go on while the filter waits. One filtered
+    // recv may be pending at a time, the others fail with `net_error::filter_busy`. The stock
+    // stream endpoints support it, the others return `net_error::unsupported`.
+    async fn recv_filter(&self, _pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        Err(net_error::unsupported("recv_filter"))
+    }
+
+    // Open the logical channel of the id on the connection, such as a snapshot transfer apart
+    // from the heartbeats, the messages of a channel are in order, they are received by the
+    // handle only, in its own bounded queue. A slow consumer of a channel holds back the
+    // senders of this channel, not the other channels, see `ChannelHandle`. The messages of a
+    // channel the peer did not open go to the default channel, the one of `send` and `recv`,
+    // of the id 0. A channel is opened once, another open fails with ET::ExistingSuchElement.
+    // The stock stream endpoints support it, the others return `net_error::unsupported`.
+    async fn open_channel(&self, _id: u16) -> Res<ChannelHandle<M>> {
+        Err(net_error::unsupported("open_channel"))
+    }
+
+    // receive a message and the payload following it in the frame, the payload is empty for a
+    // frame sent by `send`
+    async fn recv_vectored(&self) -> Res<(Message<M>, BytesMut)> {
+        let m = self.recv().await?;
+        Ok((m, BytesMut::new()))
+    }
+
+    // Send a ping control frame and wait for the pong of the peer, return the round trip time,
+    // or `net_error::timeout` after the duration. The control frames are answered by the
+    // endpoint of the peer, they are not returned by `recv`, a concurrent `recv` keeps waiting
+    // for the messages. The stock stream endpoints support it, the others return
+    // `net_error::unsupported`.
+    async fn ping(&self, _duration: Duration) -> Res<Duration> {
+        Err(net_error::unsupported("ping"))
+    }
+
     async fn close(&self) -> Res<()>;
+
+    // Close by the option, see `CloseOption`. When draining, the incoming messages are
+    // discarded until the peer closed the connection, it returns `net_error::timeout` if the
+    // peer did not close within the timeout, the endpoint is closed in any case.
+    async fn close_with(&self, opt: CloseOption) -> Res<()> {
+        if !opt.drain() {
+            return self.close().await;
+        }
+        let drain = async {
+            self.shutdown_write().await?;
+            loop {
+                match self.recv().await {
+                    Ok(_) => {}
+                    Err(ET::EOF) => { return Ok(()); }
+                    Err(e) => { return Err(e); }
+                }
+            }
+        };
+        let r = timeout(opt.timeout(), drain).await;
+        // the write direction may have been shut down already
+        let _ = self.close().await;
+        match r {
+            Ok(r) => { r }
+            Err(_) => { Err(net_error::timeout("draining the endpoint")) }
+        }
+    }
+
+    // Hand the connection over to another protocol, such as an upgrade to WebSocket after a
+    // handshake of scupt-net. The frames already queued are flushed, the writer and the reader
+    // tasks stop, and the stream is returned with the bytes read ahead but not framed yet, see
+    // `RawStream::into_tcp_stream`. It disables all the framing of scupt-net: the endpoint is
+    // unusable afterwards, `send` returns `net_error::send_closed`, `recv` returns the
+    // messages read before the hand over and then ET::EOF, and the peer must not send frames
+    // after the ones it is expected to read. The stock stream endpoints support it, the
+    // others return `net_error::unsupported`.
+    async fn into_raw_stream(&self) -> Res<RawStream> {
+        Err(net_error::unsupported("into_raw_stream"))
+    }
+
+    // Take the frames left unsent by a failed write of the connection, in the order they were
+    // to be written, see `ESConnectOption::enable_resend_unsent`. It is empty if the option
+    // was not enabled, or the connection is alive, it then writes the queued frames as
+    // `shutdown_write` would. The sends of the frames are pending until the frames are resent.
+    async fn take_unsent(&self) -> Res<Unsent> {
+        Ok(Unsent::default())
+    }
+
+    // queue the frames taken from the endpoint of the previous connection, before the sends
+    // which come after it, the stock stream endpoints support it, the others return
+    // `net_error::unsupported` and the sends of the frames fail
+    async fn resend_unsent(&self, _unsent: Unsent) -> Res<()> {
+        Err(net_error::unsupported("resend_unsent"))
+    }
+
+    // shut down the write direction only, the peer would read EOF, and `recv` keeps working
+    // until the peer close the connection, `send` after this returns the error
+    // `net_error::send_closed`
+    async fn shutdown_write(&self) -> Res<()>;
+}
+
+// The encoded frames left unsent by an endpoint, see `EndpointAsync::take_unsent`, grouped by
+// the send they belong to, with the result of that send. A frame taken by the writer keeps its
+// sequence number of the session, see `ESConnectOption::enable_dedup`.
+#[derive(Default)]
+pub struct Unsent {
+    pub(crate) sends: Vec<(Vec<UnsentFrame>, oneshot::Sender<Res<()>>)>,
+}
+
+// an encoded frame, and the sequence number stamped on it, if any
+pub(crate) type UnsentFrame = (Option<Stamp>, BytesMut);
+
+impl Unsent {
+    // the number of the frames
+    pub fn len(&self) -> usize {
+        self.sends.iter().map(|(frames, _)| { frames.len() }).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sends.is_empty()
+    }
+}
+
+impl<M: MsgTrait + 'static> dyn EndpointAsync<M> {
+    // the data set by `set_user_data`, None if it is not set or not of the type
+    pub fn user_data_of<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.user_data()?.downcast::<T>().ok()
+    }
+
+    // the sends of the endpoint as a `Sink`, see `EndpointSink`, a relay is
+    // `a.stream().forward(b.sink())`
+    pub fn sink(self: Arc<Self>) -> EndpointSink<M> {
+        EndpointSink::new(self)
+    }
+
+    // the recvs of the endpoint as a `Stream`, see `EndpointStream`
+    pub fn stream(self: Arc<Self>) -> EndpointStream<M> {
+        EndpointStream::new(self)
+    }
 }
@@ -1,15 +1,25 @@
+use std::any::Any;
+use std::io::IoSlice;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use async_trait::async_trait;
+use bytes::BytesMut;
 use scupt_util::message::{Message, MsgTrait};
 use scupt_util::res::Res;
-use tokio::net::TcpStream;
 
-use crate::endpoint_async::EndpointAsync;
-use crate::endpoint_inner::_Endpoint;
+use crate::channel::ChannelHandle;
+use crate::endpoint_async::{EndpointAsync, Unsent};
+use crate::endpoint_inner::{_Endpoint, ReaderState};
+use crate::negotiated::{NegotiatedLimits, NegotiatedParams};
+use crate::notifier::Notifier;
 use crate::opt_ep::OptEP;
+use crate::opt_send::OptSend;
+use crate::priority::Priority;
+use crate::rate_limit::RateLimit;
 use crate::task_trace;
+use crate::transport::{NetStream, RawStream};
 
 #[derive(Clone)]
 pub struct EndpointAsyncImpl {
@@ -23,40 +33,163 @@ impl<M: MsgTrait + 'static> EndpointAsync<M> for EndpointAsyncImpl {
         self._remote_address()
     }
 
-    #[async_backtrace::framed]
+    fn is_inbound(&self) -> bool {
+        self._ep.is_inbound()
+    }
+
+    fn peer_name(&self) -> Option<String> {
+        self._ep.peer_name()
+    }
+
+    fn negotiated(&self) -> Res<NegotiatedParams> {
+        Ok(self._ep.negotiated())
+    }
+
+    fn negotiated_limits(&self) -> Res<NegotiatedLimits> {
+        Ok(self._ep.negotiated_limits())
+    }
+
+    fn is_closed(&self) -> bool {
+        self._ep.is_closed()
+    }
+
+    fn set_rate_limit(&self, opt_limit: Option<RateLimit>) -> Res<()> {
+        self._ep.set_rate_limit(opt_limit);
+        Ok(())
+    }
+
+    fn set_user_data(&self, opt_data: Option<Box<dyn Any + Send + Sync>>) -> Res<()> {
+        self._ep.set_user_data(opt_data);
+        Ok(())
+    }
+
+    fn user_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self._ep.user_data()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn send(&self, m: Message<M>) -> Res<()> {
         let _t = task_trace!();
         self._send(m).await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_counted(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        self._ep.send_counted(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_priority(&self, m: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        self._ep.send_priority(m, priority).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_opt(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        self._ep.send_opt(m, opt).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_all(&self, messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        self._ep.send_all(messages).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_vectored(&self, header: &Message<M>, payload: &[IoSlice<'_>]) -> Res<()> {
+        let _t = task_trace!();
+        self._ep.send_vectored(header, payload).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn recv(&self) -> Res<Message<M>> {
         let _t = task_trace!();
 
         self._recv().await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_filter(&self, pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        self._ep.recv_filter(pred).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn open_channel(&self, id: u16) -> Res<ChannelHandle<M>> {
+        let _t = task_trace!();
+        ChannelHandle::open(self._ep.clone(), id).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_vectored(&self) -> Res<(Message<M>, BytesMut)> {
+        let _t = task_trace!();
+        self._ep.recv_vectored::<M>().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        self._ep.ping(duration).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn close(&self) -> Res<()> {
         let _t = task_trace!();
         self._close().await
     }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn into_raw_stream(&self) -> Res<RawStream> {
+        let _t = task_trace!();
+        self._ep.into_raw_stream().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn take_unsent(&self) -> Res<Unsent> {
+        let _t = task_trace!();
+        self._ep.take_unsent().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn resend_unsent(&self, unsent: Unsent) -> Res<()> {
+        let _t = task_trace!();
+        self._ep.resend_unsent(unsent).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn shutdown_write(&self) -> Res<()> {
+        let _t = task_trace!();
+        self._ep.shutdown_write().await
+    }
 }
 
 impl EndpointAsyncImpl {
-    pub fn new(stream: TcpStream, remote_address: SocketAddr, opt_ep: OptEP) -> Self {
+    // must be called in a LocalSet, the reader task of the endpoint is cancelled by the notifier
+    pub fn new(stream: NetStream, remote_address: SocketAddr, opt_ep: OptEP, notifier: Notifier) -> Self {
         Self {
-            _ep: Arc::new(_Endpoint::new(stream, remote_address, opt_ep.is_enable_dtm_test())),
+            _ep: Arc::new(_Endpoint::new(stream, remote_address, &opt_ep, notifier)),
         }
     }
 
-    #[async_backtrace::framed]
+    // does not keep the endpoint alive, the node drains the endpoints still alive when stopping
+    pub fn downgrade(&self) -> Weak<_Endpoint> {
+        Arc::downgrade(&self._ep)
+    }
+
+    // does not keep the endpoint alive
+    pub fn reader_state(&self) -> Arc<ReaderState> {
+        self._ep.reader_state()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn _send<M: MsgTrait + 'static>(&self, m: Message<M>) -> Res<()> {
         let _t = task_trace!();
         self._ep.send(m).await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn _recv<M: MsgTrait + 'static>(&self) -> Res<Message<M>> {
         let _t = task_trace!();
         self._ep.recv::<M>().await
@@ -66,7 +199,7 @@ impl EndpointAsyncImpl {
         self._ep.remote_address()
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn _close(&self) -> Res<()> {
         let _t = task_trace!();
         self._ep.close().await
@@ -0,0 +1,292 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::IoSlice;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::time::sleep;
+
+use crate::channel::ChannelHandle;
+use crate::endpoint_async::{EndpointAsync, Unsent};
+use crate::negotiated::{NegotiatedLimits, NegotiatedParams};
+use crate::opt_send::OptSend;
+use crate::priority::Priority;
+use crate::rate_limit::RateLimit;
+use crate::task_trace;
+use crate::net_error;
+use crate::test_controller::{FaultAction, FaultDirection, PartitionMode, TestController};
+use crate::transport::RawStream;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// apply the fault injection rules of a TestController on the messages of an endpoint
+pub struct EndpointFault<M: MsgTrait + 'static> {
+    inner: Arc<dyn EndpointAsync<M>>,
+    controller: Arc<TestController<M>>,
+    // the messages held by FaultAction::Reorder
+    send_held: SyncMutex<Option<Message<M>>>,
+    recv_held: SyncMutex<Option<Message<M>>>,
+    // the messages would be returned by the following recv
+    recv_pending: SyncMutex<VecDeque<Message<M>>>,
+}
+
+impl<M: MsgTrait + 'static> EndpointFault<M> {
+    pub fn new(inner: Arc<dyn EndpointAsync<M>>, controller: Arc<TestController<M>>) -> Self {
+        Self {
+            inner,
+            controller,
+            send_held: SyncMutex::new(None),
+            recv_held: SyncMutex::new(None),
+            recv_pending: SyncMutex::new(VecDeque::new()),
+        }
+    }
+
+    // send the message, with the fault of the controller if any, return the bytes written by
+    // the call when it is counted, see `send_counted`, the ones of a duplicated or a released
+    // held message included, 0 for a message dropped or held
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_fault(&self, m: Message<M>, priority: Priority, flush: bool, counted: bool) -> Res<usize> {
+        let _t = task_trace!();
+        if self.controller.is_partitioned(m.source(), m.dest()) {
+            return match self.controller.partition_mode() {
+                PartitionMode::Drop => { Ok(0) }
+                PartitionMode::Error => { Err(net_error::partitioned(m.source(), m.dest())) }
+            };
+        }
+        let opt_action = self.controller.fault(FaultDirection::Send, &m);
+        match opt_action {
+            None => {
+                self.send_and_release(m, priority, flush, counted).await
+            }
+            Some(FaultAction::Drop) => {
+                Ok(0)
+            }
+            Some(FaultAction::Delay(duration)) => {
+                sleep(duration).await;
+                self.send_and_release(m, priority, flush, counted).await
+            }
+            Some(FaultAction::Duplicate) => {
+                let n = self.inner_send(m.clone(), priority, flush, counted).await?;
+                Ok(n + self.send_and_release(m, priority, flush, counted).await?)
+            }
+            Some(FaultAction::Reorder) => {
+                let opt_prev = self.send_held.lock().unwrap().replace(m);
+                match opt_prev {
+                    Some(prev) => { self.inner_send(prev, priority, flush, counted).await }
+                    None => { Ok(0) }
+                }
+            }
+        }
+    }
+
+    // send the message, and then the held one
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_and_release(&self, m: Message<M>, priority: Priority, flush: bool, counted: bool) -> Res<usize> {
+        let _t = task_trace!();
+        let mut n = self.inner_send(m, priority, flush, counted).await?;
+        let opt_held = self.send_held.lock().unwrap().take();
+        if let Some(held) = opt_held {
+            n += self.inner_send(held, priority, flush, counted).await?;
+        }
+        Ok(n)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn inner_send(&self, m: Message<M>, priority: Priority, flush: bool, counted: bool) -> Res<usize> {
+        let _t = task_trace!();
+        if counted {
+            self.inner.send_counted(m).await
+        } else if flush {
+            self.inner.send_opt(m, OptSend::new().enable_flush(true).set_priority(priority)).await.map(|_| { 0 })
+        } else {
+            self.inner.send_priority(m, priority).await.map(|_| { 0 })
+        }
+    }
+
+    // the held message would be returned after this one
+    fn release_recv(&self, m: Message<M>) -> Message<M> {
+        let opt_held = self.recv_held.lock().unwrap().take();
+        if let Some(held) = opt_held {
+            self.recv_pending.lock().unwrap().push_back(held);
+        }
+        m
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> EndpointAsync<M> for EndpointFault<M> {
+    fn remote_address(&self) -> SocketAddr {
+        self.inner.remote_address()
+    }
+
+    fn is_inbound(&self) -> bool {
+        self.inner.is_inbound()
+    }
+
+    fn peer_name(&self) -> Option<String> {
+        self.inner.peer_name()
+    }
+
+    fn negotiated(&self) -> Res<NegotiatedParams> {
+        self.inner.negotiated()
+    }
+
+    fn negotiated_limits(&self) -> Res<NegotiatedLimits> {
+        self.inner.negotiated_limits()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn set_rate_limit(&self, opt_limit: Option<RateLimit>) -> Res<()> {
+        self.inner.set_rate_limit(opt_limit)
+    }
+
+    fn set_user_data(&self, opt_data: Option<Box<dyn Any + Send + Sync>>) -> Res<()> {
+        self.inner.set_user_data(opt_data)
+    }
+
+    fn user_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.inner.user_data()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.send_fault(m, Priority::Normal, false, false).await?;
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_counted(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        self.send_fault(m, Priority::Normal, false, true).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_priority(&self, m: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        self.send_fault(m, priority, false, false).await?;
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_opt(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        self.send_fault(m, opt.priority(), opt.is_enable_flush(), false).await?;
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv(&self) -> Res<Message<M>> {
+        let _t = task_trace!();
+        loop {
+            let opt_pending = self.recv_pending.lock().unwrap().pop_front();
+            if let Some(m) = opt_pending {
+                return Ok(m);
+            }
+            let m = self.inner.recv().await?;
+            if self.controller.is_partitioned(m.source(), m.dest()) {
+                continue;
+            }
+            let opt_action = self.controller.fault(FaultDirection::Recv, &m);
+            match opt_action {
+                None => {
+                    return Ok(self.release_recv(m));
+                }
+                Some(FaultAction::Drop) => {
+                    continue;
+                }
+                Some(FaultAction::Delay(duration)) => {
+                    sleep(duration).await;
+                    return Ok(self.release_recv(m));
+                }
+                Some(FaultAction::Duplicate) => {
+                    self.recv_pending.lock().unwrap().push_back(m.clone());
+                    return Ok(self.release_recv(m));
+                }
+                Some(FaultAction::Reorder) => {
+                    let opt_prev = self.recv_held.lock().unwrap().replace(m);
+                    if let Some(prev) = opt_prev {
+                        return Ok(prev);
+                    }
+                }
+            }
+        }
+    }
+
+    // the partition applies, the fault rules do not, they match the messages of send and recv
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_vectored(&self, header: &Message<M>, payload: &[IoSlice<'_>]) -> Res<()> {
+        let _t = task_trace!();
+        if self.controller.is_partitioned(header.source(), header.dest()) {
+            return match self.controller.partition_mode() {
+                PartitionMode::Drop => { Ok(()) }
+                PartitionMode::Error => { Err(net_error::partitioned(header.source(), header.dest())) }
+            };
+        }
+        self.inner.send_vectored(header, payload).await
+    }
+
+    // the fault rules do not apply, as `recv_vectored`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_filter(&self, pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        self.inner.recv_filter(pred).await
+    }
+
+    // the fault rules do not apply to the messages of the channels
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn open_channel(&self, id: u16) -> Res<ChannelHandle<M>> {
+        let _t = task_trace!();
+        self.inner.open_channel(id).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_vectored(&self) -> Res<(Message<M>, BytesMut)> {
+        let _t = task_trace!();
+        self.inner.recv_vectored().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        self.inner.ping(duration).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn close(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.close().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn into_raw_stream(&self) -> Res<RawStream> {
+        let _t = task_trace!();
+        self.inner.into_raw_stream().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn take_unsent(&self) -> Res<Unsent> {
+        let _t = task_trace!();
+        self.inner.take_unsent().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn resend_unsent(&self, unsent: Unsent) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.resend_unsent(unsent).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn shutdown_write(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.shutdown_write().await
+    }
+}
@@ -1,5 +1,13 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::IoSlice;
 use std::net::SocketAddr;
-use bytes::BytesMut;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, BytesMut};
 use futures::{SinkExt, StreamExt};
 use futures::stream::{SplitSink, SplitStream};
 use scupt_util::error_type::ET;
@@ -9,40 +17,441 @@ use scupt_util::message::{
     Message,
     MsgTrait,
 };
+use scupt_util::node_id::NID;
 use scupt_util::slice::Slice;
 use scupt_util::res::Res;
-use scupt_util::res_of::res_io;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::select;
+use tokio::sync::{mpsc, Mutex, oneshot, Semaphore};
+use tokio::sync::oneshot::error::TryRecvError;
 use tokio_util::codec::Framed;
-use tracing::{Instrument, trace_span};
+use tracing::{Instrument, trace, trace_span};
 
 use crate::{parse_dtm_message, task_trace};
-use crate::framed_codec::FramedCodec;
+use crate::buffer_pool::BufferPool;
+use crate::channel::{Channels, Route};
+use crate::clock::{Clock, timeout_of};
+use crate::dedup::{Attached, DedupSessions, SendSession, Stamp};
+use crate::endpoint_async::{Unsent, UnsentFrame};
+use crate::flow_control::FlowControl;
+use crate::frame::{
+    CHECKSUM_SIZE,
+    CONTROL_SEQ,
+    ControlFrame,
+    DEFAULT_CHANNEL,
+    FRAGMENT_ABORT,
+    FRAGMENT_CHANNEL,
+    FRAGMENT_HEADER_SIZE,
+    FRAGMENT_LAST,
+    FragmentHeader,
+    FrameHeader,
+    HEADER_SIZE,
+    MAX_NAME_SIZE,
+    MAX_PAYLOAD_SIZE,
+    MAX_SEQ,
+    preamble,
+};
+use crate::frame_codec::RawFrameCodec;
+use crate::framed_codec::{ChecksumMismatch, FramedCodec, OutFrame, PreambleMismatch};
+use crate::negotiated::{CAP_ADVERTISE_NAME, CAP_CHECKSUM, CAP_FLOW_CONTROL, FrameFormat, NegotiatedLimits, NegotiatedParams};
+use crate::metrics::Metrics;
+use crate::net_error;
+use crate::net_trace::net_debug;
+use crate::notifier::Notifier;
+use crate::opt_ep::OptEP;
+use crate::opt_send::OptSend;
+use crate::overflow_policy::OverflowPolicy;
+use crate::priority::Priority;
+use crate::rate_limit::{RateLimit, TokenBucket};
+use crate::reassembly::{Reassembled, Reassembly};
+use crate::recorder::{RecordDirection, RecordSink, write_record};
+use crate::send_lanes::{SendLanes, TryPushError};
+use crate::task::spawn_local_task;
+use crate::transport::{NetStream, RawStream};
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+type FramedSink = SplitSink<Framed<NetStream, FramedCodec>, OutFrame>;
+
+type FramedStream = SplitStream<Framed<NetStream, FramedCodec>>;
+
+// None after the stream was handed over by `into_raw_stream`
+type SharedSink = Arc<Mutex<Option<FramedSink>>>;
+
+type UserData = Arc<SyncMutex<Option<Arc<dyn Any + Send + Sync>>>>;
+
+// the fragments of a message queued for the writer task at a time, the frames of the other
+// sends queued meanwhile are written before the next ones
+const FRAGMENTS_IN_FLIGHT: usize = 4;
 
 pub struct _Endpoint {
-    sender: Mutex<SplitSink<Framed<TcpStream, FramedCodec>, BytesMut>>,
-    receiver: Mutex<SplitStream<Framed<TcpStream, FramedCodec>>>,
+    sender: SharedSink,
+    // the frames read by the reader task, shared by the concurrent recvs, the lock is fair, the
+    // recvs take the frames one each in the order they came
+    receiver: Mutex<mpsc::Receiver<BytesMut>>,
+    // the frames passed over by `recv_filter`, older than the ones in the receiver, read under
+    // the lock of the receiver, they are not bounded by the recv queue capacity
+    held: SyncMutex<VecDeque<BytesMut>>,
+    // a `recv_filter` is pending
+    filtering: AtomicBool,
+    // the channels opened by `open_channel`, each of a queue of the recv queue capacity
+    channels: Arc<Channels>,
+    channel_capacity: usize,
     remote_address: SocketAddr,
     // is enabled DTM testing, default is false
     // when this option was enabling, the incoming message would be parse as ActionMessage
     enable_dtm_test: bool,
+    // the frames waiting for the writer task, the lanes are closed when the write direction
+    // was shut down
+    lanes: Arc<SendLanes<WriteItem>>,
+    // the capacity of each lane, and what a send does when its lane is full
+    send_queue_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    // accepted by a listener, or connected to a remote
+    inbound: bool,
+    reader_state: Arc<ReaderState>,
+    opt_record_sink: Option<Arc<dyn RecordSink>>,
+    opt_metrics: Option<Arc<Metrics>>,
+    // the max size of an encoded message, see `ESConnectOption::set_max_message_size`
+    max_message_size: usize,
+    // the messages are in the format of a user `FrameCodec`, without the control frames, they
+    // are encoded by the send, see `wire_frame`
+    opt_frame_codec: Option<Arc<dyn RawFrameCodec>>,
+    // the frame buffers of the sends, given back by the encoder of the writer task
+    send_buffers: Arc<BufferPool>,
+    // the messages of `send_all` are queued in the chunks of the write batch
+    send_all_chunk: usize,
+    // the larger encoded messages are sent as fragments, 0 for none, see
+    // `ESConnectOption::set_fragment_size`
+    fragment_size: usize,
+    // the id of the next fragmented message, it wraps around
+    next_message_id: AtomicU64,
+    // the bytes written ahead of the payload of every frame, the header and the checksum, none
+    // for a user frame codec, its framing is part of the payload encoded by `wire_frame`
+    frame_prefix: usize,
+    // the frames kept by the writer task after a write failed, None if they are not kept, see
+    // `ESConnectOption::enable_resend_unsent`
+    opt_unsent: Option<Arc<SyncMutex<Unsent>>>,
+    // set by `drain`, the sends refused afterwards fail with `net_error::shutting_down`
+    draining: AtomicBool,
+    // the inbound budget enforced by the reader task, None for unlimited
+    rate_limit: Arc<SyncMutex<Option<TokenBucket>>>,
+    // the credits of the default channel, None if the flow is not controlled, see
+    // `ESConnectOption::set_flow_window`
+    opt_flow: Option<Arc<FlowControl>>,
+    // see `EndpointAsync::set_user_data`, dropped by `close` and when the reader task stopped
+    user_data: UserData,
+    // the name advertised by the peer, set by the reader task
+    peer_name: Arc<SyncMutex<Option<String>>>,
+    // the params of this side, see `EndpointAsync::negotiated`
+    local_params: NegotiatedParams,
+    // the capabilities and the max message size sent by the peer, set by the reader task
+    peer_params: Arc<SyncMutex<Option<(u32, u32)>>>,
+    pings: Arc<Pings>,
+    // the clock of the timeouts and of the rate limit, see `Clock`
+    clock: Arc<dyn Clock>,
+    // ask the reader task to stop and return the stream, taken by `into_raw_stream`
+    release: SyncMutex<Option<oneshot::Sender<oneshot::Sender<FramedStream>>>>,
+    // cancel the tasks of this endpoint when it is dropped
+    task_notifier: Notifier,
+}
+
+// the outcome of the reader task
+pub struct ReaderState {
+    reason: SyncMutex<Option<ET>>,
+    stopped: Notifier,
+    // the connection is counted as closed when the reader stopped
+    opt_metrics: Option<Arc<Metrics>>,
+    inbound: bool,
+}
+
+// the messages of `send_all` written, until the first failure
+struct Written {
+    count: u64,
+    result: Res<()>,
+}
+
+// the pings waiting for their pongs, by nonce
+struct Pings {
+    next_nonce: AtomicU64,
+    pending: SyncMutex<HashMap<u64, oneshot::Sender<()>>>,
+}
+
+enum WriteItem {
+    // the channel, the frame, whether to flush right after it, and the result of the write
+    Frame(u16, BytesMut, bool, oneshot::Sender<Res<()>>),
+    // the frames of a chunk of `send_all`, or of a send resent, of the default channel, and the
+    // result of the write
+    Frames(Vec<UnsentFrame>, oneshot::Sender<Res<()>>),
+    // a ping, a pong or a credit, flushed right after it
+    Control(ControlFrame),
+    // flush and shut down the write half of the stream
+    Shutdown(oneshot::Sender<Res<()>>),
+    // flush and stop the writer, the stream is kept open for `into_raw_stream`
+    Release(oneshot::Sender<Res<()>>),
+    // flush and stop the writer, the frames left unsent were kept, see `take_unsent`
+    Salvage(oneshot::Sender<Res<()>>),
+}
+
+struct Writer {
+    lanes: Arc<SendLanes<WriteItem>>,
+    sender: SharedSink,
+    address: SocketAddr,
+    limit: BatchLimit,
+    opt_unsent: Option<Arc<SyncMutex<Unsent>>>,
+    // number the frames by the session, or by the connection from 1 when it is None
+    opt_session: Option<Arc<SendSession>>,
+    next_seq: u64,
+    // a write of a batch exceeding it closes the connection, see `write_timeout_ms`
+    opt_write_timeout: Option<Duration>,
+    // stop the reader task with the error, after a write failed
+    opt_abort: Option<oneshot::Sender<ET>>,
+    clock: Arc<dyn Clock>,
+    // the preamble is in the buffer of the sink, the peer reads it before any frame is sent
+    flush_preamble: bool,
+}
+
+// the max frames and bytes coalesced into one write
+#[derive(Clone, Copy)]
+struct BatchLimit {
+    max: usize,
+    bytes: usize,
+}
+
+type WriteBatch = Vec<(OutFrame, Option<Stamp>, Option<oneshot::Sender<Res<()>>>)>;
+
+enum ReadNext {
+    // a frame, an error, or None for EOF
+    Frame(Option<Result<(FrameHeader, BytesMut), io::Error>>),
+    // nothing was read within the idle timeout
+    Idle,
+    // hand the stream over by the sender
+    Release(oneshot::Sender<FramedStream>),
+    // the writer task failed, close the connection with the error
+    Abort(ET),
+}
+
+struct Reader {
+    stream: FramedStream,
+    queue: mpsc::Sender<BytesMut>,
+    // the frames of the other channels are routed to their queues
+    channels: Arc<Channels>,
+    sender: SharedSink,
+    // the pongs are queued in the High lane
+    lanes: Arc<SendLanes<WriteItem>>,
+    pings: Arc<Pings>,
+    // drop the duplicated incoming frames by sequence number once the peer announced the
+    // session numbering them
+    dedup: Option<Attached>,
+    dedup_sessions: Arc<DedupSessions>,
+    rate_limit: Arc<SyncMutex<Option<TokenBucket>>>,
+    // the credits of the default channel granted by the peer are added to it
+    opt_flow: Option<Arc<FlowControl>>,
+    idle_timeout: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    peer_name: Arc<SyncMutex<Option<String>>>,
+    peer_params: Arc<SyncMutex<Option<(u32, u32)>>>,
+    // the messages of the frames of FRAGMENT_CHANNEL
+    reassembly: Reassembly,
+    address: SocketAddr,
+    description: String,
 }
 
 impl _Endpoint {
-    pub fn new(stream: TcpStream, address: SocketAddr,
-               enable_dtm_test: bool,
+    pub fn new(stream: NetStream, address: SocketAddr,
+               opt_ep: &OptEP,
+               notifier: Notifier,
     ) -> Self {
-        let framed = Framed::new(
+        let max_message_size = opt_ep.max_message_size().unwrap_or(MAX_PAYLOAD_SIZE).min(MAX_PAYLOAD_SIZE);
+        let send_buffers = Arc::new(BufferPool::new(opt_ep.send_buffer_pool()));
+        let opt_preamble = match opt_ep.frame_codec() {
+            Some(_) => { None }
+            None => { opt_ep.preamble() }
+        };
+        let mut framed = Framed::new(
             stream,
-            FramedCodec::new(),
+            FramedCodec::new_with_max_payload_size(max_message_size)
+                .set_custom(opt_ep.frame_codec())
+                .set_buffer_pool(Some(send_buffers.clone()))
+                .set_checksum(opt_ep.checksum())
+                .set_preamble(opt_preamble),
         );
+        if let Some(version) = opt_preamble {
+            // ahead of the first frame, flushed by the writer task when it starts
+            framed.write_buffer_mut().put_slice(&preamble(version));
+        }
         let (s, r) = framed.split();
+        let sender = Arc::new(Mutex::new(Some(s)));
+        let (queue_sender, queue_receiver) = mpsc::channel(opt_ep.recv_queue_capacity().max(1));
+        let channels = Arc::new(Channels::new());
+        let task_notifier = notifier.new_child();
+        let opt_metrics = opt_ep.metrics();
+        if let Some(metrics) = &opt_metrics {
+            metrics.connection_opened(opt_ep.is_inbound());
+        }
+        let reader_state = Arc::new(ReaderState::new(opt_metrics.clone(), opt_ep.is_inbound()));
+        let lanes = Arc::new(SendLanes::new(opt_ep.send_queue_capacity().max(1)));
+        let mut capabilities = 0;
+        if let (Some(name), None) = (opt_ep.advertised_name(), opt_ep.frame_codec()) {
+            // the first frame of the connection
+            if name.len() <= MAX_NAME_SIZE {
+                let _ = lanes.try_push(Priority::High, WriteItem::Control(ControlFrame::Name(name)));
+                capabilities |= CAP_ADVERTISE_NAME;
+            }
+        }
+        let opt_session = match opt_ep.frame_codec() {
+            Some(_) => { None }
+            None => { opt_ep.dedup_session() }
+        };
+        if let Some(session) = &opt_session {
+            // the frames before the low were written or given up by the previous connections
+            let _ = lanes.try_push(Priority::High, WriteItem::Control(ControlFrame::Session(session.id(), session.low())));
+        }
+        let opt_flow = match (opt_ep.flow_window(), opt_ep.frame_codec()) {
+            (0, _) | (_, Some(_)) => { None }
+            (window, None) => {
+                // the peer may send the window before any credit was taken back
+                let _ = lanes.try_push(Priority::High, WriteItem::Control(ControlFrame::Credit(DEFAULT_CHANNEL, window)));
+                Some(Arc::new(FlowControl::new(window)))
+            }
+        };
+        let local_params = match opt_ep.frame_codec() {
+            Some(_) => { NegotiatedParams::new(FrameFormat::Custom, max_message_size, 0) }
+            None => {
+                if opt_ep.checksum() {
+                    capabilities |= CAP_CHECKSUM;
+                }
+                if opt_flow.is_some() {
+                    capabilities |= CAP_FLOW_CONTROL;
+                }
+                if opt_ep.negotiation() {
+                    let params = ControlFrame::Params(capabilities, max_message_size as u32);
+                    let _ = lanes.try_push(Priority::High, WriteItem::Control(params));
+                }
+                NegotiatedParams::new(FrameFormat::Builtin, max_message_size, capabilities)
+            }
+        };
+        let peer_params = Arc::new(SyncMutex::new(None));
+        let pings = Arc::new(Pings::new());
+        let peer_name = Arc::new(SyncMutex::new(None));
+        let clock = opt_ep.clock();
+        let rate_limit = Arc::new(SyncMutex::new(
+            opt_ep.rate_limit().map(|l| { TokenBucket::new(l, clock.now()) })));
+        let reader = Reader {
+            stream: r,
+            queue: queue_sender,
+            channels: channels.clone(),
+            sender: sender.clone(),
+            lanes: lanes.clone(),
+            pings: pings.clone(),
+            dedup: None,
+            dedup_sessions: opt_ep.dedup_sessions().unwrap_or_default(),
+            rate_limit: rate_limit.clone(),
+            opt_flow: opt_flow.clone(),
+            idle_timeout: if opt_ep.idle_timeout_ms() > 0 {
+                Some(Duration::from_millis(opt_ep.idle_timeout_ms()))
+            } else {
+                None
+            },
+            clock: clock.clone(),
+            peer_name: peer_name.clone(),
+            peer_params: peer_params.clone(),
+            reassembly: Reassembly::new(opt_ep.max_reassemblies(), opt_ep.max_reassembly_bytes()),
+            address,
+            description: format!("{} {}", direction(opt_ep.is_inbound()), address),
+        };
+        let state = reader_state.clone();
+        let reader_pings = pings.clone();
+        let reader_channels = channels.clone();
+        let reader_flow = opt_flow.clone();
+        let user_data: UserData = Arc::new(SyncMutex::new(None));
+        let reader_user_data = user_data.clone();
+        let (release_sender, release_receiver) = oneshot::channel();
+        let (abort_sender, abort_receiver) = oneshot::channel();
+        let task_name = format!("endpoint reader {}", address);
+        let _ = spawn_local_task(task_notifier.clone(), task_name.as_str(), async move {
+            let reason = reader.read_loop(release_receiver, abort_receiver).await;
+            net_debug!(addr = %address, reason = ?reason, "endpoint reader stopped");
+            state.stop(reason);
+            // no pong would arrive
+            reader_pings.clear();
+            reader_channels.close();
+            if let Some(flow) = reader_flow {
+                flow.close();
+            }
+            let opt_data = reader_user_data.lock().unwrap().take();
+            drop(opt_data);
+        });
+        let opt_unsent = if opt_ep.is_resend_unsent() {
+            Some(Arc::new(SyncMutex::new(Unsent::default())))
+        } else {
+            None
+        };
+        let writer = Writer {
+            lanes: lanes.clone(),
+            sender: sender.clone(),
+            address,
+            limit: BatchLimit {
+                max: opt_ep.write_batch_max().max(1),
+                bytes: opt_ep.write_batch_bytes(),
+            },
+            opt_unsent: opt_unsent.clone(),
+            opt_session,
+            next_seq: 1,
+            opt_write_timeout: if opt_ep.write_timeout_ms() > 0 {
+                Some(Duration::from_millis(opt_ep.write_timeout_ms()))
+            } else {
+                None
+            },
+            opt_abort: Some(abort_sender),
+            clock: clock.clone(),
+            flush_preamble: opt_preamble.is_some(),
+        };
+        let task_name = format!("endpoint writer {}", address);
+        let _ = spawn_local_task(task_notifier.clone(), task_name.as_str(), async move {
+            writer.write_loop().await;
+        });
         Self {
-            sender: Mutex::new(s),
-            receiver: Mutex::new(r),
+            sender,
+            receiver: Mutex::new(queue_receiver),
+            held: SyncMutex::new(VecDeque::new()),
+            filtering: AtomicBool::new(false),
+            channels,
+            channel_capacity: opt_ep.recv_queue_capacity().clamp(1, u32::MAX as usize),
             remote_address: address,
-            enable_dtm_test,
+            enable_dtm_test: opt_ep.is_enable_dtm_test(),
+            lanes,
+            send_queue_capacity: opt_ep.send_queue_capacity().max(1),
+            overflow_policy: opt_ep.overflow_policy(),
+            inbound: opt_ep.is_inbound(),
+            reader_state,
+            opt_record_sink: opt_ep.record_sink(),
+            opt_metrics,
+            max_message_size,
+            opt_frame_codec: opt_ep.frame_codec(),
+            send_buffers,
+            send_all_chunk: opt_ep.write_batch_max().max(1),
+            fragment_size: match opt_ep.frame_codec() {
+                Some(_) => { 0 }
+                None => { opt_ep.fragment_size() }
+            },
+            next_message_id: AtomicU64::new(0),
+            frame_prefix: match (opt_ep.frame_codec(), opt_ep.checksum()) {
+                (Some(_), _) => { 0 }
+                (None, true) => { HEADER_SIZE + CHECKSUM_SIZE }
+                (None, false) => { HEADER_SIZE }
+            },
+            opt_unsent,
+            draining: AtomicBool::new(false),
+            rate_limit,
+            opt_flow,
+            user_data,
+            peer_name,
+            local_params,
+            peer_params,
+            pings,
+            clock,
+            release: SyncMutex::new(Some(release_sender)),
+            task_notifier,
         }
     }
 
@@ -50,44 +459,572 @@ impl _Endpoint {
         self.remote_address
     }
 
+    pub fn is_inbound(&self) -> bool {
+        self.inbound
+    }
+
+    pub fn peer_name(&self) -> Option<String> {
+        self.peer_name.lock().unwrap().clone()
+    }
+
+    pub fn negotiated(&self) -> NegotiatedParams {
+        let opt_peer = *self.peer_params.lock().unwrap();
+        match opt_peer {
+            Some((capabilities, size)) => { self.local_params.clone().agree(capabilities, size) }
+            None => { self.local_params.clone() }
+        }
+    }
+
+    pub fn negotiated_limits(&self) -> NegotiatedLimits {
+        let opt_peer = self.peer_params.lock().unwrap().map(|(_, size)| { size as usize });
+        NegotiatedLimits::new(self.max_message_size, opt_peer)
+    }
+
+    // the larger encoded messages fail to send, the smaller max message size of the two sides
+    // once the params of the peer were read
+    fn send_limit(&self) -> usize {
+        match *self.peer_params.lock().unwrap() {
+            Some((_, size)) => { self.max_message_size.min(size as usize) }
+            None => { self.max_message_size }
+        }
+    }
+
+    pub fn reader_state(&self) -> Arc<ReaderState> {
+        self.reader_state.clone()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.reader_state.is_stopped() || self.lanes.is_closed()
+    }
+
+    fn direction(&self) -> &'static str {
+        direction(self.inbound)
+    }
+
     // send message
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn send<M: MsgTrait + 'static>(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let _ = self.send_counted(m).await?;
+        Ok(())
+    }
+
+    // send in the Normal lane, return the bytes of the frames of the message written, see
+    // `EndpointAsync::send_counted`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_counted<M: MsgTrait + 'static>(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return Ok(0);
+        }
+        let dest = m.dest();
+        let bytes = self.encode_frame(m)?;
+        self.send_frame(DEFAULT_CHANNEL, dest, bytes, Priority::Normal, false).await
+    }
+
+    // queue the message in the lane of the priority, and wait until the writer task flushed the
+    // batch of the message, this is what `EndpointAsync::send_confirmed` relies on
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_priority<M: MsgTrait + 'static>(&self, m: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return Ok(());
+        }
+        let dest = m.dest();
+        let bytes = self.encode_frame(m)?;
+        let _ = self.send_frame(DEFAULT_CHANNEL, dest, bytes, priority, false).await?;
+        Ok(())
+    }
+
+    // the message is queued in the lane of `OptSend::priority`, a flush send is flushed to the
+    // socket right after it
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_opt<M: MsgTrait + 'static>(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return Ok(());
+        }
+        let dest = m.dest();
+        let bytes = self.encode_frame(m)?;
+        let _ = self.send_frame(DEFAULT_CHANNEL, dest, bytes, opt.priority(), opt.is_enable_flush()).await?;
+        Ok(())
+    }
+
+    // send on a channel of `open_channel`, the credit was taken by the channel handle
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_channel<M: MsgTrait + 'static>(&self, channel: u16, m: Message<M>) -> Res<()> {
         let _t = task_trace!();
         if self.enable_dtm_test {
             return Ok(());
         }
+        let dest = m.dest();
+        let bytes = self.encode_frame(m)?;
+        let _ = self.send_frame(channel, dest, bytes, Priority::Normal, false).await?;
+        Ok(())
+    }
+
+    // one frame of the encoded header message followed by the payload, the slices are copied
+    // once into the frame buffer, which is written as the other frames
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_vectored<M: MsgTrait + 'static>(&self, header: &Message<M>, payload: &[IoSlice<'_>]) -> Res<()> {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return Ok(());
+        }
+        let vec = encode_message(header.clone())?;
+        let payload_len: usize = payload.iter().map(|s| { s.len() }).sum();
+        let mut bytes = self.send_buffers.take(vec.len() + payload_len);
+        bytes.put_slice(vec.as_slice());
+        for s in payload {
+            bytes.put_slice(s);
+        }
+        let _ = self.send_frame(DEFAULT_CHANNEL, header.dest(), bytes, Priority::Normal, false).await?;
+        Ok(())
+    }
+
+    // Queue the messages in the chunks of the write batch, each chunk is one item of the Normal
+    // lane, its frames are written to the socket by one write, the chunks are queued without
+    // waiting for the writes of the previous ones. Return the number of the messages written
+    // before the first failure, and the failure.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send_all<M: MsgTrait + 'static>(
+        &self,
+        messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>,
+    ) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return (messages.count() as u64, Ok(()));
+        }
+        let mut messages = messages.peekable();
+        let mut written = Written::default();
+        // the chunks queued, by the length of their frames
+        let mut pending: VecDeque<(Vec<usize>, oneshot::Receiver<Res<()>>)> = VecDeque::new();
+        // the failure to encode or queue a message, the messages before it are still written
+        let mut opt_stop = None;
+        while opt_stop.is_none() && written.is_ok() {
+            if messages.peek().is_none() {
+                break;
+            }
+            // a chunk of the credits available, at least one
+            let chunk = match &self.opt_flow {
+                Some(flow) => {
+                    let max = self.send_all_chunk.min(u32::MAX as usize) as u32;
+                    match flow.acquire_up_to(max).await {
+                        Ok(n) => { n as usize }
+                        Err(e) => {
+                            opt_stop = Some(e);
+                            break;
+                        }
+                    }
+                }
+                None => { self.send_all_chunk }
+            };
+            let mut frames = Vec::with_capacity(chunk);
+            for m in messages.by_ref().take(chunk) {
+                let dest = m.dest();
+                let limit = self.send_limit();
+                let r_frame = self.encode_frame(m).and_then(|bytes| {
+                    if bytes.len() > limit {
+                        Err(net_error::message_too_large(bytes.len(), limit))
+                    } else {
+                        Ok(bytes)
+                    }
+                });
+                let r_frame = r_frame.and_then(|bytes| {
+                    if let Some(sink) = &self.opt_record_sink {
+                        write_record(sink, RecordDirection::Send, self.remote_address, dest, bytes.as_slice());
+                    }
+                    self.wire_frame(bytes)
+                });
+                match r_frame {
+                    Ok(bytes) => { frames.push((None, bytes)); }
+                    Err(e) => {
+                        opt_stop = Some(e);
+                        break;
+                    }
+                }
+            }
+            self.give_back_credits(chunk - frames.len());
+            if frames.is_empty() {
+                break;
+            }
+            let lens = frames.iter().map(|(_, b)| { b.len() }).collect();
+            let n = frames.len();
+            let (s, r) = oneshot::channel();
+            if let Err(e) = self.queue_send(Priority::Normal, WriteItem::Frames(frames, s)).await {
+                self.give_back_credits(n);
+                opt_stop = Some(e);
+                break;
+            }
+            pending.push_back((lens, r));
+            // take the results of the chunks already written
+            while let Some((_, r)) = pending.front_mut() {
+                let r_write = match r.try_recv() {
+                    Ok(r_write) => { r_write }
+                    Err(TryRecvError::Empty) => { break; }
+                    Err(TryRecvError::Closed) => { Err(net_error::writer_stopped()) }
+                };
+                let (lens, _) = pending.pop_front().unwrap();
+                self.add_written(&mut written, lens, r_write);
+            }
+        }
+        for (lens, r) in pending {
+            let r_write = Self::wait_written(r).await;
+            self.add_written(&mut written, lens, r_write);
+        }
+        if let Some(e) = opt_stop {
+            written.fail(e);
+        }
+        net_debug!(addr = %self.remote_address, written = written.count, "send all");
+        (written.count, written.result)
+    }
+
+    fn add_written(&self, written: &mut Written, lens: Vec<usize>, r_write: Res<()>) {
+        match r_write {
+            Ok(()) if written.is_ok() => {
+                written.count += lens.len() as u64;
+                if let Some(metrics) = &self.opt_metrics {
+                    for len in lens {
+                        metrics.add_message_out(len);
+                    }
+                }
+            }
+            Ok(()) => {}
+            Err(e) => { written.fail(e); }
+        }
+    }
+
+    // the encoded message in a buffer of the pool, it is encoded in full before it is queued, a
+    // message failing to encode is not written at all
+    fn encode_frame<M: MsgTrait + 'static>(&self, m: Message<M>) -> Res<BytesMut> {
         let vec = encode_message(m)?;
-        let bytes = BytesMut::from(vec.as_slice());
-        let mut sink = self.sender.lock().await;
-        let r = sink.send(bytes).await;
+        let mut bytes = self.send_buffers.take(vec.len());
+        bytes.put_slice(vec.as_slice());
+        Ok(bytes)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_frame(&self, channel: u16, dest: NID, bytes: BytesMut, priority: Priority, flush: bool) -> Res<usize> {
+        let _t = task_trace!();
+        let len = bytes.len();
+        let limit = self.send_limit();
+        if let Some(chunk) = self.fragment_chunk(channel, limit) {
+            if len > chunk {
+                return self.send_fragments(dest, bytes, chunk, priority, flush).await;
+            }
+        }
+        if len > limit {
+            return Err(net_error::message_too_large(len, limit));
+        }
+        net_debug!(peer = dest, addr = %self.remote_address, channel = channel, msg_len = len, "send message");
+        if let Some(sink) = &self.opt_record_sink {
+            write_record(sink, RecordDirection::Send, self.remote_address, dest, bytes.as_slice());
+        }
+        let bytes = self.wire_frame(bytes)?;
+        let wire_len = self.frame_prefix + bytes.len();
+        if let (Some(flow), DEFAULT_CHANNEL) = (&self.opt_flow, channel) {
+            flow.acquire().await?;
+        }
+        let (s, r) = oneshot::channel();
+        if let Err(e) = self.queue_send(priority, WriteItem::Frame(channel, bytes, flush, s)).await {
+            if channel == DEFAULT_CHANNEL {
+                self.give_back_credits(1);
+            }
+            return Err(e);
+        }
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.observe_queue_depth(self.lanes.queued());
+        }
+        if let Err(e) = Self::wait_written(r).await {
+            // the credit of an evicted frame was given back by `drop_evicted`
+            if channel == DEFAULT_CHANNEL && !net_error::is_dropped(&e) {
+                self.give_back_credits(1);
+            }
+            return Err(e);
+        }
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.add_message_out(len);
+        }
+        Ok(wire_len)
+    }
+
+    // the bytes of an encoded message carried by each fragment, within the limit of the frames,
+    // None if the messages of the channel are not fragmented
+    fn fragment_chunk(&self, channel: u16, limit: usize) -> Option<usize> {
+        if self.fragment_size == 0 || channel != DEFAULT_CHANNEL {
+            return None;
+        }
+        Some(self.fragment_size.min(limit.saturating_sub(FRAGMENT_HEADER_SIZE)).max(1))
+    }
+
+    // Send the encoded message as the fragments of `chunk` bytes on FRAGMENT_CHANNEL, up to
+    // FRAGMENTS_IN_FLIGHT of them queued at a time. The message takes one credit of the flow
+    // window, as it is taken by one recv of the peer. When a fragment failed, the peer is told
+    // to drop the ones it received.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_fragments(&self, dest: NID, bytes: BytesMut, chunk: usize, priority: Priority, flush: bool) -> Res<usize> {
+        let _t = task_trace!();
+        let len = bytes.len();
+        let n = len.div_ceil(chunk);
+        net_debug!(peer = dest, addr = %self.remote_address, msg_len = len, fragments = n, "send fragmented message");
+        if let Some(sink) = &self.opt_record_sink {
+            write_record(sink, RecordDirection::Send, self.remote_address, dest, bytes.as_slice());
+        }
+        if let Some(flow) = &self.opt_flow {
+            flow.acquire().await?;
+        }
+        let message_id = self.next_message_id.fetch_add(1, Ordering::SeqCst) as u32;
+        let mut in_flight = VecDeque::with_capacity(FRAGMENTS_IN_FLIGHT);
+        let mut wire_len = 0;
+        let mut r = Ok(());
+        for (index, part) in bytes.chunks(chunk).enumerate() {
+            if in_flight.len() >= FRAGMENTS_IN_FLIGHT {
+                if let Some(written) = in_flight.pop_front() {
+                    r = Self::wait_written(written).await;
+                }
+                if r.is_err() {
+                    break;
+                }
+            }
+            let last = index + 1 == n;
+            let mut frame = self.send_buffers.take(FRAGMENT_HEADER_SIZE + part.len());
+            FragmentHeader::new(message_id, index as u32, if last { FRAGMENT_LAST } else { 0 }).encode(&mut frame);
+            frame.put_slice(part);
+            wire_len += self.frame_prefix + frame.len();
+            let (s, written) = oneshot::channel();
+            if let Err(e) = self.queue_send(priority, WriteItem::Frame(FRAGMENT_CHANNEL, frame, flush && last, s)).await {
+                r = Err(e);
+                break;
+            }
+            in_flight.push_back(written);
+        }
+        self.send_buffers.give(bytes);
+        for written in in_flight {
+            let r_write = Self::wait_written(written).await;
+            if r.is_ok() {
+                r = r_write;
+            }
+        }
+        if let Err(e) = r {
+            self.give_back_credits(1);
+            let mut frame = self.send_buffers.take(FRAGMENT_HEADER_SIZE);
+            FragmentHeader::new(message_id, 0, FRAGMENT_ABORT).encode(&mut frame);
+            let (s, _) = oneshot::channel();
+            // the write direction may have been shut down
+            let _ = self.lanes.try_push(Priority::High, WriteItem::Frame(FRAGMENT_CHANNEL, frame, true, s));
+            return Err(e);
+        }
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.add_message_out(len);
+        }
+        Ok(wire_len)
+    }
+
+    // The bytes of the frame in the format of the user frame codec, written as they are by the
+    // writer task, the codec runs on the send rather than on the write buffer of the stream,
+    // an error of it fails the send, and no partial message is written.
+    fn wire_frame(&self, bytes: BytesMut) -> Res<BytesMut> {
+        let codec = match &self.opt_frame_codec {
+            Some(c) => { c }
+            None => { return Ok(bytes); }
+        };
+        let mut wire = self.send_buffers.take(bytes.len());
+        let r = codec.encode(&bytes[..], &mut wire);
+        self.send_buffers.give(bytes);
         match r {
-            Ok(_) => { Ok(()) }
-            Err(_e) => { Err(ET::TokioSenderError("send network message error".to_string())) }
+            Ok(()) => { Ok(wire) }
+            Err(e) => {
+                self.send_buffers.give(wire);
+                Err(e)
+            }
+        }
+    }
+
+    // queue the item of a send in the lane of the priority, as by the overflow policy when the
+    // lane is full
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn queue_send(&self, priority: Priority, item: WriteItem) -> Res<()> {
+        let _t = task_trace!();
+        match self.overflow_policy {
+            OverflowPolicy::ErrorNewest => {
+                match self.lanes.try_push(priority, item) {
+                    Ok(()) => { Ok(()) }
+                    Err(TryPushError::Full(_)) => { Err(net_error::queue_full(self.send_queue_capacity)) }
+                    Err(TryPushError::Closed(_)) => { Err(self.closed_error()) }
+                }
+            }
+            OverflowPolicy::DropOldest if priority == Priority::Normal => {
+                let opt_evicted = self.lanes.push_evict(priority, item).await
+                    .map_err(|_| { self.closed_error() })?;
+                if let Some(evicted) = opt_evicted {
+                    self.drop_evicted(evicted);
+                }
+                Ok(())
+            }
+            _ => {
+                self.lanes.push(priority, item).await.map_err(|_| { self.closed_error() })
+            }
+        }
+    }
+
+    // fail the sends of an item evicted from the Normal lane, its frames are not written
+    fn drop_evicted(&self, item: WriteItem) {
+        let (n, result) = match item {
+            WriteItem::Frame(channel, _, _, result) => {
+                if channel == DEFAULT_CHANNEL {
+                    self.give_back_credits(1);
+                }
+                (1, result)
+            }
+            WriteItem::Frames(frames, result) => {
+                self.give_back_credits(frames.len());
+                (frames.len(), result)
+            }
+            // only the sends are queued in the Normal lane
+            _ => { return; }
+        };
+        let _ = result.send(Err(net_error::dropped()));
+        net_debug!(addr = %self.remote_address, dropped = n, "evict the oldest send");
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.add_message_dropped(n as u64);
+        }
+    }
+
+    // the credits of the frames of the default channel which were not written
+    fn give_back_credits(&self, n: usize) {
+        if let (Some(flow), true) = (&self.opt_flow, n > 0) {
+            flow.give_back(n.min(u32::MAX as usize) as u32);
+        }
+    }
+
+    // a frame of the default channel was taken by a recv, its credit is granted back to the
+    // peer with the batch
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn consumed(&self) {
+        let _t = task_trace!();
+        let opt_n = self.opt_flow.as_ref().and_then(|flow| { flow.consume() });
+        if let Some(n) = opt_n {
+            // the write direction may have been shut down
+            let _ = self.grant_credit(DEFAULT_CHANNEL, n).await;
+        }
+    }
+
+    async fn wait_written(receiver: oneshot::Receiver<Res<()>>) -> Res<()> {
+        match receiver.await {
+            Ok(r) => { r }
+            Err(_) => { Err(net_error::writer_stopped()) }
         }
     }
 
     // receive a message
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn recv<M: MsgTrait + 'static>(&self) -> Res<Message<M>> {
         let _t = task_trace!();
+        let (m, _) = self.recv_vectored::<M>().await?;
+        Ok(m)
+    }
 
-        let mut stream = self.receiver.lock().instrument(trace_span!("lock")).await;
-        let opt = stream.next().await;
-        let r = match opt {
-            Some(r) => { r }
-            None => { return Err(ET::EOF); }
-        };
-        let b = match r {
-            Ok(b) => { b }
-            Err(_e) => { return Err(ET::NoneOption); }
+    // receive a message, and the payload following it in the frame, see `send_vectored`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn recv_vectored<M: MsgTrait + 'static>(&self) -> Res<(Message<M>, BytesMut)> {
+        let _t = task_trace!();
+
+        let mut queue = self.receiver.lock().instrument(trace_span!("lock")).await;
+        let opt_held = self.held.lock().unwrap().pop_front();
+        let b = match opt_held {
+            Some(b) => { b }
+            None => {
+                match queue.recv().await {
+                    Some(b) => { b }
+                    None => {
+                        // the reader task stopped, and all the frames it read were received
+                        return Err(self.reader_state.reason());
+                    }
+                }
+            }
         };
-        let r = decode_message::<Message<M>>(b.as_slice());
-        match r {
-            Ok((m, _)) => { return Ok(m); }
+        drop(queue);
+        self.consumed().await;
+        self.decode_received(b)
+    }
+
+    // the message of a frame taken by a recv, and the payload after it
+    pub(crate) fn decode_received<M: MsgTrait + 'static>(&self, b: BytesMut) -> Res<(Message<M>, BytesMut)> {
+        match self.decode_frame::<M>(b.as_slice()) {
+            Ok((m, opt_used)) => { Ok(self.received(m, opt_used, b)) }
+            Err(e) => {
+                if let Some(metrics) = &self.opt_metrics {
+                    metrics.add_decode_error();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Receive the first message matching the predicate, the frames passed over are held, in
+    // order, for the following recvs. Only one filter may be pending, another one fails with
+    // `net_error::filter_busy`, while the plain recvs go on with the held frames.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn recv_filter<M: MsgTrait + 'static>(
+        &self,
+        pred: Box<dyn Fn(&Message<M>) -> bool + Send>,
+    ) -> Res<Message<M>> {
+        let _t = task_trace!();
+        if self.filtering.swap(true, Ordering::SeqCst) {
+            return Err(net_error::filter_busy());
+        }
+        let _filtering = scopeguard::guard(&self.filtering, |f| { f.store(false, Ordering::SeqCst); });
+
+        let mut queue = self.receiver.lock().instrument(trace_span!("lock")).await;
+        // the held frames are only taken under the lock of the receiver, they are matched out
+        // of their own lock, the predicate may take time
+        let mut held = std::mem::take(&mut *self.held.lock().unwrap());
+        let opt_found = held.iter().enumerate().find_map(|(i, b)| {
+            match self.decode_frame::<M>(b.as_slice()) {
+                Ok((m, opt_used)) if pred(&m) => { Some((i, m, opt_used)) }
+                _ => { None }
+            }
+        });
+        let opt_found = opt_found.and_then(|(i, m, opt_used)| {
+            held.remove(i).map(|b| { (m, opt_used, b) })
+        });
+        *self.held.lock().unwrap() = held;
+        if let Some((m, opt_used, b)) = opt_found {
+            drop(queue);
+            self.consumed().await;
+            let (m, _) = self.received(m, opt_used, b);
+            return Ok(m);
+        }
+        loop {
+            let b = match queue.recv().await {
+                Some(b) => { b }
+                None => { return Err(self.reader_state.reason()); }
+            };
+            // a frame failing to decode is left to the plain recvs
+            if let Ok((m, opt_used)) = self.decode_frame::<M>(b.as_slice()) {
+                if pred(&m) {
+                    drop(queue);
+                    self.consumed().await;
+                    let (m, _) = self.received(m, opt_used, b);
+                    return Ok(m);
+                }
+            }
+            self.held.lock().unwrap().push_back(b);
+            // let the plain recvs waiting take the held frame
+            drop(queue);
+            queue = self.receiver.lock().instrument(trace_span!("lock")).await;
+        }
+    }
+
+    // decode a frame read by the reader task, the bytes used by the message are None for a
+    // DTM test message
+    fn decode_frame<M: MsgTrait + 'static>(&self, b: &[u8]) -> Res<(Message<M>, Option<usize>)> {
+        match decode_message::<Message<M>>(b) {
+            Ok((m, used)) => { Ok((m, Some(used))) }
             Err(e) => {
                 if self.enable_dtm_test {
-                    return parse_dtm_message::parse_dtm_message(b.as_slice());
+                    let m = parse_dtm_message::parse_dtm_message(b)?;
+                    Ok((m, None))
                 } else {
                     Err(e)
                 }
@@ -95,14 +1032,865 @@ impl _Endpoint {
         }
     }
 
-    #[async_backtrace::framed]
+    // the message decoded from the frame is returned by a recv, with the payload after it
+    fn received<M: MsgTrait + 'static>(&self, m: Message<M>, opt_used: Option<usize>, b: BytesMut) -> (Message<M>, BytesMut) {
+        let used = match opt_used {
+            Some(used) => { used }
+            None => { return (m, BytesMut::new()); }
+        };
+        net_debug!(peer = m.source(), addr = %self.remote_address, msg_len = b.len(), "recv message");
+        if let Some(sink) = &self.opt_record_sink {
+            write_record(sink, RecordDirection::Recv, self.remote_address, m.source(), b.as_slice());
+        }
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.add_message_in(b.len());
+        }
+        let mut payload = b;
+        payload.advance(used);
+        (m, payload)
+    }
+
+    // send a ping in the High lane, and wait for the pong of the peer, return the round trip
+    // time, the frames are read and answered by the reader tasks, a pending `recv` does not
+    // see them
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        if self.opt_frame_codec.is_some() {
+            return Err(net_error::unsupported("ping over a user frame codec"));
+        }
+        let (nonce, receiver) = self.pings.register();
+        let start = self.clock.now();
+        let r_push = self.lanes.push(Priority::High, WriteItem::Control(ControlFrame::Ping(nonce))).await;
+        if r_push.is_err() {
+            self.pings.cancel(nonce);
+            return Err(self.closed_error());
+        }
+        match timeout_of(self.clock.as_ref(), duration, receiver).await {
+            Some(Ok(())) => { Ok(self.clock.now().saturating_duration_since(start)) }
+            // the reader stopped
+            Some(Err(_)) => { Err(self.reader_state.reason()) }
+            None => {
+                self.pings.cancel(nonce);
+                Err(net_error::timeout("waiting for the pong"))
+            }
+        }
+    }
+
+    // Register the queue of the channel, and grant the peer its capacity, return the queue,
+    // the credits to send on it, and the capacity. The channel 0 is the one of `send` and
+    // `recv`, the user frame codecs have no channel.
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn open_channel(&self, id: u16) -> Res<(mpsc::Receiver<BytesMut>, Arc<Semaphore>, u32)> {
+        let _t = task_trace!();
+        if id == DEFAULT_CHANNEL {
+            return Err(net_error::invalid_option_of("channel", "0 is the default channel of send and recv"));
+        }
+        if id == FRAGMENT_CHANNEL {
+            return Err(net_error::invalid_option_of("channel", "the channel of the fragments"));
+        }
+        if self.opt_frame_codec.is_some() {
+            return Err(net_error::unsupported("open_channel over a user frame codec"));
+        }
+        let (s, r) = mpsc::channel(self.channel_capacity);
+        let credits = match self.channels.open(id, s) {
+            Ok(credits) => { credits }
+            Err(ET::EOF) => { return Err(self.reader_state.reason()); }
+            Err(e) => { return Err(e); }
+        };
+        let capacity = self.channel_capacity as u32;
+        self.grant_credit(id, capacity).await?;
+        net_debug!(addr = %self.remote_address, channel = id, capacity = capacity, "open channel");
+        Ok((r, credits, capacity))
+    }
+
+    // allow the peer to send n frames more on the channel
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub(crate) async fn grant_credit(&self, id: u16, n: u32) -> Res<()> {
+        let _t = task_trace!();
+        let r_push = self.lanes.push(Priority::High, WriteItem::Control(ControlFrame::Credit(id, n))).await;
+        r_push.map_err(|_| { self.closed_error() })
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn close(&self) -> Res<()> {
         let _t = task_trace!();
+        trace!("close endpoint, {} {}", self.direction(), self.remote_address);
+        net_debug!(addr = %self.remote_address, inbound = self.inbound, peer_name = ?self.peer_name(), "close endpoint");
+        let opt_data = self.user_data.lock().unwrap().take();
+        drop(opt_data);
         let r1 = {
-            let mut sink = self.sender.lock().await;
-            sink.close().await
+            let mut guard = self.sender.lock().await;
+            match &mut *guard {
+                Some(sink) => { sink.close().await }
+                // the stream was handed over
+                None => { Ok(()) }
+            }
         };
-        res_io(r1)?;
+        r1.map_err(|e| { net_error::io_error(e, "close", self.remote_address) })?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // the refilled budget starts full
+    pub fn set_rate_limit(&self, opt_limit: Option<RateLimit>) {
+        let mut guard = self.rate_limit.lock().unwrap();
+        *guard = opt_limit.map(|l| { TokenBucket::new(l, self.clock.now()) });
+    }
+
+    // the data replaced is dropped out of the lock, its drop may use the endpoint
+    pub fn set_user_data(&self, opt_data: Option<Box<dyn Any + Send + Sync>>) {
+        let opt_old = {
+            let mut guard = self.user_data.lock().unwrap();
+            std::mem::replace(&mut *guard, opt_data.map(Arc::from))
+        };
+        drop(opt_old);
+    }
+
+    pub fn user_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.user_data.lock().unwrap().clone()
+    }
+
+    // write the frames already queued and shut down the write direction, the node is stopping
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn drain(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.draining.store(true, Ordering::SeqCst);
+        self.shutdown_write().await
+    }
+
+    // Close the lanes and fail the sends still queued with `net_error::shutting_down`, the
+    // node is stopping right away, return the number of their messages. The messages of the
+    // batch the writer task is writing are not counted.
+    pub fn abort_queued(&self) -> u64 {
+        self.draining.store(true, Ordering::SeqCst);
+        let e = net_error::shutting_down();
+        let mut dropped = 0;
+        for item in self.lanes.close_drain() {
+            match item {
+                WriteItem::Frame(_, _, _, result) => {
+                    dropped += 1;
+                    let _ = result.send(Err(e.clone()));
+                }
+                WriteItem::Frames(frames, result) => {
+                    dropped += frames.len() as u64;
+                    let _ = result.send(Err(e.clone()));
+                }
+                WriteItem::Shutdown(result) | WriteItem::Release(result) => {
+                    let _ = result.send(Err(e.clone()));
+                }
+                WriteItem::Control(_) => {}
+                // nothing was kept
+                WriteItem::Salvage(result) => { let _ = result.send(Ok(())); }
+            }
+        }
+        if dropped > 0 {
+            net_debug!(addr = %self.remote_address, dropped = dropped, "drop the queued sends on stop");
+            if let Some(metrics) = &self.opt_metrics {
+                metrics.add_message_dropped(dropped);
+            }
+        }
+        dropped
+    }
+
+    // the error of a send after the lanes were closed
+    fn closed_error(&self) -> ET {
+        if self.draining.load(Ordering::SeqCst) {
+            net_error::shutting_down()
+        } else {
+            net_error::send_closed()
+        }
+    }
+
+    // write the frames already queued, stop the writer and the reader tasks, and take the
+    // stream back with the bytes read ahead, the frames already read are still returned by
+    // `recv`, it fails if the endpoint was closed or handed over before
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn into_raw_stream(&self) -> Res<RawStream> {
+        let _t = task_trace!();
+        let opt_release = self.release.lock().unwrap().take();
+        let release = opt_release.ok_or_else(net_error::send_closed)?;
+        let (s, r) = oneshot::channel();
+        self.lanes.close_with(WriteItem::Release(s)).map_err(|_| { self.closed_error() })?;
+        Self::wait_written(r).await?;
+        let (s, r) = oneshot::channel();
+        release.send(s).map_err(|_| { self.reader_state.reason() })?;
+        let stream = r.await.map_err(|_| { self.reader_state.reason() })?;
+        let opt_sink = self.sender.lock().await.take();
+        let sink = opt_sink.ok_or_else(net_error::send_closed)?;
+        let framed = stream.reunite(sink).map_err(|e| {
+            ET::FatalError(format!("reunite the stream of {}, {}", self.remote_address, e))
+        })?;
+        let parts = framed.into_parts();
+        net_debug!(addr = %self.remote_address, read_ahead = parts.read_buf.len(), "endpoint stream handed over");
+        Ok(RawStream {
+            stream: parts.io,
+            read_buf: parts.read_buf,
+        })
+    }
+
+    // stop the writer after the queued frames were written, or kept if a write failed, and take
+    // the frames kept
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn take_unsent(&self) -> Res<Unsent> {
+        let _t = task_trace!();
+        let unsent = match &self.opt_unsent {
+            Some(u) => { u }
+            None => { return Ok(Unsent::default()); }
+        };
+        let (s, r) = oneshot::channel();
+        if self.lanes.close_with(WriteItem::Salvage(s)).is_ok() {
+            let _ = r.await;
+        }
+        // else the lanes were closed by a failed write, or a shutdown
+        let taken = std::mem::take(&mut *unsent.lock().unwrap());
+        net_debug!(addr = %self.remote_address, frames = taken.len(), "take unsent");
+        Ok(taken)
+    }
+
+    // queue the frames of the sends in the Normal lane, their sends complete when they were
+    // written, the ones not queued fail
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn resend_unsent(&self, unsent: Unsent) -> Res<()> {
+        let _t = task_trace!();
+        for (frames, result) in unsent.sends {
+            if self.lanes.push(Priority::Normal, WriteItem::Frames(frames, result)).await.is_err() {
+                return Err(self.closed_error());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn shutdown_write(&self) -> Res<()> {
+        let _t = task_trace!();
+        let (s, r) = oneshot::channel();
+        // the frames already queued are written before the shutdown
+        let r_close = self.lanes.close_with(WriteItem::Shutdown(s));
+        match r_close {
+            Ok(()) => { Self::wait_written(r).await }
+            Err(_) => {
+                // it was already shut down
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for _Endpoint {
+    fn drop(&mut self) {
+        self.reader_state.stop(ET::EOF);
+        let _ = self.task_notifier.notify_all();
+    }
+}
+
+impl ReaderState {
+    fn new(opt_metrics: Option<Arc<Metrics>>, inbound: bool) -> Self {
+        Self {
+            reason: SyncMutex::new(None),
+            stopped: Notifier::new(),
+            opt_metrics,
+            inbound,
+        }
+    }
+
+    // ET::EOF if the peer closed the connection, or the reason why the reader task stopped
+    pub fn reason(&self) -> ET {
+        let guard = self.reason.lock().unwrap();
+        match &*guard {
+            Some(e) => { e.clone() }
+            None => { ET::EOF }
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.is_notified()
+    }
+
+    // wait until the reader task stopped, and return the reason
+    pub async fn wait_stopped(&self) -> ET {
+        self.stopped.notified().await;
+        self.reason()
+    }
+
+    fn stop(&self, reason: ET) {
+        {
+            let mut guard = self.reason.lock().unwrap();
+            if guard.is_some() {
+                return;
+            }
+            *guard = Some(reason);
+        }
+        if let Some(metrics) = &self.opt_metrics {
+            metrics.connection_closed(self.inbound);
+        }
+        let _ = self.stopped.notify_all();
+    }
+}
+
+impl Writer {
+    // take the frames queued in the lanes as a batch, encode them into the buffer of the sink,
+    // and flush it once, the sink writes the buffer until all of it was written, a frame sent
+    // with `OptSend::enable_flush` ends the batch
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn write_loop(mut self) {
+        let _t = task_trace!();
+        if self.flush_preamble {
+            let mut guard = self.sender.lock().await;
+            if let Some(sink) = &mut *guard {
+                // a failure is met again by the first write
+                let _ = sink.flush().await;
+            }
+        }
+        loop {
+            let mut batch: WriteBatch = vec![];
+            let mut batch_bytes = 0;
+            let mut opt_shutdown = None;
+            let mut opt_release = None;
+            let mut opt_salvage = None;
+            let mut opt_item = Some(self.lanes.pop().await);
+            while let Some(item) = opt_item.take() {
+                let flush = match item {
+                    WriteItem::Frame(channel, bytes, flush, result) => {
+                        batch_bytes += bytes.len();
+                        let (seq, opt_stamp) = self.stamp(None);
+                        batch.push((OutFrame::Data(channel, seq, bytes), opt_stamp, Some(result)));
+                        flush
+                    }
+                    WriteItem::Frames(frames, result) => {
+                        for (opt_stamp, bytes) in frames {
+                            batch_bytes += bytes.len();
+                            let (seq, opt_stamp) = self.stamp(opt_stamp);
+                            batch.push((OutFrame::Data(DEFAULT_CHANNEL, seq, bytes), opt_stamp, None));
+                        }
+                        // the result of the chunk is sent with the last frame of it
+                        if let Some((_, _, opt_result)) = batch.last_mut() {
+                            *opt_result = Some(result);
+                        }
+                        false
+                    }
+                    WriteItem::Control(c) => {
+                        batch.push((OutFrame::Control(c), None, None));
+                        true
+                    }
+                    WriteItem::Shutdown(result) => {
+                        // it is the last item
+                        opt_shutdown = Some(result);
+                        break;
+                    }
+                    WriteItem::Release(result) => {
+                        // it is the last item
+                        opt_release = Some(result);
+                        break;
+                    }
+                    WriteItem::Salvage(result) => {
+                        // it is the last item
+                        opt_salvage = Some(result);
+                        break;
+                    }
+                };
+                if self.limit.is_full(batch.len(), batch_bytes, flush) {
+                    break;
+                }
+                opt_item = self.lanes.try_pop();
+            }
+            if !batch.is_empty() {
+                if let Err(e) = self.write_batch(batch).await {
+                    trace!("endpoint write error, {}, {}", e.to_string(), self.address);
+                    if self.opt_unsent.is_none() {
+                        self.fail_queued(&e);
+                    }
+                    if let Some(abort) = self.opt_abort.take() {
+                        let _ = abort.send(e.clone());
+                    }
+                    // the frames were kept or failed, and the lanes closed
+                    for result in [opt_shutdown, opt_release].into_iter().flatten() {
+                        let _ = result.send(Err(e.clone()));
+                    }
+                    if let Some(result) = opt_salvage {
+                        let _ = result.send(Ok(()));
+                    }
+                    return;
+                }
+            }
+            if let Some(result) = opt_salvage {
+                let _ = result.send(Ok(()));
+                return;
+            }
+            if let Some(result) = opt_shutdown {
+                let r = {
+                    let mut guard = self.sender.lock().await;
+                    match &mut *guard {
+                        Some(sink) => { sink.close().await }
+                        None => { Ok(()) }
+                    }
+                };
+                let r = r.map_err(|e| { net_error::io_error(e, "shutdown", self.address) });
+                let _ = result.send(r);
+                return;
+            }
+            if let Some(result) = opt_release {
+                // the batch was flushed
+                let _ = result.send(Ok(()));
+                return;
+            }
+        }
+    }
+
+    // the sequence number of a frame taken to write, a frame resent keeps the one it was
+    // stamped with by the session
+    fn stamp(&mut self, opt_stamp: Option<Stamp>) -> (u64, Option<Stamp>) {
+        match (opt_stamp, &self.opt_session) {
+            (Some(stamp), _) => { (stamp.seq(), Some(stamp)) }
+            (None, Some(session)) => {
+                let stamp = session.stamp();
+                (stamp.seq(), Some(stamp))
+            }
+            (None, None) => {
+                let seq = self.next_seq;
+                self.next_seq = if seq == MAX_SEQ { 1 } else { seq + 1 };
+                (seq, None)
+            }
+        }
+    }
+
+    // write the batch, the frames of the default channel are copied before the write when they
+    // are kept, and kept if it failed, see `keep_unsent`, the sink is dropped if the write
+    // failed, the sequence numbers of the others are given back once it completed
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn write_batch(&self, batch: WriteBatch) -> Res<()> {
+        let _t = task_trace!();
+        let keep = self.opt_unsent.is_some();
+        // the frames of each send, and its result
+        let mut results = Vec::with_capacity(batch.len());
+        let mut frames = vec![];
+        let mut stamps = Vec::with_capacity(batch.len());
+        let mut out = Vec::with_capacity(batch.len());
+        for (frame, opt_stamp, opt_result) in batch {
+            match (keep, &frame) {
+                (true, OutFrame::Data(DEFAULT_CHANNEL, _, bytes)) => { frames.push((opt_stamp, bytes.clone())); }
+                _ => { stamps.push(opt_stamp); }
+            }
+            out.push(frame);
+            if let Some(result) = opt_result {
+                results.push((std::mem::take(&mut frames), result));
+            }
+        }
+        let r = {
+            let mut guard = self.sender.lock().await;
+            let r = match &mut *guard {
+                Some(sink) => { self.write_frames(sink, out).await }
+                None => {
+                    // the stream was handed over
+                    let e = io::Error::from(io::ErrorKind::NotConnected);
+                    Err(net_error::io_error(e, "write", self.address))
+                }
+            };
+            if r.is_err() {
+                // the socket is closed once the reader task dropped the other half
+                *guard = None;
+            }
+            r
+        };
+        drop(stamps);
+        match (&r, &self.opt_unsent) {
+            (Err(e), Some(unsent)) => { self.keep_unsent(unsent, results, e); }
+            _ => {
+                for (_, result) in results {
+                    let _ = result.send(r.clone());
+                }
+            }
+        }
+        r
+    }
+
+    // feed the frames and flush them, within the write timeout if any
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn write_frames(&self, sink: &mut FramedSink, out: Vec<OutFrame>) -> Res<()> {
+        let _t = task_trace!();
+        let write = async {
+            for frame in out {
+                sink.feed(frame).await?;
+            }
+            sink.flush().await
+        };
+        let r = match self.opt_write_timeout {
+            Some(duration) => {
+                match timeout_of(self.clock.as_ref(), duration, write).await {
+                    Some(r) => { r }
+                    None => { return Err(net_error::write_timeout()); }
+                }
+            }
+            None => { write.await }
+        };
+        r.map_err(|e| { net_error::io_error(e, "write", self.address) })
+    }
+
+    // close the lanes and fail the sends still queued, the writer stopped
+    fn fail_queued(&self, e: &ET) {
+        for item in self.lanes.close_drain() {
+            match item {
+                WriteItem::Frame(_, _, _, result)
+                | WriteItem::Frames(_, result)
+                | WriteItem::Shutdown(result)
+                | WriteItem::Release(result) => {
+                    let _ = result.send(Err(e.clone()));
+                }
+                WriteItem::Control(_) => {}
+                // nothing was kept
+                WriteItem::Salvage(result) => { let _ = result.send(Ok(())); }
+            }
+        }
+    }
+
+    // Keep the frames of the failed batch and the ones still queued, in their order, and close
+    // the lanes, the store is locked until all of them were kept. The sends of the other
+    // channels, the shutdown and the release fail with the error, the control frames are
+    // dropped.
+    fn keep_unsent(&self, unsent: &SyncMutex<Unsent>, results: Vec<(Vec<UnsentFrame>, oneshot::Sender<Res<()>>)>, e: &ET) {
+        let mut guard = unsent.lock().unwrap();
+        let mut opt_salvage = None;
+        let mut keep = |frames: Vec<UnsentFrame>, result: oneshot::Sender<Res<()>>| {
+            if frames.is_empty() {
+                let _ = result.send(Err(e.clone()));
+            } else {
+                guard.sends.push((frames, result));
+            }
+        };
+        for (frames, result) in results {
+            keep(frames, result);
+        }
+        for item in self.lanes.close_drain() {
+            match item {
+                WriteItem::Frame(DEFAULT_CHANNEL, bytes, _, result) => { keep(vec![(None, bytes)], result); }
+                WriteItem::Frame(_, _, _, result) => { keep(vec![], result); }
+                WriteItem::Frames(frames, result) => { keep(frames, result); }
+                WriteItem::Control(_) => {}
+                WriteItem::Shutdown(result) | WriteItem::Release(result) => {
+                    let _ = result.send(Err(e.clone()));
+                }
+                WriteItem::Salvage(result) => { opt_salvage = Some(result); }
+            }
+        }
+        let kept = guard.len();
+        drop(guard);
+        trace!("keep {} frames unsent by {}, {}", kept, self.address, e.to_string());
+        if let Some(result) = opt_salvage {
+            let _ = result.send(Ok(()));
+        }
+    }
+}
+
+impl BatchLimit {
+    // cut the batch at the limits, or right after a frame to flush
+    fn is_full(&self, frames: usize, bytes: usize, flush: bool) -> bool {
+        flush || frames >= self.max || bytes >= self.bytes
+    }
+}
+
+impl Reader {
+    // read frames until the connection was closed or failed, or the stream was asked back by
+    // the release, return the reason
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn read_loop(
+        mut self,
+        mut release: oneshot::Receiver<oneshot::Sender<FramedStream>>,
+        mut abort: oneshot::Receiver<ET>,
+    ) -> ET {
+        let _t = task_trace!();
+        let mut releasable = true;
+        let mut abortable = true;
+        loop {
+            let next = select! {
+                next = self.next_frame() => { next }
+                r = &mut abort, if abortable => {
+                    match r {
+                        Ok(e) => { ReadNext::Abort(e) }
+                        Err(_) => {
+                            // the writer stopped otherwise
+                            abortable = false;
+                            continue;
+                        }
+                    }
+                }
+                r = &mut release, if releasable => {
+                    match r {
+                        Ok(s) => { ReadNext::Release(s) }
+                        Err(_) => {
+                            // the endpoint was dropped
+                            releasable = false;
+                            continue;
+                        }
+                    }
+                }
+            };
+            let opt = match next {
+                ReadNext::Frame(opt) => { opt }
+                ReadNext::Idle => {
+                    trace!("endpoint idle timeout, {}", self.description);
+                    // close the connection, the peer would read EOF
+                    let mut guard = self.sender.lock().await;
+                    if let Some(sink) = &mut *guard {
+                        let _ = sink.close().await;
+                    }
+                    return net_error::idle_timeout();
+                }
+                ReadNext::Abort(e) => {
+                    trace!("endpoint reader aborted, {}, {}", e.to_string(), self.description);
+                    return e;
+                }
+                ReadNext::Release(s) => {
+                    trace!("endpoint stream released, {}", self.description);
+                    let _ = s.send(self.stream);
+                    return ET::EOF;
+                }
+            };
+            let (hdr, b) = match opt {
+                Some(Ok(f)) => { f }
+                Some(Err(e)) => {
+                    trace!("endpoint read error, {}, {}", e, self.description);
+                    let opt_error = match (ChecksumMismatch::of(&e), PreambleMismatch::of(&e)) {
+                        (Some(m), _) => { Some(net_error::checksum_mismatch(m.expected, m.actual, self.address)) }
+                        (_, Some(PreambleMismatch::Magic(received))) => {
+                            Some(net_error::bad_protocol(&received, self.address))
+                        }
+                        (_, Some(PreambleMismatch::Version { expected, actual })) => {
+                            Some(net_error::version_mismatch(expected, actual, self.address))
+                        }
+                        (None, None) => { None }
+                    };
+                    if let Some(error) = opt_error {
+                        // the bytes after it cannot be trusted, close the connection
+                        let mut guard = self.sender.lock().await;
+                        if let Some(sink) = &mut *guard {
+                            let _ = sink.close().await;
+                        }
+                        return error;
+                    }
+                    return net_error::io_error(e, "read", self.address);
+                }
+                None => {
+                    trace!("endpoint EOF, {}", self.description);
+                    return ET::EOF;
+                }
+            };
+            if hdr.seq() == CONTROL_SEQ {
+                self.handle_control(b.as_slice()).await;
+                continue;
+            }
+            if let Some(dedup) = &self.dedup {
+                match dedup.accept(hdr.seq()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        trace!("drop duplicated frame, seq {}, {}", hdr.seq(), self.description);
+                        continue;
+                    }
+                    Err(reason) => {
+                        trace!("dedup rejected, {}, {}", reason, self.description);
+                        let mut guard = self.sender.lock().await;
+                        if let Some(sink) = &mut *guard {
+                            let _ = sink.close().await;
+                        }
+                        return net_error::dedup_rejected(&reason, self.address);
+                    }
+                }
+            }
+            self.throttle(b.len()).await;
+            let (channel, b) = if hdr.channel() == FRAGMENT_CHANNEL {
+                match self.reassembly.add(b) {
+                    Ok(Reassembled::Message(m)) => { (DEFAULT_CHANNEL, m) }
+                    Ok(Reassembled::Pending) => { continue; }
+                    Ok(Reassembled::Dropped) => {
+                        trace!("drop fragment, {} bytes buffered, {}", self.reassembly.buffered(), self.description);
+                        continue;
+                    }
+                    Err(reason) => {
+                        trace!("fragment rejected, {}, {}", reason, self.description);
+                        let mut guard = self.sender.lock().await;
+                        if let Some(sink) = &mut *guard {
+                            let _ = sink.close().await;
+                        }
+                        return net_error::fragment_rejected(&reason, self.address);
+                    }
+                }
+            } else {
+                (hdr.channel(), b)
+            };
+            let b = if channel == DEFAULT_CHANNEL {
+                b
+            } else {
+                // a full channel does not hold the reader back
+                match self.channels.route(channel, b) {
+                    Route::Delivered => { continue; }
+                    Route::Discarded => {
+                        trace!("drop frame of closed channel {}, {}", channel, self.description);
+                        continue;
+                    }
+                    Route::Overrun => {
+                        trace!("channel {} overrun, {}", channel, self.description);
+                        return ET::FatalError(format!("the peer overran the credits of channel {}, {}",
+                                                      channel, self.description));
+                    }
+                    Route::Unknown(b) => {
+                        trace!("frame of unknown channel {} to the default channel, {}", channel, self.description);
+                        b
+                    }
+                }
+            };
+            let r = self.queue.send(b).await;
+            if r.is_err() {
+                // the endpoint was dropped
+                return ET::EOF;
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn next_frame(&mut self) -> ReadNext {
+        let _t = task_trace!();
+        match self.idle_timeout {
+            Some(duration) => {
+                match timeout_of(self.clock.as_ref(), duration, self.stream.next()).await {
+                    Some(opt) => { ReadNext::Frame(opt) }
+                    None => { ReadNext::Idle }
+                }
+            }
+            None => { ReadNext::Frame(self.stream.next().await) }
+        }
+    }
+
+    // wait without reading the socket until the budget admits the frame
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn throttle(&self, bytes: usize) {
+        let _t = task_trace!();
+        loop {
+            let opt_wait = {
+                let mut guard = self.rate_limit.lock().unwrap();
+                match &mut *guard {
+                    Some(bucket) => { bucket.acquire(bytes, self.clock.now()) }
+                    None => { None }
+                }
+            };
+            match opt_wait {
+                Some(wait) => { self.clock.sleep(wait).await; }
+                None => { return; }
+            }
+        }
+    }
+
+    // answer a ping, complete the ping of a pong, or add the credits of a channel, the ones
+    // of the default channel are of the flow window, or attach the connection to the session
+    // of the peer
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn handle_control(&mut self, payload: &[u8]) {
+        let _t = task_trace!();
+        match ControlFrame::decode(payload) {
+            Some(ControlFrame::Ping(nonce)) => {
+                // the write direction may have been shut down
+                let _ = self.lanes.push(Priority::High, WriteItem::Control(ControlFrame::Pong(nonce))).await;
+            }
+            Some(ControlFrame::Pong(nonce)) => {
+                self.pings.complete(nonce);
+            }
+            Some(ControlFrame::Credit(DEFAULT_CHANNEL, n)) => {
+                // ignored if the flow is not controlled on this side
+                if let Some(flow) = &self.opt_flow {
+                    flow.grant(n);
+                }
+            }
+            Some(ControlFrame::Credit(channel, n)) => {
+                self.channels.grant(channel, n);
+            }
+            Some(ControlFrame::Name(name)) => {
+                net_debug!(addr = %self.address, peer_name = %name, "peer name");
+                *self.peer_name.lock().unwrap() = Some(name);
+            }
+            Some(ControlFrame::Params(capabilities, size)) => {
+                net_debug!(addr = %self.address, capabilities = capabilities, max_message_size = size, "peer params");
+                *self.peer_params.lock().unwrap() = Some((capabilities, size));
+            }
+            Some(ControlFrame::Session(id, low)) => {
+                net_debug!(addr = %self.address, session = id, low = low, "peer session");
+                // a session announced again detaches the previous one
+                self.dedup = Some(self.dedup_sessions.attach(id, low));
+            }
+            None => {
+                trace!("drop unknown control frame, {}", self.description);
+            }
+        }
+    }
+}
+
+impl Default for Written {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            result: Ok(()),
+        }
+    }
+}
+
+impl Written {
+    fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    // keep the first failure
+    fn fail(&mut self, e: ET) {
+        if self.result.is_ok() {
+            self.result = Err(e);
+        }
+    }
+}
+
+impl Pings {
+    fn new() -> Self {
+        Self {
+            next_nonce: AtomicU64::new(1),
+            pending: SyncMutex::new(HashMap::new()),
+        }
+    }
+
+    fn register(&self) -> (u64, oneshot::Receiver<()>) {
+        let nonce = self.next_nonce.fetch_add(1, Ordering::SeqCst);
+        let (s, r) = oneshot::channel();
+        let _ = self.pending.lock().unwrap().insert(nonce, s);
+        (nonce, r)
+    }
+
+    fn complete(&self, nonce: u64) {
+        let opt = self.pending.lock().unwrap().remove(&nonce);
+        if let Some(s) = opt {
+            let _ = s.send(());
+        }
+    }
+
+    fn cancel(&self, nonce: u64) {
+        let _ = self.pending.lock().unwrap().remove(&nonce);
+    }
+
+    fn clear(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+fn direction(inbound: bool) -> &'static str {
+    if inbound {
+        "inbound"
+    } else {
+        "outbound"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::endpoint_inner::BatchLimit;
+
+    #[test]
+    fn test_batch_limit() {
+        let limit = BatchLimit { max: 4, bytes: 1024 };
+        assert!(!limit.is_full(1, 100, false));
+        assert!(limit.is_full(1, 100, true));
+        assert!(limit.is_full(4, 100, false));
+        assert!(limit.is_full(2, 1024, false));
+    }
+}
@@ -0,0 +1,292 @@
+use std::io::IoSlice;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::LocalBoxFuture;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::sync::{mpsc, Mutex};
+use tracing::trace;
+
+use crate::client::jittered;
+use crate::clock::Clock;
+use crate::endpoint_async::{EndpointAsync, Unsent};
+use crate::net_error;
+use crate::notifier::Notifier;
+use crate::opt_send::OptSend;
+use crate::priority::Priority;
+use crate::rate_limit::RateLimit;
+use crate::task::spawn_local_task;
+use crate::task_trace;
+
+type SyncRwLock<T> = std::sync::RwLock<T>;
+
+// connect a new endpoint for a slot whose connection failed
+pub(crate) type Reconnect<M> = Arc<dyn Fn() -> LocalBoxFuture<'static, Res<Arc<dyn EndpointAsync<M>>>>>;
+
+// The endpoint of a client of `OptClient::parallel_connections`, several connections to the
+// same server, one a slot. A send goes to the next live slot in turn, the messages of one
+// send and of one `send_all` take the same connection. A task of each slot forwards the
+// incoming messages of its connection to `recv`. The messages of a connection are in order,
+// the messages of different connections are not.
+// The connection of a failed slot is reconnected by its task, the other slots keep working,
+// the sends fail with ET::NetNotConnected while no slot is connected, and with
+// `net_error::send_closed` after `shutdown_write` or `close`.
+pub(crate) struct EndpointParallel<M: MsgTrait + 'static> {
+    address: SocketAddr,
+    // None while the connection of the slot is being reconnected
+    slots: Arc<SyncRwLock<Vec<Option<Arc<dyn EndpointAsync<M>>>>>>,
+    next: AtomicUsize,
+    incoming: Mutex<mpsc::Receiver<Message<M>>>,
+    closed: Arc<AtomicBool>,
+    // stops the tasks of the slots
+    notifier: Notifier,
+}
+
+impl<M: MsgTrait + 'static> EndpointParallel<M> {
+    pub(crate) fn start(
+        endpoints: Vec<Arc<dyn EndpointAsync<M>>>,
+        reconnect: Reconnect<M>,
+        retry_wait: Duration,
+        jitter_fraction: f64,
+        clock: Arc<dyn Clock>,
+        notifier: Notifier,
+    ) -> Self {
+        let address = endpoints[0].remote_address();
+        let (sender, receiver) = mpsc::channel(endpoints.len());
+        let slots = Arc::new(SyncRwLock::new(endpoints.iter().map(|e| { Some(e.clone()) }).collect()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let notifier = notifier.new_child();
+        for (i, e) in endpoints.into_iter().enumerate() {
+            let slot = Slot {
+                index: i,
+                slots: slots.clone(),
+                closed: closed.clone(),
+                sender: sender.clone(),
+                reconnect: reconnect.clone(),
+                retry_wait,
+                jitter_fraction,
+                clock: clock.clone(),
+            };
+            let task_name = format!("parallel connection {} to {}", i, address);
+            let _ = spawn_local_task(notifier.clone(), task_name.as_str(), async move {
+                slot.forward(e).await;
+            });
+        }
+        Self {
+            address,
+            slots,
+            next: AtomicUsize::new(0),
+            incoming: Mutex::new(receiver),
+            closed,
+            notifier,
+        }
+    }
+
+    // the next live slot in turn
+    fn endpoint(&self) -> Res<Arc<dyn EndpointAsync<M>>> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(net_error::send_closed());
+        }
+        let slots = self.slots.read().unwrap();
+        let start = self.next.fetch_add(1, Ordering::SeqCst);
+        for k in 0..slots.len() {
+            if let Some(e) = &slots[(start + k) % slots.len()] {
+                if !e.is_closed() {
+                    return Ok(e.clone());
+                }
+            }
+        }
+        Err(ET::NetNotConnected)
+    }
+
+    fn endpoints(&self) -> Vec<Arc<dyn EndpointAsync<M>>> {
+        let slots = self.slots.read().unwrap();
+        slots.iter().flatten().cloned().collect()
+    }
+}
+
+struct Slot<M: MsgTrait + 'static> {
+    index: usize,
+    slots: Arc<SyncRwLock<Vec<Option<Arc<dyn EndpointAsync<M>>>>>>,
+    closed: Arc<AtomicBool>,
+    sender: mpsc::Sender<Message<M>>,
+    reconnect: Reconnect<M>,
+    retry_wait: Duration,
+    // see `OptClientConnect::set_jitter_fraction`
+    jitter_fraction: f64,
+    clock: Arc<dyn Clock>,
+}
+
+impl<M: MsgTrait + 'static> Slot<M> {
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn forward(self, endpoint: Arc<dyn EndpointAsync<M>>) {
+        let _t = task_trace!();
+        let mut endpoint = endpoint;
+        loop {
+            let e = loop {
+                match endpoint.recv().await {
+                    Ok(m) => {
+                        if self.sender.send(m).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => { break e; }
+                }
+            };
+            if self.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            trace!("parallel connection {} stopped, {}, reconnect", self.index, e.to_string());
+            self.slots.write().unwrap()[self.index] = None;
+            let _ = endpoint.close().await;
+            endpoint = loop {
+                if self.closed.load(Ordering::SeqCst) {
+                    return;
+                }
+                match (self.reconnect)().await {
+                    Ok(e) => { break e; }
+                    Err(_) => { self.clock.sleep(jittered(self.retry_wait, self.jitter_fraction)).await; }
+                }
+            };
+            // closed while reconnecting, the new connection is not kept
+            if self.closed.load(Ordering::SeqCst) {
+                let _ = endpoint.close().await;
+                return;
+            }
+            self.slots.write().unwrap()[self.index] = Some(endpoint.clone());
+        }
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> EndpointAsync<M> for EndpointParallel<M> {
+    fn remote_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn is_inbound(&self) -> bool {
+        false
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    // the connections reconnected later have no budget
+    fn set_rate_limit(&self, opt_limit: Option<RateLimit>) -> Res<()> {
+        for e in self.endpoints() {
+            e.set_rate_limit(opt_limit)?;
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.send(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_counted(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        self.endpoint()?.send_counted(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_priority(&self, m: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.send_priority(m, priority).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_opt(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.send_opt(m, opt).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_confirmed(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.send_confirmed(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_all(&self, messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        match self.endpoint() {
+            Ok(e) => { e.send_all(messages).await }
+            Err(e) => { (0, Err(e)) }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_vectored(&self, header: &Message<M>, payload: &[IoSlice<'_>]) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.send_vectored(header, payload).await
+    }
+
+    // the concurrent recvs are served in FIFO order by the lock, ET::EOF after the close
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv(&self) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let mut incoming = self.incoming.lock().await;
+        match incoming.recv().await {
+            Some(m) => { Ok(m) }
+            None => { Err(ET::EOF) }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        self.endpoint()?.ping(duration).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn close(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.closed.store(true, Ordering::SeqCst);
+        for e in self.endpoints() {
+            let _ = e.close().await;
+        }
+        self.notifier.notify_all();
+        Ok(())
+    }
+
+    // the frames left by any of the connections
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn take_unsent(&self) -> Res<Unsent> {
+        let _t = task_trace!();
+        let mut unsent = Unsent::default();
+        for e in self.endpoints() {
+            unsent.sends.extend(e.take_unsent().await?.sends);
+        }
+        Ok(unsent)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn resend_unsent(&self, unsent: Unsent) -> Res<()> {
+        let _t = task_trace!();
+        self.endpoint()?.resend_unsent(unsent).await
+    }
+
+    // the slots are no longer reconnected, the messages already received are still returned
+    // by `recv`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn shutdown_write(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.closed.store(true, Ordering::SeqCst);
+        let mut r = Ok(());
+        for e in self.endpoints() {
+            if let Err(e) = e.shutdown_write().await {
+                r = Err(e);
+            }
+        }
+        r
+    }
+}
@@ -0,0 +1,315 @@
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::IoSlice;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::select;
+use tokio::sync::{Mutex, Notify, OwnedMutexGuard, Semaphore};
+use tracing::trace;
+
+use crate::channel::ChannelHandle;
+use crate::endpoint_async::{EndpointAsync, Unsent};
+use crate::handle_event::HandleEvent;
+use crate::negotiated::{NegotiatedLimits, NegotiatedParams};
+use crate::net_error;
+use crate::notifier::Notifier;
+use crate::opt_send::OptSend;
+use crate::priority::Priority;
+use crate::rate_limit::RateLimit;
+use crate::task::spawn_local_task;
+use crate::task_trace;
+use crate::transport::RawStream;
+
+// The endpoint of a node of `Delivery::Push`, a task reads the inner endpoint and calls
+// `HandleEvent::on_message`, for up to `OptNode::handler_concurrency` messages at a time.
+// `recv` waits until a handler returned `net_error::not_handled`, it delivers that message and
+// the following ones.
+pub struct EndpointPush<M: MsgTrait + 'static> {
+    inner: Arc<dyn EndpointAsync<M>>,
+    // the messages not handled by on_message, returned by the next recvs, the lock is held by
+    // the task while pushing
+    unread: Arc<Mutex<VecDeque<Message<M>>>>,
+}
+
+impl<M: MsgTrait + 'static> EndpointPush<M> {
+    // start pushing the messages of the endpoint to the handler
+    pub fn start<H: HandleEvent<M> + 'static>(
+        inner: Arc<dyn EndpointAsync<M>>,
+        handle: Arc<H>,
+        concurrency: usize,
+        notifier: Notifier,
+    ) -> Arc<dyn EndpointAsync<M>> {
+        let unread = Arc::new(Mutex::new(VecDeque::new()));
+        // locked before any recv could
+        let opt_guard = unread.clone().try_lock_owned().ok();
+        let ep = Arc::new(Self {
+            inner,
+            unread,
+        });
+        let task_name = format!("endpoint push {}", ep.remote_address());
+        let e = ep.clone();
+        if let Some(guard) = opt_guard {
+            let n = notifier.clone();
+            let _ = spawn_local_task(notifier, task_name.as_str(), async move {
+                if concurrency > 1 {
+                    e.push_concurrent(handle, guard, concurrency, n).await;
+                } else {
+                    e.push_loop(handle, guard).await;
+                }
+            });
+        }
+        ep
+    }
+
+    // the next message is not read before the handler returned, ordering the messages and
+    // pushing back on the peer
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn push_loop<H: HandleEvent<M> + 'static>(
+        self: Arc<Self>,
+        handle: Arc<H>,
+        mut unread: OwnedMutexGuard<VecDeque<Message<M>>>,
+    ) {
+        let _t = task_trace!();
+        let from: Arc<dyn EndpointAsync<M>> = self.clone();
+        loop {
+            // the reader error is reported by the watcher of the endpoint
+            let m = match self.inner.recv().await {
+                Ok(m) => { m }
+                Err(_) => { return; }
+            };
+            match handle.on_message(from.clone(), m.clone()).await {
+                Ok(()) => {}
+                Err(e) if net_error::is_not_handled(&e) => {
+                    trace!("stop pushing the messages of {}, not handled", self.remote_address());
+                    unread.push_back(m);
+                    return;
+                }
+                Err(e) => { handle.on_error(e).await; }
+            }
+        }
+    }
+
+    // a message is read when a handler is free, and handled by a task of its own, the
+    // handlers in flight are waited for after the push stopped, and the messages they did not
+    // handle are left to recv in the order of the endpoint
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn push_concurrent<H: HandleEvent<M> + 'static>(
+        self: Arc<Self>,
+        handle: Arc<H>,
+        mut unread: OwnedMutexGuard<VecDeque<Message<M>>>,
+        concurrency: usize,
+        notifier: Notifier,
+    ) {
+        let _t = task_trace!();
+        let from: Arc<dyn EndpointAsync<M>> = self.clone();
+        let handlers = Arc::new(Semaphore::new(concurrency));
+        // the messages not handled, by their index in the endpoint
+        let not_handled = Arc::new(std::sync::Mutex::new(BTreeMap::new()));
+        let stop = Arc::new(Notify::new());
+        let mut index: u64 = 0;
+        loop {
+            let permit = match handlers.clone().acquire_owned().await {
+                Ok(p) => { p }
+                Err(_) => { break; }
+            };
+            // the reader error is reported by the watcher of the endpoint
+            let m = select! {
+                biased;
+                _ = stop.notified() => { break; }
+                r = self.inner.recv() => {
+                    match r {
+                        Ok(m) => { m }
+                        Err(_) => { break; }
+                    }
+                }
+            };
+            let (h, f, n, s) = (handle.clone(), from.clone(), not_handled.clone(), stop.clone());
+            let i = index;
+            index += 1;
+            let task_name = format!("on_message {} of {}", i, self.remote_address());
+            let _ = spawn_local_task(notifier.clone(), task_name.as_str(), async move {
+                let _permit = permit;
+                match h.on_message(f, m.clone()).await {
+                    Ok(()) => {}
+                    Err(e) if net_error::is_not_handled(&e) => {
+                        n.lock().unwrap().insert(i, m);
+                        s.notify_one();
+                    }
+                    Err(e) => { h.on_error(e).await; }
+                }
+            });
+        }
+        let _all = handlers.acquire_many(concurrency as u32).await;
+        let messages = std::mem::take(&mut *not_handled.lock().unwrap());
+        if !messages.is_empty() {
+            trace!("stop pushing the messages of {}, not handled", self.remote_address());
+        }
+        unread.extend(messages.into_values());
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> EndpointAsync<M> for EndpointPush<M> {
+    fn remote_address(&self) -> SocketAddr {
+        self.inner.remote_address()
+    }
+
+    fn is_inbound(&self) -> bool {
+        self.inner.is_inbound()
+    }
+
+    fn peer_name(&self) -> Option<String> {
+        self.inner.peer_name()
+    }
+
+    fn negotiated(&self) -> Res<NegotiatedParams> {
+        self.inner.negotiated()
+    }
+
+    fn negotiated_limits(&self) -> Res<NegotiatedLimits> {
+        self.inner.negotiated_limits()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn set_rate_limit(&self, opt_limit: Option<RateLimit>) -> Res<()> {
+        self.inner.set_rate_limit(opt_limit)
+    }
+
+    fn set_user_data(&self, opt_data: Option<Box<dyn Any + Send + Sync>>) -> Res<()> {
+        self.inner.set_user_data(opt_data)
+    }
+
+    fn user_data(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.inner.user_data()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_counted(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        self.inner.send_counted(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_priority(&self, m: Message<M>, priority: Priority) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_priority(m, priority).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_opt(&self, m: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_opt(m, opt).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_confirmed(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_confirmed(m).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_all(&self, messages: Box<dyn Iterator<Item=Message<M>> + Send + '_>) -> (u64, Res<()>) {
+        let _t = task_trace!();
+        self.inner.send_all(messages).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_vectored(&self, header: &Message<M>, payload: &[IoSlice<'_>]) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.send_vectored(header, payload).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv(&self) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let opt_unread = self.unread.lock().await.pop_front();
+        match opt_unread {
+            Some(m) => { Ok(m) }
+            None => { self.inner.recv().await }
+        }
+    }
+
+    // the messages left unread by on_message are older than the ones of the inner endpoint
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_filter(&self, pred: Box<dyn Fn(&Message<M>) -> bool + Send>) -> Res<Message<M>> {
+        let _t = task_trace!();
+        {
+            let mut unread = self.unread.lock().await;
+            if let Some(i) = unread.iter().position(|m| { pred(m) }) {
+                if let Some(m) = unread.remove(i) {
+                    return Ok(m);
+                }
+            }
+        }
+        self.inner.recv_filter(pred).await
+    }
+
+    // the messages of the channels are not pushed to on_message
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn open_channel(&self, id: u16) -> Res<ChannelHandle<M>> {
+        let _t = task_trace!();
+        self.inner.open_channel(id).await
+    }
+
+    // the payload of a message pushed to on_message is not delivered
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv_vectored(&self) -> Res<(Message<M>, BytesMut)> {
+        let _t = task_trace!();
+        let opt_unread = self.unread.lock().await.pop_front();
+        match opt_unread {
+            Some(m) => { Ok((m, BytesMut::new())) }
+            None => { self.inner.recv_vectored().await }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn ping(&self, duration: Duration) -> Res<Duration> {
+        let _t = task_trace!();
+        self.inner.ping(duration).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn close(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.close().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn into_raw_stream(&self) -> Res<RawStream> {
+        let _t = task_trace!();
+        self.inner.into_raw_stream().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn take_unsent(&self) -> Res<Unsent> {
+        let _t = task_trace!();
+        self.inner.take_unsent().await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn resend_unsent(&self, unsent: Unsent) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.resend_unsent(unsent).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn shutdown_write(&self) -> Res<()> {
+        let _t = task_trace!();
+        self.inner.shutdown_write().await
+    }
+}
@@ -0,0 +1,157 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream};
+use futures::future::BoxFuture;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+
+use crate::endpoint_async::EndpointAsync;
+
+// The sending half of an endpoint as a `Sink`, see `EndpointAsync::sink`, to pipe a stream of
+// messages into the connection by `SinkExt::send_all` or `StreamExt::forward`.
+// One message is in flight at a time, its send waits for a slot in the lane of the send queue
+// and for the write, `poll_ready` and `poll_flush` are pending until it completed. A send
+// error is returned by the next `poll_ready`, `poll_flush` or `poll_close`. `poll_close`
+// shuts the write direction down, the peer reads EOF after the messages.
+// `poll_send` drives the same send by hand, for a poll loop of another executor, the tasks of
+// the endpoint still run on the tokio runtime of the node.
+pub struct EndpointSink<M: MsgTrait + 'static> {
+    endpoint: Arc<dyn EndpointAsync<M>>,
+    opt_sending: Option<BoxFuture<'static, Res<()>>>,
+    opt_closing: Option<BoxFuture<'static, Res<()>>>,
+}
+
+// The messages received by an endpoint as a `Stream`, see `EndpointAsync::stream`, it ends
+// when the peer closed the connection, the other errors of `recv` are returned once, and
+// end it too. A message is not lost by dropping the stream, as a cancelled `recv`.
+// `poll_recv` drives the same recv by hand, see `EndpointSink::poll_send`.
+pub struct EndpointStream<M: MsgTrait + 'static> {
+    endpoint: Arc<dyn EndpointAsync<M>>,
+    opt_receiving: Option<BoxFuture<'static, Res<Message<M>>>>,
+    done: bool,
+}
+
+impl<M: MsgTrait + 'static> EndpointSink<M> {
+    pub fn new(endpoint: Arc<dyn EndpointAsync<M>>) -> Self {
+        Self {
+            endpoint,
+            opt_sending: None,
+            opt_closing: None,
+        }
+    }
+
+    // Complete the send in flight, then take the message, if it is Some, and send it, ready
+    // when it was written, or failed. Call it again with the same option while it is pending,
+    // the message is taken once.
+    pub fn poll_send(&mut self, cx: &mut Context<'_>, opt_m: &mut Option<Message<M>>) -> Poll<Res<()>> {
+        futures::ready!(self.poll_sent(cx))?;
+        let m = match opt_m.take() {
+            Some(m) => { m }
+            None => { return Poll::Ready(Ok(())); }
+        };
+        self.start_sending(m);
+        self.poll_sent(cx)
+    }
+
+    // the async form of `poll_send`
+    pub async fn send(&mut self, m: Message<M>) -> Res<()> {
+        let mut opt_m = Some(m);
+        futures::future::poll_fn(|cx| { self.poll_send(cx, &mut opt_m) }).await
+    }
+
+    fn start_sending(&mut self, m: Message<M>) {
+        let endpoint = self.endpoint.clone();
+        self.opt_sending = Some(Box::pin(async move { endpoint.send(m).await }));
+    }
+
+    // complete the send in flight
+    fn poll_sent(&mut self, cx: &mut Context<'_>) -> Poll<Res<()>> {
+        let sending = match &mut self.opt_sending {
+            Some(f) => { f }
+            None => { return Poll::Ready(Ok(())); }
+        };
+        let r = futures::ready!(sending.as_mut().poll(cx));
+        self.opt_sending = None;
+        Poll::Ready(r)
+    }
+}
+
+impl<M: MsgTrait + 'static> Sink<Message<M>> for EndpointSink<M> {
+    type Error = ET;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Res<()>> {
+        self.get_mut().poll_sent(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, m: Message<M>) -> Res<()> {
+        self.get_mut().start_sending(m);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Res<()>> {
+        // the send completes after the message was written
+        self.get_mut().poll_sent(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Res<()>> {
+        let s = self.get_mut();
+        futures::ready!(s.poll_sent(cx))?;
+        let closing = s.opt_closing.get_or_insert_with(|| {
+            let endpoint = s.endpoint.clone();
+            Box::pin(async move { endpoint.shutdown_write().await })
+        });
+        closing.as_mut().poll(cx)
+    }
+}
+
+impl<M: MsgTrait + 'static> EndpointStream<M> {
+    pub fn new(endpoint: Arc<dyn EndpointAsync<M>>) -> Self {
+        Self {
+            endpoint,
+            opt_receiving: None,
+            done: false,
+        }
+    }
+
+    // receive the next message as `EndpointAsync::recv`, ET::EOF after the stream ended
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Res<Message<M>>> {
+        if self.done {
+            return Poll::Ready(Err(ET::EOF));
+        }
+        let receiving = self.opt_receiving.get_or_insert_with(|| {
+            let endpoint = self.endpoint.clone();
+            Box::pin(async move { endpoint.recv().await })
+        });
+        let r = futures::ready!(receiving.as_mut().poll(cx));
+        self.opt_receiving = None;
+        if r.is_err() {
+            self.done = true;
+        }
+        Poll::Ready(r)
+    }
+
+    // the async form of `poll_recv`
+    pub async fn recv(&mut self) -> Res<Message<M>> {
+        futures::future::poll_fn(|cx| { self.poll_recv(cx) }).await
+    }
+}
+
+impl<M: MsgTrait + 'static> Stream for EndpointStream<M> {
+    type Item = Res<Message<M>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Res<Message<M>>>> {
+        let s = self.get_mut();
+        if s.done {
+            return Poll::Ready(None);
+        }
+        match futures::ready!(s.poll_recv(cx)) {
+            Ok(m) => { Poll::Ready(Some(Ok(m))) }
+            Err(ET::EOF) => { Poll::Ready(None) }
+            Err(e) => { Poll::Ready(Some(Err(e))) }
+        }
+    }
+}
@@ -6,6 +6,9 @@ use scupt_util::res::Res;
 pub trait EndpointSync<M: MsgTrait + 'static>: Send + Sync {
     fn remote_address(&self) -> SocketAddr;
 
+    // true if the endpoint was accepted by a listener, false if it was created by a connect
+    fn is_inbound(&self) -> bool;
+
     fn send(&self, m: Message<M>) -> Res<()>;
 
     fn recv(&self) -> Res<Message<M>>;
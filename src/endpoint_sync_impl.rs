@@ -21,8 +21,8 @@ pub struct EndpointSyncImpl<M: MsgTrait + 'static> {
     endpoint: Arc<dyn EndpointAsync<M>>,
     s_sender: AsyncSender<Message<M>>,
     s_receiver: Mutex<Option<AsyncReceiver<Message<M>>>>,
-    r_invoke_sender: AsyncSender<SyncSender<Message<M>>>,
-    r_invoke_receiver: Mutex<Option<AsyncReceiver<SyncSender<Message<M>>>>>,
+    r_invoke_sender: AsyncSender<SyncSender<Res<Message<M>>>>,
+    r_invoke_receiver: Mutex<Option<AsyncReceiver<SyncSender<Res<Message<M>>>>>>,
 }
 
 impl<M: MsgTrait + 'static> EndpointSync<M> for EndpointSyncImpl<M> {
@@ -30,18 +30,27 @@ impl<M: MsgTrait + 'static> EndpointSync<M> for EndpointSyncImpl<M> {
         self.endpoint.remote_address()
     }
 
+    fn is_inbound(&self) -> bool {
+        self.endpoint.is_inbound()
+    }
+
     fn send(&self, m: Message<M>) -> Res<()> {
-        self.s_sender.send(m).unwrap();
+        self.s_sender.send(m).map_err(|e| {
+            ET::SenderError(e.to_string())
+        })?;
         Ok(())
     }
 
     fn recv(&self) -> Res<Message<M>> {
-        let (s, r) = std::sync::mpsc::channel::<Message<M>>();
+        let (s, r) = std::sync::mpsc::channel::<Res<Message<M>>>();
         let _ = self.r_invoke_sender.send(s).map_err(|e| {
             ET::SenderError(e.to_string())
         })?;
-        let message = r.recv().unwrap();
-        Ok(message)
+        // the receive loop stopped if the channel was dropped
+        let result = r.recv().map_err(|e| {
+            ET::RecvError(e.to_string())
+        })?;
+        result
     }
 
     fn close(&self) -> Res<()> {
@@ -70,13 +79,13 @@ impl<M: MsgTrait + 'static> EndpointSyncImpl<M> {
         }
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_send(&self) -> Res<()> {
         let _t = task_trace!();
         let mut opt = self.s_receiver.lock().await;
         let mut opt_receiver = None;
         std::mem::swap(&mut opt_receiver, &mut opt);
-        let mut receiver = opt_receiver.unwrap();
+        let mut receiver = opt_receiver.ok_or(ET::NoneOption)?;
         loop {
             let opt_m = receiver.recv().await;
             match opt_m {
@@ -89,13 +98,13 @@ impl<M: MsgTrait + 'static> EndpointSyncImpl<M> {
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_receive(&self) -> Res<()> {
         let _t = task_trace!();
         let mut opt = self.r_invoke_receiver.lock().await;
         let mut opt_receiver = None;
         std::mem::swap(&mut opt_receiver, &mut opt);
-        let mut receiver = opt_receiver.unwrap();
+        let mut receiver = opt_receiver.ok_or(ET::NoneOption)?;
         loop {
             let opt_channel = receiver.recv().await;
             let channel = match opt_channel {
@@ -105,12 +114,14 @@ impl<M: MsgTrait + 'static> EndpointSyncImpl<M> {
             let r = self.endpoint.recv().await;
             match r {
                 Ok(message) => {
-                    channel.send(message).map_err(|e| {
+                    channel.send(Ok(message)).map_err(|e| {
                         ET::SenderError(e.to_string())
                     })?;
                 }
                 Err(e) => {
                     error!("error {}", e);
+                    // the caller of recv gets the error, instead of a dropped channel
+                    let _ = channel.send(Err(e));
                     break;
                 }
             }
@@ -0,0 +1,131 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::message::{decode_message, encode_message, Message, MsgTrait};
+use scupt_util::res::Res;
+use tokio::net::UdpSocket;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::net_error;
+use crate::opt_ep::OptEP;
+use crate::task_trace;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// the max payload of an UDP datagram over IPv4
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
+
+// An endpoint over an UDP socket, `send` writes one datagram and `recv` reads one datagram.
+// An outbound endpoint is connected to its remote, an inbound endpoint serves all the peers, its
+// remote address is the peer of the last received datagram, which `send` replies to.
+pub struct EndpointUdp {
+    socket: Arc<UdpSocket>,
+    remote_address: SyncMutex<SocketAddr>,
+    inbound: bool,
+    enable_dtm_test: bool,
+}
+
+impl EndpointUdp {
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect(address: SocketAddr, opt_ep: &OptEP) -> Res<Self> {
+        let _t = task_trace!();
+        let local: SocketAddr = if address.is_ipv4() {
+            SocketAddr::from(([0, 0, 0, 0], 0))
+        } else {
+            SocketAddr::from(([0u16; 8], 0))
+        };
+        let socket = UdpSocket::bind(local).await.map_err(|e| {
+            net_error::io_error(e, "bind", local)
+        })?;
+        socket.connect(address).await.map_err(|e| {
+            net_error::io_error(e, "connect", address)
+        })?;
+        Ok(Self::new(socket, address, false, opt_ep))
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn bind(address: SocketAddr, opt_ep: &OptEP) -> Res<Self> {
+        let _t = task_trace!();
+        let socket = UdpSocket::bind(address).await.map_err(|e| {
+            net_error::io_error(e, "bind", address)
+        })?;
+        // the port chosen by the system for port 0
+        let local = socket.local_addr().unwrap_or(address);
+        Ok(Self::new(socket, local, true, opt_ep))
+    }
+
+    fn new(socket: UdpSocket, remote_address: SocketAddr, inbound: bool, opt_ep: &OptEP) -> Self {
+        Self {
+            socket: Arc::new(socket),
+            remote_address: SyncMutex::new(remote_address),
+            inbound,
+            enable_dtm_test: opt_ep.is_enable_dtm_test(),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> EndpointAsync<M> for EndpointUdp {
+    fn remote_address(&self) -> SocketAddr {
+        let address = self.remote_address.lock().unwrap();
+        *address
+    }
+
+    fn is_inbound(&self) -> bool {
+        self.inbound
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send(&self, m: Message<M>) -> Res<()> {
+        let _t = task_trace!();
+        let _ = self.send_counted(m).await?;
+        Ok(())
+    }
+
+    // the datagram of the message
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn send_counted(&self, m: Message<M>) -> Res<usize> {
+        let _t = task_trace!();
+        if self.enable_dtm_test {
+            return Ok(0);
+        }
+        let vec = encode_message(m)?;
+        if vec.len() > MAX_DATAGRAM_SIZE {
+            return Err(net_error::message_too_large(vec.len(), MAX_DATAGRAM_SIZE));
+        }
+        let address = EndpointAsync::<M>::remote_address(self);
+        let r = if self.inbound {
+            self.socket.send_to(vec.as_slice(), address).await
+        } else {
+            self.socket.send(vec.as_slice()).await
+        };
+        let _ = r.map_err(|e| { net_error::io_error(e, "write", address) })?;
+        Ok(vec.len())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn recv(&self) -> Res<Message<M>> {
+        let _t = task_trace!();
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let r = self.socket.recv_from(buf.as_mut_slice()).await;
+        let (size, address) = r.map_err(|e| {
+            net_error::io_error(e, "read", EndpointAsync::<M>::remote_address(self))
+        })?;
+        if self.inbound {
+            let mut remote_address = self.remote_address.lock().unwrap();
+            *remote_address = address;
+        }
+        let (m, _) = decode_message::<Message<M>>(&buf[..size])?;
+        Ok(m)
+    }
+
+    // there is no connection to close
+    async fn close(&self) -> Res<()> {
+        Ok(())
+    }
+
+    async fn shutdown_write(&self) -> Res<()> {
+        Ok(())
+    }
+}
@@ -1,3 +1,35 @@
+use std::sync::Arc;
+
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+
+use crate::dedup::SendSession;
+use crate::frame_codec::{FrameCodec, raw_codec_of, RawFrameCodec};
+use crate::opt_ep::OptEP;
+use crate::overflow_policy::OverflowPolicy;
+use crate::proxy::ProxyConfig;
+
+// the default max number of the frames the writer task of an endpoint coalesces into one write
+pub const DEFAULT_WRITE_BATCH_MAX: usize = 64;
+
+// the default max bytes of the frames coalesced into one write
+pub const DEFAULT_WRITE_BATCH_BYTES: usize = 64 * 1024;
+
+// the default number of the frames waiting for the writer task of an endpoint
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 1024;
+
+// the default number of the frames the reader task of an endpoint can read ahead of `recv`
+pub const DEFAULT_RECV_QUEUE_CAPACITY: usize = 1024;
+
+// the default number of the frame buffers an endpoint keeps for its sends
+pub const DEFAULT_SEND_BUFFER_POOL: usize = 64;
+
+// the default max number of the messages of the peer an endpoint reassembles at a time
+pub const DEFAULT_MAX_REASSEMBLIES: usize = 16;
+
+// the default max bytes of the fragments an endpoint buffers for the reassembly
+pub const DEFAULT_MAX_REASSEMBLY_BYTES: usize = 64 * 1024 * 1024;
+
 pub struct ESOption {
     no_wait: bool,
 }
@@ -32,6 +64,30 @@ impl ESConnectOption {
         Self {
             no_wait: false,
             return_endpoint: false,
+            dedup: false,
+            opt_dedup_session: None,
+            idle_timeout_ms: 0,
+            write_timeout_ms: 0,
+            write_batch_max: DEFAULT_WRITE_BATCH_MAX,
+            write_batch_bytes: DEFAULT_WRITE_BATCH_BYTES,
+            send_buffer_pool: DEFAULT_SEND_BUFFER_POOL,
+            resend_unsent: false,
+            nodelay: false,
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
+            recv_queue_capacity: DEFAULT_RECV_QUEUE_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            opt_max_message_size: None,
+            opt_frame_codec: None,
+            opt_proxy: None,
+            flow_window: 0,
+            checksum: false,
+            negotiation: false,
+            opt_preamble: None,
+            fragment_size: 0,
+            max_reassemblies: DEFAULT_MAX_REASSEMBLIES,
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+            attempt: 1,
+            opt_last_error: None,
         }
     }
 
@@ -42,6 +98,87 @@ impl ESConnectOption {
     pub fn return_endpoint(&self) -> bool {
         self.return_endpoint
     }
+
+    pub fn dedup(&self) -> bool {
+        self.dedup
+    }
+
+    pub fn idle_timeout_ms(&self) -> u64 {
+        self.idle_timeout_ms
+    }
+
+    pub fn write_timeout_ms(&self) -> u64 {
+        self.write_timeout_ms
+    }
+
+    pub fn write_batch_max(&self) -> usize {
+        self.write_batch_max
+    }
+
+    pub fn write_batch_bytes(&self) -> usize {
+        self.write_batch_bytes
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    pub fn send_buffer_pool(&self) -> usize {
+        self.send_buffer_pool
+    }
+
+    pub fn resend_unsent(&self) -> bool {
+        self.resend_unsent
+    }
+
+    pub fn send_queue_capacity(&self) -> usize {
+        self.send_queue_capacity
+    }
+
+    pub fn recv_queue_capacity(&self) -> usize {
+        self.recv_queue_capacity
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    pub fn max_message_size(&self) -> Option<usize> {
+        self.opt_max_message_size
+    }
+
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.opt_proxy.as_ref()
+    }
+
+    pub fn flow_window(&self) -> u32 {
+        self.flow_window
+    }
+
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    pub fn negotiation(&self) -> bool {
+        self.negotiation
+    }
+
+    pub fn preamble(&self) -> Option<u8> {
+        self.opt_preamble
+    }
+
+    pub fn fragment_size(&self) -> usize {
+        self.fragment_size
+    }
+
+    pub fn max_reassemblies(&self) -> usize {
+        self.max_reassemblies
+    }
+
+    pub fn max_reassembly_bytes(&self) -> usize {
+        self.max_reassembly_bytes
+    }
+
     pub fn enable_no_wait(self, no_wait: bool) -> Self {
         let mut s = self;
         s.no_wait = no_wait;
@@ -53,6 +190,243 @@ impl ESConnectOption {
         s.return_endpoint = return_endpoint;
         s
     }
+
+    // number the frames sent by a session, announced to the peer, which drops the frames of the
+    // session it already received, such as the ones resent by a replacing connection, see
+    // `enable_resend_unsent`, it keeps the state of the session across the connections of it,
+    // the connections of a `Client` share one session, a version 8 peer ignores it
+    pub fn enable_dedup(self, dedup: bool) -> Self {
+        let mut s = self;
+        s.dedup = dedup;
+        s
+    }
+
+    // close the connection if no bytes arrive within the timeout, the timer restarts on every
+    // incoming frame, 0 disables it
+    pub fn set_idle_timeout_ms(self, idle_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.idle_timeout_ms = idle_timeout_ms;
+        s
+    }
+
+    // Close the connection if a write of the writer task does not complete within the timeout,
+    // a peer which stopped reading would wedge the send queue otherwise. The pending sends fail
+    // with `net_error::write_timeout`, so does `recv`, independent of the idle timeout of the
+    // incoming bytes, 0 disables it.
+    pub fn set_write_timeout_ms(self, write_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.write_timeout_ms = write_timeout_ms;
+        s
+    }
+
+    // the writer task coalesces up to `max` queued frames, or `bytes` bytes, into one write,
+    // the batch is cut at the first frame beyond the bytes, 1 writes the frames one by one
+    pub fn set_write_batch(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.write_batch_max = max;
+        s.write_batch_bytes = bytes;
+        s
+    }
+
+    // Reuse up to `buffers` frame buffers across the sends, instead of allocating one for every
+    // message, a buffer is given back by the writer task after it was copied into the write
+    // buffer of the stream, 0 allocates one for every send. The buffers beyond 64KB are not
+    // kept. The message is still encoded into a buffer of its own, by scupt-util, first.
+    pub fn set_send_buffer_pool(self, buffers: usize) -> Self {
+        let mut s = self;
+        s.send_buffer_pool = buffers;
+        s
+    }
+
+    // Keep the frames not written when a write of the connection failed, instead of failing
+    // their sends, `Client::connect` queues them on the next endpoint before any new send, in
+    // their order. The frames of the failed write are kept whole, the peer may have read some
+    // of them already, they can be delivered twice. Their sends complete when written by the
+    // next endpoint, or fail when this one is dropped. The frames are copied before every
+    // write to be kept, the default is false.
+    pub fn enable_resend_unsent(self, resend_unsent: bool) -> Self {
+        let mut s = self;
+        s.resend_unsent = resend_unsent;
+        s
+    }
+
+    // set TCP_NODELAY on the connected socket, the other transports ignore it
+    pub fn enable_nodelay(self, nodelay: bool) -> Self {
+        let mut s = self;
+        s.nodelay = nodelay;
+        s
+    }
+
+    // a send waits for a free slot when `capacity` frames of its priority are waiting for the
+    // writer task, see `Priority`, or as by `set_overflow_policy`, must be greater than 0
+    pub fn set_send_queue_capacity(self, capacity: usize) -> Self {
+        let mut s = self;
+        s.send_queue_capacity = capacity;
+        s
+    }
+
+    // the reader task stops reading the socket when `capacity` frames are not received yet,
+    // must be greater than 0, it is the capacity of each channel of `open_channel` too
+    pub fn set_recv_queue_capacity(self, capacity: usize) -> Self {
+        let mut s = self;
+        s.recv_queue_capacity = capacity;
+        s
+    }
+
+    // what a send does when the send queue of its priority is full, see `OverflowPolicy`, it
+    // applies to every send of the endpoint, `send_all` included, the default is Block
+    pub fn set_overflow_policy(self, policy: OverflowPolicy) -> Self {
+        let mut s = self;
+        s.overflow_policy = policy;
+        s
+    }
+
+    // a larger encoded message fails to send with `net_error::message_too_large`, unless it is
+    // fragmented, see `set_fragment_size`, and a larger incoming frame closes the connection,
+    // None for the limit of the frame
+    pub fn set_max_message_size(self, opt_max_message_size: Option<usize>) -> Self {
+        let mut s = self;
+        s.opt_max_message_size = opt_max_message_size;
+        s
+    }
+
+    // write and read the messages in the format of the codec, instead of the length delimited
+    // frames, the codec must match the message type of the node, the default is None
+    pub fn set_frame_codec<M: MsgTrait + 'static>(self, codec: Arc<dyn FrameCodec<M>>) -> Self {
+        let mut s = self;
+        s.opt_frame_codec = Some(raw_codec_of(codec));
+        s
+    }
+
+    // tunnel the TCP connection through the SOCKS5 proxy, see `ProxyConfig`, the default is None,
+    // a connect through a proxy fails with `net_error::invalid_option` without the socks5 feature
+    pub fn set_proxy(self, opt_proxy: Option<ProxyConfig>) -> Self {
+        let mut s = self;
+        s.opt_proxy = opt_proxy;
+        s
+    }
+
+    // Control the flow of the default channel by credits, see `frame::ControlFrame::Credit`:
+    // each side grants the peer `window` frames, and grants them back as `recv` takes them, a
+    // send waits for a credit, so the frames queued for the writer and the ones not received
+    // by the peer yet are never more than the window. The peer must enable it too, see
+    // `OptNode::set_flow_window` for the accepted endpoints, the sends to a peer granting no
+    // credit wait forever. The frames of `resend_unsent` were counted by the previous
+    // connection, they take no credit. It does not apply to a user frame codec, 0, the
+    // default, disables it.
+    pub fn set_flow_window(self, window: u32) -> Self {
+        let mut s = self;
+        s.flow_window = window;
+        s
+    }
+
+    // Every frame carries the CRC32C of its payload, see `frame::CHECKSUM_SIZE`, the reader
+    // verifies it before decoding, a mismatch closes the connection with
+    // `net_error::checksum_mismatch`. The peer must enable it too, see `OptNode::enable_checksum`
+    // for the accepted endpoints, the frames are not told apart on the wire otherwise. It does
+    // not apply to a user frame codec, the default is disabled.
+    pub fn enable_checksum(self, checksum: bool) -> Self {
+        let mut s = self;
+        s.checksum = checksum;
+        s
+    }
+
+    // Send the capabilities and the max message size of this side to the peer after the
+    // connection was established, and agree on the ones of both sides, see
+    // `EndpointAsync::negotiated`. The peer must enable it too, see
+    // `OptNode::enable_negotiation` for the accepted endpoints. The sends are limited by the
+    // smaller max message size of the two sides once agreed, see
+    // `EndpointAsync::negotiated_limits`, the other params are only reported. It does not apply
+    // to a user frame codec, the default is disabled.
+    pub fn enable_negotiation(self, negotiation: bool) -> Self {
+        let mut s = self;
+        s.negotiation = negotiation;
+        s
+    }
+
+    // Start the connection with the preamble of the protocol version, see `frame::preamble`,
+    // and read the one of the peer before its first frame, a peer which is not a scupt-net one
+    // fails with `net_error::bad_protocol`, one of another version with
+    // `net_error::version_mismatch`, and the connection is closed. The peer must set it too,
+    // see `OptNode::set_preamble` for the accepted endpoints. It does not apply to a user frame
+    // codec, None, the default, sends and expects no preamble.
+    pub fn set_preamble(self, opt_version: Option<u8>) -> Self {
+        let mut s = self;
+        s.opt_preamble = opt_version;
+        s
+    }
+
+    // Send an encoded message larger than `size` bytes as the fragments of up to `size` bytes
+    // each, see `frame::FragmentHeader`, the peer reassembles them and returns the message by
+    // one `recv`, whatever its own fragment size. The fragments are frames of their own, within
+    // the max message size, which caps the fragment size, so the messages larger than it are
+    // sent, and the frames of the other sends are written between them. The message takes one
+    // credit of the flow window. Only the sends of the default channel are fragmented, not the
+    // ones of `send_all`, nor are the fragments kept by `enable_resend_unsent`. It does not
+    // apply to a user frame codec, 0, the default, disables it.
+    pub fn set_fragment_size(self, size: usize) -> Self {
+        let mut s = self;
+        s.fragment_size = size;
+        s
+    }
+
+    // The fragments of the peer of up to `max` messages are reassembled at a time, buffering up
+    // to `bytes` bytes, a fragment beyond either limit closes the connection with
+    // `net_error::fragment_rejected`, so does a malformed one. The defaults are
+    // DEFAULT_MAX_REASSEMBLIES and DEFAULT_MAX_REASSEMBLY_BYTES.
+    pub fn set_reassembly_limit(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.max_reassemblies = max;
+        s.max_reassembly_bytes = bytes;
+        s
+    }
+
+    // the connect is the attempt of a retry loop, such as the one of `Client::connect`, it is
+    // reported by `HandleEvent::on_connect_attempt`, the default is the first attempt
+    pub(crate) fn set_attempt(self, attempt: u64, opt_last_error: Option<ET>) -> Self {
+        let mut s = self;
+        s.attempt = attempt;
+        s.opt_last_error = opt_last_error;
+        s
+    }
+
+    // the frames of the connects by the option are numbered by the session, the one of a
+    // connect is new if it is not set, see `enable_dedup`
+    pub(crate) fn set_dedup_session(self, opt_session: Option<Arc<SendSession>>) -> Self {
+        let mut s = self;
+        s.opt_dedup_session = opt_session;
+        s
+    }
+
+    fn dedup_session(&self) -> Option<Arc<SendSession>> {
+        if !self.dedup {
+            return None;
+        }
+        Some(self.opt_dedup_session.clone().unwrap_or_default())
+    }
+
+    pub(crate) fn opt_ep(&self) -> OptEP {
+        OptEP::new()
+            .set_dedup_session(self.dedup_session())
+            .set_idle_timeout_ms(self.idle_timeout_ms)
+            .set_write_timeout_ms(self.write_timeout_ms)
+            .set_write_batch(self.write_batch_max, self.write_batch_bytes)
+            .set_send_buffer_pool(self.send_buffer_pool)
+            .enable_resend_unsent(self.resend_unsent)
+            .enable_nodelay(self.nodelay)
+            .set_queue_capacity(self.send_queue_capacity, self.recv_queue_capacity)
+            .set_overflow_policy(self.overflow_policy)
+            .set_max_message_size(self.opt_max_message_size)
+            .set_frame_codec(self.opt_frame_codec.clone())
+            .set_proxy(self.opt_proxy.clone())
+            .set_flow_window(self.flow_window)
+            .enable_checksum(self.checksum)
+            .enable_negotiation(self.negotiation)
+            .set_preamble(self.opt_preamble)
+            .set_fragment_size(self.fragment_size)
+            .set_reassembly_limit(self.max_reassemblies, self.max_reassembly_bytes)
+            .set_attempt(self.attempt, self.opt_last_error.clone())
+    }
 }
 
 impl Default for ESOption {
@@ -61,9 +435,41 @@ impl Default for ESOption {
     }
 }
 
+#[derive(Clone)]
 pub struct ESConnectOption {
     no_wait: bool,
     return_endpoint: bool,
+    dedup: bool,
+    opt_dedup_session: Option<Arc<SendSession>>,
+    idle_timeout_ms: u64,
+    write_timeout_ms: u64,
+    write_batch_max: usize,
+    write_batch_bytes: usize,
+    send_buffer_pool: usize,
+    resend_unsent: bool,
+    nodelay: bool,
+    send_queue_capacity: usize,
+    recv_queue_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    opt_max_message_size: Option<usize>,
+    opt_frame_codec: Option<Arc<dyn RawFrameCodec>>,
+    opt_proxy: Option<ProxyConfig>,
+    // the credits of the default channel, 0 for none
+    flow_window: u32,
+    // a CRC32C in the header of every frame
+    checksum: bool,
+    // send the params control frame
+    negotiation: bool,
+    // the protocol version of the preamble, None for none
+    opt_preamble: Option<u8>,
+    // the messages larger than it are fragmented, 0 for none
+    fragment_size: usize,
+    // the limits of the reassembly of the fragments of the peer
+    max_reassemblies: usize,
+    max_reassembly_bytes: usize,
+    // the attempt of a connect with retries, and the error of the previous one
+    attempt: u64,
+    opt_last_error: Option<ET>,
 }
 
 impl Default for ESConnectOption {
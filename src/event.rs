@@ -4,6 +4,7 @@ use std::sync::Arc;
 use std::sync::mpsc::Receiver as _SyncReceiver;
 use std::sync::mpsc::Sender as _SyncSender;
 
+use scupt_util::error_type::ET;
 use scupt_util::message::{Message, MsgTrait};
 use scupt_util::node_id::NID;
 use scupt_util::res::Res;
@@ -13,6 +14,7 @@ use tokio::sync::oneshot::Sender as _AsyncSender;
 use crate::endpoint_async::EndpointAsync;
 use crate::endpoint_sync::EndpointSync;
 use crate::event_channel::EventChannel;
+use crate::opt_ep::OptEP;
 
 pub type SyncSender<M> = _SyncSender<M>;
 pub type SyncReceiver<M> = _SyncReceiver<M>;
@@ -26,6 +28,35 @@ pub enum ResultSenderType<S, A> {
     Async(AsyncSender<A>),
 }
 
+// Completes a connect request exactly once.
+// If it is dropped before `complete` was called, e.g. the connecting task was cancelled by
+// node shutdown, or the event was never handled, the receiver gets ET::EOF.
+pub struct ConnectCompletion<M: MsgTrait + 'static> {
+    opt_sender: Option<AsyncSender<Res<Arc<dyn EndpointAsync<M>>>>>,
+}
+
+impl<M: MsgTrait + 'static> ConnectCompletion<M> {
+    pub fn new(sender: AsyncSender<Res<Arc<dyn EndpointAsync<M>>>>) -> Self {
+        Self {
+            opt_sender: Some(sender),
+        }
+    }
+
+    pub fn complete(mut self, result: Res<Arc<dyn EndpointAsync<M>>>) {
+        if let Some(s) = self.opt_sender.take() {
+            let _ = s.send(result);
+        }
+    }
+}
+
+impl<M: MsgTrait + 'static> Drop for ConnectCompletion<M> {
+    fn drop(&mut self) {
+        if let Some(s) = self.opt_sender.take() {
+            let _ = s.send(Err(ET::EOF));
+        }
+    }
+}
+
 pub enum NetEvent<
     M: MsgTrait + 'static,
 > {
@@ -33,10 +64,12 @@ pub enum NetEvent<
         node_id: NID,
         return_endpoint: bool,
         address: SocketAddr,
+        opt_ep: OptEP,
         opt_sender: ResultSenderType<
             Res<Option<Arc<dyn EndpointSync<M>>>>,
             Res<Option<Arc<dyn EndpointAsync<M>>>>
         >,
+        opt_completion: Option<ConnectCompletion<M>>,
     },
     NetListen(SocketAddr, ResultSenderType<
         Res<Option<Arc<dyn EndpointSync<M>>>>,
@@ -58,10 +91,12 @@ impl<M: MsgTrait + 'static> Debug for NetEvent<M> {
                 node_id,
                 return_endpoint,
                 address,
-                opt_sender: _
+                opt_ep: _,
+                opt_sender: _,
+                opt_completion,
             } => {
-                write!(f, "NetConnect({:?}, {:?} return endpoint: {:?})",
-                       node_id, address, return_endpoint)?;
+                write!(f, "NetConnect({:?}, {:?} return endpoint: {:?}, completion: {:?})",
+                       node_id, address, return_endpoint, opt_completion.is_some())?;
             }
             NetEvent::NetListen(address, _) => {
                 write!(f, "NetListen({:?})", address)?;
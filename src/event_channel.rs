@@ -41,7 +41,7 @@ impl<M: MsgTrait + 'static> EventReceiver<M> {
         self._name.clone()
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn recv(&mut self) -> Res<NetEvent<M>> {
         let _t = task_trace!();
         let opt = self.inner.recv().await;
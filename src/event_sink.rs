@@ -0,0 +1,72 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::es_option::{ESConnectOpt, ESServeOpt, ESStopOpt};
+use crate::event_sink_async::{ConnectReceiver, EventSinkAsync};
+use crate::net_handler::NodeSender;
+use crate::opt_send::OptSend;
+use crate::task_trace;
+
+// A handle of the default event channel of a node, see `Node::event_sink`. It is cheap to
+// clone and can be moved into any task of the node's local set, it does not keep the node
+// alive, the events sent after the node stopped fail with ET::TokioSenderError.
+pub struct EventSink<M: MsgTrait + 'static> {
+    sender: Arc<NodeSender<M>>,
+}
+
+impl<M: MsgTrait + 'static> EventSink<M> {
+    pub(crate) fn new(sender: NodeSender<M>) -> Self {
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    // see `EventSinkAsync::connect`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
+        let _t = task_trace!();
+        EventSinkAsync::connect(self.sender.as_ref(), node_id, address, opt).await
+    }
+
+    // see `EventSinkAsync::connect_completion`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_completion(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<ConnectReceiver<M>> {
+        let _t = task_trace!();
+        EventSinkAsync::connect_completion(self.sender.as_ref(), node_id, address, opt).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn serve(&self, address: SocketAddr, opt: ESServeOpt) -> Res<()> {
+        let _t = task_trace!();
+        EventSinkAsync::serve(self.sender.as_ref(), address, opt).await
+    }
+
+    // send the message to the endpoint of its destination, unlike `SenderAsync::send`, the
+    // error of the send is returned, such as ET::NoSuchElement for a destination not
+    // connected, unless OptSend::enable_no_wait
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn send(&self, message: Message<M>, opt: OptSend) -> Res<()> {
+        let _t = task_trace!();
+        self.sender.send_async(message, opt.is_enable_no_wait(), false).await?;
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn stop(&self, opt: ESStopOpt) -> Res<()> {
+        let _t = task_trace!();
+        EventSinkAsync::stop(self.sender.as_ref(), opt).await
+    }
+}
+
+impl<M: MsgTrait + 'static> Clone for EventSink<M> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
@@ -5,10 +5,14 @@ use async_trait::async_trait;
 use scupt_util::message::MsgTrait;
 use scupt_util::node_id::NID;
 use scupt_util::res::Res;
+use tokio::sync::oneshot;
 
 use crate::endpoint_async::EndpointAsync;
 use crate::es_option::{ESConnectOpt, ESServeOpt, ESStopOpt};
 
+// receive the outcome of a connect issued by `EventSinkAsync::connect_completion`
+pub type ConnectReceiver<M> = oneshot::Receiver<Res<Arc<dyn EndpointAsync<M>>>>;
+
 #[async_trait]
 pub trait EventSinkAsync<M: MsgTrait + 'static>: Sync + Send {
     async fn stop(&self, opt: ESStopOpt) -> Res<()>;
@@ -16,5 +20,9 @@ pub trait EventSinkAsync<M: MsgTrait + 'static>: Sync + Send {
     async fn serve(&self, addr: SocketAddr, opt: ESServeOpt) -> Res<()>;
 
     async fn connect(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<Option<Arc<dyn EndpointAsync<M>>>>;
-}
 
+    // fire a connect without waiting for it, the returned receiver is completed exactly once,
+    // with the endpoint, with the connect error, or with ET::EOF if the node stopped before
+    // the connect finished
+    async fn connect_completion(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<ConnectReceiver<M>>;
+}
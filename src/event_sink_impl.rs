@@ -13,13 +13,14 @@ use tracing::{error, trace};
 use crate::endpoint_async::EndpointAsync;
 use crate::endpoint_sync::EndpointSync;
 use crate::es_option::{ESConnectOpt, ESServeOpt, ESStopOpt};
-use crate::event::{AsyncReceiver, NetEvent, ResultSenderType, SyncReceiver};
-use crate::event_sink_async::EventSinkAsync;
+use crate::event::{AsyncReceiver, ConnectCompletion, NetEvent, ResultSenderType, SyncReceiver};
+use crate::event_sink_async::{ConnectReceiver, EventSinkAsync};
 use crate::event_sink_sync::EventSinkSync;
 use crate::message_receiver_async::ReceiverResp;
 use crate::message_receiver_endpoint::MessageReceiverEndpoint;
 use crate::message_sender_async::{SenderAsync, SenderRRAsync};
 use crate::message_sender_sync::SenderSync;
+use crate::opt_ep::OptEP;
 use crate::opt_send::OptSend;
 use crate::task_trace;
 
@@ -41,7 +42,7 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         &self.name
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn recv_result(
         &self,
         receiver: AsyncReceiver<Res<()>>,
@@ -63,7 +64,7 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         ret
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn recv_result_async_ep(
         &self,
         receiver: AsyncReceiver<Res<Option<Arc<dyn EndpointAsync<M>>>>>,
@@ -85,7 +86,7 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         ret
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn serve_async(&self, addr: SocketAddr, no_wait: bool) -> Res<()> {
         let _ = task_trace!();
         trace!("async serve {} {}", self.channel_name(), addr.to_string());
@@ -115,12 +116,13 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn connect_async(
         &self,
         node_id: NID, address: SocketAddr,
         no_wait: bool,
         read_endpoint: bool,
+        opt_ep: OptEP,
     ) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
         let _ = task_trace!();
         trace!("channel name {}, send connect to {}", self.name, node_id);
@@ -129,7 +131,9 @@ impl<M: MsgTrait> EventSenderImpl<M> {
                 node_id,
                 return_endpoint: false,
                 address,
+                opt_ep,
                 opt_sender: ResultSenderType::SendNone,
+                opt_completion: None,
             };
             self.async_event(event)?;
             Ok(None)
@@ -139,7 +143,9 @@ impl<M: MsgTrait> EventSenderImpl<M> {
                 node_id,
                 return_endpoint: read_endpoint,
                 address,
+                opt_ep,
                 opt_sender: ResultSenderType::Async(s),
+                opt_completion: None,
             };
             self.async_event(event)?;
             let _r = self.recv_result_async_ep(r).await?;
@@ -152,6 +158,7 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         node_id: NID, address: SocketAddr,
         no_wait: bool,
         read_endpoint: bool,
+        opt_ep: OptEP,
     ) -> Res<Option<Arc<dyn EndpointSync<M>>>> {
         trace!("channel name {}, send connect to {}", self.name, node_id);
         if no_wait && !read_endpoint {
@@ -159,7 +166,9 @@ impl<M: MsgTrait> EventSenderImpl<M> {
                 node_id,
                 return_endpoint: false,
                 address,
+                opt_ep,
                 opt_sender: ResultSenderType::SendNone,
+                opt_completion: None,
             };
             self.async_event(event)?;
             Ok(None)
@@ -169,7 +178,9 @@ impl<M: MsgTrait> EventSenderImpl<M> {
                 node_id,
                 return_endpoint: read_endpoint,
                 address,
+                opt_ep,
                 opt_sender: ResultSenderType::Sync(s),
+                opt_completion: None,
             };
             self.async_event(event)?;
             let _r = self.recv_result_sync_ep(r)?;
@@ -177,7 +188,27 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         }
     }
 
-    #[async_backtrace::framed]
+    fn connect_completion_async(
+        &self,
+        node_id: NID, address: SocketAddr,
+        return_endpoint: bool,
+        opt_ep: OptEP,
+    ) -> Res<ConnectReceiver<M>> {
+        trace!("channel name {}, send connect with completion to {}", self.name, node_id);
+        let (s, r) = oneshot::channel();
+        let event = NetEvent::NetConnect {
+            node_id,
+            return_endpoint,
+            address,
+            opt_ep,
+            opt_sender: ResultSenderType::SendNone,
+            opt_completion: Some(ConnectCompletion::new(s)),
+        };
+        self.async_event(event)?;
+        Ok(r)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn send_async(
         &self,
         msg: Message<M>,
@@ -224,7 +255,7 @@ impl<M: MsgTrait> EventSenderImpl<M> {
         }
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn stop_async(&self, no_wait: bool) -> Res<()> {
         if no_wait {
             let event = NetEvent::Stop(ResultSenderType::SendNone);
@@ -279,21 +310,27 @@ impl<
 > EventSinkAsync<M> for EventSenderImpl<
     M
 > {
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn stop(&self, opt: ESStopOpt) -> Res<()> {
         let _ = task_trace!();
         self.stop_async(opt.no_wait()).await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn serve(&self, addr: SocketAddr, opt: ESServeOpt) -> Res<()> {
         self.serve_async(addr, opt.no_wait()).await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn connect(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<Option<Arc<dyn EndpointAsync<M>>>> {
         let _t = task_trace!();
-        self.connect_async(node_id, address, opt.no_wait(), opt.return_endpoint()).await
+        self.connect_async(node_id, address, opt.no_wait(), opt.return_endpoint(), opt.opt_ep()).await
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_completion(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<ConnectReceiver<M>> {
+        let _t = task_trace!();
+        self.connect_completion_async(node_id, address, opt.return_endpoint(), opt.opt_ep())
     }
 }
 
@@ -312,7 +349,7 @@ impl<
     }
 
     fn connect(&self, node_id: NID, address: SocketAddr, opt: ESConnectOpt) -> Res<Option<Arc<dyn EndpointSync<M>>>> {
-        self.connect_sync(node_id, address, opt.no_wait(), opt.return_endpoint())
+        self.connect_sync(node_id, address, opt.no_wait(), opt.return_endpoint(), opt.opt_ep())
     }
 }
 
@@ -321,7 +358,7 @@ impl<
     M: MsgTrait + 'static,
 > SenderAsync<M> for EventSenderImpl<
     M> {
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn send(&self, message: Message<M>, opt: OptSend) -> Res<()> {
         let _ = task_trace!();
         let _ = self.send_async(message, opt.is_enable_no_wait(), false).await;
@@ -344,7 +381,7 @@ impl<
 impl<
     M: MsgTrait + 'static,
 > SenderRRAsync<M> for EventSenderImpl<M> {
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn send(&self, message: Message<M>, _opt: OptSend) -> Res<Arc<dyn ReceiverResp<M>>> {
         let _t = task_trace!();
         let opt = self.send_async(message, _opt.is_enable_no_wait(), true).await?;
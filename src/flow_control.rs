@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use scupt_util::res::Res;
+use tokio::sync::Semaphore;
+use tracing::{Instrument, trace_span};
+
+use crate::net_error;
+use crate::task_trace;
+
+// The credits of the default channel of an endpoint, see `ESConnectOption::set_flow_window`.
+// The receiving side grants the peer the window when the endpoint is created, and the frames
+// taken by `recv` back in batches of half the window. A send of the default channel takes a
+// credit before it is queued, so the frames queued for the writer, on the wire, and queued
+// for `recv` on the peer are never more than the window. The frames of the other channels
+// have credits of their own, the control frames take none.
+pub(crate) struct FlowControl {
+    // the frames the peer allows to send
+    credits: Semaphore,
+    // the frames received since the last credit granted
+    consumed: AtomicU32,
+    // the consumed frames are granted back by this number
+    grant_batch: u32,
+}
+
+impl FlowControl {
+    pub fn new(window: u32) -> Self {
+        Self {
+            credits: Semaphore::new(0),
+            consumed: AtomicU32::new(0),
+            grant_batch: (window / 2).max(1),
+        }
+    }
+
+    // wait for a credit of the peer, the credit is used up by the frame
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn acquire(&self) -> Res<()> {
+        let _t = task_trace!();
+        let permit = self.credits.acquire()
+            .instrument(trace_span!("credit"))
+            .await
+            // the reader task stopped
+            .map_err(|_| { net_error::send_closed() })?;
+        permit.forget();
+        Ok(())
+    }
+
+    // wait for a credit, and take the ones available too, up to `max`, a chunk of `send_all`
+    // is not held back waiting for all of its credits
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn acquire_up_to(&self, max: u32) -> Res<u32> {
+        let _t = task_trace!();
+        self.acquire().await?;
+        let mut n = 1;
+        while n < max {
+            match self.credits.try_acquire() {
+                Ok(permit) => {
+                    permit.forget();
+                    n += 1;
+                }
+                Err(_) => { break; }
+            }
+        }
+        Ok(n)
+    }
+
+    // the credits taken for the frames not queued
+    pub fn give_back(&self, n: u32) {
+        self.grant(n);
+    }
+
+    // the peer allows n frames more
+    pub fn grant(&self, n: u32) {
+        if self.credits.is_closed() {
+            return;
+        }
+        let room = Semaphore::MAX_PERMITS - self.credits.available_permits();
+        self.credits.add_permits((n as usize).min(room));
+    }
+
+    // a frame was taken by a recv, return the number of the frames to grant back, if a batch
+    // of them was consumed
+    pub fn consume(&self) -> Option<u32> {
+        let consumed = self.consumed.fetch_add(1, Ordering::SeqCst) + 1;
+        if consumed < self.grant_batch {
+            return None;
+        }
+        let n = self.consumed.swap(0, Ordering::SeqCst);
+        if n > 0 {
+            Some(n)
+        } else {
+            None
+        }
+    }
+
+    // the reader task stopped, the sends waiting for credits fail
+    pub fn close(&self) {
+        self.credits.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flow_control::FlowControl;
+
+    #[test]
+    fn test_flow_control_consume() {
+        let flow = FlowControl::new(4);
+        assert_eq!(flow.consume(), None);
+        assert_eq!(flow.consume(), Some(2));
+        assert_eq!(flow.consume(), None);
+        assert_eq!(flow.consume(), Some(2));
+
+        let flow = FlowControl::new(1);
+        assert_eq!(flow.consume(), Some(1));
+    }
+}
@@ -0,0 +1,491 @@
+use std::mem::size_of;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::{BufMut, BytesMut};
+
+// The wire format of a TCP or memory connection, a preamble, only on a connection with one,
+// and a sequence of frames.
+//
+// preamble, version 9, see `ESConnectOption::set_preamble`, written by each side first
+// 4 bytes PREAMBLE_MAGIC
+// 1 byte protocol version, the one configured on the sending side
+//
+// frame, version 9
+// 4 bytes payload length (assume it is N), unsigned, big endian
+// 2 bytes channel id, unsigned, big endian, 0 for the default channel of send and recv, see
+//   `EndpointAsync::open_channel`
+// 6 bytes sequence number, unsigned, big endian, monotonic per connection across the
+//   channels, start from 1, or per session across the connections of it, see
+//   `ESConnectOption::enable_dedup`, wraps around to 1 after MAX_SEQ, CONTROL_SEQ for a
+//   control frame, which is on channel 0
+// 4 bytes, only on a connection with checksums, see `ESConnectOption::enable_checksum`, the
+//   CRC32C of the N bytes of the payload, unsigned, big endian
+// N bytes payload, a bincode encoded message, or the control payload of a control frame, or
+//   the fragment of a message on FRAGMENT_CHANNEL
+//
+// fragment payload, version 9, see `ESConnectOption::set_fragment_size`
+// 4 bytes message id, unsigned, big endian, numbered per connection by the sender
+// 4 bytes fragment index, unsigned, big endian, start from 0
+// 1 byte flags, FRAGMENT_LAST on the last fragment, FRAGMENT_ABORT on an empty fragment which
+//   drops the fragments of the message received so far
+// the bytes of the encoded message following the ones of the previous fragment
+//
+// control payload
+// 1 byte kind, 1 for a ping, 2 for a pong, 3 for a credit, 4 for a name, 5 for the params,
+//   6 for a session
+// 8 bytes, unsigned, big endian, the nonce of a ping, a pong echoes it, or the channel id
+//   in the high 32 bits and the number of the frames granted in the low 32 bits of a credit,
+//   a credit of channel 0 is of the flow window, see `ESConnectOption::set_flow_window`, or
+//   the capability flags in the high 32 bits and the max message size in the low 32 bits of
+//   the params, see `negotiated::NegotiatedParams`, a version 5 peer drops it as a control
+//   frame of an unknown kind
+// or, for a name, up to MAX_NAME_SIZE bytes of the UTF-8 name of the sending node, the payload
+//   is of the size of the name, a version 3 peer drops it as a control frame of an unknown size
+// or, for a session, 8 bytes of the session id and 8 bytes of the low sequence number, the
+//   ones before it were written or given up by the sender, unsigned, big endian, sent once
+//   after the connection was established, the frames of the connection are numbered by the
+//   session, a version 8 peer drops it as a control frame of an unknown size
+//
+// A peer which is not a scupt-net one, or of another protocol version, is told apart by the
+// preamble, a connection without it has only one format, the header carries no version or
+// codec byte, neither is a peer with checksums told from one without, both sides are
+// configured the same. The frames without checksums are the ones of version 4, the params
+// are the only frame added by version 6, the preamble by version 7, the fragments by version
+// 8, a version 7 peer takes them for the frames of an unknown channel, the session by
+// version 9. The frames of channel 0
+// are the ones of version 2, which took the channel id for the high bytes of the sequence
+// number. A version 1 peer fails to decode the control frames. An UDP datagram is a payload
+// without a header or a preamble.
+
+// the version of the frame layout above, bumped on any change of it
+pub const WIRE_VERSION: u32 = 9;
+
+// the byte order of all the header fields
+pub type WireEndian = NetworkEndian;
+
+pub const LENGTH_PREFIX_SIZE: usize = size_of::<u32>();
+
+pub const CHANNEL_SIZE: usize = size_of::<u16>();
+
+pub const SEQ_SIZE: usize = 6;
+
+pub const HEADER_SIZE: usize = LENGTH_PREFIX_SIZE + CHANNEL_SIZE + SEQ_SIZE;
+
+// the checksum following the header on a connection with checksums
+pub const CHECKSUM_SIZE: usize = size_of::<u32>();
+
+// the start of the preamble, "SCPN"
+pub const PREAMBLE_MAGIC: [u8; 4] = [0x53, 0x43, 0x50, 0x4e];
+
+pub const PREAMBLE_SIZE: usize = PREAMBLE_MAGIC.len() + size_of::<u8>();
+
+// the preamble of the protocol version
+pub fn preamble(version: u8) -> [u8; PREAMBLE_SIZE] {
+    let mut b = [0u8; PREAMBLE_SIZE];
+    b[..PREAMBLE_MAGIC.len()].copy_from_slice(&PREAMBLE_MAGIC);
+    b[PREAMBLE_MAGIC.len()] = version;
+    b
+}
+
+// the largest sequence number, the numbering wraps around to 1 after it
+pub const MAX_SEQ: u64 = (1 << (SEQ_SIZE * 8)) - 1;
+
+// the channel of `send` and `recv`
+pub const DEFAULT_CHANNEL: u16 = 0;
+
+// the channel of the fragments of the messages, it cannot be opened by `open_channel`
+pub const FRAGMENT_CHANNEL: u16 = u16::MAX;
+
+// the largest payload the length prefix can describe
+pub const MAX_PAYLOAD_SIZE: usize = u32::MAX as usize;
+
+// the sequence number of the control frames, they are not numbered as the message frames
+pub const CONTROL_SEQ: u64 = 0;
+
+pub const CONTROL_PAYLOAD_SIZE: usize = size_of::<u8>() + size_of::<u64>();
+
+const CONTROL_PING: u8 = 1;
+
+const CONTROL_PONG: u8 = 2;
+
+const CONTROL_CREDIT: u8 = 3;
+
+const CONTROL_NAME: u8 = 4;
+
+const CONTROL_PARAMS: u8 = 5;
+
+const CONTROL_SESSION: u8 = 6;
+
+pub const SESSION_PAYLOAD_SIZE: usize = size_of::<u8>() + size_of::<u64>() + size_of::<u64>();
+
+// the max bytes of the name advertised by a name control frame
+pub const MAX_NAME_SIZE: usize = 255;
+
+const LENGTH_OFFSET: usize = 0;
+
+const CHANNEL_OFFSET: usize = LENGTH_OFFSET + LENGTH_PREFIX_SIZE;
+
+const SEQ_OFFSET: usize = CHANNEL_OFFSET + CHANNEL_SIZE;
+
+// the header preceding the payload of a frame
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct FrameHeader {
+    size: u32,
+    channel: u16,
+    seq: u64,
+}
+
+impl FrameHeader {
+    // a frame of the default channel, the sequence number is up to MAX_SEQ
+    pub fn new(size: u32, seq: u64) -> Self {
+        Self {
+            size,
+            channel: DEFAULT_CHANNEL,
+            seq: seq & MAX_SEQ,
+        }
+    }
+
+    pub fn set_channel(self, channel: u16) -> Self {
+        let mut s = self;
+        s.channel = channel;
+        s
+    }
+
+    // the payload length
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    // append the HEADER_SIZE bytes of the header
+    pub fn encode(&self, buf: &mut BytesMut) {
+        let mut b = [0u8; HEADER_SIZE];
+        self.encode_to(&mut b);
+        buf.put_slice(&b);
+    }
+
+    pub fn encode_to(&self, buf: &mut [u8; HEADER_SIZE]) {
+        WireEndian::write_u32(&mut buf[LENGTH_OFFSET..], self.size);
+        WireEndian::write_u16(&mut buf[CHANNEL_OFFSET..], self.channel);
+        WireEndian::write_u48(&mut buf[SEQ_OFFSET..], self.seq);
+    }
+
+    // decode the header at the start of the buffer, None if it is shorter than HEADER_SIZE
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            size: WireEndian::read_u32(&buf[LENGTH_OFFSET..]),
+            channel: WireEndian::read_u16(&buf[CHANNEL_OFFSET..]),
+            seq: WireEndian::read_u48(&buf[SEQ_OFFSET..]),
+        })
+    }
+}
+
+pub const FRAGMENT_HEADER_SIZE: usize = size_of::<u32>() + size_of::<u32>() + size_of::<u8>();
+
+// the flag of the last fragment of a message
+pub const FRAGMENT_LAST: u8 = 1;
+
+// the flag of the fragment aborting a message, its sender failed to send the rest
+pub const FRAGMENT_ABORT: u8 = 1 << 1;
+
+// the header at the start of the payload of a frame of FRAGMENT_CHANNEL
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct FragmentHeader {
+    message_id: u32,
+    index: u32,
+    flags: u8,
+}
+
+impl FragmentHeader {
+    pub fn new(message_id: u32, index: u32, flags: u8) -> Self {
+        Self {
+            message_id,
+            index,
+            flags,
+        }
+    }
+
+    pub fn message_id(&self) -> u32 {
+        self.message_id
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.flags & FRAGMENT_LAST != 0
+    }
+
+    pub fn is_abort(&self) -> bool {
+        self.flags & FRAGMENT_ABORT != 0
+    }
+
+    // append the FRAGMENT_HEADER_SIZE bytes of the header
+    pub fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u32(self.message_id);
+        buf.put_u32(self.index);
+        buf.put_u8(self.flags);
+    }
+
+    // decode the header at the start of the payload, None if it is shorter than
+    // FRAGMENT_HEADER_SIZE
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < FRAGMENT_HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            message_id: WireEndian::read_u32(buf),
+            index: WireEndian::read_u32(&buf[size_of::<u32>()..]),
+            flags: buf[FRAGMENT_HEADER_SIZE - 1],
+        })
+    }
+}
+
+// the reversed Castagnoli polynomial of CRC32C
+const CRC32C_POLY: u32 = 0x82f63b78;
+
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { (c >> 1) ^ CRC32C_POLY } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+// the CRC32C of the bytes, the checksum of a frame
+pub fn crc32c(buf: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in buf {
+        crc = CRC32C_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// the payload of a control frame, read and answered by the endpoint, never returned by `recv`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ControlFrame {
+    Ping(u64),
+    Pong(u64),
+    // the channel, and the number of the frames more the sender may send on it
+    Credit(u16, u32),
+    // the name of the sending node, sent once after the connection was established
+    Name(String),
+    // the capability flags and the max message size of the sending side, sent once after the
+    // connection was established, see `ESConnectOption::enable_negotiation`
+    Params(u32, u32),
+    // the id of the session numbering the frames, and the low sequence number of it, sent once
+    // after the connection was established, see `ESConnectOption::enable_dedup`
+    Session(u64, u64),
+}
+
+impl ControlFrame {
+    // the size of the payload, CONTROL_PAYLOAD_SIZE but for a name or a session
+    pub fn size(&self) -> usize {
+        match self {
+            ControlFrame::Name(name) => { size_of::<u8>() + name.len() }
+            ControlFrame::Session(_, _) => { SESSION_PAYLOAD_SIZE }
+            _ => { CONTROL_PAYLOAD_SIZE }
+        }
+    }
+
+    // append the `size` bytes of the payload
+    pub fn encode(&self, buf: &mut BytesMut) {
+        let (kind, value) = match self {
+            ControlFrame::Ping(n) => { (CONTROL_PING, *n) }
+            ControlFrame::Pong(n) => { (CONTROL_PONG, *n) }
+            ControlFrame::Credit(channel, n) => { (CONTROL_CREDIT, ((*channel as u64) << 32) | (*n as u64)) }
+            ControlFrame::Params(capabilities, size) => { (CONTROL_PARAMS, ((*capabilities as u64) << 32) | (*size as u64)) }
+            ControlFrame::Name(name) => {
+                buf.put_u8(CONTROL_NAME);
+                buf.put_slice(name.as_bytes());
+                return;
+            }
+            ControlFrame::Session(id, low) => {
+                let mut b = [0u8; SESSION_PAYLOAD_SIZE];
+                b[0] = CONTROL_SESSION;
+                WireEndian::write_u64(&mut b[1..], *id);
+                WireEndian::write_u64(&mut b[1 + size_of::<u64>()..], *low);
+                buf.put_slice(&b);
+                return;
+            }
+        };
+        let mut b = [0u8; CONTROL_PAYLOAD_SIZE];
+        b[0] = kind;
+        WireEndian::write_u64(&mut b[1..], value);
+        buf.put_slice(&b);
+    }
+
+    // None for a payload of another size, an unknown kind, or a name too long or not UTF-8
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.first() == Some(&CONTROL_NAME) {
+            if buf.len() > size_of::<u8>() + MAX_NAME_SIZE {
+                return None;
+            }
+            return String::from_utf8(buf[1..].to_vec()).ok().map(ControlFrame::Name);
+        }
+        if buf.first() == Some(&CONTROL_SESSION) {
+            if buf.len() != SESSION_PAYLOAD_SIZE {
+                return None;
+            }
+            let low = WireEndian::read_u64(&buf[1 + size_of::<u64>()..]);
+            if low == CONTROL_SEQ || low > MAX_SEQ {
+                return None;
+            }
+            return Some(ControlFrame::Session(WireEndian::read_u64(&buf[1..]), low));
+        }
+        if buf.len() != CONTROL_PAYLOAD_SIZE {
+            return None;
+        }
+        let value = WireEndian::read_u64(&buf[1..]);
+        match buf[0] {
+            CONTROL_PING => { Some(ControlFrame::Ping(value)) }
+            CONTROL_PONG => { Some(ControlFrame::Pong(value)) }
+            CONTROL_CREDIT if value >> 48 == 0 => {
+                Some(ControlFrame::Credit((value >> 32) as u16, value as u32))
+            }
+            CONTROL_PARAMS => { Some(ControlFrame::Params((value >> 32) as u32, value as u32)) }
+            _ => { None }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+
+    use crate::frame::{CONTROL_PAYLOAD_SIZE, ControlFrame, crc32c, DEFAULT_CHANNEL, FRAGMENT_ABORT, FRAGMENT_HEADER_SIZE, FRAGMENT_LAST, FragmentHeader, FrameHeader, HEADER_SIZE, MAX_NAME_SIZE, MAX_SEQ, preamble, SESSION_PAYLOAD_SIZE};
+
+    #[test]
+    fn test_frame_header_round_trip() {
+        let headers = [
+            FrameHeader::new(0, 1),
+            FrameHeader::new(17, 2),
+            FrameHeader::new(u32::MAX, MAX_SEQ).set_channel(u16::MAX),
+            FrameHeader::new(5, 3).set_channel(7),
+        ];
+        let mut buf = BytesMut::new();
+        for h in headers.iter() {
+            h.encode(&mut buf);
+        }
+        assert_eq!(buf.len(), HEADER_SIZE * headers.len());
+        for (i, h) in headers.iter().enumerate() {
+            let decoded = FrameHeader::decode(&buf[i * HEADER_SIZE..]).unwrap();
+            assert_eq!(&decoded, h);
+        }
+        assert_eq!(FrameHeader::decode(&buf[..HEADER_SIZE - 1]), None);
+    }
+
+    #[test]
+    fn test_frame_header_layout() {
+        let mut b = [0u8; HEADER_SIZE];
+        FrameHeader::new(0x01020304, 0x0708090a0b0c).set_channel(0x0506).encode_to(&mut b);
+        assert_eq!(b, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+
+    // a frame of the default channel is the one of version 2
+    #[test]
+    fn test_frame_header_default_channel() {
+        let mut b = [0u8; HEADER_SIZE];
+        FrameHeader::new(0x01020304, 0x0708090a0b0c).encode_to(&mut b);
+        assert_eq!(b, [1, 2, 3, 4, 0, 0, 7, 8, 9, 10, 11, 12]);
+        let decoded = FrameHeader::decode(&b).unwrap();
+        assert_eq!(decoded.channel(), DEFAULT_CHANNEL);
+        assert_eq!(decoded.seq(), 0x0708090a0b0c);
+    }
+
+    #[test]
+    fn test_control_frame_round_trip() {
+        for c in [ControlFrame::Ping(1), ControlFrame::Pong(u64::MAX), ControlFrame::Credit(u16::MAX, u32::MAX),
+            ControlFrame::Params(u32::MAX, 1)] {
+            let mut buf = BytesMut::new();
+            c.encode(&mut buf);
+            assert_eq!(buf.len(), CONTROL_PAYLOAD_SIZE);
+            assert_eq!(ControlFrame::decode(&buf[..]), Some(c));
+        }
+        // the bytes after the kind of a name are the name
+        assert_eq!(ControlFrame::decode(&[4, 0, 0, 0, 0, 0, 0, 0, 1]),
+            Some(ControlFrame::Name("\0\0\0\0\0\0\0\u{1}".to_string())));
+        assert_eq!(ControlFrame::decode(&[7, 0, 0, 0, 0, 0, 0, 0, 1]), None);
+        assert_eq!(ControlFrame::decode(&[6, 0, 0, 0, 0, 0, 0, 0, 1]), None);
+        assert_eq!(ControlFrame::decode(&[3, 1, 0, 0, 0, 0, 0, 0, 1]), None);
+        assert_eq!(ControlFrame::decode(&[1, 0]), None);
+    }
+
+    #[test]
+    fn test_control_frame_name() {
+        for name in ["", "node_1", "节点", &"n".repeat(MAX_NAME_SIZE)] {
+            let c = ControlFrame::Name(name.to_string());
+            let mut buf = BytesMut::new();
+            c.encode(&mut buf);
+            assert_eq!(buf.len(), c.size());
+            assert_eq!(ControlFrame::decode(&buf[..]), Some(c));
+        }
+        let mut long = vec![4u8];
+        long.extend("n".repeat(MAX_NAME_SIZE + 1).as_bytes());
+        assert_eq!(ControlFrame::decode(&long), None);
+        assert_eq!(ControlFrame::decode(&[4, 0xff, 0xfe]), None);
+    }
+
+    #[test]
+    fn test_control_frame_session() {
+        let c = ControlFrame::Session(u64::MAX, MAX_SEQ);
+        let mut buf = BytesMut::new();
+        c.encode(&mut buf);
+        assert_eq!(buf.len(), SESSION_PAYLOAD_SIZE);
+        assert_eq!(&buf[..9], &[6, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(ControlFrame::decode(&buf[..]), Some(c));
+        // of a version 8 peer, or a low out of the numbering
+        assert_eq!(ControlFrame::decode(&buf[..CONTROL_PAYLOAD_SIZE]), None);
+        let mut zero = BytesMut::new();
+        ControlFrame::Session(1, 0).encode(&mut zero);
+        assert_eq!(ControlFrame::decode(&zero[..]), None);
+    }
+
+    // the check values of RFC 3720
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+        assert_eq!(crc32c(&[0u8; 32]), 0x8a9136aa);
+        assert_eq!(crc32c(&[0xffu8; 32]), 0x62a8ab43);
+    }
+
+    #[test]
+    fn test_fragment_header_layout() {
+        let mut buf = BytesMut::new();
+        FragmentHeader::new(0x01020304, 0x05060708, FRAGMENT_LAST).encode(&mut buf);
+        assert_eq!(&buf[..], &[1, 2, 3, 4, 5, 6, 7, 8, FRAGMENT_LAST]);
+        let decoded = FragmentHeader::decode(&buf[..]).unwrap();
+        assert_eq!(decoded, FragmentHeader::new(0x01020304, 0x05060708, FRAGMENT_LAST));
+        assert!(decoded.is_last());
+        assert!(!decoded.is_abort());
+        assert!(FragmentHeader::new(1, 0, FRAGMENT_ABORT).is_abort());
+        assert_eq!(FragmentHeader::decode(&buf[..FRAGMENT_HEADER_SIZE - 1]), None);
+    }
+
+    #[test]
+    fn test_preamble_layout() {
+        assert_eq!(preamble(7), [b'S', b'C', b'P', b'N', 7]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use scupt_util::message::{decode_message, encode_message, Message, MsgTrait};
+use scupt_util::res::Res;
+
+/// The wire format of the messages of an endpoint, in place of the length delimited frames of
+/// `frame`, set by `ESConnectOption::set_frame_codec`, to talk to a peer of another protocol.
+///
+/// A codec owns both the framing and the encoding of the messages. The control frames are not
+/// part of it, `EndpointAsync::ping` fails with `net_error::unsupported` on such an endpoint.
+pub trait FrameCodec<M: MsgTrait + 'static>: Send + Sync {
+    /// Appends the encoded message to the buffer.
+    fn encode(&self, message: &Message<M>, buf: &mut BytesMut) -> Res<()>;
+
+    /// Takes a message from the front of the buffer, the bytes read so far, None if more
+    /// bytes are needed, the remaining bytes are kept for the next call. An error closes the
+    /// connection.
+    fn decode(&self, buf: &mut BytesMut) -> Res<Option<Message<M>>>;
+}
+
+// the codec without the message type, the endpoint queues the messages encoded by
+// `encode_message` whatever the wire format is
+pub(crate) trait RawFrameCodec: Send + Sync {
+    fn encode(&self, payload: &[u8], buf: &mut BytesMut) -> Res<()>;
+
+    fn decode(&self, buf: &mut BytesMut) -> Res<Option<BytesMut>>;
+}
+
+struct RawCodecOf<M: MsgTrait + 'static> {
+    codec: Arc<dyn FrameCodec<M>>,
+}
+
+pub(crate) fn raw_codec_of<M: MsgTrait + 'static>(codec: Arc<dyn FrameCodec<M>>) -> Arc<dyn RawFrameCodec> {
+    Arc::new(RawCodecOf { codec })
+}
+
+impl<M: MsgTrait + 'static> RawFrameCodec for RawCodecOf<M> {
+    fn encode(&self, payload: &[u8], buf: &mut BytesMut) -> Res<()> {
+        let (m, _) = decode_message::<Message<M>>(payload)?;
+        self.codec.encode(&m, buf)
+    }
+
+    fn decode(&self, buf: &mut BytesMut) -> Res<Option<BytesMut>> {
+        match self.codec.decode(buf)? {
+            Some(m) => {
+                let vec = encode_message(m)?;
+                Ok(Some(BytesMut::from(vec.as_slice())))
+            }
+            None => { Ok(None) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bincode::{Decode, Encode};
+    use bytes::BytesMut;
+    use scupt_util::message::{decode_message, encode_message, Message, MsgTrait};
+    use scupt_util::res::Res;
+    use serde::{Deserialize, Serialize};
+
+    use crate::frame_codec::{FrameCodec, raw_codec_of};
+
+    #[derive(Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize, Decode, Encode)]
+    struct Byte(u8);
+
+    impl MsgTrait for Byte {}
+
+    // a message per byte
+    struct ByteCodec;
+
+    impl FrameCodec<Byte> for ByteCodec {
+        fn encode(&self, message: &Message<Byte>, buf: &mut BytesMut) -> Res<()> {
+            buf.extend_from_slice(&[message.clone().payload().0]);
+            Ok(())
+        }
+
+        fn decode(&self, buf: &mut BytesMut) -> Res<Option<Message<Byte>>> {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let b = buf.split_to(1);
+            Ok(Some(Message::new(Byte(b[0]), 0, 0)))
+        }
+    }
+
+    #[test]
+    fn test_raw_codec_round_trip() {
+        let raw = raw_codec_of::<Byte>(Arc::new(ByteCodec));
+        let mut wire = BytesMut::new();
+        for i in 1..=3u8 {
+            let payload = encode_message(Message::new(Byte(i), 0, 0)).unwrap();
+            raw.encode(payload.as_slice(), &mut wire).unwrap();
+        }
+        assert_eq!(&wire[..], &[1, 2, 3]);
+        for i in 1..=3u8 {
+            let payload = raw.decode(&mut wire).unwrap().unwrap();
+            let (m, _) = decode_message::<Message<Byte>>(&payload[..]).unwrap();
+            assert_eq!(m.payload(), Byte(i));
+        }
+        assert!(raw.decode(&mut wire).unwrap().is_none());
+    }
+}
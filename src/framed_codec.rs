@@ -1,58 +1,321 @@
 
+use std::error::Error;
+use std::fmt;
 use std::io;
+use std::sync::Arc;
 
 use bytes::{Buf, BufMut, BytesMut};
-use scupt_util::slice::Slice;
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::framed_header::{FramedHdr, FramedHdrRef};
+use crate::buffer_pool::BufferPool;
+use byteorder::ByteOrder;
 
-/// A simple [`Decoder`] and [`Encoder`] implementation that just ships bytes around.
+use crate::frame::{CHECKSUM_SIZE, CONTROL_SEQ, ControlFrame, crc32c, FrameHeader, HEADER_SIZE, MAX_PAYLOAD_SIZE, PREAMBLE_MAGIC, PREAMBLE_SIZE, WireEndian};
+use crate::frame_codec::RawFrameCodec;
+
+/// A frame to encode.
+pub enum OutFrame {
+    /// The channel, the sequence number and the payload of a message frame, numbered by the
+    /// writer of the endpoint.
+    Data(u16, u64, BytesMut),
+    /// A control frame, of the sequence number `CONTROL_SEQ`.
+    Control(ControlFrame),
+}
+
+/// The checksum of a decoded frame did not match its payload, carried by the
+/// [`io::ErrorKind::InvalidData`] error of the decoder.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChecksumMismatch {
+    /// The checksum in the header.
+    pub expected: u32,
+    /// The checksum of the payload received.
+    pub actual: u32,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "frame checksum mismatch, expected {:#010x}, actual {:#010x}", self.expected, self.actual)
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+impl ChecksumMismatch {
+    /// The mismatch carried by an error of the decoder, if any.
+    pub fn of(e: &io::Error) -> Option<Self> {
+        e.get_ref().and_then(|inner| { inner.downcast_ref::<ChecksumMismatch>() }).copied()
+    }
+}
+
+/// The bytes kept of a connection which did not start with a preamble.
+const MAX_RECEIVED: usize = 32;
+
+/// The peer did not start the connection with the expected preamble, carried by the
+/// [`io::ErrorKind::InvalidData`] error of the decoder.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PreambleMismatch {
+    /// Not a preamble, the first bytes received, up to `MAX_RECEIVED`.
+    Magic(Vec<u8>),
+    /// The preamble of another protocol version.
+    Version { expected: u8, actual: u8 },
+}
+
+impl fmt::Display for PreambleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreambleMismatch::Magic(received) => {
+                write!(f, "no preamble, received {:?}", String::from_utf8_lossy(received))
+            }
+            PreambleMismatch::Version { expected, actual } => {
+                write!(f, "preamble of protocol version {}, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl Error for PreambleMismatch {}
+
+impl PreambleMismatch {
+    /// The mismatch carried by an error of the decoder, if any.
+    pub fn of(e: &io::Error) -> Option<Self> {
+        e.get_ref().and_then(|inner| { inner.downcast_ref::<PreambleMismatch>() }).cloned()
+    }
+}
+
+/// A length delimited [`Decoder`] and [`Encoder`] implementation, see `frame` for the layout.
+///
+/// The encoder writes every message frame with the sequence number it was given, the decoder
+/// returns the header of a frame together with its payload, the control frames are told apart
+/// by the sequence number of the header.
+///
+/// With a user `FrameCodec`, the messages are written and read in its format instead, the
+/// payloads to write were encoded in that format by the sends, and are written as they are,
+/// the decoder stamps the messages read with a header of the next incoming sequence number.
+///
+/// With a preamble, the decoder reads the one of the peer before the first frame, and fails
+/// with a [`PreambleMismatch`] as soon as the bytes read do not match it, it never takes
+/// them for a header. The preamble of this side is written by the endpoint.
 ///
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
-pub struct FramedCodec(());
+/// With checksums, the header of every frame is followed by the CRC32C of the payload, the
+/// decoder fails with a [`ChecksumMismatch`] on a frame which does not match it.
+#[derive(Clone)]
+pub struct FramedCodec {
+    max_payload_size: usize,
+    opt_custom: Option<Arc<dyn RawFrameCodec>>,
+    next_in_seq: u64,
+    // the buffers of the message frames are given back after they were encoded
+    opt_pool: Option<Arc<BufferPool>>,
+    checksum: bool,
+    // the protocol version of the preamble expected, until it was read
+    opt_preamble: Option<u8>,
+}
 
 impl FramedCodec {
-    /// Creates a new `BytesCodec` for shipping around raw bytes.
+    /// Creates a new `FramedCodec`.
     pub fn new() -> FramedCodec {
-        FramedCodec(())
+        Self::new_with_max_payload_size(MAX_PAYLOAD_SIZE)
+    }
+
+    /// Creates a new `FramedCodec` which rejects the frames of a larger payload, both the
+    /// encoded and the decoded ones, the limit is capped by `MAX_PAYLOAD_SIZE`.
+    pub fn new_with_max_payload_size(max_payload_size: usize) -> FramedCodec {
+        FramedCodec {
+            max_payload_size: max_payload_size.min(MAX_PAYLOAD_SIZE),
+            opt_custom: None,
+            next_in_seq: 1,
+            opt_pool: None,
+            checksum: false,
+            opt_preamble: None,
+        }
+    }
+
+    /// Writes and reads the messages in the format of the codec, when it is Some.
+    pub fn set_custom(self, opt_custom: Option<Arc<dyn RawFrameCodec>>) -> FramedCodec {
+        let mut s = self;
+        s.opt_custom = opt_custom;
+        s
+    }
+
+    /// Gives the payload buffers of the message frames back to the pool, once encoded.
+    pub fn set_buffer_pool(self, opt_pool: Option<Arc<BufferPool>>) -> FramedCodec {
+        let mut s = self;
+        s.opt_pool = opt_pool;
+        s
+    }
+
+    /// Writes and verifies the checksum of every frame, not of the ones of a user format.
+    pub fn set_checksum(self, checksum: bool) -> FramedCodec {
+        let mut s = self;
+        s.checksum = checksum;
+        s
+    }
+
+    /// Reads the preamble of the protocol version before the first frame, when it is Some,
+    /// not with a user format.
+    pub fn set_preamble(self, opt_version: Option<u8>) -> FramedCodec {
+        let mut s = self;
+        s.opt_preamble = opt_version;
+        s
+    }
+
+    // true once the preamble was read, a mismatch fails as soon as the bytes read tell it
+    fn decode_preamble(&mut self, version: u8, buf: &mut BytesMut) -> Result<bool, io::Error> {
+        let n = buf.len().min(PREAMBLE_MAGIC.len());
+        if buf[..n] != PREAMBLE_MAGIC[..n] {
+            let received = buf[..buf.len().min(MAX_RECEIVED)].to_vec();
+            return Err(io::Error::new(io::ErrorKind::InvalidData, PreambleMismatch::Magic(received)));
+        }
+        if buf.len() < PREAMBLE_SIZE {
+            return Ok(false);
+        }
+        let actual = buf[PREAMBLE_MAGIC.len()];
+        if actual != version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                PreambleMismatch::Version { expected: version, actual }));
+        }
+        buf.advance(PREAMBLE_SIZE);
+        self.opt_preamble = None;
+        Ok(true)
+    }
+
+    // the bytes preceding the payload of a frame
+    fn prefix_size(&self) -> usize {
+        if self.checksum {
+            HEADER_SIZE + CHECKSUM_SIZE
+        } else {
+            HEADER_SIZE
+        }
+    }
+
+    // write the header, and the checksum of the payload if enabled
+    fn encode_header(&self, header: FrameHeader, payload: &[u8], buf: &mut BytesMut) {
+        header.encode(buf);
+        if self.checksum {
+            buf.put_u32(crc32c(payload));
+        }
+    }
+
+    fn recycle(&self, data: BytesMut) {
+        if let Some(pool) = &self.opt_pool {
+            pool.give(data);
+        }
+    }
+
+    fn decode_custom(&mut self, buf: &mut BytesMut) -> Result<Option<(FrameHeader, BytesMut)>, io::Error> {
+        let custom = match &self.opt_custom {
+            Some(c) => { c.clone() }
+            None => { return Ok(None); }
+        };
+        let r = custom.decode(buf)
+            .map_err(|e| { io::Error::new(io::ErrorKind::InvalidData, e.to_string()) })?;
+        match r {
+            Some(payload) => {
+                let hdr = FrameHeader::new(payload.len() as u32, self.next_in_seq);
+                self.next_in_seq += 1;
+                Ok(Some((hdr, payload)))
+            }
+            None => { Ok(None) }
+        }
+    }
+}
+
+impl Default for FramedCodec {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Decoder for FramedCodec {
-    type Item = BytesMut;
+    type Item = (FrameHeader, BytesMut);
     type Error = io::Error;
 
-    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, io::Error> {
-        if buf.len() <= FramedHdr::size() {
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<(FrameHeader, BytesMut)>, io::Error> {
+        if self.opt_custom.is_some() {
+            return self.decode_custom(buf);
+        }
+        if let Some(version) = self.opt_preamble {
+            if !self.decode_preamble(version, buf)? {
+                return Ok(None);
+            }
+        }
+        // retrieve the header first, and get the message size
+        let hdr = match FrameHeader::decode(&buf[..]) {
+            Some(hdr) => { hdr }
+            None => { return Ok(None); }
+        };
+        let msg_size = hdr.size() as usize;
+        if msg_size > self.max_payload_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame payload of {} bytes exceeds {} bytes", msg_size, self.max_payload_size)));
+        }
+        let prefix_size = self.prefix_size();
+        if buf.len() < msg_size + prefix_size {
             return Ok(None);
+        }
+        // have a full message
+        let opt_expected = if self.checksum {
+            Some(WireEndian::read_u32(&buf[HEADER_SIZE..]))
         } else {
-            // retrieve the header first, and get the message size
-            let hdr = FramedHdrRef::new(buf[0..FramedHdrRef::size()].as_slice());
-            let msg_size = hdr.get_size() as usize;
-            if buf.len() >= msg_size + FramedHdrRef::size() {
-                // have a full message
-                buf.advance(FramedHdrRef::size());
-                Ok(Some(buf.split_to(msg_size)))
-            } else {
-                return Ok(None);
+            None
+        };
+        buf.advance(prefix_size);
+        let payload = buf.split_to(msg_size);
+        if let Some(expected) = opt_expected {
+            let actual = crc32c(&payload[..]);
+            if actual != expected {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, ChecksumMismatch { expected, actual }));
             }
         }
+        Ok(Some((hdr, payload)))
     }
 }
 
 
-impl Encoder<BytesMut> for FramedCodec {
+impl Encoder<OutFrame> for FramedCodec {
     type Error = io::Error;
 
-    fn encode(&mut self, data: BytesMut, buf: &mut BytesMut) -> Result<(), io::Error> {
-        let mut header = FramedHdr::new();
-        header.set_size(data.len() as u32);
-        buf.reserve(FramedHdr::size() + data.len());
+    fn encode(&mut self, frame: OutFrame, buf: &mut BytesMut) -> Result<(), io::Error> {
+        let (channel, seq, data) = match frame {
+            OutFrame::Data(channel, seq, data) => { (channel, seq, data) }
+            OutFrame::Control(_) if self.opt_custom.is_some() => {
+                // not part of the user format
+                return Ok(());
+            }
+            OutFrame::Control(c) if self.checksum => {
+                let mut payload = BytesMut::with_capacity(c.size());
+                c.encode(&mut payload);
+                buf.reserve(HEADER_SIZE + CHECKSUM_SIZE + payload.len());
+                self.encode_header(FrameHeader::new(payload.len() as u32, CONTROL_SEQ), &payload, buf);
+                buf.put_slice(&payload[..]);
+                return Ok(());
+            }
+            OutFrame::Control(c) => {
+                buf.reserve(HEADER_SIZE + c.size());
+                FrameHeader::new(c.size() as u32, CONTROL_SEQ).encode(buf);
+                c.encode(buf);
+                return Ok(());
+            }
+        };
+        if data.len() > self.max_payload_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame payload of {} bytes exceeds {} bytes", data.len(), self.max_payload_size)));
+        }
+        if self.opt_custom.is_some() {
+            // encoded in the format of the codec by the send
+            buf.put_slice(&data[..]);
+            self.recycle(data);
+            return Ok(());
+        }
+        let header = FrameHeader::new(data.len() as u32, seq).set_channel(channel);
+        buf.reserve(self.prefix_size() + data.len());
         // write the header first
-        buf.put(header.buf());
+        self.encode_header(header, &data, buf);
         // write the message
-        buf.put(data);
+        buf.put_slice(&data[..]);
+        self.recycle(data);
         Ok(())
     }
 }
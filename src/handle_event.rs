@@ -3,10 +3,11 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use scupt_util::error_type::ET;
-use scupt_util::message::MsgTrait;
+use scupt_util::message::{Message, MsgTrait};
 use scupt_util::res::Res;
 
 use crate::endpoint_async::EndpointAsync;
+use crate::net_error;
 
 #[async_trait]
 pub trait HandleEvent<M: MsgTrait + 'static>: Sync + Send {
@@ -22,9 +23,26 @@ pub trait HandleEvent<M: MsgTrait + 'static>: Sync + Send {
         endpoint: Res<Arc<dyn EndpointAsync<M>>>,
     ) -> Res<()>;
 
+    // Before every attempt of a connect, numbered from 1, with the error of the previous
+    // attempt of a connect with retries, such as `Client::connect`. A connect of the event
+    // sink is a single attempt. The attempt is made when it returns.
+    async fn on_connect_attempt(&self, _address: SocketAddr, _attempt: u64, _last_error: Option<ET>) {}
+
     // error sink
     async fn on_error(&self, error: ET);
 
+    // the connection of an endpoint was closed, the reason is ET::EOF if the peer closed it or
+    // the endpoint was dropped
+    async fn on_disconnected(&self, _address: SocketAddr, _reason: ET) {}
+
+    // A message of the endpoint, called only by a node of `Delivery::Push`, one message after
+    // another in the order of the endpoint, the next message is not read before it returns.
+    // Return `net_error::not_handled` to stop the push on this endpoint, that message and the
+    // following ones are then delivered by `recv`. Any other error is reported by `on_error`.
+    async fn on_message(&self, _from: Arc<dyn EndpointAsync<M>>, _message: Message<M>) -> Res<()> {
+        Err(net_error::not_handled())
+    }
+
     // when the runtime stop
     async fn on_stop(&self);
 }
@@ -57,4 +75,162 @@ impl<M: MsgTrait> HandleEvent<M> for HandleEventDummy {
 
 
     async fn on_stop(&self) {}
-}
\ No newline at end of file
+}
+
+// the no-op handler, for a node which only needs the default event behavior
+pub type DefaultHandler = HandleEventDummy;
+
+pub type AcceptedFn<M> = Box<dyn Fn(Arc<dyn EndpointAsync<M>>) -> Res<()> + Send + Sync>;
+
+pub type ConnectedFn<M> = Box<dyn Fn(SocketAddr, Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> + Send + Sync>;
+
+pub type ConnectAttemptFn = Box<dyn Fn(SocketAddr, u64, Option<ET>) + Send + Sync>;
+
+pub type ErrorFn = Box<dyn Fn(ET) + Send + Sync>;
+
+pub type DisconnectedFn = Box<dyn Fn(SocketAddr, ET) + Send + Sync>;
+
+pub type MessageFn<M> = Box<dyn Fn(Arc<dyn EndpointAsync<M>>, Message<M>) -> Res<()> + Send + Sync>;
+
+pub type StopFn = Box<dyn Fn() + Send + Sync>;
+
+// A handler built from closures, each callback without a closure behaves as the default one.
+// The closures are not async, spawn a task for a long work, such as reading an endpoint.
+pub struct FnHandler<M: MsgTrait + 'static> {
+    opt_accepted: Option<AcceptedFn<M>>,
+    opt_connected: Option<ConnectedFn<M>>,
+    opt_connect_attempt: Option<ConnectAttemptFn>,
+    opt_error: Option<ErrorFn>,
+    opt_disconnected: Option<DisconnectedFn>,
+    opt_message: Option<MessageFn<M>>,
+    opt_stop: Option<StopFn>,
+}
+
+impl<M: MsgTrait + 'static> FnHandler<M> {
+    pub fn new() -> Self {
+        Self {
+            opt_accepted: None,
+            opt_connected: None,
+            opt_connect_attempt: None,
+            opt_error: None,
+            opt_disconnected: None,
+            opt_message: None,
+            opt_stop: None,
+        }
+    }
+
+    pub fn set_on_accepted<F>(self, f: F) -> Self
+        where F: Fn(Arc<dyn EndpointAsync<M>>) -> Res<()> + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_accepted = Some(Box::new(f));
+        s
+    }
+
+    pub fn set_on_connected<F>(self, f: F) -> Self
+        where F: Fn(SocketAddr, Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_connected = Some(Box::new(f));
+        s
+    }
+
+    pub fn set_on_connect_attempt<F>(self, f: F) -> Self
+        where F: Fn(SocketAddr, u64, Option<ET>) + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_connect_attempt = Some(Box::new(f));
+        s
+    }
+
+    pub fn set_on_error<F>(self, f: F) -> Self
+        where F: Fn(ET) + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_error = Some(Box::new(f));
+        s
+    }
+
+    pub fn set_on_disconnected<F>(self, f: F) -> Self
+        where F: Fn(SocketAddr, ET) + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_disconnected = Some(Box::new(f));
+        s
+    }
+
+    // called by a node of `Delivery::Push` only, see `HandleEvent::on_message`
+    pub fn set_on_message<F>(self, f: F) -> Self
+        where F: Fn(Arc<dyn EndpointAsync<M>>, Message<M>) -> Res<()> + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_message = Some(Box::new(f));
+        s
+    }
+
+    pub fn set_on_stop<F>(self, f: F) -> Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        let mut s = self;
+        s.opt_stop = Some(Box::new(f));
+        s
+    }
+}
+
+impl<M: MsgTrait + 'static> Default for FnHandler<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> HandleEvent<M> for FnHandler<M> {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
+        match &self.opt_accepted {
+            Some(f) => { f(endpoint) }
+            None => { Ok(()) }
+        }
+    }
+
+    async fn on_connected(
+        &self,
+        address: SocketAddr,
+        endpoint: Res<Arc<dyn EndpointAsync<M>>>,
+    ) -> Res<()> {
+        match &self.opt_connected {
+            Some(f) => { f(address, endpoint) }
+            None => { Ok(()) }
+        }
+    }
+
+    async fn on_connect_attempt(&self, address: SocketAddr, attempt: u64, last_error: Option<ET>) {
+        if let Some(f) = &self.opt_connect_attempt {
+            f(address, attempt, last_error);
+        }
+    }
+
+    async fn on_error(&self, error: ET) {
+        if let Some(f) = &self.opt_error {
+            f(error);
+        }
+    }
+
+    async fn on_disconnected(&self, address: SocketAddr, reason: ET) {
+        if let Some(f) = &self.opt_disconnected {
+            f(address, reason);
+        }
+    }
+
+    async fn on_message(&self, from: Arc<dyn EndpointAsync<M>>, message: Message<M>) -> Res<()> {
+        match &self.opt_message {
+            Some(f) => { f(from, message) }
+            None => { Err(net_error::not_handled()) }
+        }
+    }
+
+    async fn on_stop(&self) {
+        if let Some(f) = &self.opt_stop {
+            f();
+        }
+    }
+}
@@ -1,5 +1,6 @@
 pub mod io_service;
 pub mod event_sink_async;
+pub mod event_sink;
 pub mod message_receiver_async;
 pub mod message_sender_async;
 pub mod handle_event;
@@ -8,7 +9,10 @@ pub mod node;
 pub mod message_incoming;
 pub mod message_incoming_dummy;
 pub mod opt_send;
+pub mod opt_close;
+pub mod opt_call;
 pub mod client;
+pub mod client_sync;
 pub mod io_service_async;
 pub mod io_service_sync;
 pub mod message_receiver_sync;
@@ -17,12 +21,32 @@ pub mod task;
 pub mod event_sink_sync;
 pub mod endpoint_async;
 pub mod es_option;
+pub mod net_error;
+pub mod test_controller;
+pub mod priority;
+pub mod overflow_policy;
+pub mod rate_limit;
+pub mod recorder;
+pub mod transport;
+pub mod resolver;
+pub mod clock;
+pub mod opt_node;
+pub mod metrics;
+pub mod connection_pool;
+pub mod caller;
+pub mod frame;
+pub mod negotiated;
+pub mod frame_codec;
+pub mod proxy;
+pub mod respond_handler;
+pub mod rpc_envelope;
+pub mod channel;
+pub mod endpoint_sink;
 mod message_receiver_endpoint;
 mod endpoint_async_impl;
 mod event;
 mod event_sink_impl;
 mod framed_codec;
-mod framed_header;
 mod net_handler;
 mod node_context;
 mod message_receiver_channel_async;
@@ -39,8 +63,24 @@ mod endpoint_sync;
 mod endpoint_sync_impl;
 mod endpoint_inner;
 mod message_receiver_channel_sync;
+mod dedup;
+mod flow_control;
+mod reassembly;
+mod endpoint_fault;
+mod endpoint_push;
+mod endpoint_parallel;
+mod send_lanes;
+mod buffer_pool;
+mod memory_transport;
+mod net_trace;
+#[cfg(feature = "udp")]
+mod endpoint_udp;
+#[cfg(feature = "socks5")]
+mod socks5;
 pub mod debug;
 
+pub use crate::task::dump_tasks;
+
 mod test_debug_server;
 
 
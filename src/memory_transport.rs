@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use lazy_static::lazy_static;
+use scupt_util::res::Res;
+use tokio::io::{duplex, DuplexStream};
+use tokio::sync::mpsc;
+
+use crate::net_error;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+type Incoming = (DuplexStream, SocketAddr);
+
+// the buffer size of each direction of an in-memory connection
+const MEMORY_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+lazy_static! {
+    // the process-global registry, the logical listening address to the listener
+    static ref MEMORY_LISTENERS: SyncMutex<HashMap<SocketAddr, mpsc::UnboundedSender<Incoming>>> =
+        SyncMutex::new(HashMap::new());
+}
+
+// the logical addresses of the connecting sides, they are only used to tell the endpoints apart
+static NEXT_CONNECT_PORT: AtomicU16 = AtomicU16::new(1);
+
+// An in-memory listener, the address is a logical address and is never bound on a network
+// interface.
+// Unregistered when it is dropped.
+pub struct MemoryListener {
+    address: SocketAddr,
+    receiver: mpsc::UnboundedReceiver<Incoming>,
+}
+
+impl MemoryListener {
+    pub fn bind(address: SocketAddr) -> Res<Self> {
+        let mut listeners = MEMORY_LISTENERS.lock().unwrap();
+        if listeners.contains_key(&address) {
+            return Err(net_error::io_error(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("memory address {} in use", address)), "bind", address));
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let _ = listeners.insert(address, sender);
+        Ok(Self {
+            address,
+            receiver,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub async fn accept(&mut self) -> Res<(DuplexStream, SocketAddr)> {
+        match self.receiver.recv().await {
+            Some(incoming) => { Ok(incoming) }
+            None => {
+                Err(net_error::io_error(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!("memory listener {} closed", self.address)), "accept", self.address))
+            }
+        }
+    }
+}
+
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        let mut listeners = MEMORY_LISTENERS.lock().unwrap();
+        let _ = listeners.remove(&self.address);
+    }
+}
+
+// connect to an in-memory listener, return the stream and the address of the listener
+pub fn memory_connect(address: SocketAddr) -> Res<(DuplexStream, SocketAddr)> {
+    let listeners = MEMORY_LISTENERS.lock().unwrap();
+    let refused = || {
+        Err(net_error::io_error(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("no memory listener on {}", address)), "connect", address))
+    };
+    let sender = match listeners.get(&address) {
+        Some(s) => { s }
+        None => { return refused(); }
+    };
+    let (client, server) = duplex(MEMORY_STREAM_BUFFER_SIZE);
+    let port = NEXT_CONNECT_PORT.fetch_add(1, Ordering::SeqCst);
+    let local_address = SocketAddr::from(([0, 0, 0, 0], port));
+    match sender.send((server, local_address)) {
+        Ok(()) => { Ok((client, address)) }
+        Err(_) => { refused() }
+    }
+}
@@ -25,7 +25,7 @@ impl<M: MsgTrait + 'static> MessageReceiverEndpoint<M> {
 
 #[async_trait]
 impl<M: MsgTrait + 'static> ReceiverResp<M> for MessageReceiverEndpoint<M> {
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn receive(&self) -> Res<Message<M>> {
         let _ = task_trace!();
         self.ep.recv().await
@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use scupt_util::node_id::NID;
+
+// The counters and gauges of a node, updated by the accept loop, the connect path and the
+// endpoints of the node. They are relaxed atomics, cheap enough to be always on.
+// The messages of the testing endpoints, which do not write to the network, and of the UDP
+// endpoints are not counted.
+// With the feature `metrics-export`, every update is also emitted to the `metrics` facade,
+// as the counters and the gauge of `export`, labeled by the node id and the direction.
+pub struct Metrics {
+    #[cfg(feature = "metrics-export")]
+    nid: String,
+    connections: AtomicU64,
+    accepted: AtomicU64,
+    connect_failures: AtomicU64,
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    queue_high_water: AtomicU64,
+    decode_errors: AtomicU64,
+    messages_dropped: AtomicU64,
+}
+
+// a copy of the metrics at a point
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MetricsSnapshot {
+    // the live connections, inbound and outbound
+    pub connections: u64,
+    // the total inbound connections accepted
+    pub accepted: u64,
+    pub connect_failures: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    // the bytes of the encoded messages, without the frame headers
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    // the max number of the messages queued for the writer task of an endpoint
+    pub queue_high_water: u64,
+    pub decode_errors: u64,
+    // the queued messages evicted by `OverflowPolicy::DropOldest`, or dropped by a stop, never
+    // written, see `StopReport`
+    pub messages_dropped: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::new_of_node(0)
+    }
+
+    // the metrics of the node, the node id labels the exported metrics
+    pub fn new_of_node(_nid: NID) -> Self {
+        Self {
+            #[cfg(feature = "metrics-export")]
+            nid: _nid.to_string(),
+            connections: AtomicU64::new(0),
+            accepted: AtomicU64::new(0),
+            connect_failures: AtomicU64::new(0),
+            messages_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            queue_high_water: AtomicU64::new(0),
+            decode_errors: AtomicU64::new(0),
+            messages_dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            connections: self.connections.load(Ordering::Relaxed),
+            accepted: self.accepted.load(Ordering::Relaxed),
+            connect_failures: self.connect_failures.load(Ordering::Relaxed),
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            queue_high_water: self.queue_high_water.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            messages_dropped: self.messages_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn connection_opened(&self, _inbound: bool) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::connections(&self.nid, _inbound, 1.0);
+    }
+
+    pub(crate) fn connection_closed(&self, _inbound: bool) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::connections(&self.nid, _inbound, -1.0);
+    }
+
+    pub(crate) fn add_accepted(&self) {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::count(export::ACCEPTED, &self.nid, export::IN, 1);
+    }
+
+    pub(crate) fn add_connect_failure(&self) {
+        self.connect_failures.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::count(export::CONNECT_FAILURES, &self.nid, export::OUT, 1);
+    }
+
+    pub(crate) fn add_message_in(&self, bytes: usize) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        {
+            export::count(export::MESSAGES_RECEIVED, &self.nid, export::IN, 1);
+            export::count(export::BYTES_RECEIVED, &self.nid, export::IN, bytes as u64);
+        }
+    }
+
+    pub(crate) fn add_message_out(&self, bytes: usize) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        {
+            export::count(export::MESSAGES_SENT, &self.nid, export::OUT, 1);
+            export::count(export::BYTES_SENT, &self.nid, export::OUT, bytes as u64);
+        }
+    }
+
+    pub(crate) fn observe_queue_depth(&self, depth: usize) {
+        self.queue_high_water.fetch_max(depth as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::count(export::DECODE_ERRORS, &self.nid, export::IN, 1);
+    }
+
+    pub(crate) fn add_message_dropped(&self, n: u64) {
+        self.messages_dropped.fetch_add(n, Ordering::Relaxed);
+        #[cfg(feature = "metrics-export")]
+        export::count(export::MESSAGES_DROPPED, &self.nid, export::OUT, n);
+    }
+}
+
+// the names and the labels of the metrics emitted to the `metrics` facade, the counters are
+// registered by the first update, and the connections gauge counts the live connections
+#[cfg(feature = "metrics-export")]
+pub mod export {
+    pub const BYTES_SENT: &str = "scupt_net.bytes_sent";
+    pub const BYTES_RECEIVED: &str = "scupt_net.bytes_received";
+    pub const MESSAGES_SENT: &str = "scupt_net.messages_sent";
+    pub const MESSAGES_RECEIVED: &str = "scupt_net.messages_received";
+    pub const CONNECTIONS: &str = "scupt_net.connections";
+    pub const ACCEPTED: &str = "scupt_net.accepted";
+    pub const CONNECT_FAILURES: &str = "scupt_net.connect_failures";
+    pub const DECODE_ERRORS: &str = "scupt_net.decode_errors";
+    pub const MESSAGES_DROPPED: &str = "scupt_net.messages_dropped";
+
+    pub const LABEL_NID: &str = "nid";
+    pub const LABEL_DIRECTION: &str = "direction";
+
+    pub const IN: &str = "in";
+    pub const OUT: &str = "out";
+
+    pub(crate) fn count(name: &'static str, nid: &str, direction: &'static str, n: u64) {
+        ::metrics::counter!(name, LABEL_NID => nid.to_string(), LABEL_DIRECTION => direction).increment(n);
+    }
+
+    pub(crate) fn connections(nid: &str, inbound: bool, delta: f64) {
+        let direction = if inbound { IN } else { OUT };
+        ::metrics::gauge!(CONNECTIONS, LABEL_NID => nid.to_string(), LABEL_DIRECTION => direction).increment(delta);
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::frame::WIRE_VERSION;
+
+// The parameters in effect on a connection, see `EndpointAsync::negotiated`.
+// Each side is configured on its own, with `ESConnectOption::enable_negotiation` on both of
+// them, each one sends its capabilities and its max message size in a params control frame
+// after the connection was established, see `frame::ControlFrame::Params`, and the endpoint
+// agrees on the largest message both sides accept and on the capabilities both sides have.
+// Until the params of the peer were read, or if it sends none, they are the ones of this side.
+// The agreed max message size is enforced by the sends, see `NegotiatedLimits`, the others are
+// only reported. The frames are not compressed, there is no compression to agree on.
+
+// the frames carry a CRC32C, see `ESConnectOption::enable_checksum`
+pub const CAP_CHECKSUM: u32 = 1;
+
+// the default channel is flow controlled, see `ESConnectOption::set_flow_window`
+pub const CAP_FLOW_CONTROL: u32 = 1 << 1;
+
+// the node sends its name, see `OptNode::enable_advertise_name`
+pub const CAP_ADVERTISE_NAME: u32 = 1 << 2;
+
+// the format of the frames of a connection
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum FrameFormat {
+    // the frames of `frame`, of the version `wire_version`
+    Builtin,
+    // the format of a user `FrameCodec`, no control frame and no params are exchanged
+    Custom,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NegotiatedParams {
+    pub format: FrameFormat,
+    // `frame::WIRE_VERSION`, the version of this side, the peer does not tell its own
+    pub wire_version: u32,
+    // the largest payload of a frame, the smaller one of the two sides when agreed
+    pub max_message_size: usize,
+    // the CAP_XXX flags, the ones of both sides when agreed
+    pub capabilities: u32,
+    // the params of the peer were read
+    pub agreed: bool,
+}
+
+impl NegotiatedParams {
+    // the params of this side
+    pub(crate) fn new(format: FrameFormat, max_message_size: usize, capabilities: u32) -> Self {
+        Self {
+            format,
+            wire_version: WIRE_VERSION,
+            max_message_size,
+            capabilities,
+            agreed: false,
+        }
+    }
+
+    // agree with the params the peer sent
+    pub(crate) fn agree(self, peer_capabilities: u32, peer_max_message_size: u32) -> Self {
+        let mut s = self;
+        s.max_message_size = s.max_message_size.min(peer_max_message_size as usize);
+        s.capabilities &= peer_capabilities;
+        s.agreed = true;
+        s
+    }
+
+    pub fn has(&self, capability: u32) -> bool {
+        self.capabilities & capability == capability
+    }
+}
+
+// The max message sizes of a connection, see `EndpointAsync::negotiated_limits`. A send of
+// a larger encoded message fails with `net_error::message_too_large` of `max_message_size`
+// before anything is written, rather than the peer closing the connection on the frame.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NegotiatedLimits {
+    // the limit of the sends, the smaller one of the two sides once the peer's was read
+    pub max_message_size: usize,
+    // the limit of this side, see `ESConnectOption::set_max_message_size`, the larger incoming
+    // frames close the connection
+    pub local_max_message_size: usize,
+    // the limit the peer sent in its params, None until read, or if it sends none
+    pub opt_peer_max_message_size: Option<usize>,
+}
+
+impl NegotiatedLimits {
+    pub(crate) fn new(local_max_message_size: usize, opt_peer_max_message_size: Option<usize>) -> Self {
+        Self {
+            max_message_size: match opt_peer_max_message_size {
+                Some(peer) => { local_max_message_size.min(peer) }
+                None => { local_max_message_size }
+            },
+            local_max_message_size,
+            opt_peer_max_message_size,
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use std::io;
+use std::net::SocketAddr;
+
+use scupt_util::error_type::ET;
+use scupt_util::node_id::NID;
+use scupt_util::res_of::res_io;
+
+// Errors raised by scupt-net itself.
+// They are carried by the existing variants of scupt_util::error_type::ET, use the `is_xxx`
+// functions to tell them apart from other errors of the same variant.
+
+const SEND_CLOSED: &str = "the send direction of the endpoint was shut down";
+
+// send after `EndpointAsync::shutdown_write`
+pub fn send_closed() -> ET {
+    ET::SenderError(SEND_CLOSED.to_string())
+}
+
+pub fn is_send_closed(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s == SEND_CLOSED)
+}
+
+const IDLE_TIMEOUT: &str = "no incoming bytes on the endpoint within the idle timeout";
+
+// the reader task reaped a connection that stayed silent longer than `idle_timeout_ms`
+pub fn idle_timeout() -> ET {
+    ET::RecvError(IDLE_TIMEOUT.to_string())
+}
+
+pub fn is_idle_timeout(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s == IDLE_TIMEOUT)
+}
+
+const WRITE_TIMEOUT: &str = "a write of the endpoint did not complete within the write timeout";
+
+// the writer task closed a connection whose peer stopped reading, see `write_timeout_ms`
+pub fn write_timeout() -> ET {
+    ET::SenderError(WRITE_TIMEOUT.to_string())
+}
+
+pub fn is_write_timeout(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s == WRITE_TIMEOUT)
+}
+
+const ADDR_PARSE: &str = "invalid socket address";
+
+// a malformed address given by the user
+pub fn addr_parse(address: &str) -> ET {
+    ET::FatalError(format!("{} {:?}", ADDR_PARSE, address))
+}
+
+// the malformed address of an option, named by the field
+pub fn addr_parse_of(field: &str, address: &str) -> ET {
+    ET::FatalError(format!("{} {:?} of {}", ADDR_PARSE, address, field))
+}
+
+pub fn is_addr_parse(e: &ET) -> bool {
+    matches!(e, ET::FatalError(s) if s.starts_with(ADDR_PARSE))
+}
+
+const MESSAGE_TOO_LARGE: &str = "the encoded message is too large";
+
+// the message does not fit in the limit of the transport, such as an UDP datagram
+pub fn message_too_large(size: usize, limit: usize) -> ET {
+    ET::SenderError(format!("{}, {} bytes exceeds {} bytes", MESSAGE_TOO_LARGE, size, limit))
+}
+
+pub fn is_message_too_large(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(MESSAGE_TOO_LARGE))
+}
+
+const INVALID_OPTION: &str = "invalid option";
+
+// a required option is missing or malformed when building
+pub fn invalid_option(reason: &str) -> ET {
+    ET::FatalError(format!("{}, {}", INVALID_OPTION, reason))
+}
+
+// the option of the field is missing or malformed when building
+pub fn invalid_option_of(field: &str, reason: &str) -> ET {
+    ET::FatalError(format!("{} {}, {}", INVALID_OPTION, field, reason))
+}
+
+pub fn is_invalid_option(e: &ET) -> bool {
+    matches!(e, ET::FatalError(s) if s.starts_with(INVALID_OPTION))
+}
+
+const PARTITIONED: &str = "the peer is across a network partition";
+
+// the testing node was partitioned from the peer, see `TestController::partition`
+pub fn partitioned(node_id: NID, peer: NID) -> ET {
+    ET::SenderError(format!("{}, from node {} to node {}", PARTITIONED, node_id, peer))
+}
+
+pub fn is_partitioned(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(PARTITIONED))
+}
+
+const UNRESOLVED: &str = "no address of the node to connect to";
+
+// the node has no endpoint of the peer, and its `NodeAddrResolver` gave no address of it, or
+// none was installed
+pub fn unresolved(node_id: NID, reason: &str) -> ET {
+    ET::SenderError(format!("{}, node {}, {}", UNRESOLVED, node_id, reason))
+}
+
+pub fn is_unresolved(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(UNRESOLVED))
+}
+
+const POOL_FULL: &str = "the connection pool is full";
+
+// all the pooled connections of `ConnectionPool` are alive or being dialed
+pub fn pool_full(limit: usize) -> ET {
+    ET::SenderError(format!("{}, the limit is {}", POOL_FULL, limit))
+}
+
+pub fn is_pool_full(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(POOL_FULL))
+}
+
+const TOO_MANY_INFLIGHT: &str = "too many outstanding calls";
+
+// `max_inflight` calls are outstanding, and the policy is `InflightPolicy::Error`
+pub fn too_many_inflight(limit: usize) -> ET {
+    ET::SenderError(format!("{}, the limit is {}", TOO_MANY_INFLIGHT, limit))
+}
+
+pub fn is_too_many_inflight(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(TOO_MANY_INFLIGHT))
+}
+
+const QUEUE_FULL: &str = "the send queue of the endpoint is full";
+
+// the lane of the message was full, and the policy is `OverflowPolicy::ErrorNewest`
+pub fn queue_full(capacity: usize) -> ET {
+    ET::SenderError(format!("{}, the capacity is {}", QUEUE_FULL, capacity))
+}
+
+pub fn is_queue_full(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s.starts_with(QUEUE_FULL))
+}
+
+const DROPPED: &str = "the message was evicted from the send queue";
+
+// the queued message made room for a newer one, the policy is `OverflowPolicy::DropOldest`
+pub fn dropped() -> ET {
+    ET::SenderError(DROPPED.to_string())
+}
+
+pub fn is_dropped(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s == DROPPED)
+}
+
+const UNSUPPORTED: &str = "the operation is not supported by the endpoint";
+
+// an optional method of `EndpointAsync` the endpoint does not implement
+pub fn unsupported(op: &str) -> ET {
+    ET::FatalError(format!("{}, {}", UNSUPPORTED, op))
+}
+
+pub fn is_unsupported(e: &ET) -> bool {
+    matches!(e, ET::FatalError(s) if s.starts_with(UNSUPPORTED))
+}
+
+const NET_RESET: &str = "the endpoint was replaced while receiving";
+
+// a `Client::recv` pending on an endpoint swapped out by `connect` or `disconnect`, retry the
+// recv to pick up the current endpoint
+pub fn net_reset() -> ET {
+    ET::RecvError(NET_RESET.to_string())
+}
+
+pub fn is_net_reset(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s == NET_RESET)
+}
+
+const WRITER_STOPPED: &str = "the writer of the endpoint was stopped";
+
+// the writer task stopped before writing the message, the endpoint was closed or dropped
+pub fn writer_stopped() -> ET {
+    ET::TokioSenderError(WRITER_STOPPED.to_string())
+}
+
+pub fn is_writer_stopped(e: &ET) -> bool {
+    matches!(e, ET::TokioSenderError(s) if s == WRITER_STOPPED)
+}
+
+const SHUTTING_DOWN: &str = "the node is shutting down";
+
+// a send or a connect while the node drains its endpoints, see `StopMode::Drain`
+pub fn shutting_down() -> ET {
+    ET::SenderError(SHUTTING_DOWN.to_string())
+}
+
+pub fn is_shutting_down(e: &ET) -> bool {
+    matches!(e, ET::SenderError(s) if s == SHUTTING_DOWN)
+}
+
+const CONNECT_TIMEOUT: &str = "every connect attempt timed out";
+
+// all the attempts of a connect with retries timed out, `connect_attempts` tells how many,
+// an attempt failing otherwise, such as refused, ends with that error instead
+pub fn connect_timeout(attempts: u64) -> ET {
+    ET::RecvError(format!("{}, attempts={}", CONNECT_TIMEOUT, attempts))
+}
+
+pub fn is_connect_timeout(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(CONNECT_TIMEOUT))
+}
+
+// the number of the attempts of a `connect_timeout`
+pub fn connect_attempts(e: &ET) -> Option<u64> {
+    match e {
+        ET::RecvError(s) if s.starts_with(CONNECT_TIMEOUT) => {
+            let (_, n) = s.rsplit_once("attempts=")?;
+            n.parse().ok()
+        }
+        _ => { None }
+    }
+}
+
+const PROXY_ERROR: &str = "the proxy negotiation failed";
+
+// the SOCKS5 proxy refused the tunnel, or did not speak the protocol, see `ProxyConfig`, the
+// errors of the connection to the proxy itself are `io_error` of the operation "connect proxy"
+pub fn proxy_error(proxy: SocketAddr, reason: &str) -> ET {
+    ET::RecvError(format!("{}, proxy={}, {}", PROXY_ERROR, proxy, reason))
+}
+
+pub fn is_proxy_error(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(PROXY_ERROR))
+}
+
+const FILTER_BUSY: &str = "another recv_filter is pending on the endpoint";
+
+// the filtered recvs of an endpoint are one at a time, see `EndpointAsync::recv_filter`
+pub fn filter_busy() -> ET {
+    ET::RecvError(FILTER_BUSY.to_string())
+}
+
+pub fn is_filter_busy(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s == FILTER_BUSY)
+}
+
+const NOT_HANDLED: &str = "the message was not handled by on_message";
+
+// returned by the default `HandleEvent::on_message`, the message is delivered by `recv` instead
+pub fn not_handled() -> ET {
+    ET::RecvError(NOT_HANDLED.to_string())
+}
+
+pub fn is_not_handled(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s == NOT_HANDLED)
+}
+
+const CHECKSUM_MISMATCH: &str = "the checksum of a frame did not match its payload";
+
+// the reader task closed a connection which received a corrupted frame, see
+// `ESConnectOption::enable_checksum`, `checksums` tells the expected and the actual ones
+pub fn checksum_mismatch(expected: u32, actual: u32, address: SocketAddr) -> ET {
+    ET::RecvError(format!("{}, addr={}, expected={:#010x}, actual={:#010x}",
+                          CHECKSUM_MISMATCH, address, expected, actual))
+}
+
+pub fn is_checksum_mismatch(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(CHECKSUM_MISMATCH))
+}
+
+const BAD_PROTOCOL: &str = "the peer did not start the connection with a preamble";
+
+// the connection of a peer which is not a scupt-net one, such as a port scanner or an HTTP
+// client, see `ESConnectOption::set_preamble`, with the first bytes received
+pub fn bad_protocol(received: &[u8], address: SocketAddr) -> ET {
+    ET::RecvError(format!("{}, addr={}, received {:?}", BAD_PROTOCOL, address, String::from_utf8_lossy(received)))
+}
+
+pub fn is_bad_protocol(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(BAD_PROTOCOL))
+}
+
+const VERSION_MISMATCH: &str = "the preamble of the peer is of another protocol version";
+
+// the peer is a scupt-net one, configured with another protocol version
+pub fn version_mismatch(expected: u8, actual: u8, address: SocketAddr) -> ET {
+    ET::RecvError(format!("{}, addr={}, expected={}, actual={}", VERSION_MISMATCH, address, expected, actual))
+}
+
+pub fn is_version_mismatch(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(VERSION_MISMATCH))
+}
+
+const FRAGMENT_REJECTED: &str = "the fragments of the peer were rejected";
+
+// the reader task closed a connection which received a malformed fragment, or the fragments
+// beyond the reassembly limit, see `ESConnectOption::set_reassembly_limit`
+pub fn fragment_rejected(reason: &str, address: SocketAddr) -> ET {
+    ET::RecvError(format!("{}, addr={}, {}", FRAGMENT_REJECTED, address, reason))
+}
+
+pub fn is_fragment_rejected(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(FRAGMENT_REJECTED))
+}
+
+const DEDUP_REJECTED: &str = "the sequence numbers of the peer were rejected";
+
+// the reader task closed a connection whose peer opened a gap of more than `dedup::MAX_PENDING`
+// sequence numbers received of its session, see `ESConnectOption::enable_dedup`
+pub fn dedup_rejected(reason: &str, address: SocketAddr) -> ET {
+    ET::RecvError(format!("{}, addr={}, {}", DEDUP_REJECTED, address, reason))
+}
+
+pub fn is_dedup_rejected(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(DEDUP_REJECTED))
+}
+
+// the checksum in the header and the one of the payload received of a `checksum_mismatch`
+pub fn checksums(e: &ET) -> Option<(u32, u32)> {
+    match e {
+        ET::RecvError(s) if s.starts_with(CHECKSUM_MISMATCH) => {
+            let (rest, actual) = s.rsplit_once(", actual=0x")?;
+            let (_, expected) = rest.rsplit_once("expected=0x")?;
+            Some((u32::from_str_radix(expected, 16).ok()?, u32::from_str_radix(actual, 16).ok()?))
+        }
+        _ => { None }
+    }
+}
+
+const IO_KIND: &str = "io_kind=";
+
+// the kinds told apart by `io_kind`, the others are io::ErrorKind::Other
+const IO_KINDS: [io::ErrorKind; 16] = [
+    io::ErrorKind::NotFound,
+    io::ErrorKind::PermissionDenied,
+    io::ErrorKind::ConnectionRefused,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::ConnectionAborted,
+    io::ErrorKind::NotConnected,
+    io::ErrorKind::AddrInUse,
+    io::ErrorKind::AddrNotAvailable,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::AlreadyExists,
+    io::ErrorKind::WouldBlock,
+    io::ErrorKind::InvalidInput,
+    io::ErrorKind::InvalidData,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::UnexpectedEof,
+];
+
+// A socket error, of operation `op` such as "connect" or "read", on the address of the peer, or
+// the local address of a listener. It is an ET::IOError, its message carries the kind, the
+// operation and the address.
+pub fn io_error(e: io::Error, op: &str, address: SocketAddr) -> ET {
+    let kind = e.kind();
+    let context = io::Error::new(kind, format!("{}{:?} op={} addr={}, {}", IO_KIND, kind, op, address, e));
+    match res_io::<()>(Err(context)) {
+        Err(e) => { e }
+        Ok(()) => { ET::FatalError(format!("{}{:?}", IO_KIND, kind)) }
+    }
+}
+
+// the kind of an error created by `io_error`
+pub fn io_kind(e: &ET) -> Option<io::ErrorKind> {
+    if !matches!(e, ET::IOError(_)) {
+        return None;
+    }
+    let message = e.to_string();
+    let start = message.find(IO_KIND)? + IO_KIND.len();
+    let name: String = message[start..].chars().take_while(|c| { c.is_ascii_alphanumeric() }).collect();
+    let kind = IO_KINDS.iter()
+        .find(|k| { format!("{:?}", k) == name })
+        .copied()
+        .unwrap_or(io::ErrorKind::Other);
+    Some(kind)
+}
+
+// nobody listens on the address, retry soon
+pub fn is_connection_refused(e: &ET) -> bool {
+    io_kind(e) == Some(io::ErrorKind::ConnectionRefused)
+}
+
+// an established connection broke, connect again
+pub fn is_connection_reset(e: &ET) -> bool {
+    matches!(io_kind(e), Some(io::ErrorKind::ConnectionReset)
+        | Some(io::ErrorKind::ConnectionAborted)
+        | Some(io::ErrorKind::BrokenPipe))
+}
+
+// the address is served already, a configuration error
+pub fn is_addr_in_use(e: &ET) -> bool {
+    io_kind(e) == Some(io::ErrorKind::AddrInUse)
+}
+
+// false for the errors which would fail the same way again, such as a served address, a
+// malformed address or option, the others, such as the transient network errors, are retryable
+pub fn is_retryable(e: &ET) -> bool {
+    if is_addr_parse(e) || is_invalid_option(e) {
+        return false;
+    }
+    !matches!(io_kind(e), Some(io::ErrorKind::AddrInUse)
+        | Some(io::ErrorKind::AddrNotAvailable)
+        | Some(io::ErrorKind::PermissionDenied)
+        | Some(io::ErrorKind::InvalidInput))
+}
+
+const TIMEOUT: &str = "timed out";
+
+// waited longer than the given duration
+pub fn timeout(what: &str) -> ET {
+    ET::RecvError(format!("{} {}", TIMEOUT, what))
+}
+
+pub fn is_timeout(e: &ET) -> bool {
+    matches!(e, ET::RecvError(s) if s.starts_with(TIMEOUT))
+}
+
+// The failures of a send or a recv of an endpoint or a client, grouped to decide whether to
+// retry, a NotConnected client connects first, a Reset connection is reconnected, the peer of a
+// Closed one has nothing more to say.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetErrorKind {
+    // no endpoint, never connected, or disconnected, or closed, or the connect timed out, or
+    // no address of the peer was resolved
+    NotConnected,
+    // the connection failed, was reaped by the idle or the write timeout, or was replaced, or
+    // received a corrupted frame, rejected fragments or sequence numbers, or the peer sent no
+    // preamble or one of another protocol version
+    Reset,
+    // the peer closed the connection cleanly, or the send direction was shut down
+    Closed,
+    // not an error of the connection, such as a message too large or an encoding error
+    Other,
+}
+
+pub fn net_error_kind(e: &ET) -> NetErrorKind {
+    if matches!(e, ET::NetNotConnected) || is_connect_timeout(e) || is_unresolved(e) {
+        NetErrorKind::NotConnected
+    } else if matches!(e, ET::EOF) || is_send_closed(e) {
+        NetErrorKind::Closed
+    } else if is_net_reset(e) || is_writer_stopped(e) || is_idle_timeout(e)
+        || is_write_timeout(e) || is_checksum_mismatch(e) || is_fragment_rejected(e)
+        || is_dedup_rejected(e) || is_bad_protocol(e) || is_version_mismatch(e)
+        || matches!(e, ET::IOError(_)) {
+        NetErrorKind::Reset
+    } else {
+        NetErrorKind::Other
+    }
+}
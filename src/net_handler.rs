@@ -162,7 +162,7 @@ impl<M: MsgTrait> InnerNetHandler<M> {
         self.message_sync_ch_receiver.clone()
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn receiver_message(&self, message: Message<M>, ep: Arc<dyn EndpointAsync<M>>) -> Res<()> {
         let _t = task_trace!();
         let mut hasher = DefaultHasher::new();
@@ -190,14 +190,14 @@ impl<M: MsgTrait> InnerNetHandler<M> {
         }
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn stop(&self) {
         let _t = task_trace!();
         let mut guard = self.message_async_ch_sender.lock().await;
         guard.clear();
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn process_message(
         &self,
         ep: Arc<dyn EndpointAsync<M>>,
@@ -223,7 +223,7 @@ impl<M: MsgTrait> InnerNetHandler<M> {
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn loop_handle_message(
         &self,
         ep: &Arc<dyn EndpointAsync<M>>,
@@ -248,7 +248,7 @@ impl<M: MsgTrait> InnerNetHandler<M> {
         r
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_next_message(
         &self,
         ep: &Arc<dyn EndpointAsync<M>>,
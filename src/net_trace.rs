@@ -0,0 +1,18 @@
+// The spans and events enabled by the `tracing-spans` feature, they compile out when the feature
+// is off. The fields are named `nid` for the local node, `peer` for the remote node, `addr` for
+// the remote address, and `msg_len` for the size of an encoded message, the payloads are never
+// traced.
+
+#[cfg(feature = "tracing-spans")]
+macro_rules! net_debug {
+    ($($arg:tt)*) => {
+        tracing::debug!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing-spans"))]
+macro_rules! net_debug {
+    ($($arg:tt)*) => {};
+}
+
+pub(crate) use net_debug;
@@ -1,33 +1,61 @@
 use std::net::SocketAddr;
+use std::str::FromStr;
 use std::sync::{Arc, Once};
 
 use scupt_util::error_type::ET;
 use scupt_util::message::{Message, MsgTrait};
 use scupt_util::node_id::NID;
 use scupt_util::res::Res;
-use scupt_util::res_of::res_io;
-use tokio::net::{TcpListener, TcpStream};
+use futures::future::join_all;
 use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
 use tokio::task::LocalSet;
 use tracing::{error, Instrument, trace, trace_span};
 
+use crate::clock::Clock;
 use crate::endpoint_async::EndpointAsync;
 use crate::endpoint_async_impl::EndpointAsyncImpl;
+use crate::endpoint_inner::ReaderState;
+use crate::endpoint_push::EndpointPush;
 use crate::endpoint_sync::EndpointSync;
 use crate::endpoint_sync_impl::EndpointSyncImpl;
-use crate::event::{NetEvent, ResultSenderType};
+#[cfg(feature = "udp")]
+use crate::endpoint_udp::EndpointUdp;
+use crate::es_option::{
+    DEFAULT_MAX_REASSEMBLIES,
+    DEFAULT_MAX_REASSEMBLY_BYTES,
+    DEFAULT_SEND_BUFFER_POOL,
+    DEFAULT_WRITE_BATCH_BYTES,
+    DEFAULT_WRITE_BATCH_MAX,
+    ESConnectOption,
+    ESServeOpt,
+    ESStopOpt,
+};
+use crate::event::{ConnectCompletion, NetEvent, ResultSenderType};
 use crate::event_channel::EventReceiver;
+use crate::event_sink::EventSink;
 use crate::event_sink_async::EventSinkAsync;
 use crate::event_sink_sync::EventSinkSync;
 use crate::handle_event::HandleEvent;
 use crate::message_sender_async::{SenderAsync, SenderRRAsync};
 use crate::message_sender_sync::SenderSync;
+use crate::metrics::MetricsSnapshot;
+use crate::net_error;
 use crate::net_handler::NodeSender;
+use crate::net_trace::net_debug;
 use crate::node_context::NodeContext;
 use crate::notifier::Notifier;
+use crate::opt_close::{StopMode, StopReport};
 use crate::opt_ep::OptEP;
+use crate::rate_limit::RateLimit;
+use crate::opt_node::{DEFAULT_BACKLOG, DEFAULT_HANDLER_CONCURRENCY, Delivery, OptNode};
+use crate::overflow_policy::OverflowPolicy;
 use crate::task::spawn_local_task;
+use crate::recorder::RecordSink;
+use crate::resolver::NodeAddrResolver;
 use crate::task_trace;
+use crate::test_controller::TestController;
+use crate::transport::{connect, Listener, NetStream, StreamTransport, Transport};
 
 #[derive(Clone)]
 pub struct Node<
@@ -49,6 +77,10 @@ Node<
     M,
     H
 > {
+    // The node stops when the notifier is notified, one notifier can be shared by several nodes
+    // to stop them all, each invokes its own HandleEvent::on_stop. The node runs in a child
+    // scope of the notifier, see `Node::stop_notify`, stopping the node by its event sink does
+    // not stop the other nodes sharing the notifier.
     pub fn new(
         node_id: NID,
         name: String,
@@ -56,7 +88,7 @@ Node<
         testing: bool,
         stop_notify: Notifier,
     ) -> Res<Self> {
-        let node_context = NodeContext::new(node_id.clone(), name, testing, stop_notify);
+        let node_context = NodeContext::new(node_id.clone(), name, testing, stop_notify.new_child());
         let node = Self {
             _node_id: node_id,
             handle: Arc::new(handle),
@@ -101,13 +133,129 @@ Node<
         Ok(s)
     }
 
+    // a child of the notifier given to `Node::new`, notified when this node stops
     pub fn stop_notify(&self) -> Notifier {
         self.node_context.stop_notify()
     }
 
+    // how the node serves and connects, the default is Transport::Tcp,
+    // it must be set before the node serve or connect
+    pub fn set_transport(&self, transport: Transport) {
+        self.node_context.set_transport(transport)
+    }
+
+    // serve and connect by the streams of the transport given by the user, it selects
+    // Transport::Custom, it must be set before the node serve or connect
+    pub fn set_stream_transport(&self, stream_transport: Arc<dyn StreamTransport>) {
+        self.node_context.set_stream_transport(stream_transport);
+        self.node_context.set_transport(Transport::Custom);
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.node_context.metrics().snapshot()
+    }
+
+    // the number of the live connections, the inbound ones, and the outbound ones registered
+    // by a connect not returning the endpoint, the closed ones are not counted
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connection_count(&self) -> u64 {
+        let _t = task_trace!();
+        self.node_context.inbound_connections() + self.node_context.num_endpoints().await
+    }
+
+    // a handle without the handler type, to share the node
+    pub fn handle(&self) -> NodeHandle<M> {
+        NodeHandle {
+            node_context: self.node_context.clone(),
+        }
+    }
+
+    // the live endpoints, inbound and outbound, whose peers advertised the name, see
+    // `OptNode::enable_advertise_name`, several peers may have the same name
+    pub fn find_by_name(&self, name: &str) -> Vec<Arc<dyn EndpointAsync<M>>> {
+        self.node_context.find_by_name(name)
+    }
+
+    // resolve the addresses of the peers with no endpoint, a send to such a peer connects to
+    // it first, see `NodeAddrResolver`, None to disable it
+    pub fn set_resolver(&self, opt_resolver: Option<Arc<dyn NodeAddrResolver>>) {
+        self.node_context.set_resolver(opt_resolver)
+    }
+
+    // the clock of the timers of the node and of its endpoints created after, see `Clock`
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.node_context.set_clock(clock)
+    }
+
+    // connect to the peers with no endpoint by the addresses of the resolver, and register the
+    // endpoints as a send to them would, the error of the first peer not connected is returned
+    // after trying all of them, the node must be running
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn connect_all(&self, node_ids: &[NID]) -> Res<()> {
+        let _t = task_trace!();
+        let enable_testing = self.node_context.enable_testing();
+        let mut connects = vec![];
+        for node_id in node_ids {
+            if self.node_context.get_endpoint(*node_id).await.is_ok() {
+                continue;
+            }
+            connects.push(Self::connect_resolved(&self.node_context, *node_id, self.handle.clone(), enable_testing));
+        }
+        for r in join_all(connects).await {
+            r?;
+        }
+        Ok(())
+    }
+
+    // the server side options, it must be set before the node serve
+    pub fn set_opt_node(&self, opt_node: OptNode) {
+        self.node_context.set_opt_node(opt_node)
+    }
+
+    pub fn opt_node(&self) -> OptNode {
+        self.node_context.opt_node()
+    }
+
+    // the addresses bound by the serves of the node, in the order of the binds, with the port
+    // chosen by the system for a served port 0, the requested address for a StreamTransport
+    pub fn listen_addresses(&self) -> Vec<SocketAddr> {
+        self.node_context.listen_addresses()
+    }
+
+    // serve the listen address of the OptNode, the node must be running
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn serve(&self, opt: ESServeOpt) -> Res<()> {
+        let _t = task_trace!();
+        let address = match self.node_context.opt_node().listen_address() {
+            Some(a) => { a }
+            None => { return Err(net_error::invalid_option("the listen address of the node is not set")); }
+        };
+        self.default_event_sink().serve(address, opt).await
+    }
+
+    // record the messages sent and received by the endpoints created after, None to disable it
+    pub fn set_record_sink(&self, opt_record_sink: Option<Arc<dyn RecordSink>>) {
+        self.node_context.set_record_sink(opt_record_sink)
+    }
+
+    // the fault injection rules of this node, None if testing is not enabled
+    pub fn test_controller(&self) -> Option<Arc<TestController<M>>> {
+        if self.node_context.enable_testing() {
+            Some(self.node_context.test_controller())
+        } else {
+            None
+        }
+    }
+
     pub fn default_event_sink(&self) -> Arc<dyn EventSinkAsync<M>> {
         Arc::new(self.node_event_sink())
     }
+
+    // a handle of the default event channel to clone into the tasks connecting and sending,
+    // see `EventSink`
+    pub fn event_sink(&self) -> EventSink<M> {
+        EventSink::new(self.node_event_sink())
+    }
     pub fn default_event_sink_sync(&self) -> Arc<dyn EventSinkSync<M>> {
         Arc::new(self.node_event_sink())
     }
@@ -123,6 +271,21 @@ Node<
         Arc::new(self.node_event_sink())
     }
 
+    // wait until the node was stopped, and every task spawned by the node has completed,
+    // HandleEvent::on_stop is invoked before it returns
+    // it must not be awaited in a task cancelled by the stop notifier of this node
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn join(&self) {
+        let _t = task_trace!();
+        let notifier = self.node_context.stop_notify();
+        notifier.notified().await;
+        notifier.wait_all_terminated().await;
+        if self.node_context.enter_on_stop() {
+            self.handle.on_stop().await;
+        }
+        trace!("node {} joined", self.node_context.name());
+    }
+
     pub fn run_local(&self, local_set: &LocalSet) {
         trace!("run local {}", self._node_id);
         self.run_once.call_once(|| {
@@ -153,9 +316,22 @@ Node<
         local_set.spawn_local(async move {
             spawn_local_task(notify, task_name.as_str(), f)
         });
+
+        // invoke on_stop when the node was stopped by the notifier, not by an event, it is not
+        // spawned with the notifier, which would cancel it
+        let h = self.handle.clone();
+        let n = self.node_context.clone();
+        local_set.spawn_local(async move {
+            let notifier = n.stop_notify();
+            notifier.notified().await;
+            notifier.wait_all_terminated().await;
+            if n.enter_on_stop() {
+                h.on_stop().await;
+            }
+        });
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn run_main_loop(
         name: String,
         node: Arc<NodeContext<M>>,
@@ -198,7 +374,7 @@ Node<
         trace!("{} {} end main loop", node.name(), name);
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_event(
         node: Arc<NodeContext<M>>,
         event: NetEvent<M>,
@@ -211,7 +387,9 @@ Node<
                 node_id,
                 return_endpoint,
                 address,
+                opt_ep,
                 opt_sender,
+                opt_completion,
             } => {
                 let id = node.name().clone();
                 trace!("node {}: handle event: connect {}", id, node_id);
@@ -220,10 +398,12 @@ Node<
                     return_endpoint,
                     node_id,
                     address,
+                    opt_ep,
                     handle,
                     opt_sender,
+                    opt_completion,
                     enable_testing,
-                );
+                )?;
                 trace!("node {}: handle event:connect {} done", id, node_id);
             }
             NetEvent::NetListen(address, opt_s) => {
@@ -246,13 +426,18 @@ Node<
                     false,
                     message,
                     result,
+                    handle,
+                    enable_testing,
                 ).await?;
             }
             NetEvent::Stop(opt_s) => {
+                net_debug!(nid = node.node_id(), "node stop");
                 let stop_notify = node.stop_notify();
                 let _ = spawn_local_task(stop_notify, "stop and notify", async move {
                     node.stop_and_notify().await;
-                    handle.on_stop().await;
+                    if node.enter_on_stop() {
+                        handle.on_stop().await;
+                    }
                     Self::handle_opt_send_result(None, Some(Ok(())), opt_s);
                 })?;
                 return Err(ET::EOF);
@@ -270,7 +455,7 @@ Node<
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_send_message(
         node: Arc<NodeContext<M>>,
         node_id: NID,
@@ -280,6 +465,8 @@ Node<
             Res<Option<Arc<dyn EndpointSync<M>>>>,
             Res<Option<Arc<dyn EndpointAsync<M>>>>
         >,
+        handle: Arc<H>,
+        enable_testing: bool,
     ) -> Res<()> {
         let _t = task_trace!();
         let _m = message.clone();
@@ -289,6 +476,26 @@ Node<
                 e.send(message).await?;
                 Ok(e)
             }
+            Err(ET::NoSuchElement) if node.resolver().is_some() => {
+                // the peer is connected by a task, the main loop is not blocked by the connect
+                let task_name = format!("{} send to resolved {}", node.name(), node_id);
+                let n = node.clone();
+                let send_resolved = async move {
+                    let ep_result = match Self::connect_resolved(&n, node_id, handle, enable_testing).await {
+                        Ok(e) => {
+                            match e.send(message).await {
+                                Ok(()) => { Ok(e) }
+                                Err(e) => { Err(e) }
+                            }
+                        }
+                        Err(e) => { Err(e) }
+                    };
+                    let (s_r, a_r) = Self::handle_result_endpoint(&n, return_endpoint, ep_result, &result_sender);
+                    Self::handle_opt_send_result(s_r, a_r, result_sender);
+                };
+                spawn_local_task(node.stop_notify(), task_name.as_str(), send_resolved)?;
+                return Ok(());
+            }
             Err(e) => {
                 Err(e)
             }
@@ -298,7 +505,7 @@ Node<
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn handle_new_event_channel(
         name: String,
         node: Arc<NodeContext<M>>,
@@ -322,19 +529,21 @@ Node<
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     fn handle_event_connect(
         node: Arc<NodeContext<M>>,
         return_endpoint: bool,
         node_id: NID,
         address: SocketAddr,
+        opt_ep: OptEP,
         handle: Arc<H>,
         opt_sender: ResultSenderType<
             Res<Option<Arc<dyn EndpointSync<M>>>>,
             Res<Option<Arc<dyn EndpointAsync<M>>>>
         >,
+        opt_completion: Option<ConnectCompletion<M>>,
         enable_testing: bool,
-    ) {
+    ) -> Res<()> {
         let _t = task_trace!();
         let node_name = node.name().clone();
         let notify = node.stop_notify();
@@ -344,7 +553,8 @@ Node<
         let on_connected = async move {
             Self::task_handle_connected(
                 node, return_endpoint, node_id,
-                address, handle, opt_sender,
+                address, opt_ep, handle, opt_sender,
+                opt_completion,
                 enable_testing,
             ).await;
             trace!("on connected done {}", task_name2);
@@ -353,46 +563,56 @@ Node<
             notify,
             task_name.as_str(),
             on_connected,
-        ).unwrap();
+        )?;
+        Ok(())
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(
+        name = "connect", level = "debug", skip_all,
+        fields(nid = node.node_id(), peer = node_id, addr = %address)
+    ))]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn task_handle_connected(
         node: Arc<NodeContext<M>>,
         return_endpoint: bool,
         node_id: NID,
         address: SocketAddr,
+        opt_ep: OptEP,
         handle: Arc<H>,
         opt_sender: ResultSenderType<
             Res<Option<Arc<dyn EndpointSync<M>>>>,
             Res<Option<Arc<dyn EndpointAsync<M>>>>
         >,
+        opt_completion: Option<ConnectCompletion<M>>,
         enable_testing: bool,
     ) {
         let _t = task_trace!();
         trace!("{} task handle connect to {} {}", node.name(), node_id, address.to_string());
-        let r_connect = TcpStream::connect(address).await;
+        handle.on_connect_attempt(address, opt_ep.attempt(), opt_ep.last_error()).await;
+        let opt = opt_ep
+            .enable_dtm_test(enable_testing)
+            .set_record_sink(node.record_sink())
+            .set_metrics(Some(node.metrics()))
+            .set_dedup_sessions(Some(node.dedup_sessions()))
+            .set_advertised_name(node.advertised_name());
+        let r_connect = Self::connect_endpoint(&node, node_id, address, opt, &handle).await;
+        if r_connect.is_err() {
+            node.metrics().add_connect_failure();
+        }
         trace!("{} task handle connect done, to {} {} ", node.name(), node_id, address.to_string());
+        net_debug!(ok = r_connect.is_ok(), "connect done");
 
         let result_endpoint = {
-            match res_io(r_connect) {
-                Ok(s) => {
-                    let r_addr = s.peer_addr();
-                    match res_io(r_addr) {
-                        Ok(addr) => {
-                            let opt = OptEP::new().enable_dtm_test(enable_testing);
-                            let ep: Arc<dyn EndpointAsync<M>> = Arc::new(EndpointAsyncImpl::new(s, addr, opt));
-                            if !return_endpoint {
-                                let r = node.add_endpoint(node_id, ep.clone()).await;
-                                match r {
-                                    Ok(()) => { Ok(ep) }
-                                    Err(e) => { Err(e) }
-                                }
-                            } else {
-                                Ok(ep)
-                            }
+            match r_connect {
+                Ok(ep) => {
+                    if !return_endpoint {
+                        let r = node.add_endpoint(node_id, ep.clone()).await;
+                        match r {
+                            Ok(()) => { Ok(ep) }
+                            Err(e) => { Err(e) }
                         }
-                        Err(_e) => { Err(_e) }
+                    } else {
+                        Ok(ep)
                     }
                 }
                 Err(e) => {
@@ -400,20 +620,139 @@ Node<
                 }
             }
         };
-        match handle.on_connected(
-            address,
-            result_endpoint.clone()).await {
-            Ok(_) => {}
-            Err(e) => {
-                handle.on_error(e).await;
-            }
-        };
+        // the handler is not told while draining
+        if !node.is_draining() {
+            match handle.on_connected(
+                address,
+                result_endpoint.clone()).await {
+                Ok(_) => {}
+                Err(e) => {
+                    handle.on_error(e).await;
+                }
+            };
+        }
+        if let Some(completion) = opt_completion {
+            completion.complete(result_endpoint.clone());
+        }
         let (s_r, a_r) = Self::handle_result_endpoint(&node, return_endpoint, result_endpoint, &opt_sender);
         Self::handle_opt_send_result(s_r, a_r, opt_sender);
         trace!("{} task handle connect done, on connected, to {} {} ", node.name(), node_id, address.to_string());
     }
 
-    #[async_backtrace::framed]
+    // connect to the addresses of the peer given by the resolver in turn, the endpoint of the
+    // first one connected is registered for the peer, the error of the last one is returned
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_resolved(
+        node: &Arc<NodeContext<M>>,
+        node_id: NID,
+        handle: Arc<H>,
+        enable_testing: bool,
+    ) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        let addresses = node.resolve(node_id).await?;
+        let mut opt_last_error = None;
+        for (i, address) in addresses.into_iter().enumerate() {
+            let opt_ep = ESConnectOption::default()
+                .set_attempt(i as u64 + 1, opt_last_error.clone())
+                .opt_ep();
+            let (s, r) = oneshot::channel();
+            Self::task_handle_connected(
+                node.clone(), false, node_id,
+                address, opt_ep, handle.clone(), ResultSenderType::SendNone,
+                Some(ConnectCompletion::new(s)),
+                enable_testing,
+            ).await;
+            match r.await {
+                Ok(Ok(ep)) => { return Ok(ep); }
+                Ok(Err(e)) => { opt_last_error = Some(e); }
+                Err(_) => { return Err(ET::EOF); }
+            }
+        }
+        match opt_last_error {
+            Some(e) => { Err(e) }
+            None => { Err(net_error::unresolved(node_id, "no address")) }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn connect_endpoint(
+        node: &Arc<NodeContext<M>>,
+        node_id: NID,
+        address: SocketAddr,
+        opt_ep: OptEP,
+        handle: &Arc<H>,
+    ) -> Res<Arc<dyn EndpointAsync<M>>> {
+        let _t = task_trace!();
+        node.check_partition(node_id)?;
+        if node.is_draining() {
+            return Err(net_error::shutting_down());
+        }
+        #[cfg(feature = "udp")]
+        if node.transport() == Transport::Udp {
+            let ep = EndpointUdp::connect(address, &opt_ep).await?;
+            trace!("connected {}, outbound, udp", address.to_string());
+            let ep = node.fault_endpoint(Arc::new(ep));
+            return Ok(Self::deliver_endpoint(node, ep, handle));
+        }
+        let (s, addr) = connect(address, node.transport(), node.stream_transport(), opt_ep.is_nodelay(), opt_ep.proxy()).await?;
+        trace!("connected {}, outbound", addr.to_string());
+        let ep_impl = EndpointAsyncImpl::new(s, addr, opt_ep.set_clock(node.clock()), node.stop_notify());
+        node.register_endpoint(&ep_impl);
+        Self::watch_endpoint_reader(node, node_id, addr, ep_impl.reader_state(), handle.clone());
+        let ep = node.fault_endpoint(Arc::new(ep_impl));
+        Ok(Self::deliver_endpoint(node, ep, handle))
+    }
+
+    // the endpoint given to the handler and the user, its messages are pushed to
+    // `HandleEvent::on_message` by a node of Delivery::Push
+    fn deliver_endpoint(
+        node: &NodeContext<M>,
+        endpoint: Arc<dyn EndpointAsync<M>>,
+        handle: &Arc<H>,
+    ) -> Arc<dyn EndpointAsync<M>> {
+        let ep = match node.opt_node().delivery() {
+            Delivery::Pull => { endpoint }
+            Delivery::Push => {
+                let concurrency = node.opt_node().handler_concurrency();
+                EndpointPush::start(endpoint, handle.clone(), concurrency, node.stop_notify())
+            }
+        };
+        node.register_named_endpoint(&ep);
+        ep
+    }
+
+    // report the error which stopped the reader task of an outbound endpoint, such as an idle
+    // timeout, a clean close by the peer is not an error, and drop it from the registry
+    fn watch_endpoint_reader(
+        node: &Arc<NodeContext<M>>,
+        node_id: NID,
+        address: SocketAddr,
+        reader_state: Arc<ReaderState>,
+        handle: Arc<H>,
+    ) {
+        let n = node.clone();
+        let watch = async move {
+            let reason = reader_state.wait_stopped().await;
+            n.remove_closed_endpoints(node_id).await;
+            match &reason {
+                ET::EOF => {
+                    trace!("endpoint {} reader stopped, EOF", address.to_string());
+                }
+                e => {
+                    trace!("endpoint {} reader stopped, {}", address.to_string(), e.to_string());
+                    handle.on_error(e.clone()).await;
+                }
+            }
+            handle.on_disconnected(address, reason).await;
+        };
+        let _ = spawn_local_task(
+            node.stop_notify(),
+            format!("{} watch endpoint {}", node.name(), address.to_string()).as_str(),
+            watch,
+        );
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     fn handle_event_listen_and_accept(
         node: Arc<NodeContext<M>>,
         address: SocketAddr,
@@ -430,15 +769,29 @@ Node<
         let notify = node.stop_notify();
         let future_accept_first = async move {
             trace!("bind address {}", address.to_string());
-            let r_bind = TcpListener::bind(address.to_string()).await;
-            let listener = match res_io(r_bind) {
+            #[cfg(feature = "udp")]
+            if node.transport() == Transport::Udp {
+                Self::serve_udp(node, address, h, opt_sender, enable_testing).await;
+                return;
+            }
+            let opt_node = node.opt_node();
+            let r_bind = Listener::bind(
+                address,
+                node.transport(),
+                node.stream_transport(),
+                opt_node.backlog(),
+                opt_node.reuse_address(),
+                opt_node.dual_stack()).await;
+            let listener = match r_bind {
                 Ok(l) => {
+                    node.add_listen_address(l.local_address().unwrap_or(address));
                     Self::handle_opt_send_result(Some(Ok(None)), Some(Ok(None)), opt_sender);
                     l
                 }
                 Err(e) => {
                     h.on_error(e.clone()).await;
-                    Self::handle_opt_send_result(Some(Ok(None)), Some(Ok(None)), opt_sender);
+                    // the serve fails with the bind error, such as the address in use
+                    Self::handle_opt_send_result(Some(Err(e.clone())), Some(Err(e)), opt_sender);
                     return;
                 }
             };
@@ -463,22 +816,90 @@ Node<
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    // the bound socket is the only inbound endpoint, for all the peers
+    #[cfg(feature = "udp")]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn serve_udp(
+        node: Arc<NodeContext<M>>,
+        address: SocketAddr,
+        handle: Arc<H>,
+        opt_sender: ResultSenderType<
+            Res<Option<Arc<dyn EndpointSync<M>>>>,
+            Res<Option<Arc<dyn EndpointAsync<M>>>>
+        >,
+        enable_testing: bool,
+    ) {
+        let _t = task_trace!();
+        let opt = OptEP::default()
+            .enable_dtm_test(enable_testing)
+            .set_inbound(true);
+        let r_bind = EndpointUdp::bind(address, &opt).await;
+        match r_bind {
+            Ok(ep) => {
+                trace!("bind udp {}, inbound", address.to_string());
+                node.add_listen_address(EndpointAsync::<M>::remote_address(&ep));
+                Self::handle_opt_send_result(Some(Ok(None)), Some(Ok(None)), opt_sender);
+                let ep = node.fault_endpoint(Arc::new(ep));
+                let ep = Self::deliver_endpoint(&node, ep, &handle);
+                if let Err(e) = handle.on_accepted(ep).await {
+                    handle.on_error(e).await;
+                }
+            }
+            Err(e) => {
+                handle.on_error(e.clone()).await;
+                // the serve fails with the bind error, as the one of a stream listener
+                Self::handle_opt_send_result(Some(Err(e.clone())), Some(Err(e)), opt_sender);
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn after_accept_connection(
         node: Arc<NodeContext<M>>,
-        listener: TcpListener,
+        listener: Listener,
         handle: Arc<H>,
-        socket: TcpStream,
+        socket: NetStream,
         addr: SocketAddr,
         enable_testing: bool,
     ) -> Res<()> {
         let _t = task_trace!();
-        trace!("accept new {}", addr.to_string());
-        let ep = Arc::new(EndpointAsyncImpl::new(
+        trace!("accept new {}, inbound", addr.to_string());
+        net_debug!(nid = node.node_id(), addr = %addr, "accept connection");
+        if node.is_draining() {
+            // drop the connection, and accept no more
+            trace!("refuse {} while draining", addr.to_string());
+            return Ok(());
+        }
+        node.metrics().add_accepted();
+        let opt_node = node.opt_node();
+        let ep_impl = EndpointAsyncImpl::new(
             socket,
             addr,
-            OptEP::default().enable_dtm_test(enable_testing),
-        ));
+            OptEP::default()
+                .set_write_batch(opt_node.write_batch_max(), opt_node.write_batch_bytes())
+                .set_send_buffer_pool(opt_node.send_buffer_pool())
+                .set_overflow_policy(opt_node.overflow_policy())
+                .enable_dtm_test(enable_testing)
+                .set_inbound(true)
+                .set_rate_limit(opt_node.rate_limit())
+                .set_flow_window(opt_node.flow_window())
+                .enable_checksum(opt_node.checksum())
+                .enable_negotiation(opt_node.negotiation())
+                .set_preamble(opt_node.preamble())
+                .set_max_message_size(opt_node.max_message_size())
+                .set_fragment_size(opt_node.fragment_size())
+                .set_reassembly_limit(opt_node.max_reassemblies(), opt_node.max_reassembly_bytes())
+                .set_record_sink(node.record_sink())
+                .set_metrics(Some(node.metrics()))
+                .set_dedup_sessions(Some(node.dedup_sessions()))
+                .set_advertised_name(node.advertised_name())
+                .set_clock(node.clock()),
+            node.stop_notify(),
+        );
+        node.register_endpoint(&ep_impl);
+        Self::watch_inbound_connection(&node, addr, ep_impl.reader_state(), handle.clone());
+        let ep = node.fault_endpoint(Arc::new(ep_impl));
+        let ep = Self::deliver_endpoint(&node, ep, &handle);
         let on_accepted = {
             let h = handle.clone();
             async move {
@@ -533,16 +954,45 @@ Node<
         Ok(())
     }
 
-    #[async_backtrace::framed]
+    // an inbound connection is live until its reader task stopped, a checksum or a preamble
+    // mismatch, or rejected fragments, are reported by `on_error` too
+    fn watch_inbound_connection(
+        node: &Arc<NodeContext<M>>,
+        address: SocketAddr,
+        reader_state: Arc<ReaderState>,
+        handle: Arc<H>,
+    ) {
+        let n = node.clone();
+        let _ = spawn_local_task(node.stop_notify(), "watch inbound connection", async move {
+            let reason = reader_state.wait_stopped().await;
+            n.exit_inbound_connection();
+            // a corrupted stream, or a peer of another protocol, is an error of the peer or of
+            // the path, not a mere disconnect
+            if net_error::is_checksum_mismatch(&reason) || net_error::is_bad_protocol(&reason)
+                || net_error::is_version_mismatch(&reason) || net_error::is_fragment_rejected(&reason)
+                || net_error::is_dedup_rejected(&reason) {
+                handle.on_error(reason.clone()).await;
+            }
+            handle.on_disconnected(address, reason).await;
+        });
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     async fn accept_new_connection(
         node: Arc<NodeContext<M>>,
-        listener: TcpListener,
+        listener: Listener,
         handle: Arc<H>,
         enable_testing: bool,
     ) -> Res<()> {
         let _t = task_trace!();
-        let r = listener.accept().await;
-        let (socket, addr) = res_io(r)?;
+        let mut listener = listener;
+        let (socket, addr) = loop {
+            let (socket, addr) = listener.accept().await?;
+            if node.enter_inbound_connection() {
+                break (socket, addr);
+            }
+            trace!("close {}, the inbound connections reach the limit", addr.to_string());
+        };
         Self::after_accept_connection(
             node,
             listener,
@@ -553,7 +1003,7 @@ Node<
         ).await
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     fn handle_opt_send_result<S, A>(
         opt_ep_sync: Option<S>,
         opt_ep_async: Option<A>,
@@ -588,7 +1038,7 @@ Node<
         }
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     fn handle_result_endpoint(
         node: &NodeContext<M>,
         return_endpoint: bool,
@@ -641,3 +1091,398 @@ Node<
         NodeSender::new(ch.name().clone(), ch.sender().clone())
     }
 }
+
+// The subset of a Node which does not depend on its handler, such as the one of a Client.
+#[derive(Clone)]
+pub struct NodeHandle<M: MsgTrait + 'static> {
+    node_context: Arc<NodeContext<M>>,
+}
+
+impl<M: MsgTrait + 'static> NodeHandle<M> {
+    pub fn node_id(&self) -> NID {
+        self.node_context.node_id()
+    }
+
+    pub fn name(&self) -> String {
+        self.node_context.name().clone()
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.node_context.metrics().snapshot()
+    }
+
+    // the listen address of the OptNode, None if it does not serve by `Node::serve`
+    pub fn listen_address(&self) -> Option<SocketAddr> {
+        self.node_context.opt_node().listen_address()
+    }
+
+    // see `Node::listen_addresses`
+    pub fn listen_addresses(&self) -> Vec<SocketAddr> {
+        self.node_context.listen_addresses()
+    }
+
+    // see `Node::find_by_name`
+    pub fn find_by_name(&self, name: &str) -> Vec<Arc<dyn EndpointAsync<M>>> {
+        self.node_context.find_by_name(name)
+    }
+
+    pub fn stop_notify(&self) -> Notifier {
+        self.node_context.stop_notify()
+    }
+
+    pub fn default_event_sink(&self) -> Arc<dyn EventSinkAsync<M>> {
+        let ch = self.node_context.default_event_channel();
+        Arc::new(NodeSender::new(ch.name().clone(), ch.sender().clone()))
+    }
+
+    // see `Node::event_sink`
+    pub fn event_sink(&self) -> EventSink<M> {
+        let ch = self.node_context.default_event_channel();
+        EventSink::new(NodeSender::new(ch.name().clone(), ch.sender().clone()))
+    }
+
+    pub fn new_event_channel(&self, name: String) -> Res<Arc<dyn EventSinkAsync<M>>> {
+        let r = self.node_context.new_event_channel(name)?;
+        Ok(r)
+    }
+
+    // stop the node, and wait until the stop was handled, the messages queued by the endpoints
+    // are dropped, and reported, see `StopReport`
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn shutdown(&self) -> Res<StopReport> {
+        let _t = task_trace!();
+        let report = self.node_context.abort_endpoints();
+        self.default_event_sink().stop(ESStopOpt::default()).await?;
+        Ok(report)
+    }
+
+    // stop the node as `shutdown`, after draining the endpoints for StopMode::Drain, the
+    // messages not written within the duration of the drain are reported as dropped
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn shutdown_with(&self, mode: StopMode) -> Res<StopReport> {
+        let _t = task_trace!();
+        if let StopMode::Drain(duration) = mode {
+            self.node_context.drain(duration).await;
+        }
+        self.shutdown().await
+    }
+}
+
+// Build a Node, the node id is required, the name defaults to "node_<node id>" and the notifier
+// to a new one. The options are validated when building, the error names the offending field.
+pub struct NodeBuilder {
+    opt_node_id: Option<NID>,
+    opt_name: Option<String>,
+    enable_testing: bool,
+    opt_notifier: Option<Notifier>,
+    listen_address: String,
+    backlog: u32,
+    reuse_address: bool,
+    opt_dual_stack: Option<bool>,
+    max_connections: u64,
+    write_batch_max: usize,
+    write_batch_bytes: usize,
+    send_buffer_pool: usize,
+    overflow_policy: OverflowPolicy,
+    transport: Transport,
+    opt_stream_transport: Option<Arc<dyn StreamTransport>>,
+    delivery: Delivery,
+    handler_concurrency: usize,
+    advertise_name: bool,
+    opt_rate_limit: Option<RateLimit>,
+    flow_window: u32,
+    checksum: bool,
+    negotiation: bool,
+    opt_preamble: Option<u8>,
+    opt_max_message_size: Option<usize>,
+    fragment_size: usize,
+    max_reassemblies: usize,
+    max_reassembly_bytes: usize,
+    opt_resolver: Option<Arc<dyn NodeAddrResolver>>,
+    opt_clock: Option<Arc<dyn Clock>>,
+}
+
+impl NodeBuilder {
+    pub fn new() -> Self {
+        Self {
+            opt_node_id: None,
+            opt_name: None,
+            enable_testing: false,
+            opt_notifier: None,
+            listen_address: String::new(),
+            backlog: DEFAULT_BACKLOG,
+            reuse_address: true,
+            opt_dual_stack: None,
+            max_connections: 0,
+            write_batch_max: DEFAULT_WRITE_BATCH_MAX,
+            write_batch_bytes: DEFAULT_WRITE_BATCH_BYTES,
+            send_buffer_pool: DEFAULT_SEND_BUFFER_POOL,
+            overflow_policy: OverflowPolicy::default(),
+            transport: Transport::default(),
+            opt_stream_transport: None,
+            delivery: Delivery::default(),
+            handler_concurrency: DEFAULT_HANDLER_CONCURRENCY,
+            advertise_name: false,
+            opt_rate_limit: None,
+            flow_window: 0,
+            checksum: false,
+            negotiation: false,
+            opt_preamble: None,
+            opt_max_message_size: None,
+            fragment_size: 0,
+            max_reassemblies: DEFAULT_MAX_REASSEMBLIES,
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+            opt_resolver: None,
+            opt_clock: None,
+        }
+    }
+
+    pub fn set_node_id(self, node_id: NID) -> Self {
+        let mut s = self;
+        s.opt_node_id = Some(node_id);
+        s
+    }
+
+    pub fn set_name(self, name: String) -> Self {
+        let mut s = self;
+        s.opt_name = Some(name);
+        s
+    }
+
+    pub fn enable_testing(self, enable: bool) -> Self {
+        let mut s = self;
+        s.enable_testing = enable;
+        s
+    }
+
+    pub fn set_notifier(self, notifier: Notifier) -> Self {
+        let mut s = self;
+        s.opt_notifier = Some(notifier);
+        s
+    }
+
+    // empty for a node which does not serve
+    pub fn set_listen_address(self, address: String) -> Self {
+        let mut s = self;
+        s.listen_address = address;
+        s
+    }
+
+    pub fn set_backlog(self, backlog: u32) -> Self {
+        let mut s = self;
+        s.backlog = backlog;
+        s
+    }
+
+    // see `OptNode::set_reuse_address`
+    pub fn set_reuse_address(self, reuse_address: bool) -> Self {
+        let mut s = self;
+        s.reuse_address = reuse_address;
+        s
+    }
+
+    // see `OptNode::set_dual_stack`
+    pub fn set_dual_stack(self, dual_stack: bool) -> Self {
+        let mut s = self;
+        s.opt_dual_stack = Some(dual_stack);
+        s
+    }
+
+    pub fn set_max_connections(self, max_connections: u64) -> Self {
+        let mut s = self;
+        s.max_connections = max_connections;
+        s
+    }
+
+    // the write batch of the accepted endpoints, see `ESConnectOption::set_write_batch`
+    pub fn set_write_batch(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.write_batch_max = max;
+        s.write_batch_bytes = bytes;
+        s
+    }
+
+    // see `OptNode::set_send_buffer_pool`
+    pub fn set_send_buffer_pool(self, buffers: usize) -> Self {
+        let mut s = self;
+        s.send_buffer_pool = buffers;
+        s
+    }
+
+    // see `OptNode::set_overflow_policy`
+    pub fn set_overflow_policy(self, policy: OverflowPolicy) -> Self {
+        let mut s = self;
+        s.overflow_policy = policy;
+        s
+    }
+
+    pub fn set_transport(self, transport: Transport) -> Self {
+        let mut s = self;
+        s.transport = transport;
+        s
+    }
+
+    // see `Node::set_stream_transport`
+    pub fn set_stream_transport(self, stream_transport: Arc<dyn StreamTransport>) -> Self {
+        let mut s = self;
+        s.opt_stream_transport = Some(stream_transport);
+        s.transport = Transport::Custom;
+        s
+    }
+
+    // Delivery::Push calls `HandleEvent::on_message` for the incoming messages, see `Delivery`
+    pub fn set_delivery(self, delivery: Delivery) -> Self {
+        let mut s = self;
+        s.delivery = delivery;
+        s
+    }
+
+    // see `OptNode::set_handler_concurrency`
+    pub fn set_handler_concurrency(self, concurrency: usize) -> Self {
+        let mut s = self;
+        s.handler_concurrency = concurrency;
+        s
+    }
+
+    // see `OptNode::enable_advertise_name`
+    pub fn enable_advertise_name(self, advertise_name: bool) -> Self {
+        let mut s = self;
+        s.advertise_name = advertise_name;
+        s
+    }
+
+    // see `OptNode::set_rate_limit`
+    pub fn set_rate_limit(self, rate_limit: RateLimit) -> Self {
+        let mut s = self;
+        s.opt_rate_limit = Some(rate_limit);
+        s
+    }
+
+    // see `OptNode::set_flow_window`
+    pub fn set_flow_window(self, window: u32) -> Self {
+        let mut s = self;
+        s.flow_window = window;
+        s
+    }
+
+    // see `OptNode::enable_checksum`
+    pub fn enable_checksum(self, checksum: bool) -> Self {
+        let mut s = self;
+        s.checksum = checksum;
+        s
+    }
+
+    // see `OptNode::enable_negotiation`
+    pub fn enable_negotiation(self, negotiation: bool) -> Self {
+        let mut s = self;
+        s.negotiation = negotiation;
+        s
+    }
+
+    // see `OptNode::set_preamble`
+    pub fn set_preamble(self, version: u8) -> Self {
+        let mut s = self;
+        s.opt_preamble = Some(version);
+        s
+    }
+
+    // see `OptNode::set_max_message_size`
+    pub fn set_max_message_size(self, max_message_size: usize) -> Self {
+        let mut s = self;
+        s.opt_max_message_size = Some(max_message_size);
+        s
+    }
+
+    // see `OptNode::set_fragment_size`
+    pub fn set_fragment_size(self, size: usize) -> Self {
+        let mut s = self;
+        s.fragment_size = size;
+        s
+    }
+
+    // see `OptNode::set_reassembly_limit`
+    pub fn set_reassembly_limit(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.max_reassemblies = max;
+        s.max_reassembly_bytes = bytes;
+        s
+    }
+
+    // see `Node::set_resolver`
+    pub fn set_resolver(self, resolver: Arc<dyn NodeAddrResolver>) -> Self {
+        let mut s = self;
+        s.opt_resolver = Some(resolver);
+        s
+    }
+
+    // see `Node::set_clock`
+    pub fn set_clock(self, clock: Arc<dyn Clock>) -> Self {
+        let mut s = self;
+        s.opt_clock = Some(clock);
+        s
+    }
+
+    pub fn build<M: MsgTrait + 'static, H: HandleEvent<M> + 'static>(self, handle: H) -> Res<Node<M, H>> {
+        let node_id = match self.opt_node_id {
+            Some(id) => { id }
+            None => { return Err(net_error::invalid_option_of("node_id", "the node id of the node is not set")); }
+        };
+        if self.backlog == 0 {
+            return Err(net_error::invalid_option_of("backlog", "the backlog of the node is 0"));
+        }
+        if self.write_batch_max == 0 {
+            return Err(net_error::invalid_option_of("write_batch_max", "a write batch of 0 frames"));
+        }
+        if self.handler_concurrency == 0 {
+            return Err(net_error::invalid_option_of("handler_concurrency", "no handler of the messages"));
+        }
+        if self.transport == Transport::Custom && self.opt_stream_transport.is_none() {
+            return Err(net_error::invalid_option_of("stream_transport", "Transport::Custom without a StreamTransport"));
+        }
+        let mut opt_node = OptNode::new()
+            .set_backlog(self.backlog)
+            .set_reuse_address(self.reuse_address)
+            .set_dual_stack(self.opt_dual_stack)
+            .set_max_connections(self.max_connections)
+            .set_write_batch(self.write_batch_max, self.write_batch_bytes)
+            .set_send_buffer_pool(self.send_buffer_pool)
+            .set_overflow_policy(self.overflow_policy)
+            .set_delivery(self.delivery)
+            .set_handler_concurrency(self.handler_concurrency)
+            .enable_advertise_name(self.advertise_name)
+            .set_rate_limit(self.opt_rate_limit)
+            .set_flow_window(self.flow_window)
+            .enable_checksum(self.checksum)
+            .enable_negotiation(self.negotiation)
+            .set_preamble(self.opt_preamble)
+            .set_max_message_size(self.opt_max_message_size)
+            .set_fragment_size(self.fragment_size)
+            .set_reassembly_limit(self.max_reassemblies, self.max_reassembly_bytes);
+        if !self.listen_address.is_empty() {
+            let address = match SocketAddr::from_str(self.listen_address.as_str()) {
+                Ok(a) => { a }
+                Err(_) => { return Err(net_error::addr_parse_of("listen_address", self.listen_address.as_str())); }
+            };
+            opt_node = opt_node.set_listen_address(address);
+        }
+        let name = self.opt_name.unwrap_or_else(|| { format!("node_{}", node_id) });
+        let notifier = self.opt_notifier.unwrap_or_default();
+        let node = Node::new(node_id, name, handle, self.enable_testing, notifier)?;
+        node.set_transport(self.transport);
+        if let Some(t) = self.opt_stream_transport {
+            node.set_stream_transport(t);
+        }
+        node.set_resolver(self.opt_resolver);
+        if let Some(clock) = self.opt_clock {
+            node.set_clock(clock);
+        }
+        node.set_opt_node(opt_node);
+        Ok(node)
+    }
+}
+
+impl Default for NodeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
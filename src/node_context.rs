@@ -1,5 +1,7 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use rand::seq::SliceRandom;
 use rand::thread_rng;
@@ -7,14 +9,30 @@ use scupt_util::error_type::ET;
 use scupt_util::message::MsgTrait;
 use scupt_util::node_id::NID;
 use scupt_util::res::Res;
+use std::time::Duration;
+
+use futures::future::join_all;
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, Instrument, trace, trace_span};
 
+use crate::clock::{Clock, default_clock, timeout_of};
+use crate::dedup::DedupSessions;
 use crate::endpoint_async::EndpointAsync;
+use crate::endpoint_async_impl::EndpointAsyncImpl;
+use crate::endpoint_inner::_Endpoint;
+use crate::endpoint_fault::EndpointFault;
 use crate::event::{NetEvent, ResultSenderType};
 use crate::event_channel::EventChannel;
+use crate::net_error;
+use crate::metrics::Metrics;
 use crate::net_handler::NodeSender;
 use crate::notifier::Notifier;
+use crate::opt_close::{EndpointLoss, StopReport};
+use crate::opt_node::OptNode;
+use crate::recorder::RecordSink;
+use crate::resolver::NodeAddrResolver;
+use crate::test_controller::TestController;
+use crate::transport::{StreamTransport, Transport};
 use crate::task_trace;
 
 pub type EventChannelMap<MsgTrait> = HashMap<String, Arc<EventChannel<MsgTrait>>>;
@@ -37,6 +55,29 @@ pub struct NodeContext<M: MsgTrait + 'static> {
     channel_set: Arc<SyncMutex<EventChannelMap<M>>>,
     default_channel: Arc<EventChannel<M>>,
     enable_testing: bool,
+    // HandleEvent::on_stop was invoked
+    on_stop_invoked: AtomicBool,
+    test_controller: Arc<TestController<M>>,
+    transport: SyncMutex<Transport>,
+    opt_stream_transport: SyncMutex<Option<Arc<dyn StreamTransport>>>,
+    opt_record_sink: SyncMutex<Option<Arc<dyn RecordSink>>>,
+    opt_resolver: SyncMutex<Option<Arc<dyn NodeAddrResolver>>>,
+    clock: SyncMutex<Arc<dyn Clock>>,
+    opt_node: SyncMutex<OptNode>,
+    // the number of the live inbound connections
+    inbound_connections: AtomicU64,
+    metrics: Arc<Metrics>,
+    // the receiving state of the sessions announced by the peers, see `ESConnectOption::enable_dedup`
+    dedup_sessions: Arc<DedupSessions>,
+    // the stream endpoints drained by `drain`, the dropped ones are pruned on every register
+    live_endpoints: SyncMutex<Vec<Weak<_Endpoint>>>,
+    // the endpoints given to the handler and the user, looked up by the names their peers
+    // advertised, the dropped ones are pruned on every register
+    named_endpoints: SyncMutex<Vec<Weak<dyn EndpointAsync<M>>>>,
+    // set by `drain`, no more accepts and connects
+    draining: AtomicBool,
+    // the addresses bound by the serves
+    listen_addresses: SyncMutex<Vec<SocketAddr>>,
 }
 
 
@@ -55,6 +96,21 @@ impl<M: MsgTrait + 'static> NodeContext<M> {
             channel_set: Arc::new(SyncMutex::new(map)),
             default_channel,
             enable_testing: testing,
+            on_stop_invoked: AtomicBool::new(false),
+            test_controller: Arc::new(TestController::new()),
+            transport: SyncMutex::new(Transport::default()),
+            opt_stream_transport: SyncMutex::new(None),
+            opt_record_sink: SyncMutex::new(None),
+            opt_resolver: SyncMutex::new(None),
+            clock: SyncMutex::new(default_clock()),
+            opt_node: SyncMutex::new(OptNode::default()),
+            inbound_connections: AtomicU64::new(0),
+            metrics: Arc::new(Metrics::new_of_node(node_id)),
+            dedup_sessions: Arc::new(DedupSessions::new()),
+            live_endpoints: SyncMutex::new(vec![]),
+            named_endpoints: SyncMutex::new(vec![]),
+            draining: AtomicBool::new(false),
+            listen_addresses: SyncMutex::new(vec![]),
         }
     }
 
@@ -69,7 +125,235 @@ impl<M: MsgTrait + 'static> NodeContext<M> {
         self.stop_notify.clone()
     }
 
-    #[async_backtrace::framed]
+    pub fn test_controller(&self) -> Arc<TestController<M>> {
+        self.test_controller.clone()
+    }
+
+    // apply the fault injection rules on the endpoint when testing is enabled
+    pub fn fault_endpoint(&self, endpoint: Arc<dyn EndpointAsync<M>>) -> Arc<dyn EndpointAsync<M>> {
+        if self.enable_testing {
+            self.test_controller.register_endpoint(&endpoint);
+            Arc::new(EndpointFault::new(endpoint, self.test_controller.clone()))
+        } else {
+            endpoint
+        }
+    }
+
+    // a testing node cannot connect to a peer across the partition
+    pub fn check_partition(&self, peer: NID) -> Res<()> {
+        if self.enable_testing && self.test_controller.is_partitioned(self.node_id(), peer) {
+            Err(net_error::partitioned(self.node_id(), peer))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_transport(&self, transport: Transport) {
+        let mut guard = self.transport.lock().unwrap();
+        *guard = transport;
+    }
+
+    pub fn transport(&self) -> Transport {
+        let guard = self.transport.lock().unwrap();
+        *guard
+    }
+
+    pub fn set_stream_transport(&self, stream_transport: Arc<dyn StreamTransport>) {
+        let mut guard = self.opt_stream_transport.lock().unwrap();
+        *guard = Some(stream_transport);
+    }
+
+    pub fn stream_transport(&self) -> Option<Arc<dyn StreamTransport>> {
+        let guard = self.opt_stream_transport.lock().unwrap();
+        guard.clone()
+    }
+
+    pub fn set_record_sink(&self, opt_record_sink: Option<Arc<dyn RecordSink>>) {
+        let mut guard = self.opt_record_sink.lock().unwrap();
+        *guard = opt_record_sink;
+    }
+
+    pub fn record_sink(&self) -> Option<Arc<dyn RecordSink>> {
+        let guard = self.opt_record_sink.lock().unwrap();
+        guard.clone()
+    }
+
+    pub fn set_resolver(&self, opt_resolver: Option<Arc<dyn NodeAddrResolver>>) {
+        let mut guard = self.opt_resolver.lock().unwrap();
+        *guard = opt_resolver;
+    }
+
+    pub fn resolver(&self) -> Option<Arc<dyn NodeAddrResolver>> {
+        let guard = self.opt_resolver.lock().unwrap();
+        guard.clone()
+    }
+
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        let mut guard = self.clock.lock().unwrap();
+        *guard = clock;
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        let guard = self.clock.lock().unwrap();
+        guard.clone()
+    }
+
+    // the addresses of the peer given by the resolver, not empty
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn resolve(&self, node_id: NID) -> Res<Vec<SocketAddr>> {
+        let _t = task_trace!();
+        let resolver = match self.resolver() {
+            Some(r) => { r }
+            None => { return Err(net_error::unresolved(node_id, "no resolver")); }
+        };
+        match resolver.resolve(node_id).await {
+            Ok(addresses) => {
+                if addresses.is_empty() {
+                    Err(net_error::unresolved(node_id, "not known by the resolver"))
+                } else {
+                    Ok(addresses)
+                }
+            }
+            Err(e) => {
+                if net_error::is_unresolved(&e) {
+                    Err(e)
+                } else {
+                    Err(net_error::unresolved(node_id, e.to_string().as_str()))
+                }
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn dedup_sessions(&self) -> Arc<DedupSessions> {
+        self.dedup_sessions.clone()
+    }
+
+    pub fn set_opt_node(&self, opt_node: OptNode) {
+        let mut guard = self.opt_node.lock().unwrap();
+        *guard = opt_node;
+    }
+
+    pub fn opt_node(&self) -> OptNode {
+        let guard = self.opt_node.lock().unwrap();
+        guard.clone()
+    }
+
+    // return false if the inbound connections reach `OptNode::max_connections`
+    pub fn enter_inbound_connection(&self) -> bool {
+        let max = self.opt_node().max_connections();
+        let n = self.inbound_connections.fetch_add(1, Ordering::SeqCst);
+        if max > 0 && n >= max {
+            self.inbound_connections.fetch_sub(1, Ordering::SeqCst);
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn exit_inbound_connection(&self) {
+        self.inbound_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn inbound_connections(&self) -> u64 {
+        self.inbound_connections.load(Ordering::SeqCst)
+    }
+
+    pub fn register_endpoint(&self, endpoint: &EndpointAsyncImpl) {
+        let mut live = self.live_endpoints.lock().unwrap();
+        live.retain(|e| { e.strong_count() > 0 });
+        live.push(endpoint.downgrade());
+    }
+
+    // the name advertised by the endpoints of the node, see `OptNode::enable_advertise_name`
+    pub fn advertised_name(&self) -> Option<String> {
+        if self.opt_node().advertise_name() {
+            Some(self.node_name.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn register_named_endpoint(&self, endpoint: &Arc<dyn EndpointAsync<M>>) {
+        let mut named = self.named_endpoints.lock().unwrap();
+        named.retain(|e| { e.strong_count() > 0 });
+        named.push(Arc::downgrade(endpoint));
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Vec<Arc<dyn EndpointAsync<M>>> {
+        let named = self.named_endpoints.lock().unwrap();
+        named.iter()
+            .filter_map(|e| { e.upgrade() })
+            .filter(|e| { !e.is_closed() && e.peer_name().as_deref() == Some(name) })
+            .collect()
+    }
+
+    pub fn add_listen_address(&self, address: SocketAddr) {
+        self.listen_addresses.lock().unwrap().push(address);
+    }
+
+    pub fn listen_addresses(&self) -> Vec<SocketAddr> {
+        self.listen_addresses.lock().unwrap().clone()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    // refuse the new connections, and wait until the live endpoints wrote their queued frames,
+    // or the duration passed
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn drain(&self, duration: Duration) {
+        let _t = task_trace!();
+        self.draining.store(true, Ordering::SeqCst);
+        let endpoints: Vec<Arc<_Endpoint>> = {
+            let live = self.live_endpoints.lock().unwrap();
+            live.iter().filter_map(|e| { e.upgrade() }).collect()
+        };
+        trace!("drain {} endpoints of {}", endpoints.len(), self.node_name);
+        let drains = endpoints.iter().map(|e| { e.drain() });
+        let clock = self.clock();
+        if timeout_of(clock.as_ref(), duration, join_all(drains)).await.is_none() {
+            debug!("drain of {} timed out", self.node_name);
+        }
+    }
+
+    // drop the messages queued by the live endpoints, the node is stopping right away, see
+    // `StopReport`
+    pub fn abort_endpoints(&self) -> StopReport {
+        let endpoints: Vec<Arc<_Endpoint>> = {
+            let live = self.live_endpoints.lock().unwrap();
+            live.iter().filter_map(|e| { e.upgrade() }).collect()
+        };
+        let mut report = StopReport::default();
+        for e in endpoints {
+            if e.reader_state().is_stopped() {
+                continue;
+            }
+            report.endpoints_closed += 1;
+            let dropped = e.abort_queued();
+            if dropped > 0 {
+                report.losses.push(EndpointLoss {
+                    address: e.remote_address(),
+                    inbound: e.is_inbound(),
+                    dropped,
+                });
+            }
+        }
+        trace!("abort {} endpoints of {}, {} messages lost", report.endpoints_closed, self.node_name,
+            report.messages_lost());
+        report
+    }
+
+    // return true only for the first invocation, HandleEvent::on_stop is invoked once
+    pub fn enter_on_stop(&self) -> bool {
+        !self.on_stop_invoked.swap(true, Ordering::SeqCst)
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn stop_and_notify(&self) {
         let _t = task_trace!();
         let ok = self.stop_notify.task_notify_all();
@@ -78,14 +362,31 @@ impl<M: MsgTrait + 'static> NodeContext<M> {
         }
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn get_endpoint(&self, node_id: NID) -> Res<Arc<dyn EndpointAsync<M>>> {
         let _t = task_trace!();
-        let c = self.mutex_ctx.lock().await;
+        let mut c = self.mutex_ctx.lock().await;
+        c.remove_closed_endpoints(node_id);
         c.get_endpoint(node_id)
     }
 
-    #[async_backtrace::framed]
+    // called when the reader task of an endpoint of the peer stopped
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn remove_closed_endpoints(&self, node_id: NID) {
+        let _t = task_trace!();
+        let mut c = self.mutex_ctx.lock().await;
+        c.remove_closed_endpoints(node_id);
+    }
+
+    // the number of the live registered endpoints
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    pub async fn num_endpoints(&self) -> u64 {
+        let _t = task_trace!();
+        let c = self.mutex_ctx.lock().await;
+        c.num_endpoints()
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn add_endpoint(&self, node_id: NID, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
         let _t = task_trace!();
         let mut c = self.mutex_ctx.lock().await;
@@ -128,7 +429,7 @@ impl<M: MsgTrait + 'static> NodeContext<M> {
         self.enable_testing
     }
 
-    #[async_backtrace::framed]
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
     pub async fn stop(&self) {
         let _t = task_trace!();
         let mut map = self.channel_set.lock().unwrap();
@@ -183,6 +484,10 @@ impl<M: MsgTrait + 'static> _NodeContext<M> {
 
     pub fn add_endpoint(&mut self, node_id: NID, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
         trace!("add endpoint: {}", self.name);
+        // the reader may have stopped before it was added, its watcher has removed nothing
+        if endpoint.is_closed() {
+            return Err(ET::EOF);
+        }
         match self.out_connection_async.get_mut(&node_id) {
             Some(vec) => {
                 vec.push(endpoint);
@@ -193,4 +498,24 @@ impl<M: MsgTrait + 'static> _NodeContext<M> {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn remove_closed_endpoints(&mut self, node_id: NID) {
+        let empty = match self.out_connection_async.get_mut(&node_id) {
+            Some(vec) => {
+                vec.retain(|e| { !e.is_closed() });
+                vec.is_empty()
+            }
+            None => { false }
+        };
+        if empty {
+            trace!("remove the closed endpoints of {}: {}", node_id, self.name);
+            let _ = self.out_connection_async.remove(&node_id);
+        }
+    }
+
+    pub fn num_endpoints(&self) -> u64 {
+        self.out_connection_async.values()
+            .map(|vec| { vec.iter().filter(|e| { !e.is_closed() }).count() as u64 })
+            .sum()
+    }
+}
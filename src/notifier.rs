@@ -1,5 +1,6 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
 
 use tokio::sync::Notify;
 use tracing::trace;
@@ -7,7 +8,12 @@ use tracing::trace;
 // Notifies tasks to wake up.
 // If Notifier::notify_waiters is called, all the task call Notifier::notified would complete, and
 // the following invocation of Notifier::notified, which after Notifier::notify_waiters called,
-// would return immediately
+// would return immediately.
+// The notification is sticky and idempotent, once notified, every wait returns immediately.
+// A child created by Notifier::new_child is notified when its parent is notified, notifying a
+// child does not affect its parent and siblings.
+// The tasks spawned by task::spawn_local_task or task::spawn_task are counted by their cancel
+// notifier and all its ancestors, Notifier::wait_all_terminated waits until the count drops to 0.
 #[derive(Clone)]
 pub struct Notifier {
     name: String,
@@ -17,6 +23,16 @@ pub struct Notifier {
 pub struct NotifyInner {
     stop_notifier: Notify,
     stopped: AtomicBool,
+    children: Mutex<Vec<Weak<NotifyInner>>>,
+    parent: Option<Arc<NotifyInner>>,
+    // the number of running tasks spawned with this notifier or its descendants
+    running: AtomicUsize,
+    terminated: Notify,
+}
+
+// decrease the running task count when the task completes or is dropped
+pub(crate) struct TaskGuard {
+    inner: Arc<NotifyInner>,
 }
 
 impl Default for Notifier {
@@ -29,21 +45,49 @@ impl Notifier {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
-            inner: Arc::new(NotifyInner::new()),
+            inner: Arc::new(NotifyInner::new(None)),
         }
     }
 
     pub fn new_with_name(name: String) -> Self {
         Self {
             name,
-            inner: Arc::new(NotifyInner::new()),
+            inner: Arc::new(NotifyInner::new(None)),
         }
     }
 
+    // create a child scope, if this notifier was already notified, the child starts notified
+    pub fn new_child(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            inner: self.inner.new_child(),
+        }
+    }
+
+    // a cheap probe, does not wait or register anything
     pub fn is_notified(&self) -> bool {
         self.inner.is_notified()
     }
 
+    // return true if notified within the duration
+    pub async fn wait_timeout(&self, duration: Duration) -> bool {
+        let r = tokio::time::timeout(duration, self.notified()).await;
+        r.is_ok()
+    }
+
+    // wait until every task spawned with this notifier or its descendants has completed,
+    // return immediately if there is no running task
+    pub async fn wait_all_terminated(&self) {
+        self.inner.wait_all_terminated().await;
+    }
+
+    pub(crate) fn enter_task(&self) -> TaskGuard {
+        self.inner.enter_task();
+        TaskGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
     pub async fn notified(&self) {
         trace!("notified {}", self.name);
         self.inner.notified().await;
@@ -64,16 +108,70 @@ impl Notifier {
 }
 
 impl NotifyInner {
-    fn new() -> Self {
+    fn new(parent: Option<Arc<NotifyInner>>) -> Self {
         Self {
             stopped: AtomicBool::new(false),
             stop_notifier: Notify::new(),
+            children: Mutex::new(vec![]),
+            parent,
+            running: AtomicUsize::new(0),
+            terminated: Notify::new(),
         }
     }
 
+    fn new_child(self: &Arc<Self>) -> Arc<Self> {
+        let child = Arc::new(Self::new(Some(self.clone())));
+        // test `stopped` while holding the lock, `notify_all` set `stopped` before it visit the
+        // children, so the child is either notified here, or visited by `notify_all`
+        let mut children = self.children.lock().unwrap();
+        if self.stopped.load(Ordering::SeqCst) {
+            let _ = child.notify_all();
+        } else {
+            children.retain(|c| { c.strong_count() > 0 });
+            children.push(Arc::downgrade(&child));
+        }
+        child
+    }
+
     async fn notified(&self) {
+        let notified = self.stop_notifier.notified();
+        tokio::pin!(notified);
+        // register as a waiter before testing `stopped`, a `notify_waiters` invoked between
+        // the test and the await would not be missed
+        notified.as_mut().enable();
         if !self.stopped.load(Ordering::SeqCst) {
-            self.stop_notifier.notified().await;
+            notified.await;
+        }
+    }
+
+    fn enter_task(&self) {
+        let mut opt: Option<&NotifyInner> = Some(self);
+        while let Some(n) = opt {
+            let _ = n.running.fetch_add(1, Ordering::SeqCst);
+            opt = n.parent.as_deref();
+        }
+    }
+
+    fn exit_task(&self) {
+        let mut opt: Option<&NotifyInner> = Some(self);
+        while let Some(n) = opt {
+            let running = n.running.fetch_sub(1, Ordering::SeqCst);
+            if running == 1 {
+                n.terminated.notify_waiters();
+            }
+            opt = n.parent.as_deref();
+        }
+    }
+
+    async fn wait_all_terminated(&self) {
+        loop {
+            let terminated = self.terminated.notified();
+            tokio::pin!(terminated);
+            terminated.as_mut().enable();
+            if self.running.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            terminated.await;
         }
     }
 
@@ -97,7 +195,23 @@ impl NotifyInner {
                 false
             }
         };
-
+        if ret {
+            let children: Vec<Weak<NotifyInner>> = {
+                let mut guard = self.children.lock().unwrap();
+                std::mem::take(&mut *guard)
+            };
+            for c in children {
+                if let Some(child) = c.upgrade() {
+                    let _ = child.notify_all();
+                }
+            }
+        }
         ret
     }
-}
\ No newline at end of file
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.inner.exit_task();
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+// what a call does when `max_inflight` calls are outstanding
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum InflightPolicy {
+    // wait until an outstanding call completed
+    #[default]
+    Wait,
+    // fail with `net_error::too_many_inflight`
+    Error,
+}
+
+// how a `Caller` issues the requests
+pub struct OptCall {
+    max_inflight: usize,
+    inflight_policy: InflightPolicy,
+    opt_timeout: Option<Duration>,
+}
+
+impl OptCall {
+    pub fn new() -> Self {
+        Self {
+            max_inflight: 0,
+            inflight_policy: InflightPolicy::default(),
+            opt_timeout: None,
+        }
+    }
+
+    pub fn max_inflight(&self) -> usize {
+        self.max_inflight
+    }
+
+    pub fn inflight_policy(&self) -> InflightPolicy {
+        self.inflight_policy
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.opt_timeout
+    }
+
+    // the max number of the outstanding calls on the endpoint, 0 for unlimited
+    pub fn set_max_inflight(self, max_inflight: usize) -> Self {
+        let mut s = self;
+        s.max_inflight = max_inflight;
+        s
+    }
+
+    pub fn set_inflight_policy(self, policy: InflightPolicy) -> Self {
+        let mut s = self;
+        s.inflight_policy = policy;
+        s
+    }
+
+    // how long a call waits for the response after the request was sent, None for ever
+    pub fn set_timeout(self, timeout: Duration) -> Self {
+        let mut s = self;
+        s.opt_timeout = Some(timeout);
+        s
+    }
+}
+
+impl Default for OptCall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,87 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// the default time to wait for the peer closing the connection, when draining
+pub const DEFAULT_DRAIN_TIMEOUT_MS: u64 = 5000;
+
+// how `EndpointAsync::close_with` closes an endpoint
+pub struct CloseOption {
+    drain: bool,
+    timeout: Duration,
+}
+
+impl CloseOption {
+    pub fn new() -> Self {
+        Self {
+            drain: false,
+            timeout: Duration::from_millis(DEFAULT_DRAIN_TIMEOUT_MS),
+        }
+    }
+
+    pub fn drain(&self) -> bool {
+        self.drain
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    // write the queued messages, shut down the write direction, and wait for the peer closing
+    // the connection before closing, otherwise close abruptly as `EndpointAsync::close`
+    pub fn enable_drain(self, drain: bool) -> Self {
+        let mut s = self;
+        s.drain = drain;
+        s
+    }
+
+    // how long the drain waits for the peer
+    pub fn set_timeout(self, timeout: Duration) -> Self {
+        let mut s = self;
+        s.timeout = timeout;
+        s
+    }
+}
+
+impl Default for CloseOption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// how `NodeHandle::shutdown_with` stops a node
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StopMode {
+    // write the queued messages of every endpoint and shut down its write direction, waiting
+    // at most the duration for all of them, then stop, the sends and connects in the meantime
+    // fail with `net_error::shutting_down`
+    Drain(Duration),
+    // stop right away, the queued messages are dropped
+    #[default]
+    Abort,
+}
+
+// the messages an endpoint still had queued when the node stopped, they were never written
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EndpointLoss {
+    pub address: SocketAddr,
+    pub inbound: bool,
+    pub dropped: u64,
+}
+
+// What `NodeHandle::shutdown` left behind. The dropped messages are the ones queued for the
+// writer tasks, the ones of a write in progress are not counted, nor the ones in the socket
+// buffers, so it is a lower bound. After a drain which completed, none was dropped.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StopReport {
+    // the live endpoints of the node, inbound and outbound, torn down by the stop
+    pub endpoints_closed: u64,
+    // the endpoints which dropped some messages
+    pub losses: Vec<EndpointLoss>,
+}
+
+impl StopReport {
+    // the messages dropped by all the endpoints
+    pub fn messages_lost(&self) -> u64 {
+        self.losses.iter().map(|l| { l.dropped }).sum()
+    }
+}
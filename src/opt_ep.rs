@@ -1,5 +1,57 @@
+use std::sync::Arc;
+
+use scupt_util::error_type::ET;
+
+use crate::clock::{Clock, default_clock};
+use crate::es_option::{
+    DEFAULT_MAX_REASSEMBLIES,
+    DEFAULT_MAX_REASSEMBLY_BYTES,
+    DEFAULT_RECV_QUEUE_CAPACITY,
+    DEFAULT_SEND_BUFFER_POOL,
+    DEFAULT_SEND_QUEUE_CAPACITY,
+    DEFAULT_WRITE_BATCH_BYTES,
+    DEFAULT_WRITE_BATCH_MAX,
+};
+use crate::dedup::{DedupSessions, SendSession};
+use crate::frame_codec::RawFrameCodec;
+use crate::metrics::Metrics;
+use crate::overflow_policy::OverflowPolicy;
+use crate::proxy::ProxyConfig;
+use crate::rate_limit::RateLimit;
+use crate::recorder::RecordSink;
+
 pub struct OptEP {
     dtm_test: bool,
+    opt_dedup_session: Option<Arc<SendSession>>,
+    opt_dedup_sessions: Option<Arc<DedupSessions>>,
+    inbound: bool,
+    idle_timeout_ms: u64,
+    write_timeout_ms: u64,
+    opt_record_sink: Option<Arc<dyn RecordSink>>,
+    opt_metrics: Option<Arc<Metrics>>,
+    write_batch_max: usize,
+    write_batch_bytes: usize,
+    send_buffer_pool: usize,
+    resend_unsent: bool,
+    nodelay: bool,
+    send_queue_capacity: usize,
+    recv_queue_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    opt_max_message_size: Option<usize>,
+    opt_frame_codec: Option<Arc<dyn RawFrameCodec>>,
+    opt_rate_limit: Option<RateLimit>,
+    opt_proxy: Option<ProxyConfig>,
+    opt_advertised_name: Option<String>,
+    flow_window: u32,
+    checksum: bool,
+    negotiation: bool,
+    opt_preamble: Option<u8>,
+    fragment_size: usize,
+    max_reassemblies: usize,
+    max_reassembly_bytes: usize,
+    clock: Arc<dyn Clock>,
+    attempt: u64,
+    opt_last_error: Option<ET>,
 }
 
 
@@ -7,22 +59,298 @@ impl OptEP {
     pub fn new() -> Self {
         Self {
             dtm_test: false,
+            opt_dedup_session: None,
+            opt_dedup_sessions: None,
+            inbound: false,
+            idle_timeout_ms: 0,
+            write_timeout_ms: 0,
+            opt_record_sink: None,
+            opt_metrics: None,
+            write_batch_max: DEFAULT_WRITE_BATCH_MAX,
+            write_batch_bytes: DEFAULT_WRITE_BATCH_BYTES,
+            send_buffer_pool: DEFAULT_SEND_BUFFER_POOL,
+            resend_unsent: false,
+            nodelay: false,
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
+            recv_queue_capacity: DEFAULT_RECV_QUEUE_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            opt_max_message_size: None,
+            opt_frame_codec: None,
+            opt_rate_limit: None,
+            opt_proxy: None,
+            opt_advertised_name: None,
+            flow_window: 0,
+            checksum: false,
+            negotiation: false,
+            opt_preamble: None,
+            fragment_size: 0,
+            max_reassemblies: DEFAULT_MAX_REASSEMBLIES,
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+            clock: default_clock(),
+            attempt: 1,
+            opt_last_error: None,
         }
     }
 
 
     pub fn is_enable_dtm_test(&self) -> bool { self.dtm_test }
 
+    pub fn dedup_session(&self) -> Option<Arc<SendSession>> { self.opt_dedup_session.clone() }
+
+    pub fn dedup_sessions(&self) -> Option<Arc<DedupSessions>> { self.opt_dedup_sessions.clone() }
+
+    pub fn is_inbound(&self) -> bool { self.inbound }
+
+    pub fn idle_timeout_ms(&self) -> u64 { self.idle_timeout_ms }
+
+    pub fn write_timeout_ms(&self) -> u64 { self.write_timeout_ms }
+
+    pub fn record_sink(&self) -> Option<Arc<dyn RecordSink>> { self.opt_record_sink.clone() }
+
+    pub fn metrics(&self) -> Option<Arc<Metrics>> { self.opt_metrics.clone() }
+
+    pub fn write_batch_max(&self) -> usize { self.write_batch_max }
+
+    pub fn write_batch_bytes(&self) -> usize { self.write_batch_bytes }
+
+    pub fn send_buffer_pool(&self) -> usize { self.send_buffer_pool }
+
+    pub fn is_resend_unsent(&self) -> bool { self.resend_unsent }
+
+    pub fn is_nodelay(&self) -> bool { self.nodelay }
+
+    pub fn send_queue_capacity(&self) -> usize { self.send_queue_capacity }
+
+    pub fn recv_queue_capacity(&self) -> usize { self.recv_queue_capacity }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy { self.overflow_policy }
+
+    pub fn max_message_size(&self) -> Option<usize> { self.opt_max_message_size }
+
+    pub fn frame_codec(&self) -> Option<Arc<dyn RawFrameCodec>> { self.opt_frame_codec.clone() }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> { self.opt_rate_limit }
+
+    pub fn proxy(&self) -> Option<ProxyConfig> { self.opt_proxy.clone() }
+
+    pub fn advertised_name(&self) -> Option<String> { self.opt_advertised_name.clone() }
+
+    pub fn flow_window(&self) -> u32 { self.flow_window }
+
+    pub fn checksum(&self) -> bool { self.checksum }
+
+    pub fn negotiation(&self) -> bool { self.negotiation }
+
+    pub fn preamble(&self) -> Option<u8> { self.opt_preamble }
+
+    pub fn fragment_size(&self) -> usize { self.fragment_size }
+
+    pub fn max_reassemblies(&self) -> usize { self.max_reassemblies }
+
+    pub fn max_reassembly_bytes(&self) -> usize { self.max_reassembly_bytes }
+
+    pub fn clock(&self) -> Arc<dyn Clock> { self.clock.clone() }
+
+    pub fn attempt(&self) -> u64 { self.attempt }
+
+    pub fn last_error(&self) -> Option<ET> { self.opt_last_error.clone() }
 
     pub fn enable_dtm_test(self, dtm_test: bool) -> Self {
         let mut s = self;
         s.dtm_test = dtm_test;
         s
     }
+
+    // number the frames by the session and announce it to the peer, see
+    // `ESConnectOption::enable_dedup`
+    pub fn set_dedup_session(self, opt_session: Option<Arc<SendSession>>) -> Self {
+        let mut s = self;
+        s.opt_dedup_session = opt_session;
+        s
+    }
+
+    // the receiving state of the sessions announced by the peers of the node, a connection
+    // without it keeps its own
+    pub fn set_dedup_sessions(self, opt_sessions: Option<Arc<DedupSessions>>) -> Self {
+        let mut s = self;
+        s.opt_dedup_sessions = opt_sessions;
+        s
+    }
+
+    // the endpoint was accepted by a listener, rather than connected to a remote
+    pub fn set_inbound(self, inbound: bool) -> Self {
+        let mut s = self;
+        s.inbound = inbound;
+        s
+    }
+
+    // record the messages sent and received by the endpoint
+    pub fn set_record_sink(self, opt_record_sink: Option<Arc<dyn RecordSink>>) -> Self {
+        let mut s = self;
+        s.opt_record_sink = opt_record_sink;
+        s
+    }
+
+    // the metrics of the node the endpoint belongs to
+    pub fn set_metrics(self, opt_metrics: Option<Arc<Metrics>>) -> Self {
+        let mut s = self;
+        s.opt_metrics = opt_metrics;
+        s
+    }
+
+    // see `ESConnectOption::set_write_batch`
+    pub fn set_write_batch(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.write_batch_max = max;
+        s.write_batch_bytes = bytes;
+        s
+    }
+
+    // see `ESConnectOption::set_send_buffer_pool`
+    pub fn set_send_buffer_pool(self, buffers: usize) -> Self {
+        let mut s = self;
+        s.send_buffer_pool = buffers;
+        s
+    }
+
+    // see `ESConnectOption::enable_resend_unsent`
+    pub fn enable_resend_unsent(self, resend_unsent: bool) -> Self {
+        let mut s = self;
+        s.resend_unsent = resend_unsent;
+        s
+    }
+
+    pub fn enable_nodelay(self, nodelay: bool) -> Self {
+        let mut s = self;
+        s.nodelay = nodelay;
+        s
+    }
+
+    // see `ESConnectOption::set_send_queue_capacity` and `set_recv_queue_capacity`
+    pub fn set_queue_capacity(self, send: usize, recv: usize) -> Self {
+        let mut s = self;
+        s.send_queue_capacity = send;
+        s.recv_queue_capacity = recv;
+        s
+    }
+
+    // see `ESConnectOption::set_overflow_policy`
+    pub fn set_overflow_policy(self, policy: OverflowPolicy) -> Self {
+        let mut s = self;
+        s.overflow_policy = policy;
+        s
+    }
+
+    // see `ESConnectOption::set_max_message_size`
+    pub fn set_max_message_size(self, opt_max_message_size: Option<usize>) -> Self {
+        let mut s = self;
+        s.opt_max_message_size = opt_max_message_size;
+        s
+    }
+
+    // see `ESConnectOption::set_frame_codec`
+    pub fn set_frame_codec(self, opt_frame_codec: Option<Arc<dyn RawFrameCodec>>) -> Self {
+        let mut s = self;
+        s.opt_frame_codec = opt_frame_codec;
+        s
+    }
+
+    // see `OptNode::set_rate_limit`
+    pub fn set_rate_limit(self, opt_rate_limit: Option<RateLimit>) -> Self {
+        let mut s = self;
+        s.opt_rate_limit = opt_rate_limit;
+        s
+    }
+
+    // see `ESConnectOption::set_proxy`
+    pub fn set_proxy(self, opt_proxy: Option<ProxyConfig>) -> Self {
+        let mut s = self;
+        s.opt_proxy = opt_proxy;
+        s
+    }
+
+    // the name of the node sent to the peer, see `OptNode::enable_advertise_name`
+    pub fn set_advertised_name(self, opt_advertised_name: Option<String>) -> Self {
+        let mut s = self;
+        s.opt_advertised_name = opt_advertised_name;
+        s
+    }
+
+    // see `ESConnectOption::set_flow_window`, 0 disables it
+    pub fn set_flow_window(self, window: u32) -> Self {
+        let mut s = self;
+        s.flow_window = window;
+        s
+    }
+
+    // see `ESConnectOption::enable_checksum`
+    pub fn enable_checksum(self, checksum: bool) -> Self {
+        let mut s = self;
+        s.checksum = checksum;
+        s
+    }
+
+    // see `ESConnectOption::enable_negotiation`
+    pub fn enable_negotiation(self, negotiation: bool) -> Self {
+        let mut s = self;
+        s.negotiation = negotiation;
+        s
+    }
+
+    // see `ESConnectOption::set_preamble`
+    pub fn set_preamble(self, opt_version: Option<u8>) -> Self {
+        let mut s = self;
+        s.opt_preamble = opt_version;
+        s
+    }
+
+    // see `ESConnectOption::set_fragment_size`, 0 disables it
+    pub fn set_fragment_size(self, size: usize) -> Self {
+        let mut s = self;
+        s.fragment_size = size;
+        s
+    }
+
+    // see `ESConnectOption::set_reassembly_limit`
+    pub fn set_reassembly_limit(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.max_reassemblies = max;
+        s.max_reassembly_bytes = bytes;
+        s
+    }
+
+    // the clock of the node the endpoint belongs to, see `NodeBuilder::set_clock`
+    pub fn set_clock(self, clock: Arc<dyn Clock>) -> Self {
+        let mut s = self;
+        s.clock = clock;
+        s
+    }
+
+    // see `HandleEvent::on_connect_attempt`
+    pub fn set_attempt(self, attempt: u64, opt_last_error: Option<ET>) -> Self {
+        let mut s = self;
+        s.attempt = attempt;
+        s.opt_last_error = opt_last_error;
+        s
+    }
+
+    // 0 disables the idle timeout
+    pub fn set_idle_timeout_ms(self, idle_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.idle_timeout_ms = idle_timeout_ms;
+        s
+    }
+
+    // see `ESConnectOption::set_write_timeout_ms`, 0 disables it
+    pub fn set_write_timeout_ms(self, write_timeout_ms: u64) -> Self {
+        let mut s = self;
+        s.write_timeout_ms = write_timeout_ms;
+        s
+    }
 }
 
 impl Default for OptEP {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
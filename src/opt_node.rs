@@ -0,0 +1,306 @@
+use std::net::SocketAddr;
+
+use crate::es_option::{
+    DEFAULT_MAX_REASSEMBLIES,
+    DEFAULT_MAX_REASSEMBLY_BYTES,
+    DEFAULT_SEND_BUFFER_POOL,
+    DEFAULT_WRITE_BATCH_BYTES,
+    DEFAULT_WRITE_BATCH_MAX,
+};
+use crate::overflow_policy::OverflowPolicy;
+use crate::rate_limit::RateLimit;
+
+// the default backlog of the listening TCP socket
+pub const DEFAULT_BACKLOG: u32 = 1024;
+
+// the handler invocations of an endpoint at a time, one after another by default
+pub const DEFAULT_HANDLER_CONCURRENCY: usize = 1;
+
+// How a node delivers the incoming messages of its endpoints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Delivery {
+    // the user reads the endpoints by `recv`
+    #[default]
+    Pull,
+    // the node reads the endpoints, and calls `HandleEvent::on_message` for every message,
+    // an endpoint is kept by its reading task until the connection was closed
+    Push,
+}
+
+// The server side options of a node. There is no TLS in scupt-net, the connections are plain
+// streams of the transport. The node id a peer declares in its messages is not authenticated,
+// binding it to a client certificate would need a TLS transport first.
+#[derive(Clone, Debug)]
+pub struct OptNode {
+    opt_listen_address: Option<SocketAddr>,
+    backlog: u32,
+    reuse_address: bool,
+    // IPV6_V6ONLY cleared or set on an IPv6 listening socket, None for the default of the
+    // platform
+    opt_dual_stack: Option<bool>,
+    // the max number of the live inbound connections, 0 for unlimited
+    max_connections: u64,
+    // the write batch of the inbound endpoints, see `ESConnectOption::set_write_batch`
+    write_batch_max: usize,
+    write_batch_bytes: usize,
+    // the send buffers of each inbound endpoint, see `ESConnectOption::set_send_buffer_pool`
+    send_buffer_pool: usize,
+    // the full send queue of each inbound endpoint, see `ESConnectOption::set_overflow_policy`
+    overflow_policy: OverflowPolicy,
+    // the delivery of the inbound and outbound endpoints
+    delivery: Delivery,
+    // the `HandleEvent::on_message` in flight of each endpoint of Delivery::Push
+    handler_concurrency: usize,
+    // send the name of the node to the peers of its endpoints
+    advertise_name: bool,
+    // the budget of every inbound endpoint, None for unlimited
+    opt_rate_limit: Option<RateLimit>,
+    // the credits of the default channel of every inbound endpoint, 0 for none
+    flow_window: u32,
+    // a CRC32C in the header of every frame of every inbound endpoint
+    checksum: bool,
+    // the params control frame of every inbound endpoint
+    negotiation: bool,
+    // the protocol version of the preamble of every inbound endpoint, None for none
+    opt_preamble: Option<u8>,
+    // the max message size of every inbound endpoint, None for the limit of the frame
+    opt_max_message_size: Option<usize>,
+    // the fragment size of every inbound endpoint, 0 for none
+    fragment_size: usize,
+    // the reassembly limits of every inbound endpoint
+    max_reassemblies: usize,
+    max_reassembly_bytes: usize,
+}
+
+impl OptNode {
+    pub fn new() -> Self {
+        Self {
+            opt_listen_address: None,
+            backlog: DEFAULT_BACKLOG,
+            reuse_address: true,
+            opt_dual_stack: None,
+            max_connections: 0,
+            write_batch_max: DEFAULT_WRITE_BATCH_MAX,
+            write_batch_bytes: DEFAULT_WRITE_BATCH_BYTES,
+            send_buffer_pool: DEFAULT_SEND_BUFFER_POOL,
+            overflow_policy: OverflowPolicy::default(),
+            delivery: Delivery::default(),
+            handler_concurrency: DEFAULT_HANDLER_CONCURRENCY,
+            advertise_name: false,
+            opt_rate_limit: None,
+            flow_window: 0,
+            checksum: false,
+            negotiation: false,
+            opt_preamble: None,
+            opt_max_message_size: None,
+            fragment_size: 0,
+            max_reassemblies: DEFAULT_MAX_REASSEMBLIES,
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+        }
+    }
+
+    pub fn listen_address(&self) -> Option<SocketAddr> { self.opt_listen_address }
+
+    pub fn backlog(&self) -> u32 { self.backlog }
+
+    pub fn reuse_address(&self) -> bool { self.reuse_address }
+
+    pub fn dual_stack(&self) -> Option<bool> { self.opt_dual_stack }
+
+    pub fn max_connections(&self) -> u64 { self.max_connections }
+
+    pub fn write_batch_max(&self) -> usize { self.write_batch_max }
+
+    pub fn write_batch_bytes(&self) -> usize { self.write_batch_bytes }
+
+    pub fn send_buffer_pool(&self) -> usize { self.send_buffer_pool }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy { self.overflow_policy }
+
+    pub fn delivery(&self) -> Delivery { self.delivery }
+
+    pub fn handler_concurrency(&self) -> usize { self.handler_concurrency }
+
+    pub fn advertise_name(&self) -> bool { self.advertise_name }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> { self.opt_rate_limit }
+
+    pub fn flow_window(&self) -> u32 { self.flow_window }
+
+    pub fn checksum(&self) -> bool { self.checksum }
+
+    pub fn negotiation(&self) -> bool { self.negotiation }
+
+    pub fn preamble(&self) -> Option<u8> { self.opt_preamble }
+
+    pub fn max_message_size(&self) -> Option<usize> { self.opt_max_message_size }
+
+    pub fn fragment_size(&self) -> usize { self.fragment_size }
+
+    pub fn max_reassemblies(&self) -> usize { self.max_reassemblies }
+
+    pub fn max_reassembly_bytes(&self) -> usize { self.max_reassembly_bytes }
+
+    // the address served by `Node::serve`
+    pub fn set_listen_address(self, address: SocketAddr) -> Self {
+        let mut s = self;
+        s.opt_listen_address = Some(address);
+        s
+    }
+
+    pub fn set_backlog(self, backlog: u32) -> Self {
+        let mut s = self;
+        s.backlog = backlog;
+        s
+    }
+
+    // set SO_REUSEADDR on the listening TCP socket, a restarted server can bind the port of the
+    // connections still in TIME_WAIT, it is ignored on windows, where the option would let
+    // another socket bind the port in use, there is no SO_REUSEPORT
+    pub fn set_reuse_address(self, reuse_address: bool) -> Self {
+        let mut s = self;
+        s.reuse_address = reuse_address;
+        s
+    }
+
+    // Serve the IPv4 clients too on an IPv6 address such as [::]:port, by clearing
+    // IPV6_V6ONLY, or the IPv6 clients only by setting it, None keeps the default of the
+    // platform, dual stack on linux and IPv6 only on windows. It is ignored for an IPv4
+    // address. The addresses of the IPv4 clients are reported as IPv4, not IPv4-mapped.
+    pub fn set_dual_stack(self, opt_dual_stack: Option<bool>) -> Self {
+        let mut s = self;
+        s.opt_dual_stack = opt_dual_stack;
+        s
+    }
+
+    // the connections accepted beyond the limit are closed immediately
+    pub fn set_max_connections(self, max_connections: u64) -> Self {
+        let mut s = self;
+        s.max_connections = max_connections;
+        s
+    }
+
+    // the write batch of the accepted endpoints, see `ESConnectOption::set_write_batch`
+    pub fn set_write_batch(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.write_batch_max = max;
+        s.write_batch_bytes = bytes;
+        s
+    }
+
+    // the send buffers of the accepted endpoints, see `ESConnectOption::set_send_buffer_pool`
+    pub fn set_send_buffer_pool(self, buffers: usize) -> Self {
+        let mut s = self;
+        s.send_buffer_pool = buffers;
+        s
+    }
+
+    // the full send queue of the accepted endpoints, see `ESConnectOption::set_overflow_policy`
+    pub fn set_overflow_policy(self, policy: OverflowPolicy) -> Self {
+        let mut s = self;
+        s.overflow_policy = policy;
+        s
+    }
+
+    // the initial budget of the accepted endpoints, adjusted per endpoint by
+    // `EndpointAsync::set_rate_limit`
+    pub fn set_rate_limit(self, opt_rate_limit: Option<RateLimit>) -> Self {
+        let mut s = self;
+        s.opt_rate_limit = opt_rate_limit;
+        s
+    }
+
+    // the flow window of the accepted endpoints, see `ESConnectOption::set_flow_window`, the
+    // connecting peers must set the same
+    pub fn set_flow_window(self, window: u32) -> Self {
+        let mut s = self;
+        s.flow_window = window;
+        s
+    }
+
+    // the checksums of the frames of the accepted endpoints, see
+    // `ESConnectOption::enable_checksum`, the connecting peers must enable the same
+    pub fn enable_checksum(self, checksum: bool) -> Self {
+        let mut s = self;
+        s.checksum = checksum;
+        s
+    }
+
+    // the params of the accepted endpoints, see `ESConnectOption::enable_negotiation`
+    pub fn enable_negotiation(self, negotiation: bool) -> Self {
+        let mut s = self;
+        s.negotiation = negotiation;
+        s
+    }
+
+    // the preamble of the accepted endpoints, see `ESConnectOption::set_preamble`, a connection
+    // not starting with the one of the version is closed before any frame is read
+    pub fn set_preamble(self, opt_version: Option<u8>) -> Self {
+        let mut s = self;
+        s.opt_preamble = opt_version;
+        s
+    }
+
+    // the max message size of the accepted endpoints, see
+    // `ESConnectOption::set_max_message_size`, told to the peers which enable the negotiation
+    pub fn set_max_message_size(self, opt_max_message_size: Option<usize>) -> Self {
+        let mut s = self;
+        s.opt_max_message_size = opt_max_message_size;
+        s
+    }
+
+    // the fragment size of the accepted endpoints, see `ESConnectOption::set_fragment_size`,
+    // the connecting peers reassemble the fragments whatever their own
+    pub fn set_fragment_size(self, size: usize) -> Self {
+        let mut s = self;
+        s.fragment_size = size;
+        s
+    }
+
+    // the reassembly limits of the accepted endpoints, see
+    // `ESConnectOption::set_reassembly_limit`
+    pub fn set_reassembly_limit(self, max: usize, bytes: usize) -> Self {
+        let mut s = self;
+        s.max_reassemblies = max;
+        s.max_reassembly_bytes = bytes;
+        s
+    }
+
+    // take effect on the endpoints created after it was set
+    pub fn set_delivery(self, delivery: Delivery) -> Self {
+        let mut s = self;
+        s.delivery = delivery;
+        s
+    }
+
+    // Call `HandleEvent::on_message` for up to `concurrency` messages of an endpoint at a time,
+    // the limit is per endpoint. 1, the default, is the sequential mode, a message is handled
+    // after the previous one returned. Above 1, the next message is read as soon as a handler
+    // is free, the messages are dispatched in the order of the endpoint, but the handlers may
+    // complete, and send their responses, out of that order. After a handler returned
+    // `net_error::not_handled`, no more message is dispatched, and `recv` delivers the messages
+    // not handled by the handlers in flight, in the order of the endpoint, then the following
+    // ones.
+    pub fn set_handler_concurrency(self, concurrency: usize) -> Self {
+        let mut s = self;
+        s.handler_concurrency = concurrency;
+        s
+    }
+
+    // Send the name of the node, the one given to `Node::new`, as the first frame of every
+    // endpoint created after, inbound and outbound, the peer reports it by
+    // `EndpointAsync::peer_name`, and finds the endpoint by `Node::find_by_name`. The names
+    // are not unique, nor authenticated. A name over `frame::MAX_NAME_SIZE` bytes, or an
+    // endpoint of a user frame codec or of UDP, is not advertised, the default is false.
+    pub fn enable_advertise_name(self, advertise_name: bool) -> Self {
+        let mut s = self;
+        s.advertise_name = advertise_name;
+        s
+    }
+}
+
+impl Default for OptNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,18 @@
+use crate::priority::Priority;
 
 pub struct OptSend {
     no_wait: bool,
+    flush: bool,
+    priority: Priority,
 }
 
 
 impl OptSend {
     pub fn new() -> Self {
         Self {
-            no_wait: false
+            no_wait: false,
+            flush: false,
+            priority: Priority::Normal,
         }
     }
 
@@ -15,11 +20,34 @@ impl OptSend {
         self.no_wait
     }
 
+    pub fn is_enable_flush(&self) -> bool {
+        self.flush
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
     pub fn enable_no_wait(self, no_wait: bool) -> Self {
         let mut s = self;
         s.no_wait = no_wait;
         s
     }
+
+    // the writer flushes the socket right after the frame of the message, it is not coalesced
+    // with the frames queued after it, see `EndpointAsync::send_opt`
+    pub fn enable_flush(self, flush: bool) -> Self {
+        let mut s = self;
+        s.flush = flush;
+        s
+    }
+
+    // the lane the message is queued in, see `Priority`, the default is Normal
+    pub fn set_priority(self, priority: Priority) -> Self {
+        let mut s = self;
+        s.priority = priority;
+        s
+    }
 }
 
 impl Default for OptSend {
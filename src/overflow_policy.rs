@@ -0,0 +1,16 @@
+// What a send does when the lane of its priority is full, see
+// `ESConnectOption::set_send_queue_capacity`. The control frames, the pings, the pongs and the
+// credits, always wait for a slot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OverflowPolicy {
+    // wait until the writer task took a frame of the lane
+    #[default]
+    Block,
+    // fail the new message with `net_error::queue_full`, the queued ones are kept
+    ErrorNewest,
+    // Evict the oldest queued item of the Normal lane to make room for a Normal message, the
+    // evicted send fails with `net_error::dropped`, and is counted by
+    // `MetricsSnapshot::messages_dropped`. An item of `send_all` is a chunk of its messages,
+    // they are evicted together. The High and Low messages wait as by Block.
+    DropOldest,
+}
@@ -0,0 +1,27 @@
+// The priority lane of an outgoing message.
+// Within a lane the messages are written in FIFO order, across lanes a higher lane preempts the
+// lower ones at frame boundaries, a frame which is being written is never interrupted, nor is
+// the batch of frames the writer task took already, see `ESConnectOption::set_write_batch`.
+// A constant stream of High messages starves the lower lanes, they are written when the higher
+// lanes run empty. Each lane has the capacity of `ESConnectOption::set_send_queue_capacity`,
+// a send waits only for the frames of its own lane.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub const NUM_LANES: usize = 3;
+
+    pub fn lane(&self) -> usize {
+        match self {
+            Priority::High => { 0 }
+            Priority::Normal => { 1 }
+            Priority::Low => { 2 }
+        }
+    }
+}
+
@@ -0,0 +1,78 @@
+use std::net::SocketAddr;
+
+use scupt_util::res::Res;
+use tokio::net::TcpStream;
+
+use crate::net_error;
+#[cfg(feature = "socks5")]
+use crate::socks5::socks5_connect;
+
+// The SOCKS5 proxy an outbound TCP connection is tunneled through, see
+// `ESConnectOption::set_proxy`. The connection is made to the proxy, which connects to the
+// address of the connect, the framing of scupt-net runs in the tunnel. The other transports
+// ignore it. The handshake is built by the socks5 feature.
+#[derive(Clone)]
+pub struct ProxyConfig {
+    address: SocketAddr,
+    opt_auth: Option<(String, String)>,
+    opt_remote_host: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            opt_auth: None,
+            opt_remote_host: None,
+        }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // the username and the password
+    pub fn auth(&self) -> Option<(&str, &str)> {
+        self.opt_auth.as_ref().map(|(u, p)| { (u.as_str(), p.as_str()) })
+    }
+
+    pub fn remote_host(&self) -> Option<&str> {
+        self.opt_remote_host.as_deref()
+    }
+
+    // authenticate to the proxy by the username and the password, RFC 1929, each of 1 to 255
+    // bytes, the default is no authentication
+    pub fn set_auth(self, username: String, password: String) -> Self {
+        let mut s = self;
+        s.opt_auth = Some((username, password));
+        s
+    }
+
+    // Ask the proxy to connect to the host name, resolved by the proxy, instead of the IP of
+    // the connect address, for a host which cannot be resolved locally. The port is the one of
+    // the connect address, the address still identifies the connection for the node.
+    pub fn set_remote_host(self, host: String) -> Self {
+        let mut s = self;
+        s.opt_remote_host = Some(host);
+        s
+    }
+}
+
+// connect to the proxy, and negotiate the tunnel to the address
+#[cfg(feature = "socks5")]
+pub(crate) async fn proxy_connect(address: SocketAddr, proxy: &ProxyConfig, nodelay: bool) -> Res<TcpStream> {
+    let map_err = |e| { net_error::io_error(e, "connect proxy", proxy.address()) };
+    let r = TcpStream::connect(proxy.address()).await;
+    let mut s = r.map_err(map_err)?;
+    if nodelay {
+        s.set_nodelay(true).map_err(map_err)?;
+    }
+    socks5_connect(&mut s, address, proxy).await?;
+    Ok(s)
+}
+
+// the handshake is not built, a connect through a proxy fails without connecting
+#[cfg(not(feature = "socks5"))]
+pub(crate) async fn proxy_connect(_address: SocketAddr, _proxy: &ProxyConfig, _nodelay: bool) -> Res<TcpStream> {
+    Err(net_error::invalid_option_of("proxy", "the SOCKS5 proxy needs the socks5 feature"))
+}
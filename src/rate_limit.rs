@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+// The inbound budget of an endpoint, in messages and in bytes of the encoded messages per
+// second, 0 for unlimited. The reader task of an endpoint over the budget stops reading the
+// socket until the budget refilled, the peer is slowed down by the TCP flow control, the
+// messages are neither buffered nor dropped. One second of the budget can be spent at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    messages_per_sec: u64,
+    bytes_per_sec: u64,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages_per_sec(&self) -> u64 { self.messages_per_sec }
+
+    pub fn bytes_per_sec(&self) -> u64 { self.bytes_per_sec }
+
+    pub fn set_messages_per_sec(self, messages_per_sec: u64) -> Self {
+        let mut s = self;
+        s.messages_per_sec = messages_per_sec;
+        s
+    }
+
+    pub fn set_bytes_per_sec(self, bytes_per_sec: u64) -> Self {
+        let mut s = self;
+        s.bytes_per_sec = bytes_per_sec;
+        s
+    }
+}
+
+// the token buckets of a RateLimit, a message is admitted while the buckets are not in debt,
+// and may leave them in debt, so a message larger than the budget is still admitted
+pub(crate) struct TokenBucket {
+    messages: Bucket,
+    bytes: Bucket,
+}
+
+struct Bucket {
+    // per second, 0 for unlimited
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            messages: Bucket::new(limit.messages_per_sec, now),
+            bytes: Bucket::new(limit.bytes_per_sec, now),
+        }
+    }
+
+    // None if the message of the bytes was admitted, or how long to wait before asking again
+    pub fn acquire(&mut self, bytes: usize, now: Instant) -> Option<Duration> {
+        self.messages.refill(now);
+        self.bytes.refill(now);
+        let wait = self.messages.debt_wait().max(self.bytes.debt_wait());
+        if wait > Duration::ZERO {
+            return Some(wait);
+        }
+        self.messages.take(1.0);
+        self.bytes.take(bytes as f64);
+        None
+    }
+}
+
+impl Bucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.rate == 0.0 {
+            return;
+        }
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+    }
+
+    fn debt_wait(&self) -> Duration {
+        if self.rate == 0.0 || self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    fn take(&mut self, n: f64) {
+        if self.rate != 0.0 {
+            self.tokens -= n;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::rate_limit::{RateLimit, TokenBucket};
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new().set_messages_per_sec(10), now);
+        // the burst of one second, and one more into debt
+        for _ in 0..11 {
+            assert!(bucket.acquire(100, now).is_none());
+        }
+        let wait = bucket.acquire(100, now).unwrap();
+        assert!(wait > Duration::from_millis(99) && wait <= Duration::from_millis(100));
+        assert!(bucket.acquire(100, now + Duration::from_millis(101)).is_none());
+
+        let mut bucket = TokenBucket::new(RateLimit::new().set_bytes_per_sec(1000), now);
+        // larger than the budget
+        assert!(bucket.acquire(3000, now).is_none());
+        let wait = bucket.acquire(1, now).unwrap();
+        assert!(wait > Duration::from_millis(1999) && wait <= Duration::from_secs(2));
+
+        let mut bucket = TokenBucket::new(RateLimit::new(), now);
+        for _ in 0..1000 {
+            assert!(bucket.acquire(1 << 20, now).is_none());
+        }
+    }
+}
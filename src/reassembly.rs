@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use bytes::{Buf, BytesMut};
+
+use crate::frame::{FRAGMENT_HEADER_SIZE, FragmentHeader};
+
+// The reassembly of the fragments of the messages received on FRAGMENT_CHANNEL, see
+// `ESConnectOption::set_fragment_size`. The fragments of a message arrive in their order, the
+// ones of several messages may be interleaved. A fragment out of order, such as after one was
+// evicted from the send queue of the peer, drops its message, and so do the fragments
+// following it, the peer aborts the message when its send failed.
+pub struct Reassembly {
+    max_messages: usize,
+    max_bytes: usize,
+    // the bytes of all the messages being reassembled
+    buffered: usize,
+    pending: HashMap<u32, Partial>,
+}
+
+// the fragments of a message received so far
+struct Partial {
+    next_index: u32,
+    bytes: BytesMut,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reassembled {
+    // the fragment was kept, the message is not complete yet
+    Pending,
+    // the encoded message completed by the last fragment
+    Message(BytesMut),
+    // the fragment of a message dropped, or aborted by the peer
+    Dropped,
+}
+
+impl Reassembly {
+    // up to `max_messages` messages and `max_bytes` bytes reassembled at a time
+    pub fn new(max_messages: usize, max_bytes: usize) -> Self {
+        Self {
+            max_messages,
+            max_bytes,
+            buffered: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    // add the payload of a fragment frame, the error of a malformed fragment, or of one beyond
+    // the limits, closes the connection
+    pub fn add(&mut self, payload: BytesMut) -> Result<Reassembled, String> {
+        let header = match FragmentHeader::decode(&payload[..]) {
+            Some(h) => { h }
+            None => { return Err(format!("a fragment of {} bytes, shorter than its header", payload.len())); }
+        };
+        let id = header.message_id();
+        if header.is_abort() {
+            self.remove(id);
+            return Ok(Reassembled::Dropped);
+        }
+        let mut data = payload;
+        data.advance(FRAGMENT_HEADER_SIZE);
+        if header.index() == 0 {
+            // the id of a message dropped before its last fragment, reused after the wrap around
+            self.remove(id);
+            if header.is_last() {
+                return Ok(Reassembled::Message(data));
+            }
+            if self.pending.len() >= self.max_messages {
+                return Err(format!("more than {} messages reassembled at a time", self.max_messages));
+            }
+        }
+        match self.pending.get(&id) {
+            Some(p) if p.next_index == header.index() => {}
+            Some(_) => {
+                self.remove(id);
+                return Ok(Reassembled::Dropped);
+            }
+            None if header.index() == 0 => {}
+            None => { return Ok(Reassembled::Dropped); }
+        }
+        if self.buffered + data.len() > self.max_bytes {
+            return Err(format!("more than {} bytes of the fragments buffered", self.max_bytes));
+        }
+        self.buffered += data.len();
+        let partial = self.pending.entry(id).or_insert_with(|| {
+            Partial {
+                next_index: 0,
+                bytes: BytesMut::new(),
+            }
+        });
+        if partial.bytes.is_empty() {
+            partial.bytes = data;
+        } else {
+            partial.bytes.extend_from_slice(&data[..]);
+        }
+        partial.next_index += 1;
+        if !header.is_last() {
+            return Ok(Reassembled::Pending);
+        }
+        let partial = self.pending.remove(&id).unwrap();
+        self.buffered -= partial.bytes.len();
+        Ok(Reassembled::Message(partial.bytes))
+    }
+
+    fn remove(&mut self, id: u32) {
+        if let Some(p) = self.pending.remove(&id) {
+            self.buffered -= p.bytes.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::{BufMut, BytesMut};
+
+    use crate::frame::{FRAGMENT_ABORT, FRAGMENT_LAST, FragmentHeader};
+    use crate::reassembly::{Reassembled, Reassembly};
+
+    fn fragment(id: u32, index: u32, flags: u8, data: &[u8]) -> BytesMut {
+        let mut b = BytesMut::new();
+        FragmentHeader::new(id, index, flags).encode(&mut b);
+        b.put_slice(data);
+        b
+    }
+
+    #[test]
+    fn test_reassembly_interleaved() {
+        let mut r = Reassembly::new(4, 1024);
+        assert_eq!(r.add(fragment(1, 0, 0, b"ab")), Ok(Reassembled::Pending));
+        assert_eq!(r.add(fragment(2, 0, 0, b"xy")), Ok(Reassembled::Pending));
+        assert_eq!(r.add(fragment(1, 1, 0, b"cd")), Ok(Reassembled::Pending));
+        assert_eq!(r.buffered(), 6);
+        assert_eq!(r.add(fragment(2, 1, FRAGMENT_LAST, b"z")), Ok(Reassembled::Message(BytesMut::from(&b"xyz"[..]))));
+        assert_eq!(r.add(fragment(1, 2, FRAGMENT_LAST, b"e")), Ok(Reassembled::Message(BytesMut::from(&b"abcde"[..]))));
+        assert_eq!(r.buffered(), 0);
+        // a message of one fragment is not buffered
+        assert_eq!(r.add(fragment(3, 0, FRAGMENT_LAST, b"1")), Ok(Reassembled::Message(BytesMut::from(&b"1"[..]))));
+    }
+
+    #[test]
+    fn test_reassembly_dropped() {
+        let mut r = Reassembly::new(4, 1024);
+        assert_eq!(r.add(fragment(1, 0, 0, b"ab")), Ok(Reassembled::Pending));
+        // the fragment 1 was evicted
+        assert_eq!(r.add(fragment(1, 2, 0, b"ef")), Ok(Reassembled::Dropped));
+        assert_eq!(r.add(fragment(1, 3, FRAGMENT_LAST, b"g")), Ok(Reassembled::Dropped));
+        assert_eq!(r.buffered(), 0);
+
+        assert_eq!(r.add(fragment(2, 0, 0, b"ab")), Ok(Reassembled::Pending));
+        assert_eq!(r.add(fragment(2, 0, FRAGMENT_ABORT, b"")), Ok(Reassembled::Dropped));
+        assert_eq!(r.add(fragment(2, 1, FRAGMENT_LAST, b"c")), Ok(Reassembled::Dropped));
+        assert_eq!(r.buffered(), 0);
+    }
+
+    #[test]
+    fn test_reassembly_limits() {
+        let mut r = Reassembly::new(2, 8);
+        assert_eq!(r.add(fragment(1, 0, 0, b"ab")), Ok(Reassembled::Pending));
+        assert_eq!(r.add(fragment(2, 0, 0, b"cd")), Ok(Reassembled::Pending));
+        assert!(r.add(fragment(3, 0, 0, b"ef")).is_err());
+
+        let mut r = Reassembly::new(2, 8);
+        assert_eq!(r.add(fragment(1, 0, 0, b"abcd")), Ok(Reassembled::Pending));
+        assert_eq!(r.add(fragment(1, 1, 0, b"efgh")), Ok(Reassembled::Pending));
+        assert!(r.add(fragment(1, 2, FRAGMENT_LAST, b"i")).is_err());
+
+        assert!(Reassembly::new(2, 8).add(BytesMut::from(&[0u8; 3][..])).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::{decode_message, Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use scupt_util::res_of::res_io;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::handle_event::HandleEvent;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RecordDirection {
+    Send,
+    Recv,
+}
+
+// a message sent or received by an endpoint
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub direction: RecordDirection,
+    pub remote_address: SocketAddr,
+    // the destination of a sent message, or the source of a received one
+    pub peer: NID,
+    // microseconds since the UNIX epoch
+    pub timestamp_us: u64,
+    // the encoded Message<M>
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+// Where the records go, `write` is invoked on the send and receive path of the endpoints and
+// should not block for long.
+pub trait RecordSink: Send + Sync {
+    fn write(&self, record: Record) -> Res<()>;
+}
+
+// keep the records in memory, for tests
+pub struct MemoryRecordSink {
+    records: SyncMutex<Vec<Record>>,
+}
+
+// append the records to a file, one JSON object per line
+pub struct FileRecordSink {
+    writer: SyncMutex<BufWriter<File>>,
+}
+
+// An endpoint which returns the recorded incoming messages in order, and then ET::EOF.
+// The outgoing messages are discarded, there is no network.
+pub struct ReplayEndpoint {
+    remote_address: SocketAddr,
+    records: SyncMutex<VecDeque<Record>>,
+}
+
+impl Record {
+    pub fn new(direction: RecordDirection, remote_address: SocketAddr, peer: NID, payload: &[u8]) -> Self {
+        let timestamp_us = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => { d.as_micros() as u64 }
+            Err(_) => { 0 }
+        };
+        Self {
+            direction,
+            remote_address,
+            peer,
+            timestamp_us,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn message<M: MsgTrait + 'static>(&self) -> Res<Message<M>> {
+        let (m, _) = decode_message::<Message<M>>(self.payload.as_slice())?;
+        Ok(m)
+    }
+}
+
+// a failed write is logged, it never fails the send or receive
+pub(crate) fn write_record(
+    sink: &Arc<dyn RecordSink>,
+    direction: RecordDirection,
+    remote_address: SocketAddr,
+    peer: NID,
+    payload: &[u8],
+) {
+    let r = sink.write(Record::new(direction, remote_address, peer, payload));
+    if let Err(e) = r {
+        error!("write message record error, {}", e.to_string());
+    }
+}
+
+impl MemoryRecordSink {
+    pub fn new() -> Self {
+        Self {
+            records: SyncMutex::new(vec![]),
+        }
+    }
+
+    pub fn records(&self) -> Vec<Record> {
+        let records = self.records.lock().unwrap();
+        records.clone()
+    }
+}
+
+impl Default for MemoryRecordSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordSink for MemoryRecordSink {
+    fn write(&self, record: Record) -> Res<()> {
+        let mut records = self.records.lock().unwrap();
+        records.push(record);
+        Ok(())
+    }
+}
+
+impl FileRecordSink {
+    pub fn create<P: AsRef<Path>>(path: P) -> Res<Self> {
+        let file = res_io(File::create(path))?;
+        Ok(Self {
+            writer: SyncMutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn flush(&self) -> Res<()> {
+        let mut writer = self.writer.lock().unwrap();
+        res_io(writer.flush())
+    }
+
+    pub fn read_records<P: AsRef<Path>>(path: P) -> Res<Vec<Record>> {
+        let file = res_io(File::open(path))?;
+        let mut records = vec![];
+        for r_line in BufReader::new(file).lines() {
+            let line = res_io(r_line)?;
+            if line.is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(line.as_str()).map_err(|e| {
+                ET::SerdeError(e.to_string())
+            })?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+impl RecordSink for FileRecordSink {
+    fn write(&self, record: Record) -> Res<()> {
+        let line = serde_json::to_string(&record).map_err(|e| {
+            ET::SerdeError(e.to_string())
+        })?;
+        let mut writer = self.writer.lock().unwrap();
+        res_io(writer.write_all(line.as_bytes()))?;
+        res_io(writer.write_all(b"\n"))?;
+        Ok(())
+    }
+}
+
+// feed the recorded incoming messages to a handler, as if a connection was accepted
+pub async fn replay_to_handler<M: MsgTrait + 'static, H: HandleEvent<M>>(
+    handler: &H,
+    remote_address: SocketAddr,
+    records: Vec<Record>,
+) -> Res<()> {
+    let endpoint: Arc<dyn EndpointAsync<M>> = Arc::new(ReplayEndpoint::new(remote_address, records));
+    handler.on_accepted(endpoint).await
+}
+
+impl ReplayEndpoint {
+    // only the records of RecordDirection::Recv are replayed
+    pub fn new(remote_address: SocketAddr, records: Vec<Record>) -> Self {
+        let records = records.into_iter()
+            .filter(|r| { r.direction == RecordDirection::Recv })
+            .collect();
+        Self {
+            remote_address,
+            records: SyncMutex::new(records),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> EndpointAsync<M> for ReplayEndpoint {
+    fn remote_address(&self) -> SocketAddr {
+        self.remote_address
+    }
+
+    fn is_inbound(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, _: Message<M>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn recv(&self) -> Res<Message<M>> {
+        let opt = self.records.lock().unwrap().pop_front();
+        match opt {
+            Some(record) => { record.message() }
+            None => { Err(ET::EOF) }
+        }
+    }
+
+    async fn close(&self) -> Res<()> {
+        Ok(())
+    }
+
+    async fn shutdown_write(&self) -> Res<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use scupt_util::res_of::res_io;
+use tokio::net::lookup_host;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// Resolve the node id of a peer to its addresses, consulted by a node when it must connect to
+// a peer with no endpoint, see `Node::set_resolver`. The addresses are tried in order until one
+// connects. An error, or no address, fails the connect with `net_error::unresolved`.
+#[async_trait]
+pub trait NodeAddrResolver: Send + Sync {
+    async fn resolve(&self, node_id: NID) -> Res<Vec<SocketAddr>>;
+}
+
+// the addresses of a fixed set of peers, which can be updated while the node is running
+#[derive(Default)]
+pub struct StaticResolver {
+    addresses: SyncMutex<HashMap<NID, SocketAddr>>,
+}
+
+impl StaticResolver {
+    pub fn new(addresses: HashMap<NID, SocketAddr>) -> Self {
+        Self {
+            addresses: SyncMutex::new(addresses),
+        }
+    }
+
+    // return the previous address of the node, the endpoints already connected are kept
+    pub fn set(&self, node_id: NID, address: SocketAddr) -> Option<SocketAddr> {
+        let mut guard = self.addresses.lock().unwrap();
+        guard.insert(node_id, address)
+    }
+
+    pub fn remove(&self, node_id: NID) -> Option<SocketAddr> {
+        let mut guard = self.addresses.lock().unwrap();
+        guard.remove(&node_id)
+    }
+
+    pub fn get(&self, node_id: NID) -> Option<SocketAddr> {
+        let guard = self.addresses.lock().unwrap();
+        guard.get(&node_id).cloned()
+    }
+}
+
+#[async_trait]
+impl NodeAddrResolver for StaticResolver {
+    async fn resolve(&self, node_id: NID) -> Res<Vec<SocketAddr>> {
+        Ok(self.get(node_id).into_iter().collect())
+    }
+}
+
+// Resolve a host name and a port, such as "localhost:8080", to the addresses of a server, see
+// `ClientBuilder::set_host_resolver`. A client looks the name up again on every attempt of a
+// connect, a server moved to another address behind the name is reached by the next attempt.
+#[async_trait]
+pub trait HostResolver: Send + Sync {
+    async fn lookup(&self, host: &str) -> Res<Vec<SocketAddr>>;
+}
+
+// the resolver of the system, the default of a client
+#[derive(Default)]
+pub struct DnsResolver {}
+
+#[async_trait]
+impl HostResolver for DnsResolver {
+    async fn lookup(&self, host: &str) -> Res<Vec<SocketAddr>> {
+        let r = lookup_host(host).await;
+        Ok(res_io(r)?.collect())
+    }
+}
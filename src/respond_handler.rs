@@ -0,0 +1,113 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tracing::trace;
+
+use crate::endpoint_async::EndpointAsync;
+use crate::handle_event::HandleEvent;
+use crate::notifier::Notifier;
+use crate::task::spawn_local_task;
+use crate::task_trace;
+
+// the reply to the peer when the responder failed on a request of it
+pub type ErrorReplyFn<M> = Arc<dyn Fn(NID, ET) -> Message<M> + Send + Sync>;
+
+// what a RespondHandler does when the responder returned an error
+#[derive(Clone)]
+pub enum OnRespondError<M: MsgTrait + 'static> {
+    // close the connection
+    Close,
+    // send the message built from the error, and keep serving
+    Reply(ErrorReplyFn<M>),
+}
+
+// A server handler of request and reply.
+// For every message of every accepted endpoint, the responder is called with the node id of
+// the peer, and what it returns is sent back on the same endpoint, one request after another.
+// The response should carry the correlation ID of the request, such as the one read by
+// `Caller`, there is no request framing on the wire.
+pub struct RespondHandler<M: MsgTrait + 'static, F> {
+    responder: Arc<F>,
+    on_error: OnRespondError<M>,
+    notifier: Notifier,
+}
+
+impl<M, F, Fut> RespondHandler<M, F>
+    where M: MsgTrait + 'static,
+          F: Fn(NID, Message<M>) -> Fut + Send + Sync + 'static,
+          Fut: Future<Output=Res<Message<M>>> + 'static,
+{
+    // the serving tasks are cancelled by the notifier
+    pub fn new(notifier: Notifier, responder: F) -> Self {
+        Self {
+            responder: Arc::new(responder),
+            on_error: OnRespondError::Close,
+            notifier,
+        }
+    }
+
+    pub fn set_on_error(self, on_error: OnRespondError<M>) -> Self {
+        let mut s = self;
+        s.on_error = on_error;
+        s
+    }
+
+    #[cfg_attr(feature = "task-trace", async_backtrace::framed)]
+    async fn serve(
+        endpoint: Arc<dyn EndpointAsync<M>>,
+        responder: Arc<F>,
+        on_error: OnRespondError<M>,
+    ) -> Res<()> {
+        let _t = task_trace!();
+        loop {
+            let request = endpoint.recv().await?;
+            let peer = request.source();
+            let response = match responder(peer, request).await {
+                Ok(m) => { m }
+                Err(e) => {
+                    match &on_error {
+                        OnRespondError::Close => {
+                            trace!("close {}, respond error {}", endpoint.remote_address(), e.to_string());
+                            return endpoint.close().await;
+                        }
+                        OnRespondError::Reply(f) => { f(peer, e) }
+                    }
+                }
+            };
+            endpoint.send(response).await?;
+        }
+    }
+}
+
+#[async_trait]
+impl<M, F, Fut> HandleEvent<M> for RespondHandler<M, F>
+    where M: MsgTrait + 'static,
+          F: Fn(NID, Message<M>) -> Fut + Send + Sync + 'static,
+          Fut: Future<Output=Res<Message<M>>> + 'static,
+{
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
+        let responder = self.responder.clone();
+        let on_error = self.on_error.clone();
+        let task_name = format!("respond {}", endpoint.remote_address());
+        spawn_local_task(self.notifier.clone(), task_name.as_str(), async move {
+            if let Err(e) = Self::serve(endpoint, responder, on_error).await {
+                trace!("respond stopped, {}", e.to_string());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+
+use crate::caller::DeadlineFn;
+
+// A request or a response of the RPC layer, the body with an optional deadline.
+// The deadline travels as the time left to it, in milliseconds, not as a point of time, the
+// clocks of the nodes need not be in sync. Each side runs its own monotonic timer: the sender
+// from the call, see `Caller::set_deadline`, the receiver from the receipt, see `deadline`,
+// so the deadline of the receiver is later by the transit time of the request.
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+pub struct RpcEnvelope<M> {
+    opt_timeout_ms: Option<u64>,
+    body: M,
+}
+
+impl<M: MsgTrait + 'static> MsgTrait for RpcEnvelope<M> {}
+
+impl<M: MsgTrait + 'static> RpcEnvelope<M> {
+    // no deadline
+    pub fn new(body: M) -> Self {
+        Self {
+            opt_timeout_ms: None,
+            body,
+        }
+    }
+
+    // the time left to the deadline, rounded up to milliseconds, is written into the envelope
+    pub fn with_deadline(body: M, deadline: Instant) -> Self {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let ms = left.as_micros().div_ceil(1000) as u64;
+        Self {
+            opt_timeout_ms: Some(ms),
+            body,
+        }
+    }
+
+    pub fn body(&self) -> &M {
+        &self.body
+    }
+
+    pub fn into_body(self) -> M {
+        self.body
+    }
+
+    // the time left to the deadline when the envelope was made
+    pub fn timeout(&self) -> Option<Duration> {
+        self.opt_timeout_ms.map(Duration::from_millis)
+    }
+
+    // the local deadline of a receiver, which got the envelope at `received`, it should be
+    // read as soon as the message was returned by recv, a handler abandons the work after it
+    pub fn deadline(&self, received: Instant) -> Option<Instant> {
+        self.timeout().map(|t| { received + t })
+    }
+
+    // the deadline function of a `Caller` of the envelopes
+    pub fn deadline_fn() -> DeadlineFn<RpcEnvelope<M>> {
+        Arc::new(|m: &Message<RpcEnvelope<M>>| { m.clone().payload().timeout() })
+    }
+}
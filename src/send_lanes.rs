@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+
+use tokio::sync::{Notify, Semaphore, SemaphorePermit, TryAcquireError};
+
+use crate::priority::Priority;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// The bounded lanes of the items waiting for the writer task of an endpoint.
+// Each lane has its own slots, a push waits for a free slot when its lane is full, a full
+// lower lane does not hold the higher ones back. The single consumer pops the items of the
+// highest non-empty lane first.
+pub struct SendLanes<T> {
+    queue: SyncMutex<LaneQueue<T>>,
+    slots: [Semaphore; Priority::NUM_LANES],
+    ready: Notify,
+}
+
+// the item refused by `try_push`
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryPushError<T> {
+    // the lane of the item is full
+    Full(T),
+    Closed(T),
+}
+
+struct LaneQueue<T> {
+    lanes: [VecDeque<T>; Priority::NUM_LANES],
+    // refuse the new items, the items already queued are kept
+    closed: bool,
+}
+
+impl<T> SendLanes<T> {
+    // up to `capacity` items in each lane
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: SyncMutex::new(LaneQueue {
+                lanes: Default::default(),
+                closed: false,
+            }),
+            slots: std::array::from_fn(|_| { Semaphore::new(capacity) }),
+            ready: Notify::new(),
+        }
+    }
+
+    // return the item back if the lanes were closed
+    pub async fn push(&self, priority: Priority, item: T) -> Result<(), T> {
+        let r = self.slots[priority.lane()].acquire().await;
+        let permit = match r {
+            Ok(p) => { p }
+            Err(_) => { return Err(item); }
+        };
+        self.push_permit(priority, item, permit)
+    }
+
+    // push the item if its lane has a free slot, without waiting
+    pub fn try_push(&self, priority: Priority, item: T) -> Result<(), TryPushError<T>> {
+        let permit = match self.slots[priority.lane()].try_acquire() {
+            Ok(p) => { p }
+            Err(TryAcquireError::NoPermits) => { return Err(TryPushError::Full(item)); }
+            Err(TryAcquireError::Closed) => { return Err(TryPushError::Closed(item)); }
+        };
+        self.push_permit(priority, item, permit).map_err(TryPushError::Closed)
+    }
+
+    // Push the item, when its lane is full, the oldest item of the lane is replaced, and
+    // returned, the slot of it is taken over by the new item. Wait for a slot as `push` if no
+    // item of the lane can be evicted.
+    pub async fn push_evict(&self, priority: Priority, item: T) -> Result<Option<T>, T> {
+        let item = match self.try_push(priority, item) {
+            Ok(()) => { return Ok(None); }
+            Err(TryPushError::Closed(item)) => { return Err(item); }
+            Err(TryPushError::Full(item)) => { item }
+        };
+        // the lock is not held across the wait
+        let r_evict = {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.closed {
+                return Err(item);
+            }
+            let lane = &mut queue.lanes[priority.lane()];
+            match lane.pop_front() {
+                Some(oldest) => {
+                    lane.push_back(item);
+                    Ok(oldest)
+                }
+                None => { Err(item) }
+            }
+        };
+        match r_evict {
+            Ok(oldest) => {
+                self.ready.notify_one();
+                Ok(Some(oldest))
+            }
+            Err(item) => { self.push(priority, item).await.map(|_| { None }) }
+        }
+    }
+
+    fn push_permit(&self, priority: Priority, item: T, permit: SemaphorePermit<'_>) -> Result<(), T> {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.closed {
+                return Err(item);
+            }
+            queue.lanes[priority.lane()].push_back(item);
+        }
+        // the slot is given back when the item is popped
+        permit.forget();
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    // close the lanes and push the last item, it is popped after all the queued items
+    pub fn close_with(&self, item: T) -> Result<(), T> {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.closed {
+                return Err(item);
+            }
+            queue.closed = true;
+            queue.lanes[Priority::Low.lane()].push_back(item);
+        }
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    // close the lanes and take all the items in the order of `pop`, the writer stopped
+    pub fn close_drain(&self) -> Vec<T> {
+        let mut queue = self.queue.lock().unwrap();
+        let closed = queue.closed;
+        queue.closed = true;
+        let mut items = vec![];
+        for (i, lane) in queue.lanes.iter_mut().enumerate() {
+            // the last item pushed by close_with did not take a slot
+            let slots = if closed && i == Priority::Low.lane() {
+                lane.len().saturating_sub(1)
+            } else {
+                lane.len()
+            };
+            self.slots[i].add_permits(slots);
+            items.extend(lane.drain(..));
+        }
+        items
+    }
+
+    // the number of the items in the lanes
+    pub fn queued(&self) -> usize {
+        let queue = self.queue.lock().unwrap();
+        queue.lanes.iter().map(|l| { l.len() }).sum()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        let queue = self.queue.lock().unwrap();
+        queue.closed
+    }
+
+    pub async fn pop(&self) -> T {
+        loop {
+            let opt = self.try_pop();
+            if let Some(item) = opt {
+                return item;
+            }
+            // notify_one stores a permit when there is no waiter, a push between try_pop and
+            // here is not missed
+            self.ready.notified().await;
+        }
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+        let closed = queue.closed;
+        for (i, lane) in queue.lanes.iter_mut().enumerate() {
+            if let Some(item) = lane.pop_front() {
+                // the last item pushed by close_with did not take a slot
+                let is_last = closed && i == Priority::Low.lane() && lane.is_empty();
+                if !is_last {
+                    self.slots[i].add_permits(1);
+                }
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use crate::priority::Priority;
+    use crate::send_lanes::{SendLanes, TryPushError};
+
+    #[tokio::test]
+    async fn test_send_lanes_order() {
+        let lanes = SendLanes::new(16);
+        lanes.push(Priority::Low, 1).await.unwrap();
+        lanes.push(Priority::Normal, 2).await.unwrap();
+        lanes.push(Priority::Low, 3).await.unwrap();
+        lanes.push(Priority::High, 4).await.unwrap();
+        lanes.push(Priority::Normal, 5).await.unwrap();
+        let mut order = vec![];
+        while let Some(i) = lanes.try_pop() {
+            order.push(i);
+        }
+        assert_eq!(order, vec![4, 2, 5, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_send_lanes_close() {
+        let lanes = SendLanes::new(16);
+        lanes.push(Priority::Low, 1).await.unwrap();
+        lanes.push(Priority::High, 2).await.unwrap();
+        lanes.close_with(0).unwrap();
+        assert!(lanes.is_closed());
+        assert_eq!(lanes.push(Priority::High, 3).await, Err(3));
+        assert_eq!(lanes.close_with(4), Err(4));
+        assert_eq!(lanes.pop().await, 2);
+        assert_eq!(lanes.pop().await, 1);
+        assert_eq!(lanes.pop().await, 0);
+        assert_eq!(lanes.try_pop(), None);
+    }
+
+    #[tokio::test]
+    async fn test_send_lanes_close_drain() {
+        let lanes = SendLanes::new(1);
+        lanes.push(Priority::Normal, 1).await.unwrap();
+        lanes.push(Priority::High, 2).await.unwrap();
+        assert_eq!(lanes.close_drain(), vec![2, 1]);
+        assert!(lanes.is_closed());
+        // the slots were given back, the push is refused rather than waiting
+        assert_eq!(lanes.push(Priority::Normal, 3).await, Err(3));
+        assert_eq!(lanes.close_with(4), Err(4));
+        assert!(lanes.close_drain().is_empty());
+
+        let lanes = SendLanes::new(16);
+        lanes.push(Priority::Low, 1).await.unwrap();
+        lanes.close_with(0).unwrap();
+        assert_eq!(lanes.close_drain(), vec![1, 0]);
+    }
+
+    // a full lane does not block the push of another one
+    #[tokio::test]
+    async fn test_send_lanes_capacity() {
+        let lanes = SendLanes::new(1);
+        lanes.push(Priority::Normal, 1).await.unwrap();
+        let r = timeout(Duration::from_millis(50), lanes.push(Priority::Normal, 2)).await;
+        assert!(r.is_err());
+        lanes.push(Priority::High, 3).await.unwrap();
+        assert_eq!(lanes.pop().await, 3);
+        assert_eq!(lanes.pop().await, 1);
+        lanes.push(Priority::Normal, 4).await.unwrap();
+        assert_eq!(lanes.pop().await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_send_lanes_try_push() {
+        let lanes = SendLanes::new(1);
+        lanes.try_push(Priority::Normal, 1).unwrap();
+        assert_eq!(lanes.try_push(Priority::Normal, 2), Err(TryPushError::Full(2)));
+        lanes.try_push(Priority::High, 3).unwrap();
+        assert_eq!(lanes.pop().await, 3);
+        assert_eq!(lanes.pop().await, 1);
+        lanes.try_push(Priority::Normal, 4).unwrap();
+        lanes.close_with(0).unwrap();
+        assert_eq!(lanes.try_push(Priority::Low, 5), Err(TryPushError::Closed(5)));
+    }
+
+    // the oldest item of a full lane is replaced, the other lanes are kept
+    #[tokio::test]
+    async fn test_send_lanes_push_evict() {
+        let lanes = SendLanes::new(2);
+        assert_eq!(lanes.push_evict(Priority::Normal, 1).await, Ok(None));
+        assert_eq!(lanes.push_evict(Priority::Normal, 2).await, Ok(None));
+        lanes.push(Priority::Low, 3).await.unwrap();
+        assert_eq!(lanes.push_evict(Priority::Normal, 4).await, Ok(Some(1)));
+        assert_eq!(lanes.push_evict(Priority::Normal, 5).await, Ok(Some(2)));
+        assert_eq!(lanes.queued(), 3);
+        assert_eq!(lanes.pop().await, 4);
+        assert_eq!(lanes.pop().await, 5);
+        assert_eq!(lanes.pop().await, 3);
+        // the slots were kept by the new items, and given back by the pops
+        lanes.push(Priority::Normal, 6).await.unwrap();
+        lanes.push(Priority::Normal, 7).await.unwrap();
+        assert!(lanes.try_push(Priority::Normal, 8).is_err());
+        lanes.close_with(0).unwrap();
+        assert_eq!(lanes.push_evict(Priority::Normal, 9).await, Err(9));
+    }
+}
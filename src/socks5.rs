@@ -0,0 +1,134 @@
+use std::net::SocketAddr;
+
+use scupt_util::res::Res;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::net_error;
+use crate::proxy::ProxyConfig;
+
+const SOCKS_VERSION: u8 = 5;
+
+const METHOD_NO_AUTH: u8 = 0x00;
+
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+
+const METHOD_NOT_ACCEPTABLE: u8 = 0xff;
+
+// the version of the username/password sub-negotiation, RFC 1929
+const AUTH_VERSION: u8 = 1;
+
+const COMMAND_CONNECT: u8 = 1;
+
+const ATYP_IPV4: u8 = 1;
+
+const ATYP_DOMAIN: u8 = 3;
+
+const ATYP_IPV6: u8 = 4;
+
+const REPLY_SUCCEEDED: u8 = 0;
+
+// negotiate the tunnel to the address on the stream connected to the proxy, the failures are
+// `net_error::proxy_error`
+pub(crate) async fn socks5_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    address: SocketAddr,
+    proxy: &ProxyConfig,
+) -> Res<()> {
+    let map_err = |e: std::io::Error| { net_error::proxy_error(proxy.address(), &e.to_string()) };
+    let methods = match proxy.auth() {
+        Some(_) => { vec![METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD] }
+        None => { vec![METHOD_NO_AUTH] }
+    };
+    let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+    greeting.extend_from_slice(&methods);
+    stream.write_all(&greeting).await.map_err(map_err)?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await.map_err(map_err)?;
+    if choice[0] != SOCKS_VERSION {
+        return Err(net_error::proxy_error(proxy.address(), &format!("not a SOCKS5 proxy, version {}", choice[0])));
+    }
+    match (choice[1], proxy.auth()) {
+        (METHOD_NO_AUTH, _) => {}
+        (METHOD_USERNAME_PASSWORD, Some((username, password))) => {
+            let mut request = vec![AUTH_VERSION];
+            for field in [username, password] {
+                if field.is_empty() || field.len() > 255 {
+                    return Err(net_error::proxy_error(proxy.address(), "the username or password is not of 1 to 255 bytes"));
+                }
+                request.push(field.len() as u8);
+                request.extend_from_slice(field.as_bytes());
+            }
+            stream.write_all(&request).await.map_err(map_err)?;
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await.map_err(map_err)?;
+            if status[1] != 0 {
+                return Err(net_error::proxy_error(proxy.address(), "authentication failed"));
+            }
+        }
+        (METHOD_NOT_ACCEPTABLE, _) => {
+            return Err(net_error::proxy_error(proxy.address(), "no acceptable authentication method"));
+        }
+        (method, _) => {
+            return Err(net_error::proxy_error(proxy.address(), &format!("unexpected authentication method {}", method)));
+        }
+    }
+
+    let mut request = vec![SOCKS_VERSION, COMMAND_CONNECT, 0];
+    match (proxy.remote_host(), address) {
+        (Some(host), _) => {
+            if host.is_empty() || host.len() > 255 {
+                return Err(net_error::proxy_error(proxy.address(), "the remote host is not of 1 to 255 bytes"));
+            }
+            request.push(ATYP_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+        (None, SocketAddr::V4(a)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&a.ip().octets());
+        }
+        (None, SocketAddr::V6(a)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&a.ip().octets());
+        }
+    }
+    request.extend_from_slice(&address.port().to_be_bytes());
+    stream.write_all(&request).await.map_err(map_err)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.map_err(map_err)?;
+    if reply[1] != REPLY_SUCCEEDED {
+        return Err(net_error::proxy_error(proxy.address(), &format!("connect to {} failed, {}", address, reply_message(reply[1]))));
+    }
+    // skip the bound address and port
+    let size = match reply[3] {
+        ATYP_IPV4 => { 4 }
+        ATYP_IPV6 => { 16 }
+        ATYP_DOMAIN => {
+            let mut n = [0u8; 1];
+            stream.read_exact(&mut n).await.map_err(map_err)?;
+            n[0] as usize
+        }
+        atyp => {
+            return Err(net_error::proxy_error(proxy.address(), &format!("unexpected address type {}", atyp)));
+        }
+    };
+    let mut bound = vec![0u8; size + 2];
+    stream.read_exact(&mut bound).await.map_err(map_err)?;
+    Ok(())
+}
+
+fn reply_message(reply: u8) -> String {
+    let message = match reply {
+        1 => { "general SOCKS server failure" }
+        2 => { "connection not allowed by ruleset" }
+        3 => { "network unreachable" }
+        4 => { "host unreachable" }
+        5 => { "connection refused" }
+        6 => { "TTL expired" }
+        7 => { "command not supported" }
+        8 => { "address type not supported" }
+        _ => { "unknown reply" }
+    };
+    format!("reply {}, {}", reply, message)
+}
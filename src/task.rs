@@ -1,8 +1,12 @@
+#[cfg(feature = "task-trace")]
 use std::collections::VecDeque;
 use std::future::Future;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+#[cfg(feature = "task-trace")]
+use std::sync::Mutex;
 use std::time::Duration;
 
+#[cfg(feature = "task-trace")]
 use async_backtrace::Location as BtLoc;
 use lazy_static::lazy_static;
 use scc::HashIndex;
@@ -30,6 +34,8 @@ pub struct TaskContext {
     notifier:Notifier,
     local_task:bool,
     id:u128,
+    // the locations entered by `task_trace!`, with the feature `task-trace`
+    #[cfg(feature = "task-trace")]
     backtrace: Mutex<VecDeque<BtLoc>>,
 }
 
@@ -38,6 +44,7 @@ pub struct Trace {
 }
 
 impl Trace {
+    #[cfg(feature = "task-trace")]
     pub fn new(location: BtLoc) -> Self {
         Self::enter(location);
         Self {
@@ -45,6 +52,7 @@ impl Trace {
         }
     }
 
+    #[cfg(feature = "task-trace")]
     fn enter(location: BtLoc) {
         let _id = this_task_id();
         let opt = TaskContext::get(_id);
@@ -56,6 +64,7 @@ impl Trace {
         }
     }
 
+    #[cfg(feature = "task-trace")]
     fn exit() {
         let _id = this_task_id();
         let opt = TaskContext::get(_id);
@@ -91,12 +100,29 @@ impl Trace {
     }
 }
 
+// Render the in-flight tasks, to be logged when things wedge, such as on a watchdog timeout:
+// the tasks spawned by this module, with their names and `task_trace!` locations, and the
+// async-backtrace tree of the `#[async_backtrace::framed]` futures, with the feature `task-trace`.
+pub fn dump_tasks() -> String {
+    let mut s = String::new();
+    s.push_str("tasks:\n");
+    s.push_str(Trace::dump_task_trace().as_str());
+    #[cfg(feature = "task-trace")]
+    {
+        s.push_str("async backtrace:\n");
+        s.push_str(async_backtrace::taskdump_tree(false).as_str());
+    }
+    s
+}
+
+#[cfg(feature = "task-trace")]
 impl Drop for Trace {
     fn drop(&mut self) {
         Trace::exit()
     }
 }
 
+#[cfg(feature = "task-trace")]
 #[macro_export]
 macro_rules! task_trace {
     () => {
@@ -107,6 +133,15 @@ macro_rules! task_trace {
     };
 }
 
+// the guard is an empty Trace without the feature `task-trace`, it does nothing
+#[cfg(not(feature = "task-trace"))]
+#[macro_export]
+macro_rules! task_trace {
+    () => {
+        $crate::task::Trace {}
+    };
+}
+
 #[macro_export]
 macro_rules! dump_task_trace {
     () => {
@@ -144,6 +179,7 @@ impl TaskContext {
             notifier,
             local_task,
             id,
+            #[cfg(feature = "task-trace")]
             backtrace: Default::default(),
         };
         let ret = Arc::new(r);
@@ -179,16 +215,24 @@ impl TaskContext {
         self.notifier.clone()
     }
 
+    #[cfg(feature = "task-trace")]
     pub fn enter(&self, l: BtLoc) {
         let mut location = self.backtrace.lock().unwrap();
         location.push_back(l);
     }
 
+    #[cfg(feature = "task-trace")]
     pub fn exit(&self) {
         let mut location = self.backtrace.lock().unwrap();
         let _ = location.pop_back();
     }
 
+    #[cfg(not(feature = "task-trace"))]
+    pub fn backtrace(&self) -> String {
+        String::new()
+    }
+
+    #[cfg(feature = "task-trace")]
     pub fn backtrace(&self) -> String {
         let deque = self.backtrace.lock().unwrap();
         let mut s = String::new();
@@ -216,8 +260,10 @@ pub fn spawn_local_task<F>(cancel_notifier: Notifier, _name: &str, future: F) ->
         F::Output: 'static,
 {
     let id = new_task_id();
-    let _ = TaskContext::new_context(id, _name.to_string(), false, cancel_notifier.clone());
+    let _ = TaskContext::new_context(id, _name.to_string(), true, cancel_notifier.clone());
+    let guard = cancel_notifier.enter_task();
     Ok(task::spawn_local(TASK_ID.scope(id, async move {
+        let _guard = guard;
         let r = __select_local_till_done(cancel_notifier, future).await;
         let _ = TaskContext::remove_context(id);
         r
@@ -230,7 +276,9 @@ pub fn spawn_local_task<F>(cancel_notifier: Notifier, name: &str, future: F) ->
         F: Future + 'static,
         F::Output: 'static,
 {
+    let guard = cancel_notifier.enter_task();
     let r = task::Builder::default().name(name).spawn_local(async move {
+        let _guard = guard;
         __select_local_till_done(cancel_notifier, future).await
     });
     match r {
@@ -247,7 +295,9 @@ pub fn spawn_task<F>(cancel_notifier: Notifier,  _name: &str, future: F) -> Res<
 {
     let id = new_task_id();
     let _ = TaskContext::new_context(id, _name.to_string(), false, cancel_notifier.clone());
+    let guard = cancel_notifier.enter_task();
     Ok(task::spawn(TASK_ID.scope(id, async move {
+        let _guard = guard;
         let r = __select_till_done(cancel_notifier, future).await;
         let _ = TaskContext::remove_context(id);
         r
@@ -265,9 +315,15 @@ pub fn spawn_local_task_timeout<F>(
         F: Future + Send + 'static,
         F::Output: Send + 'static,
 {
-    Ok(task::spawn_local(async move {
-        __select_local_till_done_or_timeout(cancel_notifier, duration, future).await
-    }))
+    let id = new_task_id();
+    let _ = TaskContext::new_context(id, _name.to_string(), true, cancel_notifier.clone());
+    let guard = cancel_notifier.enter_task();
+    Ok(task::spawn_local(TASK_ID.scope(id, async move {
+        let _guard = guard;
+        let r = __select_local_till_done_or_timeout(cancel_notifier, duration, future).await;
+        let _ = TaskContext::remove_context(id);
+        r
+    })))
 }
 
 #[cfg(task_name)]
@@ -276,7 +332,9 @@ pub fn spawn_task<F>(cancel_notifier: Notifier, name: &str, future: F) -> Res<Jo
         F: Future + Send + 'static,
         F::Output: Send + 'static,
 {
+    let guard = cancel_notifier.enter_task();
     let r = task::Builder::default().name(name).spawn(async move {
+        let _guard = guard;
         __select_till_done(cancel_notifier, future).await
     });
     match r {
@@ -291,7 +349,9 @@ pub fn spawn_task<F>(cancel_notifier: Notifier, duration: Duration, name: &str,
         F: Future + Send + 'static,
         F::Output: Send + 'static,
 {
+    let guard = cancel_notifier.enter_task();
     let r = task::Builder::default().name(name).spawn(async move {
+        let _guard = guard;
         __select_local_till_done_or_timeout(cancel_notifier, duration, future).await
     });
     match r {
@@ -0,0 +1,259 @@
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use tracing::trace;
+
+use crate::endpoint_async::EndpointAsync;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+pub type MessagePredicate<M> = Arc<dyn Fn(&Message<M>) -> bool + Send + Sync>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultDirection {
+    // the rule applies to the outgoing messages, the peer is the destination of the message
+    Send,
+    // the rule applies to the incoming messages, the peer is the source of the message
+    Recv,
+    // the rule applies to the messages of both directions
+    Both,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultAction {
+    Drop,
+    Delay(Duration),
+    Duplicate,
+    // hold the message, and pass it after the next one
+    Reorder,
+}
+
+// what happens to a message sent across a partition
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PartitionMode {
+    // the send succeeds, the message is lost
+    #[default]
+    Drop,
+    // the send fails with `net_error::partitioned`
+    Error,
+}
+
+pub struct FaultRule<M: MsgTrait + 'static> {
+    direction: FaultDirection,
+    action: FaultAction,
+    opt_peer: Option<NID>,
+    opt_predicate: Option<MessagePredicate<M>>,
+    // the rule applies to the first N matched messages, None for unlimited
+    opt_times: Option<u64>,
+    // the chance a matched message is applied, 1.0 for always
+    probability: f64,
+}
+
+// Fault injection rules of a node, only applied when the node enables testing.
+// The rules are applied by the endpoints in order, the first matched rule decides the action.
+// Note that the endpoints of a testing node do not write outgoing messages to the network,
+// the rules of direction FaultDirection::Send take effect on the endpoints which write.
+// A partition splits the nodes into groups, the messages between the nodes of different groups
+// are cut in both directions, on the established endpoints too, and the connects across the cut
+// fail. The nodes not in any group are not affected.
+pub struct TestController<M: MsgTrait + 'static> {
+    rules: SyncMutex<Vec<FaultRule<M>>>,
+    partition: SyncMutex<Vec<Vec<NID>>>,
+    partition_mode: SyncMutex<PartitionMode>,
+    // the node is cut from all the peers
+    isolated: SyncMutex<bool>,
+    // the endpoints of the node, closed by `close_connections`
+    endpoints: SyncMutex<Vec<Weak<dyn EndpointAsync<M>>>>,
+}
+
+// the controller of a testing node injects the faults on its endpoints
+pub type FaultInjector<M> = TestController<M>;
+
+impl<M: MsgTrait + 'static> FaultRule<M> {
+    pub fn new(direction: FaultDirection, action: FaultAction) -> Self {
+        Self {
+            direction,
+            action,
+            opt_peer: None,
+            opt_predicate: None,
+            opt_times: None,
+            probability: 1.0,
+        }
+    }
+
+    pub fn set_peer(self, peer: NID) -> Self {
+        let mut s = self;
+        s.opt_peer = Some(peer);
+        s
+    }
+
+    pub fn set_predicate(self, predicate: MessagePredicate<M>) -> Self {
+        let mut s = self;
+        s.opt_predicate = Some(predicate);
+        s
+    }
+
+    pub fn set_times(self, times: u64) -> Self {
+        let mut s = self;
+        s.opt_times = Some(times);
+        s
+    }
+
+    // a matched message is applied by the chance, the others pass
+    pub fn set_probability(self, probability: f64) -> Self {
+        let mut s = self;
+        s.probability = probability;
+        s
+    }
+
+    fn is_match(&self, direction: FaultDirection, message: &Message<M>) -> bool {
+        if (self.direction != direction && self.direction != FaultDirection::Both)
+            || self.opt_times == Some(0) {
+            return false;
+        }
+        if let Some(peer) = self.opt_peer {
+            let id = match direction {
+                FaultDirection::Send => { message.dest() }
+                FaultDirection::Recv | FaultDirection::Both => { message.source() }
+            };
+            if id != peer {
+                return false;
+            }
+        }
+        let matched = match &self.opt_predicate {
+            Some(predicate) => { predicate(message) }
+            None => { true }
+        };
+        matched && (self.probability >= 1.0 || rand::random::<f64>() < self.probability)
+    }
+}
+
+impl<M: MsgTrait + 'static> TestController<M> {
+    pub fn new() -> Self {
+        Self {
+            rules: SyncMutex::new(vec![]),
+            partition: SyncMutex::new(vec![]),
+            partition_mode: SyncMutex::new(PartitionMode::default()),
+            isolated: SyncMutex::new(false),
+            endpoints: SyncMutex::new(vec![]),
+        }
+    }
+
+    pub fn add_rule(&self, rule: FaultRule<M>) {
+        let mut rules = self.rules.lock().unwrap();
+        rules.push(rule);
+    }
+
+    pub fn clear(&self) {
+        let mut rules = self.rules.lock().unwrap();
+        rules.clear();
+    }
+
+    // drop the next N messages of both directions, before the other rules apply
+    pub fn drop_next(&self, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let mut rules = self.rules.lock().unwrap();
+        rules.insert(0, FaultRule::new(FaultDirection::Both, FaultAction::Drop).set_times(n));
+    }
+
+    pub fn partition(&self, groups: Vec<Vec<NID>>) {
+        trace!("partition {:?}", groups);
+        let mut partition = self.partition.lock().unwrap();
+        *partition = groups;
+    }
+
+    // cut the node from all the peers, as a partition of its own
+    pub fn isolate(&self) {
+        trace!("isolate node");
+        let mut isolated = self.isolated.lock().unwrap();
+        *isolated = true;
+    }
+
+    // undo both `partition` and `isolate`
+    pub fn heal(&self) {
+        trace!("heal partition");
+        {
+            let mut partition = self.partition.lock().unwrap();
+            partition.clear();
+        }
+        let mut isolated = self.isolated.lock().unwrap();
+        *isolated = false;
+    }
+
+    // how the sends across the partition behave, the incoming messages across it are always dropped
+    pub fn set_partition_mode(&self, mode: PartitionMode) {
+        let mut partition_mode = self.partition_mode.lock().unwrap();
+        *partition_mode = mode;
+    }
+
+    pub fn partition_mode(&self) -> PartitionMode {
+        let partition_mode = self.partition_mode.lock().unwrap();
+        *partition_mode
+    }
+
+    pub fn is_partitioned(&self, node_id: NID, peer: NID) -> bool {
+        if *self.isolated.lock().unwrap() {
+            return true;
+        }
+        let partition = self.partition.lock().unwrap();
+        let group_of = |id: NID| { partition.iter().position(|g| { g.contains(&id) }) };
+        match (group_of(node_id), group_of(peer)) {
+            (Some(g1), Some(g2)) => { g1 != g2 }
+            _ => { false }
+        }
+    }
+
+    // close all the live endpoints of the node, as if the connections were broken, return the
+    // number of the closed endpoints
+    pub async fn close_connections(&self) -> usize {
+        let endpoints: Vec<Arc<dyn EndpointAsync<M>>> = {
+            let endpoints = self.endpoints.lock().unwrap();
+            endpoints.iter().filter_map(|e| { e.upgrade() }).collect()
+        };
+        let mut n = 0;
+        for ep in endpoints {
+            if ep.is_closed() {
+                continue;
+            }
+            trace!("close connection {}", ep.remote_address());
+            // the write direction is closed first, the endpoint is known closed at once
+            let _ = ep.shutdown_write().await;
+            let _ = ep.close().await;
+            n += 1;
+        }
+        n
+    }
+
+    pub(crate) fn register_endpoint(&self, endpoint: &Arc<dyn EndpointAsync<M>>) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        endpoints.retain(|e| { e.strong_count() > 0 });
+        endpoints.push(Arc::downgrade(endpoint));
+    }
+
+    // the action applied on the message, None to pass it
+    pub(crate) fn fault(&self, direction: FaultDirection, message: &Message<M>) -> Option<FaultAction> {
+        let mut rules = self.rules.lock().unwrap();
+        let opt_index = rules.iter().position(|r| { r.is_match(direction, message) });
+        let index = opt_index?;
+        let rule = &mut rules[index];
+        let action = rule.action;
+        if let Some(times) = rule.opt_times.as_mut() {
+            *times -= 1;
+            if *times == 0 {
+                let _ = rules.remove(index);
+            }
+        }
+        trace!("inject fault {:?}, {:?}", action, direction);
+        Some(action)
+    }
+}
+
+impl<M: MsgTrait + 'static> Default for TestController<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
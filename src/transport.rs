@@ -0,0 +1,209 @@
+use std::any::Any;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::res::Res;
+use socket2::SockRef;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+use crate::memory_transport::{memory_connect, MemoryListener};
+use crate::net_error;
+use crate::proxy::{ProxyConfig, proxy_connect};
+
+// how a node serves and connects
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Transport {
+    #[default]
+    Tcp,
+    // in-memory connections in the process, the addresses are logical addresses
+    Memory,
+    // one message per datagram, without handshake, ordering, or delivery guarantees,
+    // a served address creates a single inbound endpoint for all the peers
+    #[cfg(feature = "udp")]
+    Udp,
+    // the streams of the StreamTransport set on the node, see `Node::set_stream_transport`
+    Custom,
+}
+
+// the byte stream under an endpoint
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+    // downcast a NetStream to the stream of the transport, see `RawStream::into_tcp_stream`
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send>;
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> AsyncStream for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send> {
+        self
+    }
+}
+
+pub type NetStream = Box<dyn AsyncStream>;
+
+// The stream of an endpoint handed over by `EndpointAsync::into_raw_stream`, to speak another
+// protocol on the connection. The read buffer holds the bytes already read from the socket
+// but not decoded as a frame, they come before anything read from the stream.
+pub struct RawStream {
+    pub stream: NetStream,
+    pub read_buf: BytesMut,
+}
+
+impl RawStream {
+    // the TcpStream of an endpoint of Transport::Tcp, or None for the other transports
+    pub fn into_tcp_stream(self) -> Option<(TcpStream, BytesMut)> {
+        let read_buf = self.read_buf;
+        match self.stream.into_any().downcast::<TcpStream>() {
+            Ok(s) => { Some((*s, read_buf)) }
+            Err(_) => { None }
+        }
+    }
+}
+
+// A stream transport provided by the user, such as a tunnel of another library, the streams
+// are framed and handled as the TCP ones, the addresses are only passed through.
+// scupt-net has no TLS of its own, a TLS transport, with or without client certificates, can
+// be plugged in here: a listener verifying the client certificates completes the handshake in
+// `accept`, and drops a rejected client there and waits for the next one, an error returned
+// by `accept` stops the serving of the node.
+// The verified identity of a peer is not passed to the endpoint.
+#[async_trait]
+pub trait StreamTransport: Send + Sync {
+    // return the stream and the address of the remote
+    async fn connect(&self, address: SocketAddr) -> Res<(NetStream, SocketAddr)>;
+
+    async fn bind(&self, address: SocketAddr) -> Res<Box<dyn StreamListener>>;
+}
+
+#[async_trait]
+pub trait StreamListener: Send {
+    // return the stream and the address of the remote
+    async fn accept(&mut self) -> Res<(NetStream, SocketAddr)>;
+}
+
+pub(crate) enum Listener {
+    // the listener and its address
+    Tcp(TcpListener, SocketAddr),
+    Memory(MemoryListener),
+    Custom(Box<dyn StreamListener>),
+}
+
+// Transport::Custom without a StreamTransport set
+fn no_stream_transport() -> ET {
+    net_error::invalid_option_of("stream_transport", "Transport::Custom without a StreamTransport")
+}
+
+impl Listener {
+    // the stream transports only, the backlog, the reuse address and the dual stack apply to TCP
+    pub async fn bind(
+        address: SocketAddr,
+        transport: Transport,
+        opt_custom: Option<Arc<dyn StreamTransport>>,
+        backlog: u32,
+        reuse_address: bool,
+        opt_dual_stack: Option<bool>,
+    ) -> Res<Self> {
+        if transport == Transport::Custom {
+            let t = opt_custom.ok_or_else(no_stream_transport)?;
+            let l = t.bind(address).await?;
+            Ok(Listener::Custom(l))
+        } else if transport == Transport::Memory {
+            let l = MemoryListener::bind(address)?;
+            Ok(Listener::Memory(l))
+        } else {
+            let map_err = |e| { net_error::io_error(e, "bind", address) };
+            let socket = if address.is_ipv4() {
+                TcpSocket::new_v4().map_err(map_err)?
+            } else {
+                TcpSocket::new_v6().map_err(map_err)?
+            };
+            // SO_REUSEADDR on windows lets another socket bind the same port while it is
+            // listening, it is not set there, as TcpListener::bind does
+            #[cfg(not(windows))]
+            socket.set_reuseaddr(reuse_address).map_err(map_err)?;
+            #[cfg(windows)]
+            let _ = reuse_address;
+            if let (Some(dual_stack), true) = (opt_dual_stack, address.is_ipv6()) {
+                SockRef::from(&socket).set_only_v6(!dual_stack).map_err(map_err)?;
+            }
+            socket.bind(address).map_err(map_err)?;
+            let l = socket.listen(backlog).map_err(map_err)?;
+            // the port chosen by the system for port 0
+            let local = l.local_addr().map_err(map_err)?;
+            Ok(Listener::Tcp(l, local))
+        }
+    }
+
+    // the bound address, None for a StreamTransport
+    pub fn local_address(&self) -> Option<SocketAddr> {
+        match self {
+            Listener::Tcp(_, address) => { Some(*address) }
+            Listener::Memory(l) => { Some(l.address()) }
+            Listener::Custom(_) => { None }
+        }
+    }
+
+    // return the stream and the address of the remote
+    pub async fn accept(&mut self) -> Res<(NetStream, SocketAddr)> {
+        match self {
+            Listener::Tcp(l, address) => {
+                let r = l.accept().await;
+                let (s, addr) = r.map_err(|e| { net_error::io_error(e, "accept", *address) })?;
+                Ok((Box::new(s), canonical_address(addr)))
+            }
+            Listener::Memory(l) => {
+                let (s, addr) = l.accept().await?;
+                Ok((Box::new(s), addr))
+            }
+            Listener::Custom(l) => {
+                l.accept().await
+            }
+        }
+    }
+}
+
+// an IPv4-mapped IPv6 address, of an IPv4 peer of a dual stack socket, as the IPv4 address
+pub fn canonical_address(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(a) => {
+            match a.ip().to_ipv4_mapped() {
+                Some(ip) => { SocketAddr::new(IpAddr::V4(ip), a.port()) }
+                None => { address }
+            }
+        }
+        SocketAddr::V4(_) => { address }
+    }
+}
+
+// the stream transports only, return the stream and the address of the remote,
+// the nodelay and the proxy apply to TCP
+pub(crate) async fn connect(
+    address: SocketAddr,
+    transport: Transport,
+    opt_custom: Option<Arc<dyn StreamTransport>>,
+    nodelay: bool,
+    opt_proxy: Option<ProxyConfig>,
+) -> Res<(NetStream, SocketAddr)> {
+    if transport == Transport::Custom {
+        let t = opt_custom.ok_or_else(no_stream_transport)?;
+        t.connect(address).await
+    } else if transport == Transport::Memory {
+        let (s, addr) = memory_connect(address)?;
+        Ok((Box::new(s), addr))
+    } else if let Some(proxy) = opt_proxy {
+        let s = proxy_connect(address, &proxy, nodelay).await?;
+        Ok((Box::new(s), address))
+    } else {
+        let map_err = |e| { net_error::io_error(e, "connect", address) };
+        let r = TcpStream::connect(address).await;
+        let s = r.map_err(map_err)?;
+        if nodelay {
+            s.set_nodelay(true).map_err(map_err)?;
+        }
+        let r_addr = s.peer_addr();
+        let addr = canonical_address(r_addr.map_err(map_err)?);
+        Ok((Box::new(s), addr))
+    }
+}
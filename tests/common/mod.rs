@@ -0,0 +1,79 @@
+// The fixtures shared by the integration tests, each test file declares `mod common;`, and
+// uses the ones it needs.
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::handle_event::{FnHandler, HandleEvent};
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+pub enum TestMsg {
+    Id(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+// forward the accepted endpoints to the test
+pub struct AcceptHandler<M: MsgTrait + 'static> {
+    pub sender: mpsc::UnboundedSender<Arc<dyn EndpointAsync<M>>>,
+}
+
+#[async_trait]
+impl<M: MsgTrait + 'static> HandleEvent<M> for AcceptHandler<M> {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<M>>) -> Res<()> {
+        let _ = self.sender.send(endpoint);
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<M>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+// run the future as a task of the local set, until all the tasks of the set completed
+pub fn block_on_local<F: Future<Output=()> + 'static>(local: LocalSet, f: F) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    local.spawn_local(async move {
+        spawn_local_task(Notifier::new(), "test", f)
+    });
+    runtime.block_on(local);
+}
+
+// the node 2, connecting to the node 1 of the test, which handles no event
+pub fn client_node<M: MsgTrait + 'static>(notifier: &Notifier) -> Node<M, FnHandler<M>> {
+    NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<M, _>(FnHandler::<M>::new())
+        .unwrap()
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::caller::{Caller, CorrelationFn};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_call::{InflightPolicy, OptCall};
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Request(u64),
+    Response(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+const REPLY_DELAY: Duration = Duration::from_millis(200);
+
+// reply every request after a delay, and report when the requests arrived
+struct DelayHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<(u64, Instant)>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for DelayHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        let notifier = self.notifier.clone();
+        spawn_local_task(self.notifier.clone(), "reply", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let id = correlation_id(&m);
+                let _ = sender.send((id, Instant::now()));
+                let ep = endpoint.clone();
+                let _ = spawn_local_task(notifier.clone(), "delay reply", async move {
+                    tokio::time::sleep(REPLY_DELAY).await;
+                    let _ = ep.send(Message::new(TestMsg::Response(id), 1, 2)).await;
+                });
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+fn correlation_id(m: &Message<TestMsg>) -> u64 {
+    match m.clone().payload() {
+        TestMsg::Request(id) => { id }
+        TestMsg::Response(id) => { id }
+    }
+}
+
+fn correlation() -> CorrelationFn<TestMsg> {
+    Arc::new(correlation_id)
+}
+
+fn run_caller_test<F, Fut>(port: u16, f: F)
+    where F: FnOnce(Arc<dyn EndpointAsync<TestMsg>>, mpsc::UnboundedReceiver<(u64, Instant)>, Notifier) -> Fut,
+          Fut: Future<Output=()> + 'static,
+{
+    let notifier = Notifier::new();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, DelayHandler>::new(
+        1,
+        "node_1".to_string(),
+        DelayHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        f(ep, receiver, notifier.clone()).await;
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_caller_max_inflight() {
+    run_caller_test(8451, |ep, mut arrived, notifier| async move {
+        let opt = OptCall::new().set_max_inflight(2);
+        let caller = Arc::new(Caller::new(ep, correlation(), opt, notifier.clone()).unwrap());
+        let (done_sender, mut done) = mpsc::unbounded_channel();
+        for id in 1..=3 {
+            let c = caller.clone();
+            let done_sender = done_sender.clone();
+            spawn_local_task(notifier.clone(), "call", async move {
+                let r = c.call(Message::new(TestMsg::Request(id), 2, 1)).await.unwrap();
+                assert_eq!(r.payload(), TestMsg::Response(id));
+                let _ = done_sender.send((id, Instant::now()));
+            }).unwrap();
+        }
+        let mut arrived_at = vec![];
+        for _ in 0..3 {
+            arrived_at.push(arrived.recv().await.unwrap());
+        }
+        let mut done_at = vec![];
+        for _ in 0..3 {
+            done_at.push(done.recv().await.unwrap());
+        }
+        // the third request does not hit the wire before the first response arrived
+        let (third, third_arrived) = arrived_at[2];
+        let first_done = done_at[0].1;
+        assert!(done_at[0].0 != third);
+        assert!(third_arrived >= first_done);
+        assert_eq!(caller.inflight(), 0);
+    });
+}
+
+#[test]
+fn test_caller_inflight_policy() {
+    run_caller_test(8452, |ep, _arrived, notifier| async move {
+        let opt = OptCall::new()
+            .set_max_inflight(1)
+            .set_inflight_policy(InflightPolicy::Error)
+            .set_timeout(Duration::from_millis(50));
+        let caller = Arc::new(Caller::new(ep, correlation(), opt, notifier.clone()).unwrap());
+        let c = caller.clone();
+        spawn_local_task(notifier.clone(), "call", async move {
+            let r = c.call(Message::new(TestMsg::Request(1), 2, 1)).await;
+            assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let r = caller.call(Message::new(TestMsg::Request(2), 2, 1)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_too_many_inflight(e)));
+
+        // the timed out call released its permit
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(caller.inflight(), 0);
+        let r = caller.call(Message::new(TestMsg::Request(3), 2, 1)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+    });
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::client::{ClientBuilder, OptClient, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const NUM_MESSAGES: u64 = 100;
+
+const RECV_QUEUE_CAPACITY: usize = 4;
+
+// the messages sent in turn on two channels and the default one are received in order on each
+#[test]
+fn test_channel_interleaved() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8584".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8584".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        let client_ep = client.endpoint().unwrap();
+
+        let r = client_ep.open_channel(0).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+        let one = client_ep.open_channel(1).await.unwrap();
+        let two = client_ep.open_channel(2).await.unwrap();
+        assert!(matches!(client_ep.open_channel(1).await, Err(ET::ExistingSuchElement)));
+        let peer_one = server_ep.open_channel(1).await.unwrap();
+        let peer_two = server_ep.open_channel(2).await.unwrap();
+
+        for id in 0..NUM_MESSAGES {
+            one.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+            two.send(Message::new(TestMsg::Id(NUM_MESSAGES + id), 2, 1)).await.unwrap();
+            client.send(Message::new(TestMsg::Id(2 * NUM_MESSAGES + id), 2, 1)).await.unwrap();
+        }
+        for id in 0..NUM_MESSAGES {
+            assert_eq!(peer_two.recv().await.unwrap().payload(), TestMsg::Id(NUM_MESSAGES + id));
+        }
+        for id in 0..NUM_MESSAGES {
+            assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Id(2 * NUM_MESSAGES + id));
+            assert_eq!(peer_one.recv().await.unwrap().payload(), TestMsg::Id(id));
+        }
+
+        // and the other way
+        peer_one.send(Message::new(TestMsg::Id(1), 1, 2)).await.unwrap();
+        assert_eq!(one.recv().await.unwrap().payload(), TestMsg::Id(1));
+        notifier.notify_all();
+    });
+}
+
+// the peer stops sending on a channel no one receives, while the default channel is
+// delivered, the channel goes on after it was received
+#[test]
+fn test_channel_full() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8585".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let opt = OptClient {
+        recv_queue_capacity: RECV_QUEUE_CAPACITY,
+        ..OptClient::default()
+    };
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8585".to_string())
+        .set_notifier(notifier.clone())
+        .set_opt_client(opt)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        let client_ep = client.endpoint().unwrap();
+        let one = client_ep.open_channel(1).await.unwrap();
+        let peer_one = Arc::new(server_ep.open_channel(1).await.unwrap());
+
+        let sent = Arc::new(AtomicU64::new(0));
+        let task_sent = sent.clone();
+        let task_peer_one = peer_one.clone();
+        spawn_local_task(task_notifier, "send channel 1", async move {
+            for id in 0..NUM_MESSAGES {
+                task_peer_one.send(Message::new(TestMsg::Id(id), 1, 2)).await.unwrap();
+                let _ = task_sent.fetch_add(1, Ordering::SeqCst);
+            }
+        }).unwrap();
+
+        // the channel is full, the default one is not held back
+        timeout(Duration::from_secs(5), async {
+            while sent.load(Ordering::SeqCst) < RECV_QUEUE_CAPACITY as u64 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        for id in 0..NUM_MESSAGES {
+            server_ep.send(Message::new(TestMsg::Id(id), 1, 2)).await.unwrap();
+            let m = timeout(Duration::from_secs(1), client.recv()).await.unwrap().unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(id));
+        }
+        assert_eq!(sent.load(Ordering::SeqCst), RECV_QUEUE_CAPACITY as u64);
+
+        for id in 0..NUM_MESSAGES {
+            let m = timeout(Duration::from_secs(1), one.recv()).await.unwrap().unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(id));
+        }
+        notifier.notify_all();
+    });
+}
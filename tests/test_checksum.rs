@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use byteorder::ByteOrder;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame::{CHECKSUM_SIZE, crc32c, HEADER_SIZE, WireEndian};
+use scupt_net::handle_event::{FnHandler, HandleEvent};
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDS: u64 = 20;
+
+enum Event {
+    Accepted(Arc<dyn EndpointAsync<TestMsg>>),
+    Error(ET),
+    Disconnected(ET),
+}
+
+// forward the accepted endpoints, the errors and the disconnects to the test
+struct EventHandler {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EventHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let _ = self.sender.send(Event::Accepted(endpoint));
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ET) {
+        let _ = self.sender.send(Event::Error(error));
+    }
+
+    async fn on_disconnected(&self, _: SocketAddr, reason: ET) {
+        let _ = self.sender.send(Event::Disconnected(reason));
+    }
+
+    async fn on_stop(&self) {}
+}
+
+// both sides with checksums exchange the messages, the pings included
+#[test]
+fn test_checksum_round_trip() {
+    let notifier = Notifier::new();
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8621".to_string())
+        .enable_checksum(true)
+        .build::<TestMsg, _>(EventHandler { sender })
+        .unwrap();
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8621".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .enable_checksum(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = match events.recv().await.unwrap() {
+            Event::Accepted(ep) => { ep }
+            _ => { panic!("an event before the accept"); }
+        };
+        for i in 0..NUM_SENDS {
+            ep.send(Message::new(TestMsg::Data(i, vec![i as u8; i as usize * 100]), 2, 1)).await.unwrap();
+            let m = server_ep.recv().await.unwrap();
+            server_ep.send(Message::new(m.payload(), 1, 2)).await.unwrap();
+            match ep.recv().await.unwrap().payload() {
+                TestMsg::Data(n, data) => {
+                    assert_eq!(n, i);
+                    assert_eq!(data, vec![i as u8; i as usize * 100]);
+                }
+            }
+        }
+        ep.ping(Duration::from_secs(5)).await.unwrap();
+        notifier.notify_all();
+    });
+}
+
+// A relay between the client and the server flips the last byte of the first frame, the
+// server closes the connection with the mismatch of the checksums.
+#[test]
+fn test_checksum_corrupted_frame() {
+    let notifier = Notifier::new();
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8623".to_string())
+        .enable_checksum(true)
+        .build::<TestMsg, _>(EventHandler { sender })
+        .unwrap();
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let relay = TcpListener::bind("127.0.0.1:8622").await.unwrap();
+        let relayed = tokio::spawn(async move {
+            let (mut from, _) = relay.accept().await.unwrap();
+            let mut to = TcpStream::connect("127.0.0.1:8623").await.unwrap();
+            let mut prefix = [0u8; HEADER_SIZE + CHECKSUM_SIZE];
+            from.read_exact(&mut prefix).await.unwrap();
+            let size = WireEndian::read_u32(&prefix[..]) as usize;
+            let checksum = WireEndian::read_u32(&prefix[HEADER_SIZE..]);
+            let mut payload = vec![0u8; size];
+            from.read_exact(&mut payload).await.unwrap();
+            assert_eq!(crc32c(&payload), checksum);
+            payload[size - 1] ^= 0xff;
+            to.write_all(&prefix).await.unwrap();
+            to.write_all(&payload).await.unwrap();
+            (from, to, checksum, crc32c(&payload))
+        });
+        let address: SocketAddr = "127.0.0.1:8622".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .enable_checksum(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Data(1, vec![7; 64]), 2, 1)).await.unwrap();
+        let (_from, _to, expected, actual) = relayed.await.unwrap();
+
+        // the accept may be reported after the reader stopped
+        let (mut opt_ep, mut opt_error, mut opt_reason) = (None, None, None);
+        while opt_ep.is_none() || opt_error.is_none() || opt_reason.is_none() {
+            match events.recv().await.unwrap() {
+                Event::Accepted(ep) => { opt_ep = Some(ep); }
+                Event::Error(e) => { opt_error = Some(e); }
+                Event::Disconnected(e) => { opt_reason = Some(e); }
+            }
+        }
+        let e = opt_error.unwrap();
+        assert_eq!(net_error::checksums(&e), Some((expected, actual)), "{}", e.to_string());
+        assert!(net_error::is_checksum_mismatch(&opt_reason.unwrap()));
+        let e = opt_ep.unwrap().recv().await.unwrap_err();
+        assert!(net_error::is_checksum_mismatch(&e), "{}", e.to_string());
+        notifier.notify_all();
+    });
+}
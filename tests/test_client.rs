@@ -0,0 +1,510 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{Client, ClientBuilder, ClientState, OptClient, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::net_error::NetErrorKind;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::respond_handler::RespondHandler;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+#[derive(Debug)]
+enum Event {
+    Connected(bool),
+    Disconnected(SocketAddr),
+}
+
+// report the events of the client to the test
+struct EventHandler {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EventHandler {
+    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, endpoint: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        let _ = self.sender.send(Event::Connected(endpoint.is_ok()));
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_disconnected(&self, address: SocketAddr, _: ET) {
+        let _ = self.sender.send(Event::Disconnected(address));
+    }
+
+    async fn on_stop(&self) {}
+}
+
+// forward the messages received by the server to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+const BAD_ADDRESSES: [&str; 6] = ["", " ", "not an addr", "127.0.0.1", "127.0.0.1:", "127.0.0.1:65536"];
+
+#[test]
+fn test_client_bad_address() {
+    for addr in BAD_ADDRESSES {
+        let r = Client::<TestMsg>::new(
+            1,
+            "client_1".to_string(),
+            addr.to_string(),
+            OptClient::default(),
+            Notifier::new(),
+        );
+        match r {
+            Ok(_) => { panic!("create a client of a malformed address {:?}", addr); }
+            Err(e) => { assert!(net_error::is_addr_parse(&e)); }
+        }
+        let r = ClientBuilder::new()
+            .set_node_id(1)
+            .set_server_addr(addr.to_string())
+            .build::<TestMsg>();
+        assert!(matches!(r, Err(ref e) if net_error::is_addr_parse(e) || net_error::is_invalid_option(e)));
+    }
+}
+
+// the validation error names the offending field
+fn names_field(e: &ET, field: &str) -> bool {
+    matches!(e, ET::FatalError(s) if s.contains(field))
+}
+
+#[test]
+fn test_client_builder() {
+    let r = ClientBuilder::new()
+        .set_server_addr("127.0.0.1:8361".to_string())
+        .build::<TestMsg>();
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+
+    let r = ClientBuilder::new()
+        .set_node_id(1)
+        .build::<TestMsg>();
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+
+    let r = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr("not an addr".to_string())
+        .build::<TestMsg>();
+    assert!(matches!(r, Err(ref e) if net_error::is_addr_parse(e) && names_field(e, "server_addr")));
+
+    let r = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr("127.0.0.1:8361".to_string())
+        .set_write_batch(0, 1024)
+        .build::<TestMsg>();
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e) && names_field(e, "write_batch_max")));
+
+    let r = NodeBuilder::new()
+        .set_node_id(1)
+        .set_listen_address("not an addr".to_string())
+        .build::<TestMsg, _>(HandleEventDummy::default());
+    assert!(matches!(r, Err(ref e) if net_error::is_addr_parse(e) && names_field(e, "listen_address")));
+
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr("127.0.0.1:8361".to_string())
+        .enable_testing(true)
+        .build::<TestMsg>()
+        .unwrap();
+    assert_eq!(client.node_id(), 1);
+    assert_eq!(client.server_addr(), "127.0.0.1:8361".to_string());
+    assert!(client.test_controller().is_some());
+}
+
+#[test]
+fn test_client_wait_connected() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8362".parse().unwrap();
+    let server = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        // nobody connects
+        let r = client.wait_connected(Duration::from_millis(100)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+        assert_eq!(client.state(), ClientState::Disconnected);
+
+        let c = client.clone();
+        spawn_local_task(notifier.clone(), "connect", async move {
+            c.connect(OptClientConnect::new()).await.unwrap();
+        }).unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.wait_connected(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(client.state(), ClientState::Connected);
+        assert!(client.is_connected().await);
+        // already connected
+        client.wait_connected(Duration::from_millis(0)).await.unwrap();
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_client_handler() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8363".parse().unwrap();
+    // the server drops the accepted endpoints, the client is disconnected
+    let server = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build_with_handler::<TestMsg>(Arc::new(EventHandler { sender }))
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        assert!(matches!(receiver.recv().await.unwrap(), Event::Connected(true)));
+        match receiver.recv().await.unwrap() {
+            Event::Disconnected(a) => { assert_eq!(a, addr); }
+            e => { panic!("unexpected event {:?}", e); }
+        }
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_client_node_handle() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr("127.0.0.1:8364".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let handle = client.node_handle();
+    assert_eq!(handle.node_id(), 1);
+    assert_eq!(handle.name(), "client_1".to_string());
+    assert_eq!(handle.metrics().connections, 0);
+    assert!(handle.listen_address().is_none());
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        handle.shutdown().await.unwrap();
+        assert!(handle.stop_notify().is_notified());
+        // the shared notifier is not notified by stopping a node
+        assert!(!notifier.is_notified());
+    });
+}
+
+#[test]
+fn test_client_send_confirmed() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8365".parse().unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        client.send_confirmed(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg {});
+
+        client.disconnect().await.unwrap();
+        let r = client.send_confirmed(Message::new(TestMsg {}, 2, 1)).await;
+        assert!(matches!(r, Err(ET::NetNotConnected)));
+        notifier.notify_all();
+    });
+}
+
+// a server and a client built with every option set, and a message round trip
+#[test]
+fn test_client_builder_round_trip() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_name("server".to_string())
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8531".to_string())
+        .set_backlog(16)
+        .set_max_connections(4)
+        .set_write_batch(8, 4096)
+        .build::<TestMsg, _>(RecvHandler { notifier: notifier.clone(), sender })
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_name("client".to_string())
+        .set_server_addr("127.0.0.1:8531".to_string())
+        .set_notifier(notifier.clone())
+        .enable_dedup(true)
+        .set_idle_timeout_ms(10_000)
+        .set_write_batch(8, 4096)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        client.send(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg {});
+        notifier.notify_all();
+    });
+}
+
+// close is idempotent, and the sends racing with it either complete or fail as not connected
+#[test]
+fn test_client_close() {
+    let notifier = Notifier::new();
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8532".to_string())
+        .build::<TestMsg, _>(RecvHandler { notifier: notifier.clone(), sender })
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8532".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (r1, r2, r3) = tokio::join!(
+            client.send(Message::new(TestMsg {}, 2, 1)),
+            client.close(),
+            client.close(),
+        );
+        assert!(matches!(r1, Ok(()) | Err(ET::NetNotConnected)));
+        assert!(r2.is_ok());
+        assert!(r3.is_ok());
+        assert!(client.close().await.is_ok());
+        assert_eq!(client.state(), ClientState::Disconnected);
+        let r = client.send(Message::new(TestMsg {}, 2, 1)).await;
+        assert!(matches!(r, Err(ET::NetNotConnected)));
+        let r = client.connect(OptClientConnect::new()).await;
+        assert!(matches!(r, Err(ET::NetNotConnected)));
+        notifier.notify_all();
+    });
+}
+
+// the endpoints returned by connect_endpoint are owned by the caller, not used by send
+#[test]
+fn test_client_connect_endpoint() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8533".to_string())
+        .build::<TestMsg, _>(RecvHandler { notifier: notifier.clone(), sender })
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8533".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let ep1 = client.connect_endpoint(OptClientConnect::new()).await.unwrap();
+        let ep2 = client.connect_endpoint(OptClientConnect::new()).await.unwrap();
+        assert!(!client.is_connected().await);
+        let r = client.send(Message::new(TestMsg {}, 2, 1)).await;
+        assert!(matches!(r, Err(ET::NetNotConnected)));
+
+        ep1.send(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        ep2.send(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg {});
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg {});
+        ep1.close().await.unwrap();
+        ep2.close().await.unwrap();
+        client.close().await.unwrap();
+        notifier.notify_all();
+    });
+}
+
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    Ok(Message::new(m.payload(), 1, peer))
+}
+
+// the endpoint returned is the one `recv` reads from
+#[test]
+fn test_client_endpoint() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8553".to_string())
+        .build::<TestMsg, _>(RespondHandler::new(notifier.clone(), echo))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8553".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        assert!(client.endpoint().is_none());
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let ep = client.endpoint().unwrap();
+        ep.send(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().payload(), TestMsg {});
+
+        // a second endpoint on the node of the client
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let address: SocketAddr = "127.0.0.1:8553".parse().unwrap();
+        let ep2 = client.default_event_sink().connect(1, address, opt).await.unwrap().unwrap();
+        ep2.send(Message::new(TestMsg {}, 2, 1)).await.unwrap();
+        assert_eq!(ep2.recv().await.unwrap().payload(), TestMsg {});
+        ep2.close().await.unwrap();
+        client.close().await.unwrap();
+        notifier.notify_all();
+    });
+}
+
+// not connected, then a peer closing cleanly, then a peer resetting the connection, and the
+// preamble failures
+#[test]
+fn test_client_error_kind() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8554".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let e = client.send(Message::new(TestMsg {}, 2, 1)).await.unwrap_err();
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::NotConnected);
+        let e = client.recv().await.unwrap_err();
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::NotConnected);
+
+        let listener = TcpListener::bind("127.0.0.1:8554").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        drop(stream);
+        let e = client.recv().await.unwrap_err();
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::Closed);
+
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        // close with a zero linger, the client reads a RST
+        stream.set_linger(Some(Duration::ZERO)).unwrap();
+        drop(stream);
+        let e = client.recv().await.unwrap_err();
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::Reset, "{}", e.to_string());
+
+        // the preamble failures of a peer reset the connection
+        let address = "127.0.0.1:8554".parse().unwrap();
+        let e = net_error::bad_protocol(b"GET / HTTP/1.1", address);
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::Reset);
+        let e = net_error::version_mismatch(9, 8, address);
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::Reset);
+        client.close().await.unwrap();
+        notifier.notify_all();
+    });
+}
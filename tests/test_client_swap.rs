@@ -0,0 +1,159 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// count the messages of an endpoint, and report the count on EOF
+struct CountHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<u64>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for CountHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "count", async move {
+            let mut n = 0;
+            while endpoint.recv().await.is_ok() {
+                n += 1;
+            }
+            let _ = sender.send(n);
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+const NUM_SENDERS: u64 = 16;
+
+const NUM_MESSAGES: u64 = 200;
+
+// every send racing with the disconnect either reaches the server, or fails with
+// ET::NetNotConnected
+#[test]
+fn test_client_send_racing_disconnect() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8366".parse().unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, CountHandler>::new(
+        1,
+        "node_1".to_string(),
+        CountHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let sent = Arc::new(AtomicU64::new(0));
+        let mut handles = vec![];
+        for i in 0..NUM_SENDERS {
+            let c = client.clone();
+            let n = sent.clone();
+            let h = spawn_local_task(notifier.clone(), "sender", async move {
+                for j in 0..NUM_MESSAGES {
+                    let m = Message::new(TestMsg::Id(i * NUM_MESSAGES + j), 2, 1);
+                    match c.send(m).await {
+                        Ok(()) => { n.fetch_add(1, Ordering::SeqCst); }
+                        Err(ET::NetNotConnected) => {}
+                        Err(e) => { panic!("unexpected send error {:?}", e); }
+                    }
+                }
+            }).unwrap();
+            handles.push(h);
+        }
+        sleep(Duration::from_millis(5)).await;
+        client.disconnect().await.unwrap();
+        for h in handles {
+            assert!(h.await.unwrap().is_some());
+        }
+        let received = receiver.recv().await.unwrap();
+        assert_eq!(received, sent.load(Ordering::SeqCst));
+        assert!(!client.is_connected().await);
+        notifier.notify_all();
+    });
+}
+
+// a recv pending on the endpoint replaced by a reconnect is released with net_reset
+#[test]
+fn test_client_recv_racing_reconnect() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8471".parse().unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, CountHandler>::new(
+        1,
+        "node_1".to_string(),
+        CountHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let c = client.clone();
+        let h = spawn_local_task(notifier.clone(), "receiver", async move {
+            c.recv().await
+        }).unwrap();
+        sleep(Duration::from_millis(50)).await;
+        client.connect(OptClientConnect::new()).await.unwrap();
+
+        let r = timeout(Duration::from_secs(1), h).await.unwrap().unwrap().unwrap();
+        assert!(matches!(r, Err(ref e) if net_error::is_net_reset(e)));
+        // the old endpoint was closed
+        assert_eq!(receiver.recv().await.unwrap(), 0);
+
+        // the retry waits on the new endpoint
+        client.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        let r = timeout(Duration::from_millis(100), client.recv()).await;
+        assert!(r.is_err());
+        assert!(client.is_connected().await);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,76 @@
+use std::thread;
+use std::time::Duration;
+
+use scupt_util::message::Message;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::runtime::Builder;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{OptClient, OptClientConnect};
+use scupt_net::client_sync::ClientSync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::respond_handler::RespondHandler;
+
+use common::TestMsg;
+
+mod common;
+
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    Ok(Message::new(m.payload(), 1, peer))
+}
+
+// an echo server on its own thread, until the notifier is notified
+fn run_server(address: &str, notifier: Notifier) -> thread::JoinHandle<()> {
+    let address = address.to_string();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let h = thread::spawn(move || {
+        let server = NodeBuilder::new()
+            .set_node_id(1)
+            .set_notifier(notifier.clone())
+            .set_listen_address(address)
+            .build::<TestMsg, _>(RespondHandler::new(notifier.clone(), echo))
+            .unwrap();
+        let local = LocalSet::new();
+        server.run_local(&local);
+        local.spawn_local(async move {
+            server.serve(ESServeOpt::default()).await.unwrap();
+            sender.send(()).unwrap();
+        });
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(local);
+    });
+    receiver.recv().unwrap();
+    h
+}
+
+#[test]
+fn test_client_sync_round_trip() {
+    let notifier = Notifier::new();
+    let server = run_server("127.0.0.1:8551", notifier.clone());
+
+    let client = ClientSync::<TestMsg>::new(
+        2,
+        "client_2".to_string(),
+        "127.0.0.1:8551".to_string(),
+        OptClient::default(),
+    ).unwrap();
+    client.connect(OptClientConnect::new()).unwrap();
+    assert!(client.is_connected());
+    for id in 1..=3 {
+        client.send(Message::new(TestMsg::Id(id), 2, 1)).unwrap();
+        let m = client.recv().unwrap();
+        assert_eq!(m.payload(), TestMsg::Id(id));
+    }
+    let r = client.recv_timeout(Duration::from_millis(100));
+    assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+    client.disconnect().unwrap();
+    assert!(!client.is_connected());
+    drop(client);
+
+    notifier.notify_all();
+    server.join().unwrap();
+}
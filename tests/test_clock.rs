@@ -0,0 +1,126 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::clock::ManualClock;
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+// the tasks waiting on the clock get to their sleeps within it
+const SETTLE: Duration = Duration::from_millis(200);
+
+// the idle timeout of a silent peer fires when the clock passes it, not before
+#[test]
+fn test_clock_idle_timeout() {
+    let notifier = Notifier::new();
+    let clock = Arc::new(ManualClock::new());
+    let node = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_clock(clock.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let address: SocketAddr = "127.0.0.1:8619".parse().unwrap();
+        let listener = TcpListener::bind(address).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_idle_timeout_ms(10_000);
+        let (r_connect, r_accept) = tokio::join!(
+            sink.connect(2, address, opt),
+            listener.accept()
+        );
+        let ep = r_connect.unwrap().unwrap();
+        let _socket = r_accept.unwrap();
+
+        let (sender, mut result) = mpsc::unbounded_channel();
+        let _ = spawn_local_task(task_notifier, "recv", async move {
+            let _ = sender.send(ep.recv().await);
+        });
+        sleep(SETTLE).await;
+        clock.advance(Duration::from_secs(9));
+        sleep(SETTLE).await;
+        assert!(result.try_recv().is_err());
+
+        clock.advance(Duration::from_secs(2));
+        match result.recv().await.unwrap() {
+            Ok(_) => { panic!("unexpected message"); }
+            Err(e) => { assert!(net_error::is_idle_timeout(&e)); }
+        }
+        notifier.notify_all();
+    });
+}
+
+// the waits of the connect retries follow the clock, an hour each
+#[test]
+fn test_clock_connect_retry() {
+    let notifier = Notifier::new();
+    let clock = Arc::new(ManualClock::new());
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        // nothing listens on it
+        .set_server_addr("127.0.0.1:8620".to_string())
+        .set_notifier(notifier.clone())
+        .set_clock(clock.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let client = Arc::new(client);
+        let (sender, mut result) = mpsc::unbounded_channel();
+        let c = client.clone();
+        let _ = spawn_local_task(task_notifier, "connect", async move {
+            let opt = OptClientConnect {
+                retry_max: 2,
+                retry_wait_ms: 3_600_000,
+                ..OptClientConnect::new()
+            };
+            let _ = sender.send(c.connect(opt).await);
+        });
+        for _ in 0..2 {
+            sleep(SETTLE).await;
+            assert!(result.try_recv().is_err());
+            clock.advance(Duration::from_secs(3600));
+        }
+        assert!(result.recv().await.unwrap().is_err());
+        notifier.notify_all();
+    });
+}
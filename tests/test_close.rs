@@ -0,0 +1,107 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_close::CloseOption;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward the received messages to the test, and drop the endpoint on EOF if `close_on_eof`,
+// otherwise keep it open
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+    close_on_eof: bool,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        let close_on_eof = self.close_on_eof;
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+            if !close_on_eof {
+                std::future::pending::<()>().await;
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+fn test_close_drain(port: u16, close_on_eof: bool) {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender, close_on_eof },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        let opt = CloseOption::new()
+            .enable_drain(true)
+            .set_timeout(Duration::from_millis(500));
+        let r = ep.close_with(opt).await;
+        if close_on_eof {
+            r.unwrap();
+        } else {
+            assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+        }
+        // the final message was received before the close returned
+        assert_eq!(receiver.try_recv().unwrap(), TestMsg::Id(1));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_close_drain_peer_closed() {
+    test_close_drain(8411, true);
+}
+
+#[test]
+fn test_close_drain_timeout() {
+    test_close_drain(8412, false);
+}
@@ -0,0 +1,98 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use scupt_util::error_type::ET;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+type Attempt = (SocketAddr, u64, Option<ET>);
+
+// report the connect attempts to the test
+fn handler(sender: mpsc::UnboundedSender<Attempt>) -> FnHandler<TestMsg> {
+    FnHandler::<TestMsg>::new()
+        .set_on_connect_attempt(move |address, attempt, last_error| {
+            let _ = sender.send((address, attempt, last_error));
+        })
+}
+
+// the retries of a client are numbered, with the error of the previous attempt
+#[test]
+fn test_client_connect_attempt() {
+    let notifier = Notifier::new();
+    let address: SocketAddr = "127.0.0.1:8582".parse().unwrap();
+    let (sender, mut attempts) = mpsc::unbounded_channel();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr(address.to_string())
+        .set_notifier(notifier.clone())
+        .build_with_handler::<TestMsg>(Arc::new(handler(sender)))
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let opt = OptClientConnect {
+            retry_max: 3,
+            retry_wait_ms: 10,
+            connect_timeout_ms: 0,
+            ..OptClientConnect::new()
+        };
+        let e = client.connect(opt.clone()).await.unwrap_err();
+        assert!(net_error::is_connection_refused(&e));
+        for n in 1..=3 {
+            let (a, attempt, last_error) = attempts.recv().await.unwrap();
+            assert_eq!(a, address);
+            assert_eq!(attempt, n);
+            match last_error {
+                Some(e) => { assert!(n > 1 && net_error::is_connection_refused(&e)) }
+                None => { assert_eq!(n, 1) }
+            }
+        }
+
+        let _listener = TcpListener::bind(address).await.unwrap();
+        client.connect(opt).await.unwrap();
+        let (_, attempt, last_error) = attempts.recv().await.unwrap();
+        assert_eq!(attempt, 1);
+        assert!(last_error.is_none());
+        assert!(attempts.try_recv().is_err());
+        client.close().await.unwrap();
+        notifier.notify_all();
+    });
+}
+
+// a connect of the event sink is a single attempt
+#[test]
+fn test_event_sink_connect_attempt() {
+    let notifier = Notifier::new();
+    let address: SocketAddr = "127.0.0.1:8583".parse().unwrap();
+    let (sender, mut attempts) = mpsc::unbounded_channel();
+    let node = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(handler(sender))
+        .unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let _listener = TcpListener::bind(address).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let _ep = sink.connect(2, address, opt).await.unwrap().unwrap();
+        let (a, attempt, last_error) = attempts.recv().await.unwrap();
+        assert_eq!(a, address);
+        assert_eq!(attempt, 1);
+        assert!(last_error.is_none());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,94 @@
+use std::net::SocketAddr;
+
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+use scupt_util::node_id::NID;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+type TestNode = Node<TestMsg, HandleEventDummy>;
+
+fn new_node(node_id: NID, notifier: Notifier) -> TestNode {
+    TestNode::new(
+        node_id,
+        format!("node_{}", node_id),
+        HandleEventDummy::default(),
+        false,
+        notifier).unwrap()
+}
+
+#[test]
+fn test_connect_completion_ok() {
+    let notifier = Notifier::new();
+    let node = new_node(1, notifier.clone());
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8311".parse().unwrap();
+        sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let receiver = sink.connect_completion(2, addr, ESConnectOpt::default()).await.unwrap();
+        let result = receiver.await.unwrap();
+        assert!(result.is_ok());
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_connect_completion_error() {
+    let notifier = Notifier::new();
+    let node = new_node(1, notifier.clone());
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        // nobody listen on this address
+        let addr: SocketAddr = "127.0.0.1:8312".parse().unwrap();
+        let receiver = sink.connect_completion(2, addr, ESConnectOpt::default()).await.unwrap();
+        let result = receiver.await.unwrap();
+        assert!(matches!(result, Err(ET::IOError(_))));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_connect_completion_cancel() {
+    let notifier = Notifier::new();
+    let node = new_node(1, notifier.clone());
+    let local = LocalSet::new();
+    // the node never runs, the pending connect is dropped with the node
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8313".parse().unwrap();
+        let receiver = sink.connect_completion(2, addr, ESConnectOpt::default()).await.unwrap();
+        notifier.notify_all();
+        drop(node);
+        let result = receiver.await.unwrap();
+        assert!(matches!(result, Err(ET::EOF)));
+    });
+}
@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::task::LocalSet;
+use tokio::time::timeout;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const RETRY_MAX: u64 = 3;
+
+fn opt_connect() -> OptClientConnect {
+    OptClientConnect {
+        retry_max: RETRY_MAX,
+        retry_wait_ms: 10,
+        connect_timeout_ms: 200,
+        ..OptClientConnect::new()
+    }
+}
+
+// nobody listens, the last error is the refused attempt
+#[test]
+fn test_connect_refused() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8567".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let e = client.connect(opt_connect()).await.unwrap_err();
+        assert!(net_error::is_connection_refused(&e), "{}", e.to_string());
+        assert!(!net_error::is_connect_timeout(&e));
+        assert!(!client.is_connected().await);
+        notifier.notify_all();
+    });
+}
+
+// the accept queue of the listener is full, the SYNs of the client are dropped
+#[test]
+fn test_connect_timeout() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8568".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8568".parse().unwrap();
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.bind(addr).unwrap();
+        let _listener = socket.listen(1).unwrap();
+        let mut streams = vec![];
+        loop {
+            match timeout(Duration::from_millis(100), TcpStream::connect(addr)).await {
+                Ok(r) => { streams.push(r.unwrap()); }
+                Err(_) => { break; }
+            }
+        }
+        let e = client.connect(opt_connect()).await.unwrap_err();
+        assert!(net_error::is_connect_timeout(&e), "{}", e.to_string());
+        assert_eq!(net_error::connect_attempts(&e), Some(RETRY_MAX));
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,158 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::connection_pool::ConnectionPool;
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward the received messages to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+fn new_server(notifier: &Notifier, sender: mpsc::UnboundedSender<TestMsg>) -> Node<TestMsg, RecvHandler> {
+    Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap()
+}
+
+fn same_endpoint(a: &Arc<dyn EndpointAsync<TestMsg>>, b: &Arc<dyn EndpointAsync<TestMsg>>) -> bool {
+    Arc::as_ptr(a) as *const u8 == Arc::as_ptr(b) as *const u8
+}
+
+#[test]
+fn test_pool_concurrent_connect() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8441".parse().unwrap();
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let server = new_server(&notifier, sender);
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_handle = server.handle();
+    let server_sink = server.default_event_sink();
+    let pool = Arc::new(ConnectionPool::<TestMsg>::new(client.default_event_sink(), 1));
+    let n = notifier.clone();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let (ep_sender, mut ep_receiver) = mpsc::unbounded_channel();
+        let num_tasks = 10;
+        for _ in 0..num_tasks {
+            let pool = pool.clone();
+            let ep_sender = ep_sender.clone();
+            spawn_local_task(n.clone(), "get_or_connect", async move {
+                let ep = pool.get_or_connect(1, addr).await.unwrap();
+                let _ = ep_sender.send(ep);
+            }).unwrap();
+        }
+        let first = ep_receiver.recv().await.unwrap();
+        for _ in 1..num_tasks {
+            let ep = ep_receiver.recv().await.unwrap();
+            assert!(same_endpoint(&first, &ep));
+        }
+        assert_eq!(pool.len(), 1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server_handle.metrics().accepted, 1);
+
+        // the pool is full with a live endpoint
+        let r = pool.get_or_connect(3, addr).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_pool_full(e)));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_pool_reconnect() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8442".parse().unwrap();
+    // the first server is stopped by its own notifier
+    let server_notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = new_server(&server_notifier, sender.clone());
+    let restarted = new_server(&notifier, sender);
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    restarted.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let restarted_sink = restarted.default_event_sink();
+    let pool = ConnectionPool::<TestMsg>::new(client.default_event_sink(), 0);
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        pool.send(1, addr, Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(1));
+        let first = pool.get_or_connect(1, addr).await.unwrap();
+
+        // kill the server, and start another one at the same address
+        server_notifier.notify_all();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        restarted_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+
+        // a send on the old endpoint may still succeed before the error is detected
+        for i in 0..50 {
+            let r = pool.send(1, addr, Message::new(TestMsg::Id(2), 2, 1)).await;
+            if r.is_ok() && !same_endpoint(&first, &pool.get_or_connect(1, addr).await.unwrap()) {
+                break;
+            }
+            assert!(i < 49, "the pool did not reconnect");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(2));
+        assert!(first.is_closed());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,144 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout_at};
+
+use scupt_net::caller::Caller;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_call::OptCall;
+use scupt_net::respond_handler::RespondHandler;
+use scupt_net::rpc_envelope::RpcEnvelope;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    // the id and the milliseconds of the work
+    Work(u64, u64),
+    Done(u64),
+    Abandoned(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+type Envelope = RpcEnvelope<TestMsg>;
+
+fn correlation_id(m: &Message<Envelope>) -> u64 {
+    match m.clone().payload().into_body() {
+        TestMsg::Work(id, _) => { id }
+        TestMsg::Done(id) => { id }
+        TestMsg::Abandoned(id) => { id }
+    }
+}
+
+fn request(id: u64, work_ms: u64, opt_deadline: Option<Instant>) -> Message<Envelope> {
+    let body = TestMsg::Work(id, work_ms);
+    let envelope = match opt_deadline {
+        Some(deadline) => { Envelope::with_deadline(body, deadline) }
+        None => { Envelope::new(body) }
+    };
+    Message::new(envelope, 2, 1)
+}
+
+// do the work of the request, and give it up at the deadline, the outcomes are reported
+async fn work(
+    peer: NID,
+    m: Message<Envelope>,
+    outcome: mpsc::UnboundedSender<(TestMsg, Duration)>,
+) -> Res<Message<Envelope>> {
+    let received = Instant::now();
+    let envelope = m.payload();
+    let opt_deadline = envelope.deadline(received);
+    let (id, work_ms) = match envelope.into_body() {
+        TestMsg::Work(id, work_ms) => { (id, work_ms) }
+        _ => { panic!("not a request") }
+    };
+    let w = sleep(Duration::from_millis(work_ms));
+    let done = match opt_deadline {
+        Some(deadline) => { timeout_at(deadline.into(), w).await.is_ok() }
+        None => {
+            w.await;
+            true
+        }
+    };
+    let reply = if done { TestMsg::Done(id) } else { TestMsg::Abandoned(id) };
+    let _ = outcome.send((reply.clone(), received.elapsed()));
+    Ok(Message::new(Envelope::new(reply), 1, peer))
+}
+
+// the server gives up the work at the deadline of the request, and the call fails at the same
+// deadline, without waiting for the late response
+#[test]
+fn test_call_deadline() {
+    let notifier = Notifier::new();
+    let (sender, mut outcome) = mpsc::unbounded_channel();
+    let handler = RespondHandler::new(notifier.clone(), move |peer, m| {
+        work(peer, m, sender.clone())
+    });
+    let server = Node::<Envelope, _>::new(1, "node_1".to_string(), handler, false, notifier.clone()).unwrap();
+    let client = Node::<Envelope, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8579".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        let caller = Caller::new(ep, Arc::new(correlation_id), OptCall::new(), notifier.clone())
+            .unwrap()
+            .set_deadline(Envelope::deadline_fn());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let r = caller.call(request(1, 10, Some(deadline))).await.unwrap();
+        assert_eq!(r.payload().into_body(), TestMsg::Done(1));
+        assert_eq!(outcome.recv().await.unwrap().0, TestMsg::Done(1));
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(100);
+        let e = caller.call(request(2, 2000, Some(deadline))).await.unwrap_err();
+        assert!(net_error::is_timeout(&e), "{}", e.to_string());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+        assert_eq!(caller.inflight(), 0);
+        let (reply, took) = outcome.recv().await.unwrap();
+        assert_eq!(reply, TestMsg::Abandoned(2));
+        assert!(took < Duration::from_millis(1000), "{:?}", took);
+
+        // no deadline, the late response of the abandoned request was dropped
+        let r = caller.call(request(3, 150, None)).await.unwrap();
+        assert_eq!(r.payload().into_body(), TestMsg::Done(3));
+        notifier.notify_all();
+    });
+}
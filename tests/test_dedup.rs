@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::message::{encode_message, Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::frame::{CONTROL_SEQ, ControlFrame, FrameHeader, HEADER_SIZE};
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const SESSION: u64 = 0x5e55_1011;
+
+// the frames of a connection of the session, started by the session frame of the low, the
+// message of a frame is the id of its sequence number
+fn session_frames(low: u64, seqs: &[u64]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    let c = ControlFrame::Session(SESSION, low);
+    FrameHeader::new(c.size() as u32, CONTROL_SEQ).encode(&mut buf);
+    c.encode(&mut buf);
+    for seq in seqs {
+        let payload = encode_message(Message::new(TestMsg::Data(*seq, vec![]), 2, 1)).unwrap();
+        FrameHeader::new(payload.len() as u32, *seq).encode(&mut buf);
+        buf.extend_from_slice(&payload);
+    }
+    buf
+}
+
+// the ids of the next `n` messages received
+async fn recv_ids(ep: &dyn EndpointAsync<TestMsg>, n: usize) -> Vec<u64> {
+    let mut ids = vec![];
+    for _ in 0..n {
+        let TestMsg::Data(id, _) = ep.recv().await.unwrap().payload();
+        ids.push(id);
+    }
+    ids
+}
+
+// The connections of a session resend the frames the previous ones delivered already, and fill
+// a gap out of order, the handler receives each message once, and the gaps before the low
+// announced by a connection are given up once the previous connections were closed.
+#[test]
+fn test_dedup_replay() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8635".to_string())
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let mut received = vec![];
+        // 3 was not delivered by the first connection
+        let connections = [
+            (1, vec![1, 2, 4], 3),
+            (2, vec![2, 3, 4, 5], 2),
+            // 6 and 7 were given up by the sender
+            (8, vec![5, 9, 8], 2),
+        ];
+        for (low, seqs, n) in connections {
+            let mut peer = TcpStream::connect("127.0.0.1:8635").await.unwrap();
+            peer.write_all(&session_frames(low, &seqs)).await.unwrap();
+            let ep = accepted.recv().await.unwrap();
+            received.extend(recv_ids(ep.as_ref(), n).await);
+            drop(peer);
+            assert!(ep.recv().await.is_err());
+        }
+        assert_eq!(received, vec![1, 2, 4, 3, 5, 9, 8]);
+        notifier.notify_all();
+    });
+}
+
+// the next frame of the peer, its header and payload
+async fn read_frame(peer: &mut TcpStream) -> (FrameHeader, Vec<u8>) {
+    let mut header = [0u8; HEADER_SIZE];
+    peer.read_exact(&mut header).await.unwrap();
+    let header = FrameHeader::decode(&header).unwrap();
+    let mut payload = vec![0u8; header.size() as usize];
+    peer.read_exact(&mut payload).await.unwrap();
+    (header, payload)
+}
+
+// the session announced by the peer, and the header of its first message frame
+async fn read_session(peer: &mut TcpStream) -> (Option<(u64, u64)>, FrameHeader) {
+    let mut opt_session = None;
+    loop {
+        let (header, payload) = read_frame(peer).await;
+        if header.seq() != CONTROL_SEQ {
+            return (opt_session, header);
+        }
+        if let Some(ControlFrame::Session(id, low)) = ControlFrame::decode(&payload) {
+            opt_session = Some((id, low));
+        }
+    }
+}
+
+const NUM_MESSAGES: u64 = 100;
+
+// the connections of a client number the frames by one session, the frames resent by the
+// next connection keep their sequence numbers, the first of them is the low it announced
+#[test]
+fn test_dedup_client_session() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8636".to_string())
+        .set_notifier(notifier.clone())
+        .enable_dedup(true)
+        .enable_resend_unsent(true)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8636").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut stalled, _) = listener.accept().await.unwrap();
+        let (result_sender, mut result) = mpsc::unbounded_channel();
+        let c = client.clone();
+        spawn_local_task(task_notifier, "send all", async move {
+            let messages = (0..NUM_MESSAGES).map(|id| {
+                Message::new(TestMsg::Data(id, vec![id as u8; 64 * 1024]), 2, 1)
+            });
+            let _ = result_sender.send(c.send_all(messages).await);
+        }).unwrap();
+        let (opt_session, header) = read_session(&mut stalled).await;
+        let (id, low) = opt_session.unwrap();
+        assert_eq!(low, 1);
+        assert_eq!(header.seq(), 1);
+        // the socket buffers are full, the rest of the messages are queued
+        sleep(Duration::from_millis(200)).await;
+
+        // kill the server, the unread bytes reset the connection
+        drop(stalled);
+        drop(listener);
+        let ep = client.endpoint().unwrap();
+        while !ep.is_closed() {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:8636").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let (opt_session, header) = read_session(&mut peer).await;
+        assert_eq!(opt_session, Some((id, header.seq())));
+        assert!(header.seq() > 1);
+        // the message of the sequence number n is the one of the id n - 1
+        for seq in header.seq() + 1..=NUM_MESSAGES {
+            let (header, _) = read_frame(&mut peer).await;
+            assert_eq!(header.seq(), seq);
+        }
+        let (n, r) = result.recv().await.unwrap();
+        r.unwrap();
+        assert_eq!(n, NUM_MESSAGES);
+        notifier.notify_all();
+    });
+}
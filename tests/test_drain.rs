@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use scupt_util::message::Message;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::{LocalSet, yield_now};
+use tokio::time::timeout;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::frame::{FrameHeader, HEADER_SIZE};
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_close::StopMode;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const NUM_MESSAGES: u64 = 100;
+
+// the frames read until the connection was closed
+async fn count_frames(mut stream: TcpStream) -> u64 {
+    let mut n = 0;
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        if stream.read_exact(&mut header).await.is_err() {
+            return n;
+        }
+        let hdr = FrameHeader::decode(&header).unwrap();
+        let mut payload = vec![0u8; hdr.size() as usize];
+        if stream.read_exact(&mut payload).await.is_err() {
+            return n;
+        }
+        n += 1;
+    }
+}
+
+// queue the messages without waiting for them to be written, and stop the client
+async fn send_and_close(client: Client<TestMsg>, notifier: Notifier, listener: TcpListener, mode: StopMode) -> u64 {
+    client.connect(OptClientConnect::new()).await.unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let count = tokio::spawn(count_frames(stream));
+    let ep = client.endpoint().unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    for id in 0..NUM_MESSAGES {
+        let c = client.clone();
+        let s = sender.clone();
+        let _ = spawn_local_task(notifier.clone(), "send", async move {
+            let _ = s.send(c.send(Message::new(TestMsg::Id(id), 2, 1)).await);
+        });
+    }
+    drop(sender);
+    // the sends queue their frames
+    yield_now().await;
+    client.close_with(mode).await.unwrap();
+    if let StopMode::Drain(_) = mode {
+        let r = ep.send(Message::new(TestMsg::Id(0), 2, 1)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_shutting_down(e)));
+        for _ in 0..NUM_MESSAGES {
+            assert!(receiver.recv().await.unwrap().is_ok());
+        }
+    }
+    timeout(Duration::from_secs(5), count).await.unwrap().unwrap()
+}
+
+// every queued message is read by the peer before the connection closed
+#[test]
+fn test_drain_stop() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8558".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8558").await.unwrap();
+        let n = send_and_close(client, notifier.clone(), listener, StopMode::Drain(Duration::from_secs(5))).await;
+        assert_eq!(n, NUM_MESSAGES);
+        notifier.notify_all();
+    });
+}
+
+// the queued messages may be dropped
+#[test]
+fn test_abort_stop() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8559".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8559").await.unwrap();
+        let n = send_and_close(client, notifier.clone(), listener, StopMode::Abort).await;
+        assert!(n <= NUM_MESSAGES);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,81 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::res::Res;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::dump_tasks;
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// keep the accepted endpoints alive, and never send
+#[derive(Default)]
+struct KeepHandler {
+    endpoints: Mutex<Vec<Arc<dyn EndpointAsync<TestMsg>>>>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for KeepHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        self.endpoints.lock().unwrap().push(endpoint);
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_dump_tasks_blocked_recv() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8421".parse().unwrap();
+    let server = Node::<TestMsg, KeepHandler>::new(
+        1,
+        "node_1".to_string(),
+        KeepHandler::default(),
+        false,
+        notifier.clone()).unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let c = client.clone();
+        spawn_local_task(notifier.clone(), "client recv", async move {
+            let _ = c.recv().await;
+        }).unwrap();
+        sleep(Duration::from_millis(100)).await;
+
+        let dump = dump_tasks();
+        assert!(dump.contains("client recv"));
+        // the reader task of the endpoint names its peer
+        assert!(dump.contains("endpoint reader 127.0.0.1:8421"));
+        notifier.notify_all();
+    });
+}
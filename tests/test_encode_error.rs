@@ -0,0 +1,181 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+use bincode::enc::Encoder;
+use bincode::error::EncodeError;
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize, Serializer};
+use serde::ser::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame_codec::FrameCodec;
+use scupt_net::handle_event::{FnHandler, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::net_error::NetErrorKind;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+// a value which fails to encode when it is true
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Deserialize,
+Decode,
+)]
+struct Poison(bool);
+
+impl Encode for Poison {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        if self.0 {
+            return Err(EncodeError::Other("poison"));
+        }
+        self.0.encode(encoder)
+    }
+}
+
+impl Serialize for Poison {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 {
+            return Err(S::Error::custom("poison"));
+        }
+        serializer.serialize_bool(self.0)
+    }
+}
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Id(u64, Poison),
+    Line(String),
+}
+
+impl MsgTrait for TestMsg {}
+
+// a line of text per message, a line of "bad" writes a part of it, and fails
+struct LineCodec;
+
+impl FrameCodec<TestMsg> for LineCodec {
+    fn encode(&self, message: &Message<TestMsg>, buf: &mut BytesMut) -> Res<()> {
+        let line = match message.clone().payload() {
+            TestMsg::Line(line) => { line }
+            TestMsg::Id(id, _) => { id.to_string() }
+        };
+        buf.extend_from_slice(line.as_bytes());
+        if line == "bad" {
+            return Err(ET::SerdeError("a bad line".to_string()));
+        }
+        buf.extend_from_slice(b"\n");
+        Ok(())
+    }
+
+    fn decode(&self, _buf: &mut BytesMut) -> Res<Option<Message<TestMsg>>> {
+        Ok(None)
+    }
+}
+
+// the send of a message failing to encode returns the error, the connection keeps working
+#[test]
+fn test_encode_error() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8596".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8596".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let ep = accepted.recv().await.unwrap();
+
+        client.send(Message::new(TestMsg::Id(1, Poison(false)), 2, 1)).await.unwrap();
+        let e = client.send(Message::new(TestMsg::Id(2, Poison(true)), 2, 1)).await.unwrap_err();
+        assert_eq!(net_error::net_error_kind(&e), NetErrorKind::Other);
+        let messages = vec![
+            Message::new(TestMsg::Id(3, Poison(false)), 2, 1),
+            Message::new(TestMsg::Id(4, Poison(true)), 2, 1),
+        ];
+        let (n, r) = client.send_all(messages.into_iter()).await;
+        assert_eq!(n, 1);
+        assert!(r.is_err());
+        client.send(Message::new(TestMsg::Id(5, Poison(false)), 2, 1)).await.unwrap();
+
+        for id in [1, 3, 5] {
+            assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Id(id, Poison(false)));
+        }
+        assert!(!ep.is_closed());
+        notifier.notify_all();
+    });
+}
+
+// the frame codec fails after a part of the line, none of it is written
+#[test]
+fn test_frame_codec_encode_error() {
+    let notifier = Notifier::new();
+    let node = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8597".parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_frame_codec::<TestMsg>(Arc::new(LineCodec));
+        let ep = sink.connect(2, addr, opt).await.unwrap().unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut lines = BufReader::new(stream).lines();
+
+        ep.send(Message::new(TestMsg::Line("hello".to_string()), 1, 2)).await.unwrap();
+        let r = ep.send(Message::new(TestMsg::Line("bad".to_string()), 1, 2)).await;
+        assert!(matches!(r, Err(ET::SerdeError(_))));
+        ep.send(Message::new(TestMsg::Line("world".to_string()), 1, 2)).await.unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "world");
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,96 @@
+use std::net::SocketAddr;
+
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt, ESStopOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_send::OptSend;
+use scupt_net::task::spawn_local_task;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_TASKS: u64 = 4;
+
+// the clones of the sink connect and send from tasks of their own, the node itself is not
+// moved into them
+#[test]
+fn test_event_sink_tasks() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8605".to_string())
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let node = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    node.run_local(&local);
+    let sink = node.event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8605".parse().unwrap();
+        sink.connect(1, address, ESConnectOpt::default()).await.unwrap();
+        let ep = accepted.recv().await.unwrap();
+
+        let (result_sender, mut results) = mpsc::unbounded_channel();
+        for i in 0..NUM_TASKS {
+            let s = sink.clone();
+            let r = result_sender.clone();
+            let _ = spawn_local_task(task_notifier.clone(), "send", async move {
+                let _ = r.send(s.send(Message::new(TestMsg::Hello(i), 2, 1), OptSend::default()).await);
+            });
+        }
+        let mut received = vec![];
+        for _ in 0..NUM_TASKS {
+            results.recv().await.unwrap().unwrap();
+            match ep.recv().await.unwrap().payload() {
+                TestMsg::Hello(i) => { received.push(i); }
+            }
+        }
+        received.sort();
+        assert_eq!(received, (0..NUM_TASKS).collect::<Vec<_>>());
+
+        // the error of a send is returned
+        let r = sink.send(Message::new(TestMsg::Hello(0), 2, 3), OptSend::default()).await;
+        assert!(matches!(r, Err(ET::NoSuchElement)));
+
+        // a clone stops the node, the others fail after
+        sink.clone().stop(ESStopOpt::default()).await.unwrap();
+        let r = sink.send(Message::new(TestMsg::Hello(0), 2, 1), OptSend::default()).await;
+        assert!(r.is_err());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,231 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+use scupt_net::test_controller::{FaultAction, FaultDirection, FaultInjector, FaultRule};
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward every received message to the test, with the time it was received
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<(TestMsg, Instant)>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send((m.payload(), Instant::now()));
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+fn is_id(id: u64) -> Arc<dyn Fn(&Message<TestMsg>) -> bool + Send + Sync> {
+    Arc::new(move |m: &Message<TestMsg>| { m.clone().payload() == TestMsg::Id(id) })
+}
+
+#[test]
+fn test_fault_injection() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    // the faults are injected on the testing node
+    let node_server = Node::<TestMsg, RecvHandler>::new(
+        2,
+        "node_2".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        true,
+        notifier.clone()).unwrap();
+    let node_client = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    assert!(node_client.test_controller().is_none());
+    let controller = node_server.test_controller().unwrap();
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Drop)
+            .set_peer(1)
+            .set_predicate(is_id(1))
+            .set_times(1));
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Duplicate)
+            .set_predicate(is_id(2)));
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Delay(Duration::from_millis(300)))
+            .set_predicate(is_id(3)));
+    // never matched, the messages come from node 1
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Drop)
+            .set_peer(5));
+
+    let local = LocalSet::new();
+    node_server.run_local(&local);
+    node_client.run_local(&local);
+    let sink_server = node_server.default_event_sink();
+    let sink_client = node_client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8341".parse().unwrap();
+        sink_server.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink_client.connect(2, addr, opt).await.unwrap().unwrap();
+        for id in [0, 1, 2, 3, 1] {
+            ep.send(Message::new(TestMsg::Id(id), 1, 2)).await.unwrap();
+        }
+
+        let mut received = vec![];
+        for _ in 0..5 {
+            let r = receiver.recv().await.unwrap();
+            received.push(r);
+        }
+        let ids: Vec<TestMsg> = received.iter().map(|(m, _)| { m.clone() }).collect();
+        // only the first Id(1) was dropped, Id(2) was duplicated
+        assert_eq!(ids, vec![
+            TestMsg::Id(0),
+            TestMsg::Id(2),
+            TestMsg::Id(2),
+            TestMsg::Id(3),
+            TestMsg::Id(1),
+        ]);
+        // Id(3) was delayed
+        assert!(received[3].1.duration_since(received[2].1) >= Duration::from_millis(250));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_fault_injector() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let node_server = Node::<TestMsg, RecvHandler>::new(
+        2,
+        "node_2".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        true,
+        notifier.clone()).unwrap();
+    let node_client = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let injector: Arc<FaultInjector<TestMsg>> = node_server.test_controller().unwrap();
+    injector.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Drop)
+            .set_predicate(is_id(10))
+            .set_probability(1.0));
+    injector.add_rule(
+        FaultRule::new(FaultDirection::Recv, FaultAction::Drop)
+            .set_predicate(is_id(11))
+            .set_probability(0.0));
+
+    let local = LocalSet::new();
+    node_server.run_local(&local);
+    node_client.run_local(&local);
+    let sink_server = node_server.default_event_sink();
+    let sink_client = node_client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8342".parse().unwrap();
+        sink_server.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink_client.connect(2, addr, opt).await.unwrap().unwrap();
+
+        // the drops go before the rules
+        injector.drop_next(2);
+        for id in [0, 10, 1, 2, 10, 11] {
+            ep.send(Message::new(TestMsg::Id(id), 1, 2)).await.unwrap();
+        }
+        for id in [1, 2, 11] {
+            assert_eq!(receiver.recv().await.unwrap().0, TestMsg::Id(id));
+        }
+
+        injector.isolate();
+        ep.send(Message::new(TestMsg::Id(20), 1, 2)).await.unwrap();
+        injector.heal();
+        ep.send(Message::new(TestMsg::Id(21), 1, 2)).await.unwrap();
+        // the message sent when isolated was dropped
+        assert_eq!(receiver.recv().await.unwrap().0, TestMsg::Id(21));
+
+        assert_eq!(injector.close_connections().await, 1);
+        assert_eq!(injector.close_connections().await, 0);
+        // the peer reads the end of the stream
+        assert!(ep.recv().await.is_err());
+        notifier.notify_all();
+    });
+}
+
+// the sends counted go through the faults, a dropped message writes no byte, a duplicated one
+// writes its frame twice
+#[test]
+fn test_fault_send_counted() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let node_server = Node::<TestMsg, RecvHandler>::new(
+        2,
+        "node_2".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let node_client = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        true,
+        notifier.clone()).unwrap();
+    let controller = node_client.test_controller().unwrap();
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Send, FaultAction::Drop)
+            .set_predicate(is_id(1)));
+    controller.add_rule(
+        FaultRule::new(FaultDirection::Send, FaultAction::Duplicate)
+            .set_predicate(is_id(2)));
+
+    let local = LocalSet::new();
+    node_server.run_local(&local);
+    node_client.run_local(&local);
+    let sink_server = node_server.default_event_sink();
+    let sink_client = node_client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8343".parse().unwrap();
+        sink_server.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink_client.connect(2, addr, opt).await.unwrap().unwrap();
+
+        let n = ep.send_counted(Message::new(TestMsg::Id(0), 1, 2)).await.unwrap();
+        assert!(n > 0);
+        assert_eq!(ep.send_counted(Message::new(TestMsg::Id(1), 1, 2)).await.unwrap(), 0);
+        assert_eq!(ep.send_counted(Message::new(TestMsg::Id(2), 1, 2)).await.unwrap(), 2 * n);
+        for id in [0, 2, 2] {
+            assert_eq!(receiver.recv().await.unwrap().0, TestMsg::Id(id));
+        }
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,243 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame::{CONTROL_SEQ, ControlFrame, DEFAULT_CHANNEL, FrameHeader, HEADER_SIZE};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::overflow_policy::OverflowPolicy;
+use scupt_net::task::spawn_local_task;
+
+use common::{AcceptHandler, block_on_local, client_node};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const WINDOW: u32 = 4;
+
+const NUM_SENDS: u64 = 40;
+
+// large enough for the socket buffers to take all of them without the credits
+const PAYLOAD_SIZE: usize = 1024;
+
+fn message(i: u64) -> Message<TestMsg> {
+    Message::new(TestMsg::Data(i, vec![0; PAYLOAD_SIZE]), 2, 1)
+}
+
+fn nodes(port: u16, notifier: &Notifier) -> (
+    Node<TestMsg, AcceptHandler<TestMsg>>,
+    Node<TestMsg, FnHandler<TestMsg>>,
+    mpsc::UnboundedReceiver<Arc<dyn EndpointAsync<TestMsg>>>,
+) {
+    let (sender, accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .set_flow_window(WINDOW)
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    (server, client, accepted)
+}
+
+// The sender pauses when the credits of the window are used up, and goes on as the slow
+// consumer drains `recv`, the messages sent and not received yet are never more than the
+// window.
+#[test]
+fn test_flow_control_slow_consumer() {
+    let notifier = Notifier::new();
+    let (server, client, mut accepted) = nodes(8615, &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8615".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_flow_window(WINDOW);
+        let ep = client_sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+
+        let sent = Arc::new(AtomicU64::new(0));
+        let sender_sent = sent.clone();
+        let _ = spawn_local_task(task_notifier, "sender", async move {
+            for i in 0..NUM_SENDS {
+                ep.send(message(i)).await.unwrap();
+                sender_sent.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        // nothing received, the sender waits for a credit
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(sent.load(Ordering::SeqCst), WINDOW as u64);
+
+        for received in 1..=NUM_SENDS {
+            match server_ep.recv().await.unwrap().payload() {
+                TestMsg::Data(i, _) => { assert_eq!(i, received - 1); }
+            }
+            sleep(Duration::from_millis(5)).await;
+            let outstanding = sent.load(Ordering::SeqCst) - received;
+            assert!(outstanding <= WINDOW as u64, "{} messages over the window", outstanding);
+        }
+        assert_eq!(sent.load(Ordering::SeqCst), NUM_SENDS);
+        notifier.notify_all();
+    });
+}
+
+// the chunks of a `send_all` larger than the window are cut to the credits available
+#[test]
+fn test_flow_control_send_all() {
+    let notifier = Notifier::new();
+    let (server, client, mut accepted) = nodes(8616, &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8616".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_flow_window(WINDOW);
+        let ep = client_sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+
+        let (result_sender, mut result) = mpsc::unbounded_channel();
+        let _ = spawn_local_task(task_notifier, "send all", async move {
+            let r = ep.send_all(Box::new((0..NUM_SENDS).map(message))).await;
+            let _ = result_sender.send(r);
+        });
+        for n in 0..NUM_SENDS {
+            match server_ep.recv().await.unwrap().payload() {
+                TestMsg::Data(i, _) => { assert_eq!(i, n); }
+            }
+        }
+        let (written, r) = result.recv().await.unwrap();
+        r.unwrap();
+        assert_eq!(written, NUM_SENDS);
+        notifier.notify_all();
+    });
+}
+
+// a few of them fill the socket buffers, the writer stalls, and the sends beyond a lane of 2
+// evict the queued ones
+const STALLED_SIZE: usize = 1024 * 1024;
+
+const STALLED_SENDS: u64 = 32;
+
+// the next message frame written by the client, None if nothing came for a while
+async fn read_data_frame(peer: &mut TcpStream) -> Option<FrameHeader> {
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        timeout(Duration::from_millis(500), peer.read_exact(&mut header)).await.ok()?.unwrap();
+        let header = FrameHeader::decode(&header).unwrap();
+        let mut payload = vec![0u8; header.size() as usize];
+        peer.read_exact(&mut payload).await.unwrap();
+        if header.seq() != CONTROL_SEQ {
+            return Some(header);
+        }
+    }
+}
+
+// Under DropOldest, a frame evicted from the full lane gives its credit back once, the frames
+// written to a stalled peer are never more than the window it granted.
+#[test]
+fn test_flow_control_drop_oldest() {
+    let notifier = Notifier::new();
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let address: SocketAddr = "127.0.0.1:8639".parse().unwrap();
+        // a small receive buffer, the unread frames stay in the lane of the client
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        socket.bind(address).unwrap();
+        let listener = socket.listen(16).unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_flow_window(WINDOW)
+            .set_send_queue_capacity(2)
+            .set_overflow_policy(OverflowPolicy::DropOldest);
+        let ep = client_sink.connect(1, address, opt).await.unwrap().unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        // the window of the client, never granted back
+        let mut buf = BytesMut::new();
+        let c = ControlFrame::Credit(DEFAULT_CHANNEL, WINDOW);
+        FrameHeader::new(c.size() as u32, CONTROL_SEQ).encode(&mut buf);
+        c.encode(&mut buf);
+        peer.write_all(&buf).await.unwrap();
+
+        let (result_sender, mut results) = mpsc::unbounded_channel();
+        for i in 0..STALLED_SENDS {
+            let ep = ep.clone();
+            let result_sender = result_sender.clone();
+            let _ = spawn_local_task(task_notifier.clone(), "sender", async move {
+                let m = Message::new(TestMsg::Data(i, vec![0; STALLED_SIZE]), 2, 1);
+                let _ = result_sender.send(ep.send(m).await);
+            });
+        }
+        sleep(Duration::from_millis(500)).await;
+
+        let mut written = 0;
+        while read_data_frame(&mut peer).await.is_some() {
+            written += 1;
+        }
+        let (mut sent, mut dropped) = (0, 0);
+        while let Ok(r) = results.try_recv() {
+            match r {
+                Ok(()) => { sent += 1; }
+                Err(e) => {
+                    assert!(net_error::is_dropped(&e), "{:?}", e);
+                    dropped += 1;
+                }
+            }
+        }
+        assert!(dropped > 0);
+        assert_eq!(sent, written);
+        assert!(written <= WINDOW, "{} frames over the window of {}", written, WINDOW);
+        notifier.notify_all();
+    });
+}
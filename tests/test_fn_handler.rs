@@ -0,0 +1,115 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use scupt_util::message::Message;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{DefaultHandler, FnHandler};
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_node::Delivery;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+#[derive(Default)]
+struct Counters {
+    accepted: AtomicU64,
+    connected: AtomicU64,
+    error: AtomicU64,
+    disconnected: AtomicU64,
+    stop: AtomicU64,
+}
+
+fn count(counter: &AtomicU64) {
+    let _ = counter.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn test_default_handler() {
+    let node = Node::<TestMsg, DefaultHandler>::new(
+        1,
+        "node_1".to_string(),
+        DefaultHandler::default(),
+        false,
+        Notifier::new());
+    assert!(node.is_ok());
+}
+
+// every closure provided is invoked
+#[test]
+fn test_fn_handler() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8521".parse().unwrap();
+    // nobody listens
+    let closed_addr: SocketAddr = "127.0.0.1:8522".parse().unwrap();
+    let counters = Arc::new(Counters::default());
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let (c1, c2, c3) = (counters.clone(), counters.clone(), counters.clone());
+    let server_handler = FnHandler::<TestMsg>::new()
+        .set_on_accepted(move |_| {
+            count(&c1.accepted);
+            Ok(())
+        })
+        .set_on_message(move |_, m| {
+            let _ = sender.send(m.payload());
+            Ok(())
+        })
+        .set_on_disconnected(move |_, _| { count(&c2.disconnected); })
+        .set_on_stop(move || { count(&c3.stop); });
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_delivery(Delivery::Push)
+        .build::<TestMsg, _>(server_handler)
+        .unwrap();
+    let (c1, c2) = (counters.clone(), counters.clone());
+    let client_handler = FnHandler::<TestMsg>::new()
+        .set_on_connected(move |_, r| {
+            count(&c1.connected);
+            r?;
+            Ok(())
+        })
+        .set_on_error(move |e| {
+            if net_error::is_connection_refused(&e) {
+                count(&c2.error);
+            }
+        });
+    let client = Node::<TestMsg, _>::new(2, "node_2".to_string(), client_handler, false, notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    let n = notifier.clone();
+    let c = counters.clone();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(1));
+        ep.close().await.unwrap();
+        drop(ep);
+
+        // the connect failure returned by on_connected is reported by on_error
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        assert!(client_sink.connect(1, closed_addr, opt).await.is_err());
+        while c.disconnected.load(Ordering::SeqCst) == 0 || c.error.load(Ordering::SeqCst) == 0 {
+            sleep(Duration::from_millis(10)).await;
+        }
+        n.notify_all();
+    });
+    assert_eq!(counters.accepted.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.connected.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.disconnected.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.error.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.stop.load(Ordering::SeqCst), 1);
+}
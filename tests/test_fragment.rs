@@ -0,0 +1,192 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::{LocalSet, yield_now};
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, client_node};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Heartbeat(u64),
+    Snapshot(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+// the max message size of both sides
+const FRAME_LIMIT: usize = 16 * 1024;
+
+const SNAPSHOT_SIZE: usize = 10 * FRAME_LIMIT;
+
+const NUM_HEARTBEATS: u64 = 8;
+
+enum Event {
+    Accepted(Arc<dyn EndpointAsync<TestMsg>>),
+    Error(ET),
+    Disconnected(ET),
+}
+
+// forward the accepted endpoints, the errors and the disconnects to the test
+struct EventHandler {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EventHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let _ = self.sender.send(Event::Accepted(endpoint));
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ET) {
+        let _ = self.sender.send(Event::Error(error));
+    }
+
+    async fn on_disconnected(&self, _: SocketAddr, reason: ET) {
+        let _ = self.sender.send(Event::Disconnected(reason));
+    }
+
+    async fn on_stop(&self) {}
+}
+
+// the server reassembles up to `max_bytes` bytes of the fragments
+fn server(port: u16, max_bytes: usize, notifier: &Notifier) -> (Node<TestMsg, EventHandler>, mpsc::UnboundedReceiver<Event>) {
+    let (sender, events) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .set_max_message_size(FRAME_LIMIT)
+        .set_reassembly_limit(2, max_bytes)
+        .build::<TestMsg, _>(EventHandler { sender })
+        .unwrap();
+    (server, events)
+}
+
+fn client_option() -> ESConnectOpt {
+    ESConnectOpt::default()
+        .enable_return_endpoint(true)
+        .set_max_message_size(Some(FRAME_LIMIT))
+        .set_fragment_size(FRAME_LIMIT)
+}
+
+fn snapshot() -> Vec<u8> {
+    (0..SNAPSHOT_SIZE).map(|i| { (i % 251) as u8 }).collect()
+}
+
+// A snapshot 10 times the frame limit round-trips intact, the heartbeats sent during the
+// transfer are written between its fragments, and received before it.
+#[test]
+fn test_fragment_round_trip() {
+    let notifier = Notifier::new();
+    let (server, mut events) = server(8631, SNAPSHOT_SIZE * 2, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8631".parse().unwrap();
+        let ep = sink.connect(1, address, client_option()).await.unwrap().unwrap();
+        let server_ep = match events.recv().await.unwrap() {
+            Event::Accepted(ep) => { ep }
+            _ => { panic!("an event before the accept"); }
+        };
+        let task_ep = ep.clone();
+        let sent = spawn_local_task(task_notifier, "send snapshot", async move {
+            task_ep.send(Message::new(TestMsg::Snapshot(snapshot()), 2, 1)).await.unwrap();
+        }).unwrap();
+        // the first fragments are queued
+        yield_now().await;
+        for i in 0..NUM_HEARTBEATS {
+            ep.send(Message::new(TestMsg::Heartbeat(i), 2, 1)).await.unwrap();
+        }
+
+        let (mut heartbeats, mut before_snapshot, mut opt_snapshot) = (0, 0, None);
+        while heartbeats < NUM_HEARTBEATS || opt_snapshot.is_none() {
+            match server_ep.recv().await.unwrap().payload() {
+                TestMsg::Heartbeat(n) => {
+                    assert_eq!(n, heartbeats);
+                    heartbeats += 1;
+                    if opt_snapshot.is_none() {
+                        before_snapshot += 1;
+                    }
+                }
+                TestMsg::Snapshot(data) => { opt_snapshot = Some(data); }
+            }
+        }
+        assert!(opt_snapshot.unwrap() == snapshot());
+        assert!(before_snapshot > 0);
+        sent.await.unwrap();
+        assert!(!ep.is_closed());
+        assert!(!server_ep.is_closed());
+        notifier.notify_all();
+    });
+}
+
+// the fragments beyond the reassembly limit close the connection
+#[test]
+fn test_fragment_reassembly_limit() {
+    let notifier = Notifier::new();
+    let (server, mut events) = server(8632, SNAPSHOT_SIZE / 2, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8632".parse().unwrap();
+        let ep = sink.connect(1, address, client_option()).await.unwrap().unwrap();
+        // the connection may be closed before all the fragments were written
+        let _ = ep.send(Message::new(TestMsg::Snapshot(snapshot()), 2, 1)).await;
+
+        let (mut opt_error, mut opt_reason) = (None, None);
+        while opt_error.is_none() || opt_reason.is_none() {
+            match events.recv().await.unwrap() {
+                Event::Accepted(_) => {}
+                Event::Error(e) => { opt_error = Some(e); }
+                Event::Disconnected(e) => { opt_reason = Some(e); }
+            }
+        }
+        let e = opt_error.unwrap();
+        assert!(net_error::is_fragment_rejected(&e), "{}", e.to_string());
+        assert!(e.to_string().contains(&format!("more than {} bytes", SNAPSHOT_SIZE / 2)), "{}", e.to_string());
+        assert!(net_error::is_fragment_rejected(&opt_reason.unwrap()));
+        assert!(ep.recv().await.is_err());
+        notifier.notify_all();
+    });
+}
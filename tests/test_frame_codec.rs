@@ -0,0 +1,145 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::frame_codec::FrameCodec;
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Line(String),
+}
+
+impl MsgTrait for TestMsg {}
+
+// a line of text per message, as a legacy protocol would
+struct LineCodec;
+
+impl FrameCodec<TestMsg> for LineCodec {
+    fn encode(&self, message: &Message<TestMsg>, buf: &mut BytesMut) -> Res<()> {
+        let TestMsg::Line(line) = message.clone().payload();
+        buf.extend_from_slice(line.as_bytes());
+        buf.extend_from_slice(b"\n");
+        Ok(())
+    }
+
+    fn decode(&self, buf: &mut BytesMut) -> Res<Option<Message<TestMsg>>> {
+        let n = match buf.iter().position(|b| { *b == b'\n' }) {
+            Some(n) => { n }
+            None => { return Ok(None); }
+        };
+        let line = buf.split_to(n + 1);
+        match String::from_utf8(line[..n].to_vec()) {
+            Ok(s) => { Ok(Some(Message::new(TestMsg::Line(s), 0, 0))) }
+            Err(e) => { Err(ET::SerdeError(e.to_string())) }
+        }
+    }
+}
+
+// the peer speaks lines, and writes its reply a byte at a time
+#[test]
+fn test_frame_codec_lines() {
+    let notifier = Notifier::new();
+    let node = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8556".parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_frame_codec::<TestMsg>(Arc::new(LineCodec));
+        let ep = sink.connect(2, addr, opt).await.unwrap().unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+
+        ep.send(Message::new(TestMsg::Line("hello".to_string()), 1, 2)).await.unwrap();
+        ep.send(Message::new(TestMsg::Line("world".to_string()), 1, 2)).await.unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "world");
+
+        for b in b"reply\n" {
+            write.write_all(&[*b]).await.unwrap();
+            write.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let m = ep.recv().await.unwrap();
+        assert_eq!(m.payload(), TestMsg::Line("reply".to_string()));
+
+        let r = ep.ping(Duration::from_millis(100)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_unsupported(e)));
+        notifier.notify_all();
+    });
+}
+
+// the count of a send is the bytes of the line, its newline included, the bytes the peer reads
+#[test]
+fn test_frame_codec_send_counted() {
+    let notifier = Notifier::new();
+    let node = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8555".parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_frame_codec::<TestMsg>(Arc::new(LineCodec));
+        let ep = sink.connect(2, addr, opt).await.unwrap().unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let n = ep.send_counted(Message::new(TestMsg::Line("hello".to_string()), 1, 2)).await.unwrap();
+        assert_eq!(n, "hello\n".len());
+        let mut line = vec![0u8; n];
+        stream.read_exact(&mut line).await.unwrap();
+        assert_eq!(&line[..], b"hello\n");
+        // nothing else was written for the message
+        ep.close().await.unwrap();
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,157 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use scupt_util::res_of::res_io;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::resolver::HostResolver;
+use scupt_net::transport::{NetStream, StreamListener, StreamTransport};
+
+use common::block_on_local;
+
+mod common;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello,
+}
+
+impl MsgTrait for TestMsg {}
+
+// the IPv6 connects are black-holed, they never complete, the IPv4 ones are TCP connects, the
+// addresses are recorded
+#[derive(Default)]
+struct BlackholeV6Transport {
+    connects: SyncMutex<Vec<SocketAddr>>,
+}
+
+impl BlackholeV6Transport {
+    fn connects(&self) -> Vec<SocketAddr> {
+        self.connects.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl StreamTransport for BlackholeV6Transport {
+    async fn connect(&self, address: SocketAddr) -> Res<(NetStream, SocketAddr)> {
+        self.connects.lock().unwrap().push(address);
+        if address.is_ipv6() {
+            sleep(Duration::from_secs(3600)).await;
+        }
+        let s = res_io(TcpStream::connect(address).await)?;
+        Ok((Box::new(s), address))
+    }
+
+    async fn bind(&self, _: SocketAddr) -> Res<Box<dyn StreamListener>> {
+        panic!("the client does not serve");
+    }
+}
+
+// the IPv6 address of the server first, as a resolver preferring IPv6 would
+struct DualStackResolver {
+    port: u16,
+}
+
+#[async_trait]
+impl HostResolver for DualStackResolver {
+    async fn lookup(&self, _: &str) -> Res<Vec<SocketAddr>> {
+        Ok(vec![
+            format!("[2001:db8::1]:{}", self.port).parse().unwrap(),
+            format!("127.0.0.1:{}", self.port).parse().unwrap(),
+        ])
+    }
+}
+
+fn client(port: u16, transport: Arc<BlackholeV6Transport>, notifier: &Notifier) -> Client<TestMsg> {
+    ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr(format!("server.test:{}", port))
+        .set_notifier(notifier.clone())
+        .set_host_resolver(Arc::new(DualStackResolver { port }))
+        .set_stream_transport(transport)
+        .build()
+        .unwrap()
+}
+
+// the IPv4 address is started after the delay, and connects while the IPv6 one hangs
+#[test]
+fn test_happy_eyeballs_v6_blackholed() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8612".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let transport = Arc::new(BlackholeV6Transport::default());
+    let client = client(8612, transport.clone(), &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let opt = OptClientConnect {
+            retry_max: 1,
+            happy_eyeballs_delay_ms: 100,
+            ..OptClientConnect::new()
+        }.enable_happy_eyeballs(true);
+        let start = Instant::now();
+        client.connect(opt).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(client.is_connected().await);
+        let connects = transport.connects();
+        assert_eq!(connects.len(), 2);
+        assert!(connects[0].is_ipv6());
+        assert!(connects[1].is_ipv4());
+        notifier.notify_all();
+    });
+}
+
+// without happy eyeballs, the attempt waits for the IPv6 address until it times out
+#[test]
+fn test_happy_eyeballs_disabled() {
+    let notifier = Notifier::new();
+    let transport = Arc::new(BlackholeV6Transport::default());
+    let client = client(8613, transport.clone(), &notifier);
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let opt = OptClientConnect {
+            retry_max: 1,
+            connect_timeout_ms: 200,
+            ..OptClientConnect::new()
+        };
+        let e = client.connect(opt).await.unwrap_err();
+        assert!(net_error::is_connect_timeout(&e), "{}", e.to_string());
+        let connects = transport.connects();
+        assert_eq!(connects.len(), 1);
+        assert!(connects[0].is_ipv6());
+        notifier.notify_all();
+    });
+}
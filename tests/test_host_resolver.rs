@@ -0,0 +1,147 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::resolver::HostResolver;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello,
+}
+
+impl MsgTrait for TestMsg {}
+
+// nobody listens on it
+const DEAD_ADDRESS: &str = "127.0.0.1:8606";
+
+// the server moved from the dead address to the live one after the first lookup
+struct FailoverResolver {
+    port: u16,
+    lookups: AtomicU64,
+    // the first lookup stalls
+    stall_first: bool,
+    // every lookup gives both addresses
+    both: bool,
+}
+
+impl FailoverResolver {
+    fn new(port: u16, stall_first: bool, both: bool) -> Self {
+        Self {
+            port,
+            lookups: AtomicU64::new(0),
+            stall_first,
+            both,
+        }
+    }
+
+    fn lookups(&self) -> u64 {
+        self.lookups.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl HostResolver for FailoverResolver {
+    async fn lookup(&self, host: &str) -> Res<Vec<SocketAddr>> {
+        assert_eq!(host, format!("server.test:{}", self.port));
+        let n = self.lookups.fetch_add(1, Ordering::SeqCst);
+        let dead: SocketAddr = DEAD_ADDRESS.parse().unwrap();
+        let live: SocketAddr = format!("127.0.0.1:{}", self.port).parse().unwrap();
+        if self.both {
+            return Ok(vec![dead, live]);
+        }
+        if n == 0 {
+            if self.stall_first {
+                sleep(Duration::from_secs(3600)).await;
+            }
+            Ok(vec![dead])
+        } else {
+            Ok(vec![live])
+        }
+    }
+}
+
+fn server(port: u16, notifier: &Notifier) -> Node<TestMsg, FnHandler<TestMsg>> {
+    NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap()
+}
+
+fn run_connect(resolver: Arc<FailoverResolver>, builder: ClientBuilder, expected_lookups: u64) {
+    let notifier = Notifier::new();
+    let server = server(resolver.port, &notifier);
+    let client: Client<TestMsg> = builder
+        .set_node_id(2)
+        .set_server_addr(format!("server.test:{}", resolver.port))
+        .set_notifier(notifier.clone())
+        .set_host_resolver(resolver.clone())
+        .build()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let opt = OptClientConnect {
+            retry_max: 3,
+            retry_wait_ms: 10,
+            connect_timeout_ms: 0,
+            ..OptClientConnect::new()
+        };
+        client.connect(opt).await.unwrap();
+        assert!(client.is_connected().await);
+        assert_eq!(resolver.lookups(), expected_lookups);
+        notifier.notify_all();
+    });
+}
+
+// the second attempt looks the name up again and connects to the new address
+#[test]
+fn test_host_resolver_lookup_per_attempt() {
+    let resolver = Arc::new(FailoverResolver::new(8607, false, false));
+    run_connect(resolver, ClientBuilder::new(), 2);
+}
+
+// the first attempt tries the live address after the dead one
+#[test]
+fn test_host_resolver_try_all_addresses() {
+    let resolver = Arc::new(FailoverResolver::new(8608, false, true));
+    run_connect(resolver, ClientBuilder::new().enable_try_all_addresses(true), 1);
+}
+
+// the stalled lookup fails the first attempt, the second one connects
+#[test]
+fn test_host_resolver_resolve_timeout() {
+    let resolver = Arc::new(FailoverResolver::new(8609, true, false));
+    run_connect(resolver, ClientBuilder::new().set_resolve_timeout_ms(100), 2);
+}
@@ -0,0 +1,118 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+// forward the errors to the test
+struct ErrorHandler {
+    sender: mpsc::UnboundedSender<ET>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for ErrorHandler {
+    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ET) {
+        let _ = self.sender.send(error);
+    }
+
+    async fn on_stop(&self) {}
+}
+
+// connect to a peer which accepts the connection and never sends anything
+fn test_silent_peer(port: u16, idle_timeout_ms: u64, check: bool) {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let node = Node::<TestMsg, ErrorHandler>::new(
+        1,
+        "node_1".to_string(),
+        ErrorHandler { sender },
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_idle_timeout_ms(idle_timeout_ms);
+        let (r_connect, r_accept) = tokio::join!(
+            sink.connect(2, addr, opt),
+            listener.accept()
+        );
+        let ep = r_connect.unwrap().unwrap();
+        let (mut socket, _) = r_accept.unwrap();
+        if check {
+            match ep.recv().await {
+                Ok(_) => { panic!("unexpected message"); }
+                Err(e) => { assert!(net_error::is_idle_timeout(&e)); }
+            }
+            let e = receiver.recv().await.unwrap();
+            assert!(net_error::is_idle_timeout(&e));
+
+            // the reaped connection was closed
+            let mut buf = [0u8; 16];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_eq!(n, 0);
+        } else {
+            let r = tokio::time::timeout(Duration::from_millis(500), ep.recv()).await;
+            assert!(r.is_err());
+            assert!(receiver.try_recv().is_err());
+        }
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_idle_timeout_reap_silent_peer() {
+    test_silent_peer(8321, 200, true);
+}
+
+#[test]
+fn test_idle_timeout_disabled() {
+    test_silent_peer(8322, 0, false);
+}
@@ -0,0 +1,168 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+// report the errors of the node to the test
+struct ErrorHandler {
+    sender: mpsc::UnboundedSender<ET>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for ErrorHandler {
+    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, e: ET) {
+        let _ = self.sender.send(e);
+    }
+
+    async fn on_stop(&self) {}
+}
+
+fn new_node(notifier: &Notifier, node_id: u64) -> (Node<TestMsg, ErrorHandler>, mpsc::UnboundedReceiver<ET>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let node = Node::<TestMsg, ErrorHandler>::new(
+        node_id,
+        format!("node_{}", node_id),
+        ErrorHandler { sender },
+        false,
+        notifier.clone()).unwrap();
+    (node, receiver)
+}
+
+#[test]
+fn test_io_error_kind() {
+    let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let e = net_error::io_error(io::Error::from(io::ErrorKind::ConnectionRefused), "connect", addr);
+    assert!(matches!(e, ET::IOError(_)));
+    assert_eq!(net_error::io_kind(&e), Some(io::ErrorKind::ConnectionRefused));
+    assert!(e.to_string().contains("op=connect addr=127.0.0.1:1"));
+    assert!(net_error::is_connection_refused(&e));
+    assert!(net_error::is_retryable(&e));
+
+    let e = net_error::io_error(io::Error::from(io::ErrorKind::BrokenPipe), "write", addr);
+    assert!(net_error::is_connection_reset(&e));
+    let e = net_error::io_error(io::Error::from(io::ErrorKind::Unsupported), "read", addr);
+    assert_eq!(net_error::io_kind(&e), Some(io::ErrorKind::Other));
+    assert_eq!(net_error::io_kind(&ET::EOF), None);
+    assert!(!net_error::is_retryable(&net_error::addr_parse("not an addr")));
+}
+
+#[test]
+fn test_io_error_refused() {
+    let notifier = Notifier::new();
+    let (node, _errors) = new_node(&notifier, 1);
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        // nobody listens
+        let addr: SocketAddr = "127.0.0.1:8461".parse().unwrap();
+        let r = sink.connect(2, addr, ESConnectOpt::default().enable_return_endpoint(true)).await;
+        match r {
+            Err(e) => {
+                assert!(net_error::is_connection_refused(&e), "{}", e.to_string());
+                assert!(net_error::is_retryable(&e));
+            }
+            Ok(_) => { panic!("connected to a closed port"); }
+        }
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_io_error_addr_in_use() {
+    let notifier = Notifier::new();
+    let (node_1, _errors_1) = new_node(&notifier, 1);
+    let (node_2, mut errors_2) = new_node(&notifier, 2);
+    let local = LocalSet::new();
+    node_1.run_local(&local);
+    node_2.run_local(&local);
+    let sink_1 = node_1.default_event_sink();
+    let sink_2 = node_2.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8462".parse().unwrap();
+        sink_1.serve(addr, ESServeOpt::default()).await.unwrap();
+        // the bind error is returned, and reported to the handler
+        let r = sink_2.serve(addr, ESServeOpt::default()).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_addr_in_use(e)));
+        let e = errors_2.recv().await.unwrap();
+        assert!(net_error::is_addr_in_use(&e), "{}", e.to_string());
+        assert!(!net_error::is_retryable(&e));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_io_error_reset() {
+    let notifier = Notifier::new();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8463".parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink.connect(2, addr, opt).await.unwrap().unwrap();
+        // close with a zero linger, the peer reads a RST
+        let (stream, _) = listener.accept().await.unwrap();
+        stream.set_linger(Some(Duration::ZERO)).unwrap();
+        drop(stream);
+        match ep.recv().await {
+            Err(e) => {
+                assert!(net_error::is_connection_reset(&e), "{}", e.to_string());
+                assert!(net_error::is_retryable(&e));
+            }
+            Ok(_) => { panic!("received from a reset connection"); }
+        }
+        notifier.notify_all();
+    });
+}
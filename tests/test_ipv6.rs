@@ -0,0 +1,131 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// the platform may have no IPv6
+fn has_ipv6() -> bool {
+    std::net::TcpListener::bind("[::1]:0").is_ok()
+}
+
+// a server reporting the remote addresses of the accepted endpoints
+fn server(
+    listen_address: &str,
+    opt_dual_stack: Option<bool>,
+    notifier: &Notifier,
+    accepted: mpsc::UnboundedSender<SocketAddr>,
+) -> Node<TestMsg, FnHandler<TestMsg>> {
+    let builder = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(listen_address.to_string());
+    let builder = match opt_dual_stack {
+        Some(dual_stack) => { builder.set_dual_stack(dual_stack) }
+        None => { builder }
+    };
+    builder.build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+        .set_on_accepted(move |ep| {
+            let _ = accepted.send(ep.remote_address());
+            Ok(())
+        }))
+        .unwrap()
+}
+
+fn client(node_id: u64, server_addr: &str, notifier: &Notifier) -> Client<TestMsg> {
+    ClientBuilder::new()
+        .set_node_id(node_id)
+        .set_server_addr(server_addr.to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap()
+}
+
+#[test]
+fn test_ipv6_loopback() {
+    if !has_ipv6() {
+        return;
+    }
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = server("[::1]:8574", None, &notifier, sender);
+    let client = client(2, "[::1]:8574", &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let address = accepted.recv().await.unwrap();
+        assert!(address.is_ipv6());
+        assert!(address.ip().is_loopback());
+        let ep = client.endpoint().unwrap();
+        assert_eq!(ep.remote_address(), "[::1]:8574".parse::<SocketAddr>().unwrap());
+        notifier.notify_all();
+    });
+}
+
+// an IPv4 client of a dual stack listener is reported by its IPv4 address, a host name is
+// resolved by the client
+#[test]
+fn test_dual_stack() {
+    if !has_ipv6() {
+        return;
+    }
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = server("[::]:8575", Some(true), &notifier, sender);
+    let v4 = client(2, "127.0.0.1:8575", &notifier);
+    let by_name = client(3, "localhost:8575", &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    v4.run(&local);
+    by_name.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        v4.connect(OptClientConnect::new()).await.unwrap();
+        let address = accepted.recv().await.unwrap();
+        assert_eq!(address.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        by_name.connect(OptClientConnect::new()).await.unwrap();
+        let address = accepted.recv().await.unwrap();
+        assert!(address.ip().is_loopback());
+        notifier.notify_all();
+    });
+}
+
+// one dual stack listener serves the clients of both families
+#[test]
+fn test_dual_stack_both_families() {
+    if !has_ipv6() {
+        return;
+    }
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = server("[::]:8576", Some(true), &notifier, sender);
+    let v4 = client(2, "127.0.0.1:8576", &notifier);
+    let v6 = client(3, "[::1]:8576", &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    v4.run(&local);
+    v6.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        v4.connect(OptClientConnect::new()).await.unwrap();
+        assert!(accepted.recv().await.unwrap().is_ipv4());
+        v6.connect(OptClientConnect::new()).await.unwrap();
+        assert!(accepted.recv().await.unwrap().is_ipv6());
+        assert!(v4.is_connected().await);
+        assert!(v6.is_connected().await);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+
+use scupt_net::client::{Client, OptClient, OptClientConnect};
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+const RETRY_WAIT_MS: u64 = 1000;
+
+const NUM_DRAWS: usize = 1000;
+
+fn opt(fraction: f64) -> OptClientConnect {
+    OptClientConnect {
+        retry_wait_ms: RETRY_WAIT_MS,
+        ..OptClientConnect::default()
+    }.set_jitter_fraction(fraction)
+}
+
+// the successive waits spread within the base +- the fraction
+#[test]
+fn test_jitter_range() {
+    for fraction in [0.1, 0.5, 1.0] {
+        let opt = opt(fraction);
+        let base = Duration::from_millis(RETRY_WAIT_MS);
+        let (min, max) = (base.mul_f64(1.0 - fraction), base.mul_f64(1.0 + fraction));
+        let mut waits = HashSet::new();
+        for _ in 0..NUM_DRAWS {
+            let wait = opt.retry_wait();
+            assert!(min <= wait && wait <= max, "{:?} not in [{:?}, {:?}]", wait, min, max);
+            waits.insert(wait);
+        }
+        assert!(waits.len() > 1);
+        // both sides of the base are drawn
+        assert!(waits.iter().any(|w| { *w < base }));
+        assert!(waits.iter().any(|w| { *w > base }));
+    }
+}
+
+// no jitter waits exactly the base
+#[test]
+fn test_jitter_disabled() {
+    let opt = opt(0.0);
+    for _ in 0..NUM_DRAWS {
+        assert_eq!(opt.retry_wait(), Duration::from_millis(RETRY_WAIT_MS));
+    }
+}
+
+// a fraction out of [0, 1] fails the connect before any attempt
+#[test]
+fn test_jitter_invalid_fraction() {
+    let notifier = Notifier::new();
+    let client = Client::<TestMsg>::new(
+        1, "client_1".to_string(), "127.0.0.1:8630".to_string(), OptClient::default(), notifier.clone(),
+    ).unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        for fraction in [-0.1, 1.5] {
+            let e = client.connect(opt(fraction)).await.unwrap_err();
+            assert!(net_error::is_invalid_option(&e), "{}", e.to_string());
+            assert!(e.to_string().contains("jitter_fraction"));
+        }
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+
+use scupt_util::message::Message;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::respond_handler::RespondHandler;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    Ok(Message::new(m.payload(), 1, peer))
+}
+
+// the system chooses the ports, the clients connect to the ones read back
+#[test]
+fn test_listen_port_zero() {
+    let notifier = Notifier::new();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let server_notifier = notifier.clone();
+    let server_thread = std::thread::spawn(move || {
+        let server = NodeBuilder::new()
+            .set_node_id(1)
+            .set_notifier(server_notifier.clone())
+            .set_listen_address("127.0.0.1:0".to_string())
+            .build::<TestMsg, _>(RespondHandler::new(server_notifier, echo))
+            .unwrap();
+        let local = LocalSet::new();
+        server.run_local(&local);
+        block_on_local(local, async move {
+            assert!(server.listen_addresses().is_empty());
+            server.serve(ESServeOpt::default()).await.unwrap();
+            // a second listener of the node
+            let address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+            server.default_event_sink().serve(address, ESServeOpt::default()).await.unwrap();
+            let addresses = server.listen_addresses();
+            assert_eq!(server.handle().listen_addresses(), addresses);
+            sender.send(addresses).unwrap();
+        });
+    });
+
+    let addresses = receiver.recv().unwrap();
+    assert_eq!(addresses.len(), 2);
+    assert!(addresses.iter().all(|a| { a.ip().is_loopback() && a.port() != 0 }));
+    assert_ne!(addresses[0], addresses[1]);
+    let clients: Vec<_> = addresses.iter().enumerate().map(|(i, a)| {
+        ClientBuilder::new()
+            .set_node_id(2 + i as NID)
+            .set_server_addr(a.to_string())
+            .set_notifier(notifier.clone())
+            .build::<TestMsg>()
+            .unwrap()
+    }).collect();
+    let local = LocalSet::new();
+    for c in &clients {
+        c.run(&local);
+    }
+    block_on_local(local, async move {
+        for (i, c) in clients.iter().enumerate() {
+            let id = 2 + i as NID;
+            c.connect(OptClientConnect::new()).await.unwrap();
+            c.send(Message::new(TestMsg::Id(id), id, 1)).await.unwrap();
+            assert_eq!(c.recv().await.unwrap().payload(), TestMsg::Id(id));
+        }
+        notifier.notify_all();
+    });
+    server_thread.join().unwrap();
+}
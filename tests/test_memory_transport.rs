@@ -0,0 +1,169 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+use scupt_net::transport::Transport;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    Accepted,
+    Connected(bool),
+    Closed,
+}
+
+// echo the messages of the accepted endpoints, and report the events to the test
+struct EchoHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EchoHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let _ = self.sender.send(Event::Accepted);
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "echo", async move {
+            loop {
+                match endpoint.recv().await {
+                    Ok(m) => {
+                        let _ = endpoint.send(m).await;
+                    }
+                    Err(_) => {
+                        let _ = sender.send(Event::Closed);
+                        break;
+                    }
+                }
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, endpoint: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        let _ = self.sender.send(Event::Connected(endpoint.is_ok()));
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+type TestNode = Node<TestMsg, EchoHandler>;
+
+fn new_memory_node(node_id: NID, notifier: Notifier) -> (TestNode, mpsc::UnboundedReceiver<Event>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let handler = EchoHandler {
+        notifier: notifier.clone(),
+        sender,
+    };
+    let node = TestNode::new(
+        node_id,
+        format!("node_{}", node_id),
+        handler,
+        false,
+        notifier).unwrap();
+    node.set_transport(Transport::Memory);
+    (node, receiver)
+}
+
+#[test]
+fn test_memory_transport_echo_and_close() {
+    let notifier = Notifier::new();
+    let (server, mut server_events) = new_memory_node(1, notifier.clone());
+    let (client, mut client_events) = new_memory_node(2, notifier.clone());
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        // a logical address, nothing is bound on the network
+        let addr: SocketAddr = "10.255.0.1:1".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        assert_eq!(ep.remote_address(), addr);
+        assert!(!ep.is_inbound());
+        assert_eq!(client_events.recv().await.unwrap(), Event::Connected(true));
+        assert_eq!(server_events.recv().await.unwrap(), Event::Accepted);
+
+        for id in 0..3 {
+            ep.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+            let m = ep.recv().await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(id));
+        }
+
+        ep.close().await.unwrap();
+        assert_eq!(server_events.recv().await.unwrap(), Event::Closed);
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_memory_transport_connect_refused() {
+    let notifier = Notifier::new();
+    let (client, mut client_events) = new_memory_node(2, notifier.clone());
+    let local = LocalSet::new();
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "10.255.0.2:1".parse().unwrap();
+        let receiver = client_sink.connect_completion(1, addr, ESConnectOpt::default()).await.unwrap();
+        let result = receiver.await.unwrap();
+        assert!(matches!(result, Err(ET::IOError(_))));
+        assert_eq!(client_events.recv().await.unwrap(), Event::Connected(false));
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_memory_transport_client() {
+    let notifier = Notifier::new();
+    let (server, mut server_events) = new_memory_node(1, notifier.clone());
+    let addr: SocketAddr = "10.255.0.3:1".parse().unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(1)
+        .set_server_addr(addr.to_string())
+        .set_notifier(notifier.clone())
+        .set_transport(Transport::Memory)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        assert_eq!(server_events.recv().await.unwrap(), Event::Accepted);
+        for id in 0..3 {
+            client.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+            let m = client.recv().await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(id));
+        }
+
+        // the server drops the endpoint on EOF, the client reads EOF as over TCP
+        client.close_send().await.unwrap();
+        assert_eq!(server_events.recv().await.unwrap(), Event::Closed);
+        assert!(matches!(client.recv().await, Err(ET::EOF)));
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,109 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward the received messages to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_node_metrics() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8401".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        for id in 0..3 {
+            ep.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+        }
+        for id in 0..3 {
+            assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(id));
+        }
+        // nothing is listening
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let r = client_sink.connect(3, "127.0.0.1:8402".parse().unwrap(), opt).await;
+        assert!(r.is_err());
+
+        let m_client = client.metrics();
+        let m_server = server.metrics();
+        assert_eq!(m_client.connections, 1);
+        assert_eq!(m_client.accepted, 0);
+        assert_eq!(m_client.connect_failures, 1);
+        assert_eq!(m_client.messages_out, 3);
+        assert_eq!(m_client.messages_in, 0);
+        assert!(m_client.bytes_out > 0);
+        assert!(m_client.queue_high_water >= 1);
+
+        assert_eq!(m_server.connections, 1);
+        assert_eq!(m_server.accepted, 1);
+        assert_eq!(m_server.connect_failures, 0);
+        assert_eq!(m_server.messages_in, 3);
+        assert_eq!(m_server.messages_out, 0);
+        assert_eq!(m_server.bytes_in, m_client.bytes_out);
+        assert_eq!(m_server.decode_errors, 0);
+
+        // the endpoint was the only owner of the connection
+        drop(ep);
+        assert_eq!(client.metrics().connections, 0);
+        notifier.notify_all();
+    });
+}
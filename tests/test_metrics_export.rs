@@ -0,0 +1,166 @@
+#![cfg(feature = "metrics-export")]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use metrics::{Counter, CounterFn, Gauge, GaugeFn, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use scupt_util::message::Message;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::metrics::export;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_node::Delivery;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+struct Value(AtomicU64);
+
+impl CounterFn for Value {
+    fn increment(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::SeqCst);
+    }
+
+    fn absolute(&self, value: u64) {
+        self.0.store(value, Ordering::SeqCst);
+    }
+}
+
+struct Level(AtomicI64);
+
+impl GaugeFn for Level {
+    fn increment(&self, value: f64) {
+        self.0.fetch_add(value as i64, Ordering::SeqCst);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.0.fetch_sub(value as i64, Ordering::SeqCst);
+    }
+
+    fn set(&self, value: f64) {
+        self.0.store(value as i64, Ordering::SeqCst);
+    }
+}
+
+// the metrics by name and labels, such as "scupt_net.bytes_sent nid=2 direction=out"
+#[derive(Default)]
+struct TestRecorder {
+    counters: Mutex<HashMap<String, Arc<Value>>>,
+    gauges: Mutex<HashMap<String, Arc<Level>>>,
+}
+
+fn key_name(key: &Key) -> String {
+    let labels: Vec<String> = key.labels().map(|l| { format!("{}={}", l.key(), l.value()) }).collect();
+    format!("{} {}", key.name(), labels.join(" "))
+}
+
+impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        let mut counters = self.counters.lock().unwrap();
+        let value = counters.entry(key_name(key))
+            .or_insert_with(|| { Arc::new(Value(AtomicU64::new(0))) })
+            .clone();
+        Counter::from_arc(value)
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.gauges.lock().unwrap();
+        let level = gauges.entry(key_name(key))
+            .or_insert_with(|| { Arc::new(Level(AtomicI64::new(0))) })
+            .clone();
+        Gauge::from_arc(level)
+    }
+
+    fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+#[derive(Clone)]
+struct Shared(Arc<TestRecorder>);
+
+impl Recorder for Shared {
+    fn describe_counter(&self, k: KeyName, u: Option<Unit>, d: SharedString) { self.0.describe_counter(k, u, d) }
+
+    fn describe_gauge(&self, k: KeyName, u: Option<Unit>, d: SharedString) { self.0.describe_gauge(k, u, d) }
+
+    fn describe_histogram(&self, k: KeyName, u: Option<Unit>, d: SharedString) { self.0.describe_histogram(k, u, d) }
+
+    fn register_counter(&self, key: &Key, m: &Metadata<'_>) -> Counter { self.0.register_counter(key, m) }
+
+    fn register_gauge(&self, key: &Key, m: &Metadata<'_>) -> Gauge { self.0.register_gauge(key, m) }
+
+    fn register_histogram(&self, key: &Key, m: &Metadata<'_>) -> Histogram { self.0.register_histogram(key, m) }
+}
+
+impl TestRecorder {
+    fn counter(&self, name: &str, nid: u64, direction: &str) -> u64 {
+        let key = format!("{} {}={} {}={}", name, export::LABEL_NID, nid, export::LABEL_DIRECTION, direction);
+        let counters = self.counters.lock().unwrap();
+        counters.get(&key).map(|v| { v.0.load(Ordering::SeqCst) }).unwrap_or(0)
+    }
+
+    fn gauge(&self, name: &str, nid: u64, direction: &str) -> i64 {
+        let key = format!("{} {}={} {}={}", name, export::LABEL_NID, nid, export::LABEL_DIRECTION, direction);
+        let gauges = self.gauges.lock().unwrap();
+        gauges.get(&key).map(|v| { v.0.load(Ordering::SeqCst) }).unwrap_or(0)
+    }
+}
+
+const NUM_MESSAGES: u64 = 10;
+
+#[test]
+fn test_metrics_export() {
+    let recorder = Arc::new(TestRecorder::default());
+    metrics::set_global_recorder(Shared(recorder.clone())).unwrap();
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8560".to_string())
+        .set_delivery(Delivery::Push)
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_message(move |_, m| {
+                let _ = sender.send(m.payload());
+                Ok(())
+            }))
+        .unwrap();
+    let client = Node::<TestMsg, _>::new(2, "node_2".to_string(), FnHandler::<TestMsg>::new(), false, notifier.clone())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    let r = recorder.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, "127.0.0.1:8560".parse().unwrap(), opt).await.unwrap().unwrap();
+        for id in 0..NUM_MESSAGES {
+            ep.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+            assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(id));
+        }
+        assert_eq!(r.counter(export::MESSAGES_SENT, 2, export::OUT), NUM_MESSAGES);
+        assert_eq!(r.counter(export::MESSAGES_RECEIVED, 1, export::IN), NUM_MESSAGES);
+        let sent = r.counter(export::BYTES_SENT, 2, export::OUT);
+        assert!(sent > 0);
+        assert_eq!(r.counter(export::BYTES_RECEIVED, 1, export::IN), sent);
+        assert_eq!(r.counter(export::ACCEPTED, 1, export::IN), 1);
+        assert_eq!(r.gauge(export::CONNECTIONS, 2, export::OUT), 1);
+        assert_eq!(r.gauge(export::CONNECTIONS, 1, export::IN), 1);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,140 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame::WIRE_VERSION;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::negotiated::{CAP_ADVERTISE_NAME, CAP_CHECKSUM, CAP_FLOW_CONTROL, FrameFormat};
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+const CLIENT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+// the server checksums and advertises its name, of the default max message size
+fn server(port: u16, negotiation: bool, notifier: &Notifier) -> (
+    Node<TestMsg, AcceptHandler<TestMsg>>,
+    mpsc::UnboundedReceiver<Arc<dyn EndpointAsync<TestMsg>>>,
+) {
+    let (sender, accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .enable_checksum(true)
+        .enable_advertise_name(true)
+        .enable_negotiation(negotiation)
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    (server, accepted)
+}
+
+// the client checksums and controls the flow, of a smaller max message size
+fn client_option() -> ESConnectOpt {
+    ESConnectOpt::default()
+        .enable_return_endpoint(true)
+        .enable_checksum(true)
+        .set_flow_window(8)
+        .set_max_message_size(Some(CLIENT_MAX_MESSAGE_SIZE))
+        .enable_negotiation(true)
+}
+
+// Both sides agree on the smaller max message size and on the capabilities of both, the
+// checksum only. The params are sent before the pongs, they were read when a ping returned.
+#[test]
+fn test_negotiated_converge() {
+    let notifier = Notifier::new();
+    let (server, mut accepted) = server(8624, true, &notifier);
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8624".parse().unwrap();
+        let ep = sink.connect(1, address, client_option()).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        ep.ping(Duration::from_secs(5)).await.unwrap();
+        server_ep.ping(Duration::from_secs(5)).await.unwrap();
+
+        for e in [&ep, &server_ep] {
+            let params = e.negotiated().unwrap();
+            assert!(params.agreed);
+            assert_eq!(params.format, FrameFormat::Builtin);
+            assert_eq!(params.wire_version, WIRE_VERSION);
+            assert_eq!(params.max_message_size, CLIENT_MAX_MESSAGE_SIZE);
+            assert_eq!(params.capabilities, CAP_CHECKSUM);
+            assert!(params.has(CAP_CHECKSUM));
+            assert!(!params.has(CAP_FLOW_CONTROL));
+            assert!(!params.has(CAP_ADVERTISE_NAME));
+        }
+        notifier.notify_all();
+    });
+}
+
+// a peer which sends no params leaves the ones of this side
+#[test]
+fn test_negotiated_peer_disabled() {
+    let notifier = Notifier::new();
+    let (server, mut accepted) = server(8625, false, &notifier);
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8625".parse().unwrap();
+        let ep = sink.connect(1, address, client_option()).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        ep.ping(Duration::from_secs(5)).await.unwrap();
+        server_ep.ping(Duration::from_secs(5)).await.unwrap();
+
+        let params = ep.negotiated().unwrap();
+        assert!(!params.agreed);
+        assert_eq!(params.max_message_size, CLIENT_MAX_MESSAGE_SIZE);
+        assert_eq!(params.capabilities, CAP_CHECKSUM | CAP_FLOW_CONTROL);
+
+        // the server read the params of the client all the same
+        let params = server_ep.negotiated().unwrap();
+        assert!(params.agreed);
+        assert_eq!(params.max_message_size, CLIENT_MAX_MESSAGE_SIZE);
+        assert_eq!(params.capabilities, CAP_CHECKSUM);
+        notifier.notify_all();
+    });
+}
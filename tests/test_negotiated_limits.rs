@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const SERVER_MAX_MESSAGE_SIZE: usize = 1024;
+
+const CLIENT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+// The client of the larger limit sends by the one of the server once agreed, a larger message
+// fails locally, and the connection stays open.
+#[test]
+fn test_negotiated_limits_sender() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8629".to_string())
+        .set_max_message_size(SERVER_MAX_MESSAGE_SIZE)
+        .enable_negotiation(true)
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8629".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_max_message_size(Some(CLIENT_MAX_MESSAGE_SIZE))
+            .enable_negotiation(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        // the params of the server were read before the pong
+        ep.ping(Duration::from_secs(5)).await.unwrap();
+        server_ep.ping(Duration::from_secs(5)).await.unwrap();
+
+        let limits = ep.negotiated_limits().unwrap();
+        assert_eq!(limits.max_message_size, SERVER_MAX_MESSAGE_SIZE);
+        assert_eq!(limits.local_max_message_size, CLIENT_MAX_MESSAGE_SIZE);
+        assert_eq!(limits.opt_peer_max_message_size, Some(SERVER_MAX_MESSAGE_SIZE));
+        let limits = server_ep.negotiated_limits().unwrap();
+        assert_eq!(limits.max_message_size, SERVER_MAX_MESSAGE_SIZE);
+        assert_eq!(limits.opt_peer_max_message_size, Some(CLIENT_MAX_MESSAGE_SIZE));
+
+        let e = ep.send(Message::new(TestMsg::Data(vec![0; 10 * 1024]), 2, 1)).await.unwrap_err();
+        assert!(net_error::is_message_too_large(&e), "{}", e.to_string());
+        assert!(e.to_string().contains(&format!("exceeds {} bytes", SERVER_MAX_MESSAGE_SIZE)), "{}", e.to_string());
+
+        ep.send(Message::new(TestMsg::Data(vec![1; 16]), 2, 1)).await.unwrap();
+        assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Data(vec![1; 16]));
+        assert!(!ep.is_closed());
+        assert!(!server_ep.is_closed());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::res::Res;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// keep the accepted endpoints alive
+#[derive(Default)]
+struct KeepHandler {
+    endpoints: Mutex<Vec<Arc<dyn EndpointAsync<TestMsg>>>>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for KeepHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        self.endpoints.lock().unwrap().push(endpoint);
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_node_builder_validate() {
+    let r = NodeBuilder::new()
+        .build::<TestMsg, _>(HandleEventDummy::default());
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+
+    // an empty listen address is not set
+    for addr in [" ", "not an addr", "127.0.0.1", "127.0.0.1:", "[::1]", "127.0.0.1:65536"] {
+        let r = NodeBuilder::new()
+            .set_node_id(1)
+            .set_listen_address(addr.to_string())
+            .build::<TestMsg, _>(HandleEventDummy::default());
+        assert!(matches!(r, Err(ref e) if net_error::is_addr_parse(e)), "{:?}", addr);
+    }
+
+    let r = NodeBuilder::new()
+        .set_node_id(1)
+        .set_backlog(0)
+        .build::<TestMsg, _>(HandleEventDummy::default());
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+
+    let node = NodeBuilder::new()
+        .set_node_id(1)
+        .set_listen_address("127.0.0.1:8391".to_string())
+        .set_backlog(16)
+        .set_max_connections(2)
+        .build::<TestMsg, _>(HandleEventDummy::default())
+        .unwrap();
+    let opt = node.opt_node();
+    assert_eq!(opt.listen_address(), Some("127.0.0.1:8391".parse().unwrap()));
+    assert_eq!(opt.backlog(), 16);
+    assert_eq!(opt.max_connections(), 2);
+}
+
+#[test]
+fn test_node_max_connections() {
+    let notifier = Notifier::new();
+    let server: Node<TestMsg, KeepHandler> = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8392".to_string())
+        .set_max_connections(1)
+        .build(KeepHandler::default())
+        .unwrap();
+    let client: Node<TestMsg, HandleEventDummy> = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build(HandleEventDummy::default())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let addr = server.opt_node().listen_address().unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let _ep1 = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        // beyond the limit, the server closes the connection
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep2 = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        assert!(ep2.recv().await.is_err());
+
+        // a node without the listen address cannot serve
+        let r = client.serve(ESServeOpt::default()).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+        notifier.notify_all();
+    });
+}
+
+// the server closed the connection first, its port is in TIME_WAIT when it is served again
+#[test]
+fn test_node_restart_reuse_address() {
+    let notifier = Notifier::new();
+    let build_server = |notifier: Notifier| {
+        NodeBuilder::new()
+            .set_node_id(1)
+            .set_notifier(notifier)
+            .set_listen_address("127.0.0.1:8552".to_string())
+            .set_reuse_address(true)
+            .build::<TestMsg, _>(HandleEventDummy::default())
+            .unwrap()
+    };
+    let server1 = build_server(notifier.clone());
+    let server2 = build_server(notifier.clone());
+    let client: Node<TestMsg, HandleEventDummy> = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build(HandleEventDummy::default())
+        .unwrap();
+    assert!(server1.opt_node().reuse_address());
+    let local = LocalSet::new();
+    server1.run_local(&local);
+    server2.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server1.serve(ESServeOpt::default()).await.unwrap();
+        let addr = server1.opt_node().listen_address().unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        // the endpoint was dropped by the handler of the server
+        assert!(ep.recv().await.is_err());
+        server1.handle().shutdown().await.unwrap();
+        // the listener was dropped by its task
+        server1.stop_notify().wait_all_terminated().await;
+
+        server2.serve(ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        assert!(client_sink.connect(1, addr, opt).await.is_ok());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,160 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::MsgTrait;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+// every accepted connection starts a task which keeps touching the counter
+#[derive(Clone)]
+struct CountHandler {
+    notifier: Notifier,
+    accepted: Arc<AtomicU64>,
+    counter: Arc<AtomicU64>,
+    stopped: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for CountHandler {
+    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let counter = self.counter.clone();
+        spawn_local_task(self.notifier.clone(), "touch counter", async move {
+            loop {
+                let _ = counter.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(10)).await;
+            }
+        })?;
+        let _ = self.accepted.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_node_join() {
+    let notifier = Notifier::new();
+    let handler = CountHandler {
+        notifier: notifier.clone(),
+        accepted: Arc::new(AtomicU64::new(0)),
+        counter: Arc::new(AtomicU64::new(0)),
+        stopped: Arc::new(AtomicBool::new(false)),
+    };
+    let node = Node::<TestMsg, CountHandler>::new(
+        1,
+        "node_1".to_string(),
+        handler.clone(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8331".parse().unwrap();
+        sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        for i in 0..3 {
+            sink.connect(10 + i, addr, ESConnectOpt::default()).await.unwrap();
+        }
+        while handler.accepted.load(Ordering::SeqCst) < 3 {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        notifier.notify_all();
+        node.join().await;
+        assert!(handler.stopped.load(Ordering::SeqCst));
+
+        // no task of the node is still running
+        let n = handler.counter.load(Ordering::SeqCst);
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(n, handler.counter.load(Ordering::SeqCst));
+    });
+}
+
+fn new_handler(notifier: &Notifier) -> CountHandler {
+    CountHandler {
+        notifier: notifier.clone(),
+        accepted: Arc::new(AtomicU64::new(0)),
+        counter: Arc::new(AtomicU64::new(0)),
+        stopped: Arc::new(AtomicBool::new(false)),
+    }
+}
+
+#[test]
+fn test_shared_notifier() {
+    let notifier = Notifier::new();
+    let handlers: Vec<CountHandler> = (0..3).map(|_| { new_handler(&notifier) }).collect();
+    let nodes: Vec<Node<TestMsg, CountHandler>> = handlers.iter().enumerate().map(|(i, h)| {
+        Node::<TestMsg, CountHandler>::new(
+            i as u64 + 1,
+            format!("node_{}", i + 1),
+            h.clone(),
+            false,
+            notifier.clone()).unwrap()
+    }).collect();
+    let local = LocalSet::new();
+    for node in nodes.iter() {
+        node.run_local(&local);
+    }
+    let first = nodes[0].handle();
+    block_on_local(local, async move {
+        // stopping a node does not stop the others
+        first.shutdown().await.unwrap();
+        assert!(first.stop_notify().is_notified());
+        assert!(!notifier.is_notified());
+        assert!(!nodes[1].stop_notify().is_notified());
+        while !handlers[0].stopped.load(Ordering::SeqCst) {
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!handlers[1].stopped.load(Ordering::SeqCst));
+
+        // the shared notifier stops all, each invokes its on_stop without a join
+        notifier.notify_all();
+        for h in handlers.iter() {
+            while !h.stopped.load(Ordering::SeqCst) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }
+        assert!(nodes.iter().all(|n| { n.stop_notify().is_notified() }));
+    });
+}
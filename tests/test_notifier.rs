@@ -0,0 +1,113 @@
+use std::thread;
+use std::time::Duration;
+
+use tokio::runtime::Builder;
+
+use scupt_net::notifier::Notifier;
+
+#[test]
+fn test_parent_notify_children() {
+    let parent = Notifier::new();
+    let child = parent.new_child();
+    let grandchild = child.new_child();
+    assert!(!child.is_notified());
+    assert!(!grandchild.is_notified());
+
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let waiter = grandchild.clone();
+    runtime.block_on(async move {
+        let wait = tokio::spawn(async move {
+            waiter.notified().await;
+        });
+        assert!(parent.notify_all());
+        wait.await.unwrap();
+    });
+    assert!(child.is_notified());
+    assert!(grandchild.is_notified());
+}
+
+#[test]
+fn test_child_not_notify_parent() {
+    let parent = Notifier::new();
+    let child1 = parent.new_child();
+    let child2 = parent.new_child();
+    let grandchild = child1.new_child();
+    assert!(child1.notify_all());
+    assert!(child1.is_notified());
+    assert!(grandchild.is_notified());
+    assert!(!parent.is_notified());
+    assert!(!child2.is_notified());
+}
+
+#[test]
+fn test_late_child() {
+    let parent = Notifier::new();
+    assert!(parent.notify_all());
+    let child = parent.new_child();
+    assert!(child.is_notified());
+}
+
+#[test]
+fn test_concurrent_child_creation() {
+    let parent = Notifier::new();
+    let mut threads = vec![];
+    for _ in 0..4 {
+        let p = parent.clone();
+        threads.push(thread::spawn(move || {
+            let mut children = vec![];
+            for _ in 0..1000 {
+                children.push(p.new_child());
+            }
+            children
+        }));
+    }
+    let _ = parent.notify_all();
+    for t in threads {
+        let children = t.join().unwrap();
+        for c in children {
+            assert!(c.is_notified());
+        }
+    }
+}
+
+#[test]
+fn test_wait_before_notify() {
+    let notifier = Notifier::new();
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async move {
+        let n = notifier.clone();
+        let wait = tokio::spawn(async move {
+            n.wait_timeout(Duration::from_secs(10)).await
+        });
+        // let the waiter register first
+        tokio::task::yield_now().await;
+        assert!(notifier.notify_all());
+        assert!(wait.await.unwrap());
+    });
+}
+
+#[test]
+fn test_wait_after_notify() {
+    let notifier = Notifier::new();
+    assert!(notifier.notify_all());
+    // idempotent
+    assert!(!notifier.notify_all());
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async move {
+        for _ in 0..3 {
+            assert!(notifier.wait_timeout(Duration::from_millis(1)).await);
+            notifier.notified().await;
+            assert!(notifier.is_notified());
+        }
+    });
+}
+
+#[test]
+fn test_wait_timeout_expired() {
+    let notifier = Notifier::new();
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async move {
+        assert!(!notifier.wait_timeout(Duration::from_millis(20)).await);
+        assert!(!notifier.is_notified());
+    });
+}
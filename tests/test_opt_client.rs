@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::client::{Client, ClientBuilder, ClientState, OptClient};
+use scupt_net::frame::{FrameHeader, HEADER_SIZE};
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDS: usize = 32;
+
+const SEND_QUEUE_CAPACITY: usize = 2;
+
+#[test]
+fn test_opt_client_validate() {
+    let opt = OptClient {
+        send_queue_capacity: 0,
+        ..OptClient::default()
+    };
+    let r = Client::<TestMsg>::new(1, "client_1".to_string(), "127.0.0.1:8534".to_string(), opt, Notifier::new());
+    assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+}
+
+// the server never reads, once the socket buffers are full, the sends wait for the slots of
+// the tiny send queue
+#[test]
+fn test_opt_client_send_queue_capacity() {
+    let notifier = Notifier::new();
+    let opt = OptClient {
+        nodelay: true,
+        send_queue_capacity: SEND_QUEUE_CAPACITY,
+        ..OptClient::default()
+    };
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8534".to_string())
+        .set_notifier(notifier.clone())
+        .set_opt_client(opt)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8534").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        client.connect_default().await.unwrap();
+        let _stream = accept.await.unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        for _ in 0..NUM_SENDS {
+            let c = client.clone();
+            let s = sender.clone();
+            let _ = spawn_local_task(notifier.clone(), "send", async move {
+                let r = c.send(Message::new(TestMsg::Data(vec![0u8; 1024 * 1024]), 2, 1)).await;
+                let _ = s.send(r.is_ok());
+            });
+        }
+        sleep(Duration::from_millis(500)).await;
+        let mut sent = 0;
+        while let Ok(ok) = receiver.try_recv() {
+            assert!(ok);
+            sent += 1;
+        }
+        assert!(sent < NUM_SENDS);
+        let metrics = client.node_handle().metrics();
+        assert!(metrics.queue_high_water <= SEND_QUEUE_CAPACITY as u64);
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_opt_client_max_message_size() {
+    let notifier = Notifier::new();
+    let opt = OptClient {
+        max_message_size: Some(1024),
+        ..OptClient::default()
+    };
+    let client = Client::<TestMsg>::new(2, "client_2".to_string(), "127.0.0.1:8535".to_string(), opt, notifier.clone())
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8535").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        client.connect_default().await.unwrap();
+        let _stream = accept.await.unwrap();
+        client.send(Message::new(TestMsg::Data(vec![0u8; 16]), 2, 1)).await.unwrap();
+        let r = client.send(Message::new(TestMsg::Data(vec![0u8; 4096]), 2, 1)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_message_too_large(e)));
+        notifier.notify_all();
+    });
+}
+
+// the flushed message is read by the peer before the client sends anything else
+#[test]
+fn test_client_send_flush() {
+    let notifier = Notifier::new();
+    let opt = OptClient {
+        nodelay: true,
+        ..OptClient::default()
+    };
+    let client = Client::<TestMsg>::new(2, "client_2".to_string(), "127.0.0.1:8536".to_string(), opt, notifier.clone())
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8536").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        client.connect_default().await.unwrap();
+        let (mut stream, _) = accept.await.unwrap();
+        for seq in 1..=3 {
+            client.send_flush(Message::new(TestMsg::Data(vec![seq as u8; 8]), 2, 1)).await.unwrap();
+            let mut header = [0u8; HEADER_SIZE];
+            timeout(Duration::from_secs(1), stream.read_exact(&mut header)).await.unwrap().unwrap();
+            let hdr = FrameHeader::decode(&header).unwrap();
+            assert_eq!(hdr.seq(), seq);
+            let mut payload = vec![0u8; hdr.size() as usize];
+            timeout(Duration::from_secs(1), stream.read_exact(&mut payload)).await.unwrap().unwrap();
+        }
+        notifier.notify_all();
+    });
+}
+
+// a send stalled by the peer not reading holds no lock of the client, the state is answered
+// right away
+#[test]
+fn test_client_slow_send() {
+    let notifier = Notifier::new();
+    let client = Client::<TestMsg>::new(2, "client_2".to_string(), "127.0.0.1:8573".to_string(), OptClient::default(), notifier.clone())
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8573").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        client.connect_default().await.unwrap();
+        let _stream = accept.await.unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let c = client.clone();
+        let _ = spawn_local_task(notifier.clone(), "slow send", async move {
+            for _ in 0..NUM_SENDS {
+                let r = c.send(Message::new(TestMsg::Data(vec![0u8; 1024 * 1024]), 2, 1)).await;
+                if r.is_err() {
+                    break;
+                }
+            }
+            let _ = sender.send(());
+        });
+        sleep(Duration::from_millis(200)).await;
+        // the sends are still waiting for the socket
+        assert!(receiver.try_recv().is_err());
+        let connected = timeout(Duration::from_millis(100), client.is_connected()).await.unwrap();
+        assert!(connected);
+        assert_eq!(client.state(), ClientState::Connected);
+        notifier.notify_all();
+    });
+}
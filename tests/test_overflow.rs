@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{decode_message, Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClient};
+use scupt_net::frame::{FrameHeader, HEADER_SIZE};
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+use scupt_net::overflow_policy::OverflowPolicy;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDS: u64 = 32;
+
+const SEND_QUEUE_CAPACITY: usize = 2;
+
+fn build_client(port: u16, policy: OverflowPolicy, notifier: Notifier) -> Client<TestMsg> {
+    let opt = OptClient {
+        nodelay: true,
+        send_queue_capacity: SEND_QUEUE_CAPACITY,
+        overflow_policy: policy,
+        ..OptClient::default()
+    };
+    ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr(format!("127.0.0.1:{}", port))
+        .set_notifier(notifier)
+        .set_opt_client(opt)
+        .build::<TestMsg>()
+        .unwrap()
+}
+
+// the peer does not read, the sends of 1MB messages fill the socket buffers first, then the
+// lane, the results are sent by their ids
+fn spawn_sends(client: &Client<TestMsg>, notifier: Notifier) -> mpsc::UnboundedReceiver<(u64, Res<()>)> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    for id in 0..NUM_SENDS {
+        let c = client.clone();
+        let s = sender.clone();
+        let _ = spawn_local_task(notifier.clone(), "send", async move {
+            let r = c.send(Message::new(TestMsg::Data(id, vec![0u8; 1024 * 1024]), 2, 1)).await;
+            let _ = s.send((id, r));
+        });
+    }
+    receiver
+}
+
+async fn read_id(peer: &mut TcpStream) -> u64 {
+    let mut header = [0u8; HEADER_SIZE];
+    peer.read_exact(&mut header).await.unwrap();
+    let header = FrameHeader::decode(&header).unwrap();
+    let mut payload = vec![0u8; header.size() as usize];
+    peer.read_exact(&mut payload).await.unwrap();
+    let (m, _) = decode_message::<Message<TestMsg>>(&payload).unwrap();
+    match m.payload() {
+        TestMsg::Data(id, _) => { id }
+    }
+}
+
+// the sends beyond the full lane stay pending, none fails
+#[test]
+fn test_overflow_block() {
+    let notifier = Notifier::new();
+    let client = build_client(8598, OverflowPolicy::Block, notifier.clone());
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8598").await.unwrap();
+        client.connect_default().await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let mut results = spawn_sends(&client, task_notifier);
+        sleep(Duration::from_millis(500)).await;
+        let mut done = 0;
+        while let Ok((_, r)) = results.try_recv() {
+            r.unwrap();
+            done += 1;
+        }
+        assert!(done < NUM_SENDS);
+
+        // all of them are written once the peer reads
+        for id in 0..NUM_SENDS {
+            assert_eq!(read_id(&mut peer).await, id);
+        }
+        while done < NUM_SENDS {
+            let (_, r) = results.recv().await.unwrap();
+            r.unwrap();
+            done += 1;
+        }
+        assert_eq!(client.node_handle().metrics().messages_dropped, 0);
+        notifier.notify_all();
+    });
+}
+
+// the sends beyond the full lane fail at once, the queued ones are written
+#[test]
+fn test_overflow_error_newest() {
+    let notifier = Notifier::new();
+    let client = build_client(8599, OverflowPolicy::ErrorNewest, notifier.clone());
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8599").await.unwrap();
+        client.connect_default().await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let mut results = spawn_sends(&client, task_notifier);
+        sleep(Duration::from_millis(500)).await;
+        let mut refused = vec![];
+        let mut done = 0;
+        while let Ok((id, r)) = results.try_recv() {
+            match r {
+                Ok(()) => {}
+                Err(e) => {
+                    assert!(net_error::is_queue_full(&e), "{:?}", e);
+                    refused.push(id);
+                }
+            }
+            done += 1;
+        }
+        assert!(!refused.is_empty());
+
+        let mut last = None;
+        for _ in 0..NUM_SENDS - refused.len() as u64 {
+            let id = read_id(&mut peer).await;
+            assert!(!refused.contains(&id));
+            assert!(last < Some(id));
+            last = Some(id);
+        }
+        while done < NUM_SENDS {
+            let (_, r) = results.recv().await.unwrap();
+            r.unwrap();
+            done += 1;
+        }
+        assert_eq!(client.node_handle().metrics().messages_dropped, 0);
+        notifier.notify_all();
+    });
+}
+
+// the sends beyond the full lane evict the oldest queued ones, the peer receives the messages
+// already written, then the newest ones
+#[test]
+fn test_overflow_drop_oldest() {
+    let notifier = Notifier::new();
+    let client = build_client(8600, OverflowPolicy::DropOldest, notifier.clone());
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8600").await.unwrap();
+        client.connect_default().await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let mut results = spawn_sends(&client, task_notifier);
+        sleep(Duration::from_millis(500)).await;
+        let mut dropped = vec![];
+        let mut done = 0;
+        while let Ok((id, r)) = results.try_recv() {
+            match r {
+                Ok(()) => {}
+                Err(e) => {
+                    assert!(net_error::is_dropped(&e), "{:?}", e);
+                    dropped.push(id);
+                }
+            }
+            done += 1;
+        }
+        assert!(!dropped.is_empty());
+        assert_eq!(client.node_handle().metrics().messages_dropped, dropped.len() as u64);
+
+        let mut received = vec![];
+        for _ in 0..NUM_SENDS - dropped.len() as u64 {
+            received.push(read_id(&mut peer).await);
+        }
+        // the newest messages took the slots of the lane
+        let newest: Vec<u64> = (NUM_SENDS - SEND_QUEUE_CAPACITY as u64..NUM_SENDS).collect();
+        assert!(received.ends_with(&newest), "{:?}", received);
+        // the dropped ones are the run before the newest ones
+        let first_dropped = received.len() as u64 - SEND_QUEUE_CAPACITY as u64;
+        assert_eq!(dropped, (first_dropped..NUM_SENDS - SEND_QUEUE_CAPACITY as u64).collect::<Vec<_>>());
+        while done < NUM_SENDS {
+            let (_, r) = results.recv().await.unwrap();
+            r.unwrap();
+            done += 1;
+        }
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,102 @@
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClient, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_CONNECTIONS: usize = 3;
+
+const NUM_SENDS: u64 = 30;
+
+// the sends are spread over the connections, every connection echoes the messages it
+// receives, and the client receives all the echoes
+#[test]
+fn test_parallel_connections() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8614".to_string())
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let opt = OptClient {
+        parallel_connections: NUM_CONNECTIONS,
+        ..OptClient::default()
+    };
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8614".to_string())
+        .set_notifier(notifier.clone())
+        .set_opt_client(opt)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (count_sender, mut counts) = mpsc::unbounded_channel();
+        for c in 0..NUM_CONNECTIONS {
+            let ep = accepted.recv().await.unwrap();
+            let s = count_sender.clone();
+            let _ = spawn_local_task(task_notifier.clone(), "echo", async move {
+                while let Ok(m) = ep.recv().await {
+                    let _ = s.send(c);
+                    let _ = ep.send(Message::new(m.payload(), 1, 2)).await;
+                }
+            });
+        }
+        drop(count_sender);
+
+        for i in 0..NUM_SENDS {
+            client.send(Message::new(TestMsg::Hello(i), 2, 1)).await.unwrap();
+        }
+        let mut received = vec![];
+        for _ in 0..NUM_SENDS {
+            match client.recv().await.unwrap().payload() {
+                TestMsg::Hello(i) => { received.push(i); }
+            }
+        }
+        received.sort();
+        assert_eq!(received, (0..NUM_SENDS).collect::<Vec<_>>());
+
+        // the sends went to the connections in turn
+        let mut per_connection = [0; NUM_CONNECTIONS];
+        for _ in 0..NUM_SENDS {
+            per_connection[counts.recv().await.unwrap()] += 1;
+        }
+        assert_eq!(per_connection, [NUM_SENDS / NUM_CONNECTIONS as u64; NUM_CONNECTIONS]);
+        assert!(accepted.try_recv().is_err());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,102 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::timeout;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward the received messages to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_partition_and_heal() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    // the partition is applied by the testing node
+    let node_server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        true,
+        notifier.clone()).unwrap();
+    let node_client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let controller = node_server.test_controller().unwrap();
+
+    let local = LocalSet::new();
+    node_server.run_local(&local);
+    node_client.run_local(&local);
+    let sink_server = node_server.default_event_sink();
+    let sink_client = node_client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8371".parse().unwrap();
+        sink_server.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink_client.connect(1, addr, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(1));
+
+        controller.partition(vec![vec![1], vec![2, 3]]);
+        assert!(controller.is_partitioned(1, 3));
+        assert!(!controller.is_partitioned(2, 3));
+        assert!(!controller.is_partitioned(1, 4));
+        // cut on the established endpoint
+        ep.send(Message::new(TestMsg::Id(2), 2, 1)).await.unwrap();
+        let r = timeout(Duration::from_millis(300), receiver.recv()).await;
+        assert!(r.is_err());
+        // a new connect across the cut fails before reaching the network
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let r = sink_server.connect(3, "127.0.0.1:8372".parse().unwrap(), opt).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_partitioned(e)));
+
+        controller.heal();
+        ep.send(Message::new(TestMsg::Id(3), 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(3));
+        notifier.notify_all();
+    });
+}
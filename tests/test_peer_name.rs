@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::{FnHandler, HandleEvent};
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello(String),
+}
+
+impl MsgTrait for TestMsg {}
+
+fn build_client(node_id: u64, name: &str, notifier: Notifier) -> Client<TestMsg> {
+    ClientBuilder::new()
+        .set_node_id(node_id)
+        .set_name(name.to_string())
+        .set_server_addr("127.0.0.1:8603".to_string())
+        .set_notifier(notifier)
+        .enable_advertise_name(true)
+        .build::<TestMsg>()
+        .unwrap()
+}
+
+// the name frame is read by the reader task after the endpoint was accepted
+async fn wait_named<H: HandleEvent<TestMsg> + 'static>(
+    server: &Node<TestMsg, H>,
+    name: &str,
+) -> Vec<Arc<dyn EndpointAsync<TestMsg>>> {
+    for _ in 0..500 {
+        let found = server.find_by_name(name);
+        if !found.is_empty() {
+            return found;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    vec![]
+}
+
+// two clients advertising their names connect to one server, which resolves each of them by
+// name
+#[test]
+fn test_peer_name() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_name("server".to_string())
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8603".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let alpha = build_client(2, "alpha", notifier.clone());
+    let beta = build_client(3, "beta", notifier.clone());
+    let local = LocalSet::new();
+    server.run_local(&local);
+    alpha.run(&local);
+    beta.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        alpha.connect(OptClientConnect::new()).await.unwrap();
+        beta.connect(OptClientConnect::new()).await.unwrap();
+
+        for (client, name, node_id) in [(&alpha, "alpha", 2), (&beta, "beta", 3)] {
+            let found = wait_named(&server, name).await;
+            assert_eq!(found.len(), 1);
+            let ep = &found[0];
+            assert!(ep.is_inbound());
+            assert_eq!(ep.peer_name().as_deref(), Some(name));
+            ep.send(Message::new(TestMsg::Hello(name.to_string()), 1, node_id)).await.unwrap();
+            let m = client.recv().await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Hello(name.to_string()));
+        }
+        assert!(server.find_by_name("gamma").is_empty());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use scupt_util::message::Message;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::respond_handler::RespondHandler;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    Ok(Message::new(m.payload(), 1, peer))
+}
+
+// the pongs are not received by the recv pending at the same time
+#[test]
+fn test_ping() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8541".to_string())
+        .build::<TestMsg, _>(RespondHandler::new(notifier.clone(), echo))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8541".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let c = client.clone();
+        let _ = spawn_local_task(notifier.clone(), "recv", async move {
+            let r = c.recv().await;
+            let _ = sender.send(r.map(|m| { m.payload() }));
+        });
+        for _ in 0..10 {
+            let rtt = client.ping(Duration::from_secs(1)).await.unwrap();
+            assert!(rtt < Duration::from_secs(1));
+        }
+        assert!(receiver.try_recv().is_err());
+        client.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().unwrap(), TestMsg::Id(1));
+        notifier.notify_all();
+    });
+}
+
+// the peer accepts but never answers
+#[test]
+fn test_ping_timeout() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8542".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8542").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let _stream = accept.await.unwrap();
+        let r = client.ping(Duration::from_millis(100)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_timeout(e)));
+        notifier.notify_all();
+    });
+}
+
+const NUM_PINGS: usize = 16;
+
+// every pending ping completes by its own pong
+#[test]
+fn test_ping_concurrent() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8557".to_string())
+        .build::<TestMsg, _>(RespondHandler::new(notifier.clone(), echo))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8557".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        for _ in 0..NUM_PINGS {
+            let c = client.clone();
+            let s = sender.clone();
+            let _ = spawn_local_task(notifier.clone(), "ping", async move {
+                let _ = s.send(c.ping(Duration::from_secs(1)).await);
+            });
+        }
+        for _ in 0..NUM_PINGS {
+            let rtt = receiver.recv().await.unwrap().unwrap();
+            assert!(rtt < Duration::from_secs(1));
+        }
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,83 @@
+use std::task::{Context, Poll};
+
+use futures::task::noop_waker_ref;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::{LocalSet, yield_now};
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const NUM_MESSAGES: u64 = 100;
+
+// a poll loop of its own, without waking, yielding to the tasks of the endpoints in between
+async fn poll_loop<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(mut f: F) -> T {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    loop {
+        if let Poll::Ready(r) = f(&mut cx) {
+            return r;
+        }
+        yield_now().await;
+    }
+}
+
+// the sends and the recvs are driven by polling by hand, as by the async forms
+#[test]
+fn test_poll_send_recv() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8590".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8590".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        let mut sink = client.endpoint().unwrap().sink();
+        let mut stream = server_ep.stream();
+
+        for id in 0..NUM_MESSAGES {
+            let mut opt_m = Some(Message::new(TestMsg::Id(id), 2, 1));
+            let r: Res<()> = poll_loop(|cx| { sink.poll_send(cx, &mut opt_m) }).await;
+            r.unwrap();
+            assert!(opt_m.is_none());
+        }
+        for id in 0..NUM_MESSAGES {
+            let m = poll_loop(|cx| { stream.poll_recv(cx) }).await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(id));
+        }
+
+        sink.send(Message::new(TestMsg::Id(NUM_MESSAGES), 2, 1)).await.unwrap();
+        assert_eq!(stream.recv().await.unwrap().payload(), TestMsg::Id(NUM_MESSAGES));
+
+        client.close().await.unwrap();
+        assert!(matches!(stream.recv().await, Err(ET::EOF)));
+        assert!(matches!(stream.recv().await, Err(ET::EOF)));
+        notifier.notify_all();
+    });
+}
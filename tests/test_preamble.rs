@@ -0,0 +1,186 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame::preamble;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, client_node};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+const VERSION: u8 = 7;
+
+enum Event {
+    Accepted(Arc<dyn EndpointAsync<TestMsg>>),
+    Error(ET),
+    Disconnected(ET),
+}
+
+// forward the accepted endpoints, the errors and the disconnects to the test
+struct EventHandler {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EventHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let _ = self.sender.send(Event::Accepted(endpoint));
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ET) {
+        let _ = self.sender.send(Event::Error(error));
+    }
+
+    async fn on_disconnected(&self, _: SocketAddr, reason: ET) {
+        let _ = self.sender.send(Event::Disconnected(reason));
+    }
+
+    async fn on_stop(&self) {}
+}
+
+fn server(port: u16, notifier: &Notifier) -> (Node<TestMsg, EventHandler>, mpsc::UnboundedReceiver<Event>) {
+    let (sender, events) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .set_preamble(VERSION)
+        .build::<TestMsg, _>(EventHandler { sender })
+        .unwrap();
+    (server, events)
+}
+
+// the error and the reason of the disconnect of the first inbound connection which failed
+async fn inbound_failure(events: &mut mpsc::UnboundedReceiver<Event>) -> (ET, ET) {
+    let (mut opt_error, mut opt_reason) = (None, None);
+    while opt_error.is_none() || opt_reason.is_none() {
+        match events.recv().await.unwrap() {
+            Event::Accepted(_) => {}
+            Event::Error(e) => { opt_error = Some(e); }
+            Event::Disconnected(e) => { opt_reason = Some(e); }
+        }
+    }
+    (opt_error.unwrap(), opt_reason.unwrap())
+}
+
+// the peers of the same version exchange the messages
+#[test]
+fn test_preamble_same_version() {
+    let notifier = Notifier::new();
+    let (server, mut events) = server(8626, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8626".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_preamble(Some(VERSION));
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = match events.recv().await.unwrap() {
+            Event::Accepted(ep) => { ep }
+            _ => { panic!("an event before the accept"); }
+        };
+        ep.send(Message::new(TestMsg::Hello(1), 2, 1)).await.unwrap();
+        assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Hello(1));
+        server_ep.send(Message::new(TestMsg::Hello(2), 1, 2)).await.unwrap();
+        assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Hello(2));
+        notifier.notify_all();
+    });
+}
+
+// an HTTP request is rejected before any of it is taken for a frame
+#[test]
+fn test_preamble_http_client() {
+    let notifier = Notifier::new();
+    let (server, mut events) = server(8627, &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let mut stream = TcpStream::connect("127.0.0.1:8627").await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+
+        // the preamble of the server, then the connection is closed
+        let mut received = vec![];
+        let _ = stream.read_to_end(&mut received).await;
+        assert_eq!(received, preamble(VERSION).to_vec());
+
+        let (e, reason) = inbound_failure(&mut events).await;
+        assert!(net_error::is_bad_protocol(&e), "{}", e.to_string());
+        assert!(e.to_string().contains("GET / HTTP/1.1"), "{}", e.to_string());
+        assert!(net_error::is_bad_protocol(&reason));
+        assert!(!net_error::is_version_mismatch(&reason));
+        notifier.notify_all();
+    });
+}
+
+// the peers of different versions refuse each other
+#[test]
+fn test_preamble_version_mismatch() {
+    let notifier = Notifier::new();
+    let (server, mut events) = server(8628, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8628".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_preamble(Some(VERSION + 1));
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let e = ep.recv().await.unwrap_err();
+        assert!(net_error::is_version_mismatch(&e), "{}", e.to_string());
+        assert!(e.to_string().contains(&format!("expected={}, actual={}", VERSION + 1, VERSION)));
+
+        let (e, reason) = inbound_failure(&mut events).await;
+        assert!(net_error::is_version_mismatch(&e), "{}", e.to_string());
+        assert!(e.to_string().contains(&format!("expected={}, actual={}", VERSION, VERSION + 1)));
+        assert!(!net_error::is_bad_protocol(&reason));
+        notifier.notify_all();
+    });
+}
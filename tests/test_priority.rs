@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::message::{decode_message, Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpSocket;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::frame::{CONTROL_SEQ, FrameHeader, HEADER_SIZE};
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_send::OptSend;
+use scupt_net::priority::Priority;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Bulk(u64, Vec<u8>),
+    Cancel(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+// 10 MB of Normal payload
+const NUM_BULK: u64 = 80;
+
+const BULK_SIZE: usize = 128 * 1024;
+
+// the peer reads about 12 MB per second
+const READ_CHUNK: usize = 64 * 1024;
+
+const READ_WAIT_MS: u64 = 5;
+
+// a High message queued behind the Normal bulk is written after the frames already taken by
+// the writer, ahead of the rest, the Normal messages keep their order
+#[test]
+fn test_priority_lanes() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8578".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let n = notifier.clone();
+    block_on_local(local, async move {
+        // a small receive buffer, the unread bulk stays in the lanes of the client
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(READ_CHUNK as u32).unwrap();
+        socket.bind("127.0.0.1:8578".parse().unwrap()).unwrap();
+        let listener = socket.listen(16).unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+
+        let ep = client.endpoint().unwrap();
+        for id in 0..NUM_BULK {
+            let ep = ep.clone();
+            spawn_local_task(n.clone(), "bulk", async move {
+                let m = Message::new(TestMsg::Bulk(id, vec![0u8; BULK_SIZE]), 2, 1);
+                ep.send(m).await.unwrap();
+            }).unwrap();
+        }
+        let ep_high = ep.clone();
+        spawn_local_task(n.clone(), "cancel", async move {
+            // let the bulk queue up first
+            sleep(Duration::from_millis(50)).await;
+            let opt = OptSend::new().set_priority(Priority::High);
+            ep_high.send_opt(Message::new(TestMsg::Cancel(0), 2, 1), opt).await.unwrap();
+        }).unwrap();
+
+        let start = Instant::now();
+        let mut buf = BytesMut::new();
+        let mut bulk_ids = vec![];
+        let mut opt_cancel = None;
+        while bulk_ids.len() < NUM_BULK as usize || opt_cancel.is_none() {
+            let mut chunk = vec![0u8; READ_CHUNK];
+            let size = peer.read(&mut chunk).await.unwrap();
+            assert!(size > 0);
+            buf.extend_from_slice(&chunk[..size]);
+            while let Some(header) = FrameHeader::decode(&buf) {
+                let len = HEADER_SIZE + header.size() as usize;
+                if buf.len() < len {
+                    break;
+                }
+                let frame = buf.split_to(len);
+                if header.seq() == CONTROL_SEQ {
+                    continue;
+                }
+                let (m, _) = decode_message::<Message<TestMsg>>(&frame[HEADER_SIZE..]).unwrap();
+                match m.payload() {
+                    TestMsg::Bulk(id, _) => { bulk_ids.push(id) }
+                    TestMsg::Cancel(_) => { opt_cancel = Some((bulk_ids.len(), start.elapsed())) }
+                }
+            }
+            sleep(Duration::from_millis(READ_WAIT_MS)).await;
+        }
+        let all = start.elapsed();
+        let (before, cancel_at) = opt_cancel.unwrap();
+        // only the bulk in the socket buffers and the batch being written precede it
+        assert!(before < NUM_BULK as usize / 2, "{} bulk messages before the cancel", before);
+        assert!(cancel_at < all / 2, "cancel at {:?} of {:?}", cancel_at, all);
+        assert_eq!(bulk_ids, (0..NUM_BULK).collect::<Vec<_>>());
+        notifier.notify_all();
+    });
+}
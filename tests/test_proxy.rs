@@ -0,0 +1,139 @@
+#![cfg(feature = "socks5")]
+
+use std::net::SocketAddr;
+
+use scupt_util::message::Message;
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{Client, ClientBuilder, OptClient, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::proxy::ProxyConfig;
+use scupt_net::respond_handler::RespondHandler;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const USERNAME: &str = "user";
+
+const PASSWORD: &str = "password";
+
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    Ok(Message::new(m.payload(), 1, peer))
+}
+
+// a length prefixed field of the SOCKS5 protocol
+async fn read_field(stream: &mut TcpStream) -> String {
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut field = vec![0u8; len[0] as usize];
+    stream.read_exact(&mut field).await.unwrap();
+    String::from_utf8(field).unwrap()
+}
+
+// a SOCKS5 proxy of the username/password authentication and the domain address type, it
+// reports the requested targets, and relays the tunnels to the end server
+async fn socks5_stub(listener: TcpListener, end: SocketAddr, targets: mpsc::UnboundedSender<String>) {
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut greeting = [0u8; 2];
+        stream.read_exact(&mut greeting).await.unwrap();
+        let mut methods = vec![0u8; greeting[1] as usize];
+        stream.read_exact(&mut methods).await.unwrap();
+        assert!(methods.contains(&2));
+        stream.write_all(&[5, 2]).await.unwrap();
+
+        let mut version = [0u8; 1];
+        stream.read_exact(&mut version).await.unwrap();
+        let username = read_field(&mut stream).await;
+        let password = read_field(&mut stream).await;
+        if username != USERNAME || password != PASSWORD {
+            stream.write_all(&[1, 1]).await.unwrap();
+            continue;
+        }
+        stream.write_all(&[1, 0]).await.unwrap();
+
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[1], 1);
+        assert_eq!(request[3], 3);
+        let host = read_field(&mut stream).await;
+        let mut port = [0u8; 2];
+        stream.read_exact(&mut port).await.unwrap();
+        let _ = targets.send(format!("{}:{}", host, u16::from_be_bytes(port)));
+        stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).await.unwrap();
+
+        let mut end_stream = TcpStream::connect(end).await.unwrap();
+        tokio::spawn(async move {
+            let _ = copy_bidirectional(&mut stream, &mut end_stream).await;
+        });
+    }
+}
+
+fn client(node_id: NID, password: &str, notifier: &Notifier) -> Client<TestMsg> {
+    let proxy = ProxyConfig::new("127.0.0.1:8571".parse().unwrap())
+        .set_auth(USERNAME.to_string(), password.to_string())
+        .set_remote_host("localhost".to_string());
+    let opt = OptClient {
+        proxy: Some(proxy),
+        ..OptClient::default()
+    };
+    ClientBuilder::new()
+        .set_node_id(node_id)
+        .set_server_addr("127.0.0.1:8572".to_string())
+        .set_notifier(notifier.clone())
+        .set_opt_client(opt)
+        .build::<TestMsg>()
+        .unwrap()
+}
+
+// the host name is resolved by the proxy, and the messages run through the tunnel, a proxy
+// refusing the credentials fails the connect with a proxy error
+#[test]
+fn test_socks5_proxy() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8572".to_string())
+        .build::<TestMsg, _>(RespondHandler::new(notifier.clone(), echo))
+        .unwrap();
+    let good = client(2, PASSWORD, &notifier);
+    let bad = client(3, "wrong", &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    good.run(&local);
+    bad.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:8571").await.unwrap();
+        let (sender, mut targets) = mpsc::unbounded_channel();
+        tokio::spawn(socks5_stub(listener, "127.0.0.1:8572".parse().unwrap(), sender));
+
+        good.connect(OptClientConnect::new()).await.unwrap();
+        assert_eq!(targets.recv().await.unwrap(), "localhost:8572");
+        good.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        assert_eq!(good.recv().await.unwrap().payload(), TestMsg::Id(1));
+
+        let opt = OptClientConnect {
+            retry_max: 2,
+            retry_wait_ms: 10,
+            connect_timeout_ms: 0,
+            ..OptClientConnect::new()
+        };
+        let e = bad.connect(opt).await.unwrap_err();
+        assert!(net_error::is_proxy_error(&e), "{}", e.to_string());
+        assert!(!net_error::is_connection_refused(&e));
+        assert!(!bad.is_connected().await);
+        good.close().await.unwrap();
+        notifier.notify_all();
+    });
+}
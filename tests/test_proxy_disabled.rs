@@ -0,0 +1,60 @@
+#![cfg(not(feature = "socks5"))]
+
+use std::net::SocketAddr;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::MsgTrait;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::proxy::ProxyConfig;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+struct TestMsg {}
+
+impl MsgTrait for TestMsg {}
+
+// the handshake was not built, the connect fails before connecting to the proxy
+#[test]
+fn test_proxy_without_socks5() {
+    let notifier = Notifier::new();
+    let node = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        let address: SocketAddr = "127.0.0.1:8611".parse().unwrap();
+        let proxy = ProxyConfig::new("127.0.0.1:8610".parse().unwrap());
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_proxy(Some(proxy));
+        let e = sink.connect(2, address, opt).await.unwrap_err();
+        assert!(net_error::is_invalid_option(&e), "{}", e.to_string());
+        assert!(!net_error::is_retryable(&e));
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,194 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_node::Delivery;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// count and echo the pushed messages, the ones of id `pull_from` and above are not handled,
+// the handlers in flight at a time peak at `peak`
+struct PushHandler {
+    count: Arc<AtomicU64>,
+    pull_from: u64,
+    sender: mpsc::UnboundedSender<Arc<dyn EndpointAsync<TestMsg>>>,
+    in_flight: AtomicU64,
+    peak: Arc<AtomicU64>,
+    delay: Duration,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for PushHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let _ = self.sender.send(endpoint);
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_message(&self, from: Arc<dyn EndpointAsync<TestMsg>>, message: Message<TestMsg>) -> Res<()> {
+        let TestMsg::Id(id) = message.payload();
+        if id >= self.pull_from {
+            return Err(net_error::not_handled());
+        }
+        let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.peak.fetch_max(n, Ordering::SeqCst);
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        let _ = self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        let _ = self.count.fetch_add(1, Ordering::SeqCst);
+        from.send(Message::new(TestMsg::Id(id), 1, 2)).await
+    }
+
+    async fn on_stop(&self) {}
+}
+
+fn run_push_test<F, Fut>(port: u16, pull_from: u64, concurrency: usize, delay: Duration, f: F)
+    where F: FnOnce(
+        Arc<dyn EndpointAsync<TestMsg>>,
+        mpsc::UnboundedReceiver<Arc<dyn EndpointAsync<TestMsg>>>,
+        Arc<AtomicU64>,
+        Arc<AtomicU64>,
+    ) -> Fut,
+          Fut: Future<Output=()> + 'static,
+{
+    let notifier = Notifier::new();
+    let count = Arc::new(AtomicU64::new(0));
+    let peak = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let handler = PushHandler {
+        count: count.clone(),
+        pull_from,
+        sender,
+        in_flight: AtomicU64::new(0),
+        peak: peak.clone(),
+        delay,
+    };
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_delivery(Delivery::Push)
+        .set_handler_concurrency(concurrency)
+        .build::<TestMsg, _>(handler)
+        .unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        f(ep, receiver, count, peak).await;
+        notifier.notify_all();
+    });
+}
+
+const NUM_MESSAGES: u64 = 100;
+
+// the server echoes without any recv loop, in the order of the endpoint
+#[test]
+fn test_push_echo() {
+    run_push_test(8481, u64::MAX, 1, Duration::ZERO, |ep, _accepted, count, peak| async move {
+        for i in 0..NUM_MESSAGES {
+            ep.send(Message::new(TestMsg::Id(i), 2, 1)).await.unwrap();
+        }
+        for i in 0..NUM_MESSAGES {
+            let m = ep.recv().await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Id(i));
+        }
+        assert_eq!(count.load(Ordering::SeqCst), NUM_MESSAGES);
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+    });
+}
+
+// the message not handled, and the following ones, are delivered by recv
+#[test]
+fn test_push_not_handled() {
+    run_push_test(8482, 3, 1, Duration::ZERO, |ep, mut accepted, count, _peak| async move {
+        for i in 0..5 {
+            ep.send(Message::new(TestMsg::Id(i), 2, 1)).await.unwrap();
+        }
+        for i in 0..3 {
+            assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Id(i));
+        }
+        let server_ep = accepted.recv().await.unwrap();
+        for i in 3..5 {
+            assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Id(i));
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    });
+}
+
+const CONCURRENCY: usize = 4;
+
+// the slow handlers run up to the concurrency at a time, every message is echoed once
+#[test]
+fn test_push_concurrency() {
+    run_push_test(8593, u64::MAX, CONCURRENCY, Duration::from_millis(50), |ep, _accepted, count, peak| async move {
+        for i in 0..4 * CONCURRENCY as u64 {
+            ep.send(Message::new(TestMsg::Id(i), 2, 1)).await.unwrap();
+        }
+        let mut ids = Vec::new();
+        for _ in 0..4 * CONCURRENCY as u64 {
+            let TestMsg::Id(id) = ep.recv().await.unwrap().payload();
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids, (0..4 * CONCURRENCY as u64).collect::<Vec<_>>());
+        assert_eq!(count.load(Ordering::SeqCst), 4 * CONCURRENCY as u64);
+        assert_eq!(peak.load(Ordering::SeqCst), CONCURRENCY as u64);
+    });
+}
+
+// the messages not handled by the concurrent handlers are delivered by recv, in order
+#[test]
+fn test_push_concurrency_not_handled() {
+    run_push_test(8594, 3, CONCURRENCY, Duration::from_millis(20), |ep, mut accepted, count, _peak| async move {
+        for i in 0..8 {
+            ep.send(Message::new(TestMsg::Id(i), 2, 1)).await.unwrap();
+        }
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let TestMsg::Id(id) = ep.recv().await.unwrap().payload();
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+        let server_ep = accepted.recv().await.unwrap();
+        for i in 3..8 {
+            assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Id(i));
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    });
+}
@@ -0,0 +1,119 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::timeout;
+
+use scupt_net::client::{Client, ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::rate_limit::RateLimit;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const MESSAGES_PER_SEC: u64 = 20;
+
+const NUM_FLOOD: u64 = 40;
+
+// lower the budget of every accepted endpoint, forward the messages to the test, and echo them
+struct RateHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RateHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        endpoint.set_rate_limit(Some(RateLimit::new().set_messages_per_sec(MESSAGES_PER_SEC)))?;
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let source = m.source();
+                let payload = m.payload();
+                let _ = sender.send(payload.clone());
+                let _ = endpoint.send(Message::new(payload, 1, source)).await;
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+fn client(node_id: u64, notifier: &Notifier) -> Client<TestMsg> {
+    ClientBuilder::new()
+        .set_node_id(node_id)
+        .set_server_addr("127.0.0.1:8569".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap()
+}
+
+// the flood is received at about the budget, after the burst of one second, while a quiet
+// connection is answered right away
+#[test]
+fn test_rate_limit() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8569".to_string())
+        .set_rate_limit(RateLimit::new().set_messages_per_sec(1000))
+        .build::<TestMsg, _>(RateHandler { notifier: notifier.clone(), sender })
+        .unwrap();
+    let flood = client(2, &notifier);
+    let quiet = client(3, &notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    flood.run(&local);
+    quiet.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        flood.connect(OptClientConnect::new()).await.unwrap();
+        quiet.connect(OptClientConnect::new()).await.unwrap();
+        let start = Instant::now();
+        for id in 0..NUM_FLOOD {
+            flood.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+        }
+
+        let quiet_start = Instant::now();
+        quiet.send(Message::new(TestMsg::Id(NUM_FLOOD), 3, 1)).await.unwrap();
+        let m = timeout(Duration::from_millis(500), quiet.recv()).await.unwrap().unwrap();
+        assert_eq!(m.payload(), TestMsg::Id(NUM_FLOOD));
+        assert!(quiet_start.elapsed() < Duration::from_millis(500));
+
+        let mut received = 0;
+        while received < NUM_FLOOD {
+            if let TestMsg::Id(id) = receiver.recv().await.unwrap() {
+                if id < NUM_FLOOD {
+                    received += 1;
+                }
+            }
+        }
+        // the messages beyond the burst wait for the refill
+        let elapsed = start.elapsed();
+        let expected = Duration::from_secs_f64((NUM_FLOOD - MESSAGES_PER_SEC - 1) as f64 / MESSAGES_PER_SEC as f64);
+        assert!(elapsed >= expected.mul_f64(0.8), "{:?}", elapsed);
+        assert!(elapsed < expected * 3, "{:?}", elapsed);
+        notifier.notify_all();
+    });
+}
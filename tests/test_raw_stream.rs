@@ -0,0 +1,68 @@
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::message::{encode_message, Message};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::frame::FrameHeader;
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const UPGRADE: &[u8] = b"up";
+
+// the peer writes a frame and the bytes of another protocol at once, the endpoint hands the
+// stream over after receiving the message, with the bytes read ahead
+#[test]
+fn test_raw_stream() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8570".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8570").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let payload = encode_message(Message::new(TestMsg::Id(1), 1, 2)).unwrap();
+        let mut buf = BytesMut::new();
+        FrameHeader::new(payload.len() as u32, 1).encode(&mut buf);
+        buf.extend_from_slice(&payload);
+        buf.extend_from_slice(UPGRADE);
+        peer.write_all(&buf).await.unwrap();
+
+        let ep = client.endpoint().unwrap();
+        assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Id(1));
+        let raw = ep.into_raw_stream().await.unwrap();
+        let (mut stream, read_buf) = raw.into_tcp_stream().unwrap();
+        let mut upgrade = read_buf.to_vec();
+        while upgrade.len() < UPGRADE.len() {
+            let mut b = [0u8; 16];
+            let n = stream.read(&mut b).await.unwrap();
+            assert!(n > 0);
+            upgrade.extend_from_slice(&b[..n]);
+        }
+        assert_eq!(upgrade, UPGRADE);
+
+        // the framing is gone, the bytes are passed as they are
+        stream.write_all(b"ok").await.unwrap();
+        let mut b = [0u8; 2];
+        peer.read_exact(&mut b).await.unwrap();
+        assert_eq!(&b, b"ok");
+
+        assert!(matches!(ep.recv().await, Err(ET::EOF)));
+        let r = ep.send(Message::new(TestMsg::Id(2), 2, 1)).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_send_closed(e)));
+        assert!(ep.into_raw_stream().await.is_err());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,135 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::recorder::{
+    FileRecordSink,
+    MemoryRecordSink,
+    RecordDirection,
+    RecordSink,
+    replay_to_handler,
+    ReplayEndpoint,
+};
+use scupt_net::task::spawn_local_task;
+use scupt_net::transport::Transport;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// forward the received messages to the test
+#[derive(Clone)]
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_record_and_replay() {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let handler = RecvHandler { notifier: notifier.clone(), sender };
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        handler.clone(),
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    server.set_transport(Transport::Memory);
+    client.set_transport(Transport::Memory);
+    let server_records = Arc::new(MemoryRecordSink::new());
+    let client_records = Arc::new(MemoryRecordSink::new());
+    server.set_record_sink(Some(server_records.clone()));
+    client.set_record_sink(Some(client_records.clone()));
+
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "10.255.1.1:1".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        let sent = vec![TestMsg::Id(1), TestMsg::Id(2), TestMsg::Id(3)];
+        for m in sent.iter() {
+            ep.send(Message::new(m.clone(), 2, 1)).await.unwrap();
+        }
+        for m in sent.iter() {
+            assert_eq!(&receiver.recv().await.unwrap(), m);
+        }
+
+        let records = client_records.records();
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|r| {
+            r.direction == RecordDirection::Send && r.peer == 1 && r.remote_address == addr
+        }));
+        let records = server_records.records();
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|r| { r.direction == RecordDirection::Recv && r.peer == 2 }));
+        assert!(records.windows(2).all(|w| { w[0].timestamp_us <= w[1].timestamp_us }));
+
+        // replay by an endpoint
+        let replay = ReplayEndpoint::new(addr, records.clone());
+        for m in sent.iter() {
+            let r = EndpointAsync::<TestMsg>::recv(&replay).await.unwrap();
+            assert_eq!(&r.payload(), m);
+        }
+        assert!(matches!(EndpointAsync::<TestMsg>::recv(&replay).await, Err(ET::EOF)));
+
+        // replay to the handler, through a file
+        let path = std::env::temp_dir().join("scupt_net_test_record_and_replay.json");
+        let file_sink = FileRecordSink::create(&path).unwrap();
+        for r in records.iter() {
+            file_sink.write(r.clone()).unwrap();
+        }
+        file_sink.flush().unwrap();
+        let loaded = FileRecordSink::read_records(&path).unwrap();
+        assert_eq!(loaded, records);
+        replay_to_handler(&handler, addr, loaded).await.unwrap();
+        for m in sent.iter() {
+            assert_eq!(&receiver.recv().await.unwrap(), m);
+        }
+        let _ = std::fs::remove_file(&path);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use bytes::BytesMut;
+use scupt_util::message::{encode_message, Message};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::frame::FrameHeader;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const NUM_CONSUMERS: usize = 4;
+
+const NUM_MESSAGES: u64 = 200;
+
+// the workers pull from one client, every message is received once, by one of them
+#[test]
+fn test_recv_consumers() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8580".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let n = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8580").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+
+        let (sender, mut received) = mpsc::unbounded_channel();
+        for consumer in 0..NUM_CONSUMERS {
+            let c = client.clone();
+            let sender = sender.clone();
+            spawn_local_task(n.clone(), "consumer", async move {
+                while let Ok(m) = c.recv().await {
+                    let TestMsg::Id(id) = m.payload();
+                    let _ = sender.send((consumer, id));
+                }
+            }).unwrap();
+        }
+
+        let mut buf = BytesMut::new();
+        for id in 0..NUM_MESSAGES {
+            let payload = encode_message(Message::new(TestMsg::Id(id), 1, 2)).unwrap();
+            FrameHeader::new(payload.len() as u32, id + 1).encode(&mut buf);
+            buf.extend_from_slice(&payload);
+        }
+        peer.write_all(&buf).await.unwrap();
+
+        let mut ids = vec![];
+        let mut per_consumer: HashMap<usize, u64> = HashMap::new();
+        for _ in 0..NUM_MESSAGES {
+            let (consumer, id) = received.recv().await.unwrap();
+            ids.push(id);
+            *per_consumer.entry(consumer).or_default() += 1;
+        }
+        ids.sort();
+        assert_eq!(ids, (0..NUM_MESSAGES).collect::<Vec<_>>());
+        // the waiting recvs take the messages in turn
+        assert_eq!(per_consumer.len(), NUM_CONSUMERS);
+        assert!(received.try_recv().is_err());
+        notifier.notify_all();
+    });
+}
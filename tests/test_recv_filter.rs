@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::message::{encode_message, Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::frame::FrameHeader;
+use scupt_net::net_error;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    A(u64),
+    B(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_EACH: u64 = 50;
+
+fn is_b(m: &Message<TestMsg>) -> bool {
+    matches!(m.clone().payload(), TestMsg::B(_))
+}
+
+// the ids are in order, without a gap or a duplicate
+fn assert_in_order(ids: &[u64]) {
+    assert!(ids.windows(2).all(|w| { w[0] < w[1] }), "{:?}", ids);
+}
+
+// the kinds are interleaved on the wire, a filtered consumer takes the Bs while a plain one
+// takes what is left, in order
+#[test]
+fn test_recv_filter() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8581".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let n = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8581").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+
+        let (first_sender, first) = oneshot::channel();
+        let c = client.clone();
+        spawn_local_task(n.clone(), "first filter", async move {
+            let _ = first_sender.send(c.recv_filter(Box::new(is_b)).await);
+        }).unwrap();
+        sleep(Duration::from_millis(50)).await;
+        let e = client.recv_filter(Box::new(is_b)).await.unwrap_err();
+        assert!(net_error::is_filter_busy(&e), "{}", e.to_string());
+
+        let mut buf = BytesMut::new();
+        for i in 0..NUM_EACH * 2 {
+            let m = if i % 2 == 0 { TestMsg::A(i / 2) } else { TestMsg::B(i / 2) };
+            let payload = encode_message(Message::new(m, 1, 2)).unwrap();
+            FrameHeader::new(payload.len() as u32, i + 1).encode(&mut buf);
+            buf.extend_from_slice(&payload);
+        }
+        peer.write_all(&buf).await.unwrap();
+        // A(0) was passed over
+        assert_eq!(first.await.unwrap().unwrap().payload(), TestMsg::B(0));
+
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let (c, s) = (client.clone(), sender.clone());
+        spawn_local_task(n.clone(), "filtered", async move {
+            while let Ok(m) = c.recv_filter(Box::new(is_b)).await {
+                let _ = s.send(("filtered", m.payload()));
+            }
+        }).unwrap();
+        let (c, s) = (client.clone(), sender);
+        spawn_local_task(n.clone(), "plain", async move {
+            while let Ok(m) = c.recv().await {
+                let _ = s.send(("plain", m.payload()));
+            }
+        }).unwrap();
+
+        let (mut a_ids, mut plain_b_ids, mut filtered_b_ids) = (vec![], vec![], vec![]);
+        for _ in 0..NUM_EACH * 2 - 1 {
+            match received.recv().await.unwrap() {
+                ("plain", TestMsg::A(id)) => { a_ids.push(id) }
+                ("plain", TestMsg::B(id)) => { plain_b_ids.push(id) }
+                ("filtered", TestMsg::B(id)) => { filtered_b_ids.push(id) }
+                (consumer, m) => { panic!("{} received {:?}", consumer, m) }
+            }
+        }
+        assert_eq!(a_ids, (0..NUM_EACH).collect::<Vec<_>>());
+        assert_in_order(&plain_b_ids);
+        assert_in_order(&filtered_b_ids);
+        let mut b_ids = [plain_b_ids, filtered_b_ids].concat();
+        b_ids.sort();
+        assert_eq!(b_ids, (1..NUM_EACH).collect::<Vec<_>>());
+        assert!(received.try_recv().is_err());
+        notifier.notify_all();
+    });
+}
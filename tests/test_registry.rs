@@ -0,0 +1,128 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_send::OptSend;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// close every accepted endpoint at once
+struct CloseHandler {}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for CloseHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        endpoint.close().await
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+const NUM_CLIENTS: u64 = 10;
+
+// wait until the closed connections were reaped
+async fn wait_connection_count<H: HandleEvent<TestMsg> + 'static>(node: &Node<TestMsg, H>, count: u64) {
+    for _ in 0..50 {
+        if node.connection_count().await == count {
+            return;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(node.connection_count().await, count);
+}
+
+#[test]
+fn test_registry_inbound() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8511".parse().unwrap();
+    let server = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    let mut clients = vec![];
+    for i in 0..NUM_CLIENTS {
+        let client = ClientBuilder::new()
+            .set_node_id(i + 2)
+            .set_server_addr(addr.to_string())
+            .set_notifier(notifier.clone())
+            .build::<TestMsg>()
+            .unwrap();
+        client.run(&local);
+        clients.push(client);
+    }
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        for c in clients.iter() {
+            c.connect(OptClientConnect::new()).await.unwrap();
+        }
+        wait_connection_count(&server, NUM_CLIENTS).await;
+        for c in clients.iter() {
+            c.disconnect().await.unwrap();
+        }
+        wait_connection_count(&server, 0).await;
+        notifier.notify_all();
+    });
+}
+
+// the registered endpoints closed by the peer are removed, and not sent to
+#[test]
+fn test_registry_outbound() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8512".parse().unwrap();
+    let server = Node::<TestMsg, CloseHandler>::new(
+        1,
+        "node_1".to_string(),
+        CloseHandler {},
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        for _ in 0..NUM_CLIENTS {
+            // registered, or failed if it was closed already
+            let _ = client_sink.connect(1, addr, ESConnectOpt::default()).await;
+        }
+        wait_connection_count(&client, 0).await;
+        wait_connection_count(&server, 0).await;
+        let r = client.default_message_sender_rr()
+            .send(Message::new(TestMsg::Id(1), 2, 1), OptSend::default()).await;
+        assert!(r.is_err());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{decode_message, Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::sleep;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::frame::{FrameHeader, HEADER_SIZE};
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_MESSAGES: u64 = 100;
+
+const ADDRESS: &str = "127.0.0.1:8595";
+
+// read a message from the raw peer
+async fn read_message(peer: &mut TcpStream) -> Message<TestMsg> {
+    let mut header = [0u8; HEADER_SIZE];
+    peer.read_exact(&mut header).await.unwrap();
+    let header = FrameHeader::decode(&header).unwrap();
+    let mut payload = vec![0u8; header.size() as usize];
+    peer.read_exact(&mut payload).await.unwrap();
+    let (m, _) = decode_message::<Message<TestMsg>>(&payload).unwrap();
+    m
+}
+
+// the messages queued against a peer which does not read, and killed, are written by the next
+// connection, after the ones written before, and their sends complete
+#[test]
+fn test_resend_unsent() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr(ADDRESS.to_string())
+        .set_notifier(notifier.clone())
+        .enable_resend_unsent(true)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind(ADDRESS).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (stalled, _) = listener.accept().await.unwrap();
+        let (result_sender, mut result) = mpsc::unbounded_channel();
+        let c = client.clone();
+        spawn_local_task(task_notifier, "send all", async move {
+            let messages = (0..NUM_MESSAGES).map(|id| {
+                Message::new(TestMsg::Data(id, vec![id as u8; 64 * 1024]), 2, 1)
+            });
+            let _ = result_sender.send(c.send_all(messages).await);
+        }).unwrap();
+        // the socket buffers are full, the rest of the messages are queued
+        sleep(Duration::from_millis(200)).await;
+
+        // kill the server, the unread bytes reset the connection
+        drop(stalled);
+        drop(listener);
+        let ep = client.endpoint().unwrap();
+        while !ep.is_closed() {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        let listener = TcpListener::bind(ADDRESS).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let TestMsg::Data(first, _) = read_message(&mut peer).await.payload();
+        assert!(first > 0);
+        for id in first + 1..NUM_MESSAGES {
+            let TestMsg::Data(i, data) = read_message(&mut peer).await.payload();
+            assert_eq!(i, id);
+            assert_eq!(data, vec![id as u8; 64 * 1024]);
+        }
+        let (n, r) = result.recv().await.unwrap();
+        r.unwrap();
+        assert_eq!(n, NUM_MESSAGES);
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_send::OptSend;
+use scupt_net::resolver::StaticResolver;
+
+use common::{AcceptHandler, block_on_local};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Hello(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+// the node sends to peers it was never told to connect to, the resolver gives their addresses
+#[test]
+fn test_resolver_send() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8604".to_string())
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    let server_address: SocketAddr = "127.0.0.1:8604".parse().unwrap();
+    let resolver = Arc::new(StaticResolver::new(HashMap::from([(1, server_address)])));
+    let node = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .set_resolver(resolver.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    node.run_local(&local);
+    let message_sender = node.default_message_sender_async();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+
+        // connected by the send, then the following sends use the registered endpoint
+        for i in 0..3 {
+            message_sender.send(Message::new(TestMsg::Hello(i), 2, 1), OptSend::default()).await.unwrap();
+        }
+        let ep = accepted.recv().await.unwrap();
+        for i in 0..3 {
+            let m = ep.recv().await.unwrap();
+            assert_eq!(m.payload(), TestMsg::Hello(i));
+        }
+        assert_eq!(node.connection_count().await, 1);
+        assert!(accepted.try_recv().is_err());
+
+        // the peer is not known by the resolver, the send connects nothing
+        message_sender.send(Message::new(TestMsg::Hello(0), 2, 3), OptSend::default()).await.unwrap();
+        let e = node.connect_all(&[1, 3]).await.unwrap_err();
+        assert!(net_error::is_unresolved(&e), "{}", e.to_string());
+        assert_eq!(net_error::net_error_kind(&e), net_error::NetErrorKind::NotConnected);
+        assert_eq!(node.connection_count().await, 1);
+
+        // the address is added at runtime
+        assert_eq!(resolver.set(3, server_address), None);
+        node.connect_all(&[1, 3]).await.unwrap();
+        let _ = accepted.recv().await.unwrap();
+        assert_eq!(node.connection_count().await, 2);
+        notifier.notify_all();
+    });
+}
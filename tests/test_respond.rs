@@ -0,0 +1,119 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::node_id::NID;
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::task::LocalSet;
+
+use scupt_net::caller::Caller;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_call::OptCall;
+use scupt_net::respond_handler::{OnRespondError, RespondHandler};
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Request(u64, String),
+    Response(u64, String),
+    Error(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+fn correlation_id(m: &Message<TestMsg>) -> u64 {
+    match m.clone().payload() {
+        TestMsg::Request(id, _) => { id }
+        TestMsg::Response(id, _) => { id }
+        TestMsg::Error(id) => { id }
+    }
+}
+
+// echo the requests, fail on an empty one
+async fn echo(peer: NID, m: Message<TestMsg>) -> Res<Message<TestMsg>> {
+    match m.payload() {
+        TestMsg::Request(id, s) if !s.is_empty() => { Ok(Message::new(TestMsg::Response(id, s), 1, peer)) }
+        TestMsg::Request(id, _) => { Err(ET::FatalError(id.to_string())) }
+        _ => { Err(ET::NoneOption) }
+    }
+}
+
+fn run_respond_test<F, Fut>(port: u16, on_error: OnRespondError<TestMsg>, f: F)
+    where F: FnOnce(Caller<TestMsg>) -> Fut,
+          Fut: Future<Output=()> + 'static,
+{
+    let notifier = Notifier::new();
+    let handler = RespondHandler::new(notifier.clone(), echo).set_on_error(on_error);
+    let server = Node::<TestMsg, _>::new(1, "node_1".to_string(), handler, false, notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        let caller = Caller::new(ep, Arc::new(correlation_id), OptCall::new(), notifier.clone()).unwrap();
+        f(caller).await;
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_respond_echo() {
+    run_respond_test(8491, OnRespondError::Close, |caller| async move {
+        for id in 1..=10 {
+            let r = caller.call(Message::new(TestMsg::Request(id, format!("hello {}", id)), 2, 1)).await.unwrap();
+            assert_eq!(r.payload(), TestMsg::Response(id, format!("hello {}", id)));
+        }
+        // the failed request closes the connection
+        let r = caller.call(Message::new(TestMsg::Request(11, String::new()), 2, 1)).await;
+        assert!(r.is_err());
+    });
+}
+
+#[test]
+fn test_respond_error_reply() {
+    let on_error = OnRespondError::Reply(Arc::new(|peer, e: ET| {
+        let id = match e {
+            ET::FatalError(s) => { s.parse().unwrap() }
+            _ => { 0 }
+        };
+        Message::new(TestMsg::Error(id), 1, peer)
+    }));
+    run_respond_test(8492, on_error, |caller| async move {
+        let r = caller.call(Message::new(TestMsg::Request(1, String::new()), 2, 1)).await.unwrap();
+        assert_eq!(r.payload(), TestMsg::Error(1));
+        // the connection is still served
+        let r = caller.call(Message::new(TestMsg::Request(2, "again".to_string()), 2, 1)).await.unwrap();
+        assert_eq!(r.payload(), TestMsg::Response(2, "again".to_string()));
+    });
+}
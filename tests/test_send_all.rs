@@ -0,0 +1,144 @@
+use bincode::{Decode, Encode};
+use futures::stream;
+use scupt_util::message::{decode_message, Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::frame::{FrameHeader, HEADER_SIZE};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Id(u64),
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_MESSAGES: u64 = 50_000;
+
+const NUM_LARGE: u64 = 50_000;
+
+// the messages of an iterator and of a stream are all received, in order
+#[test]
+fn test_send_all() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8591".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8591".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let ep = accepted.recv().await.unwrap();
+        let (result_sender, mut result) = mpsc::unbounded_channel();
+        let c = client.clone();
+        spawn_local_task(task_notifier, "send all", async move {
+            let messages = (0..NUM_MESSAGES).map(|id| { Message::new(TestMsg::Id(id), 2, 1) });
+            let _ = result_sender.send(c.send_all(messages).await);
+            let messages = stream::iter((NUM_MESSAGES..2 * NUM_MESSAGES).map(|id| {
+                Message::new(TestMsg::Id(id), 2, 1)
+            }));
+            let _ = result_sender.send(c.send_stream(messages).await);
+        }).unwrap();
+
+        for id in 0..2 * NUM_MESSAGES {
+            assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Id(id));
+        }
+        let (n, r) = result.recv().await.unwrap();
+        assert_eq!(n, NUM_MESSAGES);
+        r.unwrap();
+        let (n, r) = result.recv().await.unwrap();
+        assert_eq!(n, NUM_MESSAGES);
+        r.unwrap();
+        notifier.notify_all();
+    });
+}
+
+// the peer closes the connection after reading half of the messages, the messages written are
+// counted, and not all of the messages
+#[test]
+fn test_send_all_partial() {
+    let notifier = Notifier::new();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8592".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8592").await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let (result_sender, mut result) = mpsc::unbounded_channel();
+        let c = client.clone();
+        spawn_local_task(task_notifier, "send all", async move {
+            let messages = (0..NUM_LARGE).map(|id| {
+                Message::new(TestMsg::Data(id, vec![0u8; 1024]), 2, 1)
+            });
+            let _ = result_sender.send(c.send_all(messages).await);
+        }).unwrap();
+
+        let mut read = 0;
+        while read < NUM_LARGE / 2 {
+            let mut header = [0u8; HEADER_SIZE];
+            peer.read_exact(&mut header).await.unwrap();
+            let header = FrameHeader::decode(&header).unwrap();
+            let mut payload = vec![0u8; header.size() as usize];
+            peer.read_exact(&mut payload).await.unwrap();
+            let (m, _) = decode_message::<Message<TestMsg>>(&payload).unwrap();
+            assert!(matches!(m.payload(), TestMsg::Data(id, _) if id == read));
+            read += 1;
+        }
+        // the unread bytes reset the connection
+        drop(peer);
+
+        let (n, r) = result.recv().await.unwrap();
+        assert!(r.is_err());
+        assert!(n >= NUM_LARGE / 4, "{}", n);
+        assert!(n < NUM_LARGE, "{}", n);
+        notifier.notify_all();
+    });
+}
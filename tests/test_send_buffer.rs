@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDERS: u64 = 8;
+
+const NUM_MESSAGES: u64 = 100;
+
+// the payload of the id, of a size varying by the id
+fn payload(id: u64) -> Vec<u8> {
+    vec![id as u8; (id as usize * 37) % 3000]
+}
+
+// the concurrent sends through a small buffer pool deliver every message intact
+#[test]
+fn test_send_buffer_pool() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8588".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8588".to_string())
+        .set_notifier(notifier.clone())
+        .set_send_buffer_pool(2)
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let ep = accepted.recv().await.unwrap();
+        for s in 0..NUM_SENDERS {
+            let c = client.clone();
+            spawn_local_task(task_notifier.clone(), "sender", async move {
+                for i in 0..NUM_MESSAGES {
+                    let id = s * NUM_MESSAGES + i;
+                    c.send(Message::new(TestMsg::Data(id, payload(id)), 2, 1)).await.unwrap();
+                }
+            }).unwrap();
+        }
+        let mut ids = HashSet::new();
+        for _ in 0..NUM_SENDERS * NUM_MESSAGES {
+            let TestMsg::Data(id, data) = ep.recv().await.unwrap().payload();
+            assert_eq!(data, payload(id));
+            assert!(ids.insert(id));
+        }
+        notifier.notify_all();
+    });
+}
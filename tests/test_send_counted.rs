@@ -0,0 +1,136 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::frame::{CHECKSUM_SIZE, FRAGMENT_HEADER_SIZE, HEADER_SIZE};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::{Node, NodeBuilder};
+use scupt_net::notifier::Notifier;
+
+use common::{AcceptHandler, block_on_local, client_node};
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+// the max message size of both sides
+const FRAME_LIMIT: usize = 16 * 1024;
+
+fn server(port: u16, checksum: bool, notifier: &Notifier) -> (Node<TestMsg, AcceptHandler<TestMsg>>, mpsc::UnboundedReceiver<Arc<dyn EndpointAsync<TestMsg>>>) {
+    let (sender, accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(format!("127.0.0.1:{}", port))
+        .set_max_message_size(FRAME_LIMIT)
+        .enable_checksum(checksum)
+        .build::<TestMsg, _>(AcceptHandler { sender })
+        .unwrap();
+    (server, accepted)
+}
+
+// send the data, return the count and the encoded bytes of the message, the delta of the
+// `bytes_out` of the client, once the peer received it
+async fn send_counted(
+    client: &Node<TestMsg, FnHandler<TestMsg>>,
+    ep: &Arc<dyn EndpointAsync<TestMsg>>,
+    server_ep: &Arc<dyn EndpointAsync<TestMsg>>,
+    data: Vec<u8>,
+) -> (usize, usize) {
+    let before = client.metrics().bytes_out;
+    let count = ep.send_counted(Message::new(TestMsg::Data(data.clone()), 2, 1)).await.unwrap();
+    assert_eq!(server_ep.recv().await.unwrap().payload(), TestMsg::Data(data));
+    (count, (client.metrics().bytes_out - before) as usize)
+}
+
+// A message in one frame counts its header, the fragments of a larger one count a header and
+// a fragment header each, and `send` writes as many bytes as `send_counted`.
+#[test]
+fn test_send_counted() {
+    let notifier = Notifier::new();
+    let (server, mut accepted) = server(8633, false, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8633".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_max_message_size(Some(FRAME_LIMIT))
+            .set_fragment_size(FRAME_LIMIT);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+
+        for size in [0, 1, 1024] {
+            let (count, delta) = send_counted(&client, &ep, &server_ep, vec![1; size]).await;
+            assert_eq!(count, delta + HEADER_SIZE);
+        }
+
+        let (count, delta) = send_counted(&client, &ep, &server_ep, vec![2; 10 * FRAME_LIMIT]).await;
+        // the fragments carry up to the limit of a frame, their header included
+        let chunk = FRAME_LIMIT.min(FRAME_LIMIT - FRAGMENT_HEADER_SIZE).max(1);
+        let fragments = delta.div_ceil(chunk);
+        assert!(fragments > 10, "{} fragments", fragments);
+        assert_eq!(count, delta + fragments * (HEADER_SIZE + FRAGMENT_HEADER_SIZE));
+
+        // the delta of `send` matches the one of the same message counted
+        let (_, counted_delta) = send_counted(&client, &ep, &server_ep, vec![3; 1024]).await;
+        let before = client.metrics().bytes_out;
+        ep.send(Message::new(TestMsg::Data(vec![3; 1024]), 2, 1)).await.unwrap();
+        let _ = server_ep.recv().await.unwrap();
+        assert_eq!((client.metrics().bytes_out - before) as usize, counted_delta);
+        notifier.notify_all();
+    });
+}
+
+// the checksum of every frame is counted
+#[test]
+fn test_send_counted_checksum() {
+    let notifier = Notifier::new();
+    let (server, mut accepted) = server(8634, true, &notifier);
+    let client = client_node::<TestMsg>(&notifier);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8634".parse().unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .enable_checksum(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+
+        for size in [0, 1024, 8 * 1024] {
+            let (count, delta) = send_counted(&client, &ep, &server_ep, vec![1; size]).await;
+            assert_eq!(count, delta + HEADER_SIZE + CHECKSUM_SIZE);
+        }
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,72 @@
+use futures::{SinkExt, stream, StreamExt};
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+const NUM_MESSAGES: u64 = 1000;
+
+// the messages of a stream are forwarded into the sink of the client, the server relays them
+// back by forwarding its stream into its sink, both in order, the closed sinks end the streams
+#[test]
+fn test_sink_forward() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8589".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8589".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let server_ep = accepted.recv().await.unwrap();
+        spawn_local_task(task_notifier, "relay", async move {
+            server_ep.clone().stream().forward(server_ep.sink()).await.unwrap();
+        }).unwrap();
+
+        let ep = client.endpoint().unwrap();
+        let messages = stream::iter((0..NUM_MESSAGES).map(|id| {
+            Ok::<_, ET>(Message::new(TestMsg::Id(id), 2, 1))
+        }));
+        messages.forward(ep.clone().sink()).await.unwrap();
+
+        let received: Vec<_> = ep.clone().stream()
+            .map(|r| { r.unwrap().payload() })
+            .collect()
+            .await;
+        let expected: Vec<_> = (0..NUM_MESSAGES).map(TestMsg::Id).collect();
+        assert_eq!(received, expected);
+
+        // the write direction was shut down by the close of the sink
+        let mut sink = ep.sink();
+        assert!(sink.send(Message::new(TestMsg::Id(0), 2, 1)).await.is_err());
+        notifier.notify_all();
+    });
+}
@@ -0,0 +1,133 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+use scupt_util::message::{Message, MsgTrait};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::FnHandler;
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::opt_close::StopMode;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDS: usize = 32;
+
+// the peer never reads, the sends beyond the socket buffers are still queued when the node
+// stops, they fail and are reported as lost
+#[test]
+fn test_stop_report_abort() {
+    let notifier = Notifier::new();
+    let node = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let handle = node.handle();
+    let sink = node.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let listener = TcpListener::bind("127.0.0.1:8617").await.unwrap();
+        let accept = tokio::spawn(async move {
+            listener.accept().await.unwrap()
+        });
+        let address: SocketAddr = "127.0.0.1:8617".parse().unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        let _stream = accept.await.unwrap();
+
+        let (sender, mut results) = mpsc::unbounded_channel();
+        for _ in 0..NUM_SENDS {
+            let e = ep.clone();
+            let s = sender.clone();
+            let _ = spawn_local_task(task_notifier.clone(), "send", async move {
+                let r = e.send(Message::new(TestMsg::Data(vec![0u8; 1024 * 1024]), 2, 1)).await;
+                let _ = s.send(r);
+            });
+        }
+        drop(sender);
+        sleep(Duration::from_millis(500)).await;
+
+        let report = handle.shutdown().await.unwrap();
+        assert_eq!(report.endpoints_closed, 1);
+        assert_eq!(report.losses.len(), 1);
+        assert_eq!(report.losses[0].address, address);
+        assert!(!report.losses[0].inbound);
+        let lost = report.messages_lost();
+        assert!(lost > 0 && lost < NUM_SENDS as u64, "{} lost", lost);
+
+        // the sends dropped fail with shutting down, the others were written, or cut by the stop
+        let mut shutting_down = 0;
+        while let Ok(Some(r)) = timeout(Duration::from_secs(1), results.recv()).await {
+            if matches!(r, Err(ref e) if net_error::is_shutting_down(e)) {
+                shutting_down += 1;
+            }
+        }
+        assert_eq!(shutting_down, lost);
+        notifier.notify_all();
+    });
+}
+
+// a drain which completed drops nothing
+#[test]
+fn test_stop_report_drain() {
+    let notifier = Notifier::new();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8618".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let node = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    node.run_local(&local);
+    let handle = node.handle();
+    let sink = node.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let address: SocketAddr = "127.0.0.1:8618".parse().unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = sink.connect(1, address, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Data(vec![0u8; 16]), 2, 1)).await.unwrap();
+
+        let report = handle.shutdown_with(StopMode::Drain(Duration::from_secs(5))).await.unwrap();
+        assert_eq!(report.endpoints_closed, 1);
+        assert!(report.losses.is_empty());
+        assert_eq!(report.messages_lost(), 0);
+        notifier.notify_all();
+    });
+}
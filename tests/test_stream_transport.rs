@@ -0,0 +1,147 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scupt_util::error_type::ET;
+use scupt_util::message::Message;
+use scupt_util::res::Res;
+use scupt_util::res_of::res_io;
+use tokio::io::{duplex, DuplexStream};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+use scupt_net::transport::{NetStream, StreamListener, StreamTransport, Transport};
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+type SyncMutex<T> = std::sync::Mutex<T>;
+
+// the connections of tokio duplex pipes, to the only listener
+struct DuplexTransport {
+    sender: mpsc::UnboundedSender<DuplexStream>,
+    opt_receiver: SyncMutex<Option<mpsc::UnboundedReceiver<DuplexStream>>>,
+}
+
+struct DuplexListener {
+    address: SocketAddr,
+    receiver: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+impl DuplexTransport {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            opt_receiver: SyncMutex::new(Some(receiver)),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamTransport for DuplexTransport {
+    async fn connect(&self, address: SocketAddr) -> Res<(NetStream, SocketAddr)> {
+        let (client, server) = duplex(4096);
+        if self.sender.send(server).is_err() {
+            return res_io(Err(io::Error::from(io::ErrorKind::ConnectionRefused)));
+        }
+        Ok((Box::new(client), address))
+    }
+
+    async fn bind(&self, address: SocketAddr) -> Res<Box<dyn StreamListener>> {
+        match self.opt_receiver.lock().unwrap().take() {
+            Some(receiver) => { Ok(Box::new(DuplexListener { address, receiver })) }
+            None => { res_io(Err(io::Error::from(io::ErrorKind::AddrInUse))) }
+        }
+    }
+}
+
+#[async_trait]
+impl StreamListener for DuplexListener {
+    async fn accept(&mut self) -> Res<(NetStream, SocketAddr)> {
+        match self.receiver.recv().await {
+            Some(s) => { Ok((Box::new(s), self.address)) }
+            None => { Err(ET::EOF) }
+        }
+    }
+}
+
+// forward the messages received by the server to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+                let _ = endpoint.send(Message::new(TestMsg::Id(0), 1, 2)).await;
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_stream_transport_duplex() {
+    let notifier = Notifier::new();
+    // a logical address, never bound
+    let addr: SocketAddr = "10.0.0.1:1".parse().unwrap();
+    let transport = Arc::new(DuplexTransport::new());
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address(addr.to_string())
+        .set_stream_transport(transport.clone())
+        .build::<TestMsg, _>(RecvHandler { notifier: notifier.clone(), sender })
+        .unwrap();
+    let client = NodeBuilder::new()
+        .set_node_id(2)
+        .set_notifier(notifier.clone())
+        .set_stream_transport(transport)
+        .build::<TestMsg, _>(HandleEventDummy::default())
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        for id in 1..=10 {
+            ep.send(Message::new(TestMsg::Id(id), 2, 1)).await.unwrap();
+            assert_eq!(receiver.recv().await.unwrap(), TestMsg::Id(id));
+            assert_eq!(ep.recv().await.unwrap().payload(), TestMsg::Id(0));
+        }
+        // Transport::Custom without a StreamTransport
+        let r = NodeBuilder::new()
+            .set_node_id(3)
+            .set_transport(Transport::Custom)
+            .build::<TestMsg, _>(HandleEventDummy::default());
+        assert!(matches!(r, Err(ref e) if net_error::is_invalid_option(e)));
+        notifier.notify_all();
+    });
+}
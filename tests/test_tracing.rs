@@ -0,0 +1,81 @@
+#![cfg(feature = "tracing-spans")]
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use scupt_util::message::Message;
+use tokio::task::LocalSet;
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::HandleEventDummy;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+// collect the formatted output of the subscriber
+#[derive(Clone)]
+struct Output {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tracing_connect_and_send() {
+    let output = Output { buffer: Arc::new(Mutex::new(vec![])) };
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .with_span_events(FmtSpan::NEW)
+        .with_ansi(false)
+        .with_writer(move || { writer.clone() })
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let notifier = Notifier::new();
+    let server = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8381".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        notifier.notify_all();
+    });
+
+    let text = String::from_utf8(output.buffer.lock().unwrap().clone()).unwrap();
+    assert!(text.contains("connect{nid=2 peer=1 addr=127.0.0.1:8381}"));
+    assert!(text.contains("send message"));
+    assert!(text.contains("msg_len="));
+}
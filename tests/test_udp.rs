@@ -0,0 +1,135 @@
+#![cfg(feature = "udp")]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+use scupt_net::transport::Transport;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Id(u64),
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+// echo the datagrams to their sender
+struct EchoHandler {
+    notifier: Notifier,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for EchoHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        spawn_local_task(self.notifier.clone(), "echo", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = endpoint.send(Message::new(m.payload(), 1, 2)).await;
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+#[test]
+fn test_udp_echo() {
+    let notifier = Notifier::new();
+    let server = Node::<TestMsg, EchoHandler>::new(
+        1,
+        "node_1".to_string(),
+        EchoHandler { notifier: notifier.clone() },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    server.set_transport(Transport::Udp);
+    client.set_transport(Transport::Udp);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8351".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        assert!(!ep.is_inbound());
+
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        let m = ep.recv().await.unwrap();
+        assert_eq!(m.payload(), TestMsg::Id(1));
+
+        // does not fit one datagram
+        let r = ep.send(Message::new(TestMsg::Data(vec![0; 70000]), 2, 1)).await;
+        match r {
+            Ok(_) => { panic!("send a message larger than a datagram"); }
+            Err(e) => { assert!(net_error::is_message_too_large(&e)); }
+        }
+        notifier.notify_all();
+    });
+}
+
+// the serve of a port bound already fails with the bind error
+#[test]
+fn test_udp_addr_in_use() {
+    let notifier = Notifier::new();
+    let server = Node::<TestMsg, HandleEventDummy>::new(
+        1,
+        "node_1".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    server.set_transport(Transport::Udp);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    let server_sink = server.default_event_sink();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "127.0.0.1:8352".parse().unwrap();
+        let _bound = UdpSocket::bind(addr).await.unwrap();
+        let r = server_sink.serve(addr, ESServeOpt::default()).await;
+        assert!(matches!(r, Err(ref e) if net_error::is_addr_in_use(e)));
+        notifier.notify_all();
+    });
+}
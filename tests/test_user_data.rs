@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::client::{ClientBuilder, OptClientConnect};
+use scupt_net::es_option::ESServeOpt;
+use scupt_net::handle_event::FnHandler;
+use scupt_net::node::NodeBuilder;
+use scupt_net::notifier::Notifier;
+
+use common::{block_on_local, TestMsg};
+
+mod common;
+
+#[derive(Debug, PartialEq)]
+struct Session {
+    user: String,
+}
+
+// the session attached by on_accepted is read back from the endpoint, and dropped when the
+// connection is gone
+#[test]
+fn test_user_data() {
+    let notifier = Notifier::new();
+    let (sender, mut accepted) = mpsc::unbounded_channel();
+    let server = NodeBuilder::new()
+        .set_node_id(1)
+        .set_notifier(notifier.clone())
+        .set_listen_address("127.0.0.1:8577".to_string())
+        .build::<TestMsg, _>(FnHandler::<TestMsg>::new()
+            .set_on_accepted(move |ep| {
+                ep.set_user_data(Some(Box::new(Session { user: "alice".to_string() })))?;
+                let _ = sender.send(ep);
+                Ok(())
+            }))
+        .unwrap();
+    let client = ClientBuilder::new()
+        .set_node_id(2)
+        .set_server_addr("127.0.0.1:8577".to_string())
+        .set_notifier(notifier.clone())
+        .build::<TestMsg>()
+        .unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run(&local);
+    block_on_local(local, async move {
+        server.serve(ESServeOpt::default()).await.unwrap();
+        client.connect(OptClientConnect::new()).await.unwrap();
+        let ep = accepted.recv().await.unwrap();
+        let session = ep.user_data_of::<Session>().unwrap();
+        assert_eq!(session.user, "alice");
+        assert!(ep.user_data_of::<String>().is_none());
+
+        // the endpoints of a client do not keep user data of another one
+        let client_ep = client.endpoint().unwrap();
+        assert!(client_ep.user_data().is_none());
+
+        client.close().await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while !ep.is_closed() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        assert!(ep.user_data().is_none());
+        notifier.notify_all();
+    });
+}
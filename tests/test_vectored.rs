@@ -0,0 +1,119 @@
+use std::io::IoSlice;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use bytes::BytesMut;
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Forward(u64),
+    Id(u64),
+}
+
+impl MsgTrait for TestMsg {}
+
+// forward the received messages and their payloads to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<(TestMsg, BytesMut)>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok((m, payload)) = endpoint.recv_vectored().await {
+                let _ = sender.send((m.payload(), payload));
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+// the payload slices arrive in one frame after the header, between the frames of plain sends
+#[test]
+fn test_send_vectored() {
+    let notifier = Notifier::new();
+    let addr: SocketAddr = "127.0.0.1:8501".parse().unwrap();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    block_on_local(local, async move {
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default().enable_return_endpoint(true);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+
+        let head = vec![1u8; 10];
+        let body = vec![2u8; 100_000];
+        let tail = vec![3u8; 7];
+        let payload = [IoSlice::new(&head), IoSlice::new(&body), IoSlice::new(&tail)];
+        ep.send(Message::new(TestMsg::Id(1), 2, 1)).await.unwrap();
+        ep.send_vectored(&Message::new(TestMsg::Forward(2), 2, 1), &payload).await.unwrap();
+        ep.send(Message::new(TestMsg::Id(3), 2, 1)).await.unwrap();
+
+        let (m, p) = receiver.recv().await.unwrap();
+        assert_eq!(m, TestMsg::Id(1));
+        assert!(p.is_empty());
+        let (m, p) = receiver.recv().await.unwrap();
+        assert_eq!(m, TestMsg::Forward(2));
+        let expected: Vec<u8> = [head, body, tail].concat();
+        assert_eq!(p.as_ref(), expected.as_slice());
+        let (m, p) = receiver.recv().await.unwrap();
+        assert_eq!(m, TestMsg::Id(3));
+        assert!(p.is_empty());
+        notifier.notify_all();
+    });
+}
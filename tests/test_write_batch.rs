@@ -0,0 +1,189 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::{DEFAULT_WRITE_BATCH_BYTES, DEFAULT_WRITE_BATCH_MAX, ESConnectOpt, ESServeOpt};
+use scupt_net::handle_event::{HandleEvent, HandleEventDummy};
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+use scupt_net::transport::Transport;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(u64, Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+// forward the received messages to the test
+struct RecvHandler {
+    notifier: Notifier,
+    sender: mpsc::UnboundedSender<TestMsg>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for RecvHandler {
+    async fn on_accepted(&self, endpoint: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        let sender = self.sender.clone();
+        spawn_local_task(self.notifier.clone(), "receive", async move {
+            while let Ok(m) = endpoint.recv().await {
+                let _ = sender.send(m.payload());
+            }
+        })?;
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, _: ET) {}
+
+    async fn on_stop(&self) {}
+}
+
+// the size varies from a few bytes to larger than the buffer of a memory connection
+fn test_data(id: u64) -> TestMsg {
+    let size = ((id * 7919) % 100_000) as usize;
+    TestMsg::Data(id, vec![(id % 251) as u8; size])
+}
+
+fn new_nodes(notifier: &Notifier, transport: Transport) -> (
+    Node<TestMsg, RecvHandler>,
+    Node<TestMsg, HandleEventDummy>,
+    mpsc::UnboundedReceiver<TestMsg>,
+) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let server = Node::<TestMsg, RecvHandler>::new(
+        1,
+        "node_1".to_string(),
+        RecvHandler { notifier: notifier.clone(), sender },
+        false,
+        notifier.clone()).unwrap();
+    let client = Node::<TestMsg, HandleEventDummy>::new(
+        2,
+        "node_2".to_string(),
+        HandleEventDummy::default(),
+        false,
+        notifier.clone()).unwrap();
+    server.set_transport(transport);
+    client.set_transport(transport);
+    (server, client, receiver)
+}
+
+#[test]
+fn test_write_batch_frame_boundary() {
+    let notifier = Notifier::new();
+    let (server, client, mut receiver) = new_nodes(&notifier, Transport::Memory);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    let n = notifier.clone();
+    block_on_local(local, async move {
+        let addr: SocketAddr = "10.255.2.1:1".parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_write_batch(16, 128 * 1024);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        // the concurrent senders fill the queue, the writer task writes them in batches
+        let num_senders = 8u64;
+        let per_sender = 50u64;
+        for s in 0..num_senders {
+            let ep = ep.clone();
+            spawn_local_task(n.clone(), "send", async move {
+                for i in 0..per_sender {
+                    let id = s * per_sender + i;
+                    ep.send(Message::new(test_data(id), 2, 1)).await.unwrap();
+                }
+            }).unwrap();
+        }
+        let mut received = vec![false; (num_senders * per_sender) as usize];
+        for _ in 0..num_senders * per_sender {
+            let m = receiver.recv().await.unwrap();
+            let TestMsg::Data(id, _) = &m;
+            assert_eq!(m, test_data(*id));
+            assert!(!received[*id as usize]);
+            received[*id as usize] = true;
+        }
+        assert!(received.iter().all(|r| { *r }));
+        notifier.notify_all();
+    });
+}
+
+// the time to send and receive the messages of the concurrent senders, with the writes of up
+// to `write_batch_max` frames
+fn send_time(port: u16, write_batch_max: usize) -> Duration {
+    let notifier = Notifier::new();
+    let (server, client, mut receiver) = new_nodes(&notifier, Transport::Tcp);
+    let local = LocalSet::new();
+    server.run_local(&local);
+    client.run_local(&local);
+    let server_sink = server.default_event_sink();
+    let client_sink = client.default_event_sink();
+    let n = notifier.clone();
+    let (elapsed_sender, elapsed) = std::sync::mpsc::channel();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        server_sink.serve(addr, ESServeOpt::default()).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_write_batch(write_batch_max, DEFAULT_WRITE_BATCH_BYTES);
+        let ep = client_sink.connect(1, addr, opt).await.unwrap().unwrap();
+        let num_senders = 100u64;
+        let per_sender = 1000u64;
+        let start = Instant::now();
+        for s in 0..num_senders {
+            let ep = ep.clone();
+            spawn_local_task(n.clone(), "send", async move {
+                for i in 0..per_sender {
+                    let m = TestMsg::Data(s * per_sender + i, vec![]);
+                    ep.send(Message::new(m, 2, 1)).await.unwrap();
+                }
+            }).unwrap();
+        }
+        for _ in 0..num_senders * per_sender {
+            let _ = receiver.recv().await.unwrap();
+        }
+        elapsed_sender.send(start.elapsed()).unwrap();
+        notifier.notify_all();
+    });
+    elapsed.recv().unwrap()
+}
+
+// the batches take less time than one write for every frame, it depends on the machine, see
+// the write_batch bench for the numbers
+#[test]
+#[ignore]
+fn test_write_batch_throughput() {
+    let unbatched = send_time(8431, 1);
+    let batched = send_time(8432, DEFAULT_WRITE_BATCH_MAX);
+    assert!(batched < unbatched, "batched {:?}, unbatched {:?}", batched, unbatched);
+}
@@ -0,0 +1,155 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use scupt_util::error_type::ET;
+use scupt_util::message::{Message, MsgTrait};
+use scupt_util::res::Res;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio::time::{sleep, timeout};
+
+use scupt_net::endpoint_async::EndpointAsync;
+use scupt_net::es_option::ESConnectOpt;
+use scupt_net::handle_event::HandleEvent;
+use scupt_net::net_error;
+use scupt_net::node::Node;
+use scupt_net::notifier::Notifier;
+use scupt_net::task::spawn_local_task;
+
+use common::block_on_local;
+
+mod common;
+
+#[derive(
+Clone,
+Hash,
+PartialEq,
+Eq,
+Debug,
+Serialize,
+Deserialize,
+Decode,
+Encode,
+)]
+enum TestMsg {
+    Data(Vec<u8>),
+}
+
+impl MsgTrait for TestMsg {}
+
+const NUM_SENDS: usize = 32;
+
+// forward the errors to the test
+struct ErrorHandler {
+    sender: mpsc::UnboundedSender<ET>,
+}
+
+#[async_trait]
+impl HandleEvent<TestMsg> for ErrorHandler {
+    async fn on_accepted(&self, _: Arc<dyn EndpointAsync<TestMsg>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_connected(&self, _: SocketAddr, _: Res<Arc<dyn EndpointAsync<TestMsg>>>) -> Res<()> {
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ET) {
+        let _ = self.sender.send(error);
+    }
+
+    async fn on_stop(&self) {}
+}
+
+// connect to a peer which accepts the connection and never reads, the sends of 1MB messages
+// fill the socket buffers
+fn test_stuck_peer(port: u16, write_timeout_ms: u64, check: bool) {
+    let notifier = Notifier::new();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let node = Node::<TestMsg, ErrorHandler>::new(
+        1,
+        "node_1".to_string(),
+        ErrorHandler { sender },
+        false,
+        notifier.clone()).unwrap();
+    let local = LocalSet::new();
+    node.run_local(&local);
+    let sink = node.default_event_sink();
+    let task_notifier = notifier.clone();
+    block_on_local(local, async move {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let opt = ESConnectOpt::default()
+            .enable_return_endpoint(true)
+            .set_write_timeout_ms(write_timeout_ms);
+        let (r_connect, r_accept) = tokio::join!(
+            sink.connect(2, addr, opt),
+            listener.accept()
+        );
+        let ep = r_connect.unwrap().unwrap();
+        let (mut socket, _) = r_accept.unwrap();
+        let (result_sender, mut results) = mpsc::unbounded_channel();
+        for _ in 0..NUM_SENDS {
+            let e = ep.clone();
+            let s = result_sender.clone();
+            let _ = spawn_local_task(task_notifier.clone(), "send", async move {
+                let r = e.send(Message::new(TestMsg::Data(vec![0u8; 1024 * 1024]), 1, 2)).await;
+                let _ = s.send(r);
+            });
+        }
+        if check {
+            // the sends not written fail, none is left pending
+            let mut timed_out = 0;
+            for _ in 0..NUM_SENDS {
+                let r = timeout(Duration::from_secs(10), results.recv()).await.unwrap().unwrap();
+                if let Err(e) = r {
+                    assert!(net_error::is_write_timeout(&e), "{:?}", e);
+                    timed_out += 1;
+                }
+            }
+            assert!(timed_out > 0);
+            match ep.recv().await {
+                Ok(_) => { panic!("unexpected message"); }
+                Err(e) => { assert!(net_error::is_write_timeout(&e)); }
+            }
+            let e = receiver.recv().await.unwrap();
+            assert!(net_error::is_write_timeout(&e));
+            assert!(ep.is_closed());
+
+            // the connection was closed, the peer reads what was written, then the end
+            let mut buf = vec![0u8; 64 * 1024];
+            while let Ok(n) = socket.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+            }
+        } else {
+            sleep(Duration::from_millis(500)).await;
+            let mut done = 0;
+            while let Ok(r) = results.try_recv() {
+                r.unwrap();
+                done += 1;
+            }
+            assert!(done < NUM_SENDS);
+            assert!(!ep.is_closed());
+            assert!(receiver.try_recv().is_err());
+        }
+        notifier.notify_all();
+    });
+}
+
+#[test]
+fn test_write_timeout_stuck_peer() {
+    test_stuck_peer(8601, 200, true);
+}
+
+#[test]
+fn test_write_timeout_disabled() {
+    test_stuck_peer(8602, 0, false);
+}